@@ -14,11 +14,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         output,
         yolo: false,
         tool_call_limit: 10,
-        system_message_extension: None,
-        persona_prompt: None,
-        persona_name: None,
-        bash_auto_allow: None,
-        agent_prompt: None,
+        ..Default::default()
     }).await?;
 
     println!("--- Picocode Library Example ---");
@@ -13,11 +13,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         output,
         yolo: true, // Auto-confirm everything since there's no output
         tool_call_limit: 5,
-        system_message_extension: None,
-        persona_prompt: None,
-        persona_name: None,
-        bash_auto_allow: None,
-        agent_prompt: None,
+        ..Default::default()
     }).await?;
 
     println!("Running agent in silent mode...");
@@ -1,27 +1,62 @@
-use crate::output::Confirmation;
+use crate::audit::AuditLog;
+use crate::batch::Batch;
+use crate::checkpoint;
+use crate::clipboard;
+use crate::config::{ConfirmDefault, ConfirmationConfig, KeyRotation, NetworkPolicy, ToolSettings};
+use crate::debug::{DebugLog, DebugModel};
+use crate::editor;
+use crate::freshness;
+use crate::git::{self, GitState};
+use crate::load;
+use crate::loops::{LoopGuard, Verdict, DEFAULT_REPEAT_LIMIT};
+use crate::memory;
+use crate::output::{call_budget_warning, ConfirmOptions, Confirmation, Status};
+use crate::plan;
+use crate::platform::Shell;
+use crate::ratelimit::RateLimiter;
+use crate::routing::{self, Route};
+use crate::session::{self, new_session_id, Session, SessionStore, TokenUsage};
+use crate::shell::CommandClass;
+use crate::stats::{ToolStats, TurnStats, TurnTiming};
+use crate::storage;
+use crate::summarize::{FetchFullResult, ResultStore, Summarized, ToolSummaries};
+use crate::telemetry;
+use crate::tokens;
 use crate::tools::{
-    AgentBrowser, Bash, CopyFile, EditFile, GlobFiles, GrepText, ListDir, MakeDir, MoveFile,
-    ReadFile, Remove, WriteFile,
+    is_tool_available, AgentBrowser, Answers, ArchiveCreate, ArchiveExtract, AskUser, Bash,
+    CopyFile, DataPreview, DownloadFile, EditFile, EnvInfo, GhIssueGet, GhIssueList, GhPrComment,
+    GhPrCreate, GitDiff, GitHub, GitLab, GitLog, GlIssueGet, GlIssueList, GlMrComment, GlMrCreate,
+    GlPipelineStatus, GlobFiles, GrepText, ListDir, MakeDir, MoveFile, NotebookEdit, NotebookRead,
+    ReadFile, Remember, Remove, ReplaceInFiles, RunFormatter, RunLinter, RunTests, WebSearch,
+    WriteFile,
 };
 use crate::Output;
 use crate::Result;
+use async_trait::async_trait;
 use rig::agent::{Agent, AgentBuilder, CancelSignal, PromptHook};
 use rig::client::{CompletionClient, ProviderClient};
-use rig::completion::{CompletionModel, Prompt, ToolDefinition};
+use rig::completion::{CompletionModel, CompletionResponse, Prompt, PromptError, ToolDefinition};
 use rig::message::Message;
 use rig::providers::{
     anthropic, azure, cohere, deepseek, galadriel, gemini, groq, huggingface, hyperbolic, mira,
     mistral, moonshot, ollama, openai, openrouter, perplexity, together, xai,
 };
 use serde_json;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use async_trait::async_trait;
+use std::collections::{HashMap, VecDeque};
+use std::future::IntoFuture;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum AgentMode {
     Code,
+    /// Read-only tools; the agent explores and writes a plan.
     Plan,
+    /// Read-only tools plus `git_diff` and `git_log`; the agent reviews changes.
+    Review,
 }
 
 impl AgentMode {
@@ -29,6 +64,16 @@ impl AgentMode {
         match self {
             AgentMode::Code => "c>",
             AgentMode::Plan => "p>",
+            AgentMode::Review => "r>",
+        }
+    }
+
+    /// Sent ahead of each request made in this mode.
+    fn instructions(&self) -> Option<&'static str> {
+        match self {
+            AgentMode::Code => None,
+            AgentMode::Plan => Some(PLAN_MODE_PROMPT),
+            AgentMode::Review => Some(REVIEW_MODE_PROMPT),
         }
     }
 }
@@ -37,11 +82,32 @@ impl AgentMode {
 pub trait PicoAgent: Send + Sync {
     async fn run_interactive(self: Box<Self>) -> Result<()>;
     async fn run_once(&self, input: String) -> Result<String>;
+    /// Like `run_once`, but stops as soon as `cancel` is cancelled: the in-flight
+    /// completion request is dropped, running tool processes are killed, and
+    /// `PicocodeError::Cancelled` is returned. A stateful history is left as it
+    /// was before the call.
+    async fn run_once_with_cancel(
+        &self,
+        input: String,
+        cancel: CancellationToken,
+    ) -> Result<String>;
+
+    /// The conversation so far: a resumed session, earlier stateful `run_once`
+    /// turns, and anything added with `set_history` or `push_message`.
+    fn history(&self) -> Vec<Message>;
+    fn set_history(&mut self, history: Vec<Message>);
+    fn push_message(&mut self, message: Message);
+    fn clear_history(&mut self);
+    /// When stateful, `run_once` continues from `history()` and appends its turn
+    /// to it, so each call sees the earlier ones. Off by default.
+    fn set_stateful(&mut self, stateful: bool);
+    /// Tokens used so far, including a resumed session's.
+    fn usage(&self) -> TokenUsage;
 }
 
 #[async_trait]
 impl<M: CompletionModel + 'static> PicoAgent for CodeAgent<M> {
-    async fn run_interactive(self: Box<Self>) -> Result<()> {
+    async fn run_interactive(mut self: Box<Self>) -> Result<()> {
         self.output.display_header(
             &self.provider,
             &self.model,
@@ -53,29 +119,205 @@ impl<M: CompletionModel + 'static> PicoAgent for CodeAgent<M> {
         // Add usage hint
         self.output.display_system("💡 Tip: Press Enter to submit, Shift+Enter for new line. /help for commands.");
 
-        let mut history = Vec::new();
+        let mut history = std::mem::take(self.history.get_mut().unwrap());
+        if !history.is_empty() {
+            self.output.display_system(&format!(
+                "Resumed session {} ({} messages)",
+                self.session.id,
+                history.len()
+            ));
+        }
+        crate::crash::track(
+            Session {
+                messages: history.clone(),
+                ..self.session.clone()
+            },
+            self.session_store.clone(),
+        );
+        let mut needs_title = history.is_empty();
         let mut current_mode = AgentMode::Code;
+        if self.require_plan_approval && self.set_mode(AgentMode::Plan) {
+            current_mode = AgentMode::Plan;
+            self.awaiting_approval = true;
+            self.output.display_system(
+                "Plan approval is required: ask for a plan, then /approve it to enable editing tools.",
+            );
+        }
+        let (commands, notes) =
+            crate::commands::load(std::path::Path::new(crate::commands::COMMANDS_DIR));
+        for note in notes {
+            self.output.display_system(&format!("⚠ {}", note));
+        }
         let mut responses: Vec<String> = Vec::new(); // For /write
+        let mut first_message = self.first_message.take();
+        // Typed while a turn was running
+        let mut queue: VecDeque<String> = VecDeque::new();
 
         loop {
             self.output.display_separator();
 
             let prompt = format!("{} ", current_mode.prompt_symbol());
-            let input = self.output.get_user_input(&prompt);
+            // The first message of a chat recipe is a prompt, never a command
+            if let Some(first) = first_message.take() {
+                self.output.display_system(&format!("{}{}", prompt, first));
+                self.send_prompt(
+                    &first,
+                    current_mode,
+                    &mut needs_title,
+                    &mut history,
+                    &mut queue,
+                    &mut responses,
+                )
+                .await;
+                continue;
+            }
+            let input = match queue.pop_front() {
+                Some(queued) => {
+                    self.output
+                        .display_system(&format!("{}{}", prompt, queued));
+                    queued
+                }
+                None => self.output.get_user_input(&prompt).await,
+            };
 
             if input.is_empty() {
                 continue;
             }
 
+            // Handle /paste command: the clipboard becomes (part of) the prompt,
+            // sent as is even if it looks like a command
+            if input == "/paste" || input.starts_with("/paste ") {
+                match clipboard::paste() {
+                    Ok(text) if !text.trim().is_empty() => {
+                        self.output.display_system(&format!(
+                            "Pasted {} lines from the clipboard",
+                            text.lines().count()
+                        ));
+                        let pasted =
+                            clipboard::paste_prompt(input.strip_prefix("/paste").unwrap(), &text);
+                        self.send_prompt(
+                            &pasted,
+                            current_mode,
+                            &mut needs_title,
+                            &mut history,
+                            &mut queue,
+                            &mut responses,
+                        )
+                        .await;
+                    }
+                    Ok(_) => self.output.display_system("Clipboard is empty"),
+                    Err(e) => self.output.display_error(&e.to_string()),
+                }
+                continue;
+            }
+
+            // Handle /compose command: the prompt is written in $EDITOR
+            let input = if input == "/compose" || input.starts_with("/compose ") {
+                match editor::compose(input.strip_prefix("/compose").unwrap().trim_start()) {
+                    Ok(Some(text)) => {
+                        self.output
+                            .display_system(&format!("Composed {} lines", text.lines().count()));
+                        text
+                    }
+                    Ok(None) => {
+                        self.output.display_system("Empty prompt, nothing sent");
+                        continue;
+                    }
+                    Err(e) => {
+                        self.output.display_error(&e.to_string());
+                        continue;
+                    }
+                }
+            } else {
+                input
+            };
+
+            // Handle /load command: a file's contents become the prompt
+            let input = if input == "/load" || input.starts_with("/load ") {
+                let Some((path, instruction)) =
+                    load::parse_args(input.strip_prefix("/load").unwrap())
+                else {
+                    self.output
+                        .display_system("Usage: /load <file> [instruction]");
+                    continue;
+                };
+                match load::prompt(std::path::Path::new(path), instruction) {
+                    Ok(prompt) => {
+                        self.output.display_system(&format!("Loaded {}", path));
+                        prompt
+                    }
+                    Err(e) => {
+                        self.output.display_error(&e.to_string());
+                        continue;
+                    }
+                }
+            } else {
+                input
+            };
+
+            // Handle /edit command: revise the last prompt and send it instead
+            let input = if input == "/edit" {
+                let Some(index) = last_prompt_index(&history) else {
+                    self.output.display_system("No previous prompt to edit");
+                    continue;
+                };
+                let Some(original) = prompt_text(&history[index]) else {
+                    continue;
+                };
+                let original = [AgentMode::Plan, AgentMode::Review]
+                    .iter()
+                    .filter_map(AgentMode::instructions)
+                    .find_map(|instructions| {
+                        original.strip_prefix(&format!("{}\n\nUser Request: ", instructions))
+                    })
+                    .unwrap_or(&original);
+                let edited = self.output.edit_input(&prompt, original).await;
+                if edited.trim().is_empty() {
+                    self.output.display_system("Edit cancelled");
+                    continue;
+                }
+                history.truncate(index);
+                self.save_session(&history);
+                edited
+            } else {
+                input
+            };
+
             // Handle /help command
             if input == "/help" || input == "/?" {
                 self.output.display_system("Commands:");
                 self.output.display_system("  /plan          Switch to PLAN mode for exploration");
                 self.output.display_system("  /code          Switch to CODE mode for implementation");
-                self.output.display_system("  /go            Switch to CODE mode and auto-implement the plan");
-                self.output.display_system("  /write [file]  Save last response to file (default: plan.md)");
+                self.output.display_system("  /review        Switch to REVIEW mode to review changes (read-only)");
+                self.output.display_system("  /go            Switch to CODE mode and implement the plan step by step");
+                self.output.display_system("  /approve       Approve the plan and enable editing tools (require_plan_approval)");
+                self.output.display_system("  /continue      Resume a task that hit the tool call limit");
+                self.output.display_system("  /retry [model] Re-send the last prompt, optionally on another model");
+                self.output.display_system("  /resume-turn   Continue a turn that failed after running tools, keeping their results");
+                self.output.display_system("  /edit          Revise the last prompt and send it again in its place");
+                self.output.display_system("  /write [file]  Save last response to file (default: .picocode/plan.md)");
+                self.output.display_system("  /copy [code]   Copy last response (or its last code block) to the clipboard");
+                self.output.display_system("  /paste [text]  Send clipboard contents, after optional text");
+                self.output.display_system("  /load <file> [instruction]  Send a file's contents, after optional instruction");
+                self.output.display_system("  /compose [text]  Write the prompt in $EDITOR (also Ctrl+X), starting from text");
+                self.output.display_system("  /open <file>[:line]  Open a file in $EDITOR (or VS Code)");
+                self.output.display_system("  /diff [file]   Show files the agent changed this session, or one file's diff");
+                self.output.display_system("  /keep <file>   Accept the agent's changes to a file");
+                self.output.display_system("  /discard <file|--all>  Revert the agent's changes to a file, or to all files");
+                self.output.display_system("  /persona [name|off]  Switch persona (off drops it)");
+                self.output.display_system("  /remember <fact>     Save a project fact for future sessions");
+                self.output.display_system("  /memory [edit]       Show or edit remembered facts");
+                self.output.display_system("  /history [search <words>]  List recent sessions, or search past ones");
+                self.output.display_system("  /queue [clear|drop N|move N M]  Inspect or reorder queued messages");
                 self.output.display_system("  /help or /?    Show this help message");
                 self.output.display_system("  /q or /exit    Quit picocode");
+                if !commands.is_empty() {
+                    self.output.display_system("");
+                    self.output.display_system(&format!("Custom commands ({}):", crate::commands::COMMANDS_DIR));
+                    for command in &commands {
+                        self.output.display_system(&format!("  /{:<13} {}", command.name, command.description));
+                    }
+                }
                 self.output.display_system("");
                 self.output.display_system("Keys:");
                 self.output.display_system("  Enter          Submit input");
@@ -83,12 +325,40 @@ impl<M: CompletionModel + 'static> PicoAgent for CodeAgent<M> {
                 continue;
             }
 
+            // Editing needs an approved plan when require_plan_approval is set
+            if (input == "/code" || input == "/go") && self.awaiting_approval {
+                self.output.display_system(
+                    "Editing tools need an approved plan: ask for a plan, then /approve it",
+                );
+                continue;
+            }
+
+            // Handle /approve command
+            if input == "/approve" {
+                if !self.awaiting_approval {
+                    self.output
+                        .display_system("Nothing to approve: editing tools are available");
+                } else if responses.is_empty() && plan::load(plan::PLAN_FILE).is_none() {
+                    self.output
+                        .display_system("No plan to approve yet. Ask for a plan first.");
+                } else {
+                    self.awaiting_approval = false;
+                    current_mode = AgentMode::Code;
+                    self.set_mode(current_mode);
+                    self.output.display_system(
+                        "Plan approved. Switched to CODE mode with editing tools. Use /go to implement it.",
+                    );
+                }
+                continue;
+            }
+
             // Handle /plan command
             if input == "/plan" {
                 if current_mode == AgentMode::Plan {
                     self.output.display_system("Already in plan mode");
                 } else {
                     current_mode = AgentMode::Plan;
+                    self.set_mode(current_mode);
                     self.output.display_system("Switched to PLAN mode. Ask for a plan to begin exploration.");
                 }
                 continue;
@@ -100,11 +370,74 @@ impl<M: CompletionModel + 'static> PicoAgent for CodeAgent<M> {
                     self.output.display_system("Already in code mode");
                 } else {
                     current_mode = AgentMode::Code;
+                    self.set_mode(current_mode);
                     self.output.display_system("Switched to CODE mode. Ready to implement.");
                 }
                 continue;
             }
 
+            // Handle /review command
+            if input == "/review" {
+                if current_mode == AgentMode::Review {
+                    self.output.display_system("Already in review mode");
+                } else {
+                    current_mode = AgentMode::Review;
+                    self.set_mode(current_mode);
+                    self.output.display_system(
+                        "Switched to REVIEW mode. Ask for a review of the current changes or a branch.",
+                    );
+                }
+                continue;
+            }
+
+            // Handle /queue command
+            if input == "/queue" || input.starts_with("/queue ") {
+                self.handle_queue_command(input.strip_prefix("/queue").unwrap().trim(), &mut queue);
+                continue;
+            }
+
+            // Handle /persona command
+            if input == "/persona" || input.starts_with("/persona ") {
+                let name = input.strip_prefix("/persona").unwrap().trim();
+                self.switch_persona(name);
+                continue;
+            }
+
+            // Handle /remember command
+            if input == "/remember" || input.starts_with("/remember ") {
+                let fact = input.strip_prefix("/remember").unwrap();
+                match memory::remember(&*storage::project(), fact) {
+                    Ok(()) => {
+                        self.rebuild(|_| {});
+                        self.output
+                            .display_system(&format!("Remembered in {}", memory::MEMORY_FILE));
+                    }
+                    Err(e) => self.output.display_error(&e.to_string()),
+                }
+                continue;
+            }
+
+            // Handle /memory command
+            if input == "/memory" || input == "/memory edit" {
+                if input == "/memory edit" {
+                    self.edit_memory();
+                } else {
+                    match memory::load(&*storage::project()) {
+                        Some(content) => self.output.display_text(&content),
+                        None => self.output.display_system(
+                            "Nothing remembered yet. Use /remember <fact> or ask the agent to remember something.",
+                        ),
+                    }
+                }
+                continue;
+            }
+
+            // Handle /history command
+            if input == "/history" || input.starts_with("/history ") {
+                self.show_history(input.strip_prefix("/history").unwrap().trim());
+                continue;
+            }
+
             // Handle /write command
             if input.starts_with("/write") {
                 let filename = input
@@ -112,13 +445,13 @@ impl<M: CompletionModel + 'static> PicoAgent for CodeAgent<M> {
                     .unwrap()
                     .trim();
                 let filename = if filename.is_empty() {
-                    "plan.md"
+                    plan::PLAN_FILE
                 } else {
                     filename
                 };
 
                 if let Some(last_response) = responses.last() {
-                    std::fs::write(filename, last_response)
+                    plan::save(filename, last_response)
                         .map_err(|e| crate::PicocodeError::Other(format!("Failed to save response: {}", e)))?;
                     self.output.display_system(&format!("Response saved to: {}", filename));
                 } else {
@@ -127,21 +460,153 @@ impl<M: CompletionModel + 'static> PicoAgent for CodeAgent<M> {
                 continue;
             }
 
+            // Handle /copy command
+            if input == "/copy" || input == "/copy code" {
+                let text = responses.last().map(|response| {
+                    if input == "/copy code" {
+                        clipboard::last_code_block(response)
+                    } else {
+                        Some(response.clone())
+                    }
+                });
+                match text {
+                    None => self.output.display_system("No response to copy yet"),
+                    Some(None) => self.output.display_system("No code block in the last response"),
+                    Some(Some(text)) => match clipboard::copy(&text) {
+                        Ok(()) => self.output.display_system(&format!(
+                            "Copied {} lines to the clipboard",
+                            text.lines().count()
+                        )),
+                        Err(e) => self.output.display_error(&e.to_string()),
+                    },
+                }
+                continue;
+            }
+
+            // Handle /open command
+            if input == "/open" || input.starts_with("/open ") {
+                let spec = input.strip_prefix("/open").unwrap().trim();
+                if spec.is_empty() {
+                    self.output.display_system("Usage: /open <file>[:line]");
+                } else if let Err(e) = editor::open(&editor::Location::parse(spec)) {
+                    self.output.display_error(&e.to_string());
+                }
+                continue;
+            }
+
+            // Handle /diff command
+            if input == "/diff" || input.starts_with("/diff ") {
+                self.show_diff(input.strip_prefix("/diff").unwrap().trim());
+                continue;
+            }
+
+            // Handle /keep and /discard commands
+            if input == "/keep" || input.starts_with("/keep ") {
+                self.keep_changes(input.strip_prefix("/keep").unwrap().trim());
+                continue;
+            }
+            if input == "/discard" || input.starts_with("/discard ") {
+                self.discard_changes(input.strip_prefix("/discard").unwrap().trim())
+                    .await;
+                continue;
+            }
+
             // Handle /go command - switch to code mode and auto-implement
             if input == "/go" {
-                if current_mode == AgentMode::Code {
+                let has_steps = plan::load(plan::PLAN_FILE)
+                    .and_then(|content| plan::next_step(&content))
+                    .is_some();
+                if current_mode == AgentMode::Code && !has_steps {
                     self.output.display_system("Already in code mode");
                     continue;
                 }
 
                 current_mode = AgentMode::Code;
+                self.set_mode(current_mode);
+                if has_steps {
+                    self.output.display_system(&format!(
+                        "Switched to CODE mode. Executing {} step by step...",
+                        plan::PLAN_FILE
+                    ));
+                    self.execute_plan(&mut history, &mut queue, &mut responses)
+                        .await?;
+                    continue;
+                }
                 self.output.display_system("Switched to CODE mode. Implementing the plan...");
                 self.output.display_separator();
 
                 // Automatically send "Implement the plan." to the agent
-                let response = self.prompt("Implement the plan.", Some(&mut history)).await?;
-                responses.push(response.clone());
-                self.output.display_text(&response);
+                self.run_and_display(
+                    "Implement the plan.",
+                    &mut history,
+                    &mut queue,
+                    &mut responses,
+                )
+                .await;
+                continue;
+            }
+
+            // Handle /continue command - resume a turn cut off by the tool call limit
+            if input == "/continue" {
+                if history.is_empty() {
+                    self.output.display_system("Nothing to continue yet");
+                    continue;
+                }
+                self.output.display_separator();
+                self.run_and_display(CONTINUE_PROMPT, &mut history, &mut queue, &mut responses)
+                    .await;
+                continue;
+            }
+
+            // Handle /resume-turn command - continue a turn that failed after running tools
+            if input == "/resume-turn" {
+                let results = unanswered_tool_results(&history);
+                if results == 0 {
+                    self.output.display_system(
+                        "No interrupted turn to resume; /retry sends the last prompt again",
+                    );
+                    continue;
+                }
+                self.output.display_separator();
+                self.output.display_system(&format!(
+                    "Resuming with {} tool result(s) from the interrupted turn",
+                    results
+                ));
+                self.run_and_display(RESUME_PROMPT, &mut history, &mut queue, &mut responses)
+                    .await;
+                continue;
+            }
+
+            // Handle /retry command - drop the last turn and send its prompt again
+            if input == "/retry" || input.starts_with("/retry ") {
+                let Some(index) = last_prompt_index(&history) else {
+                    self.output.display_system("Nothing to retry yet");
+                    continue;
+                };
+                let Some(prompt) = prompt_text(&history[index]) else {
+                    continue;
+                };
+                let model = input.strip_prefix("/retry").unwrap().trim();
+                let previous = if model.is_empty() {
+                    None
+                } else {
+                    match self.swap_model(model) {
+                        Some(previous) => Some(previous),
+                        None => {
+                            self.output
+                                .display_error("Switching models is not supported for this agent");
+                            continue;
+                        }
+                    }
+                };
+                history.truncate(index);
+                self.output.display_separator();
+                self.output.display_system(&format!("Retrying on {}", self.model));
+                self.run_and_display(&prompt, &mut history, &mut queue, &mut responses)
+                    .await;
+                if let Some(previous) = previous {
+                    self.restore_model(previous);
+                }
                 continue;
             }
 
@@ -150,19 +615,27 @@ impl<M: CompletionModel + 'static> PicoAgent for CodeAgent<M> {
                 break;
             }
 
-            self.output.display_separator();
-
-            // Inject mode-specific context into the prompt
-            let prompt_with_mode = match current_mode {
-                AgentMode::Plan => format!("{}\n\nUser Request: {}", PLAN_MODE_PROMPT, input),
-                AgentMode::Code => input,
+            // Custom commands from .picocode/commands become their prompt
+            let input = match crate::commands::find(&commands, &input) {
+                Some((command, args)) => command.expand(args),
+                None => input,
             };
 
-            let response = self.prompt(&prompt_with_mode, Some(&mut history)).await?;
-            responses.push(response.clone());
-            self.output.display_text(&response);
+            self.send_prompt(
+                &input,
+                current_mode,
+                &mut needs_title,
+                &mut history,
+                &mut queue,
+                &mut responses,
+            )
+            .await;
         }
 
+        crate::crash::untrack();
+        self.output.display_summary(&self.tool_stats.snapshot());
+        self.output
+            .display_turn_stats(&self.turn_stats.lock().unwrap());
         Ok(())
     }
 
@@ -174,31 +647,174 @@ impl<M: CompletionModel + 'static> PicoAgent for CodeAgent<M> {
             self.tool_call_limit,
             self.persona_name.as_deref(),
         );
+        if self.require_plan_approval {
+            self.output.display_system(
+                "Plan approval is required: this run only plans, with read-only tools. Approve the plan in an interactive session.",
+            );
+        }
         self.output.display_separator();
-        let response = self.prompt(&input, None).await?;
+        let mut history = if self.stateful {
+            self.history()
+        } else {
+            Vec::new()
+        };
+        let result = self.prompt(&input, Some(&mut history)).await;
+        if self.stateful {
+            let last_prompt = last_prompt_index(&history).and_then(|i| prompt_text(&history[i]));
+            if result.is_err() && last_prompt.as_deref() != Some(input.as_str()) {
+                history.push(Message::user(&input));
+            }
+            self.save_session(&history);
+            *self.history.lock().unwrap() = history.clone();
+        }
+        let response = match result {
+            Err(
+                e @ (crate::PicocodeError::ToolCallLimit(_) | crate::PicocodeError::ToolLoop(_)),
+            ) => {
+                if let Some(progress) = turn_progress(&history) {
+                    self.output.display_text(&progress);
+                }
+                self.output.display_summary(&self.tool_stats.snapshot());
+                self.output
+                    .display_turn_stats(&self.turn_stats.lock().unwrap());
+                return Err(e);
+            }
+            other => other?,
+        };
         self.output.display_text(&response);
+        self.output.display_summary(&self.tool_stats.snapshot());
+        self.output
+            .display_turn_stats(&self.turn_stats.lock().unwrap());
         Ok(response)
     }
+
+    async fn run_once_with_cancel(
+        &self,
+        input: String,
+        cancel: CancellationToken,
+    ) -> Result<String> {
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => {
+                self.output.stop_thinking();
+                Err(crate::PicocodeError::Cancelled)
+            }
+            result = self.run_once(input) => result,
+        }
+    }
+
+    fn history(&self) -> Vec<Message> {
+        self.history.lock().unwrap().clone()
+    }
+
+    fn set_history(&mut self, history: Vec<Message>) {
+        *self.history.get_mut().unwrap() = history;
+    }
+
+    fn push_message(&mut self, message: Message) {
+        self.history.get_mut().unwrap().push(message);
+    }
+
+    fn clear_history(&mut self) {
+        self.history.get_mut().unwrap().clear();
+    }
+
+    fn set_stateful(&mut self, stateful: bool) {
+        self.stateful = stateful;
+    }
+
+    fn usage(&self) -> TokenUsage {
+        *self.usage.lock().unwrap()
+    }
+}
+
+/// Settings used to build the underlying rig agent. Kept on `CodeAgent` so the
+/// preamble can be rebuilt mid-session (e.g. on `/persona`) while keeping history.
+#[derive(Clone)]
+struct AgentOptions {
+    model: String,
+    yolo: bool,
+    output: Arc<dyn Output>,
+    system_message_extension: Option<String>,
+    persona_prompt: Option<String>,
+    bash_auto_allow: Vec<String>,
+    agent_prompt: Option<String>,
+    audit: Option<Arc<AuditLog>>,
+    web_search: Option<crate::config::WebSearchConfig>,
+    github: Option<Arc<GitHub>>,
+    gitlab: Option<Arc<GitLab>>,
+    sandbox: Option<crate::sandbox::SandboxConfig>,
+    tool_config: HashMap<String, ToolSettings>,
+    workspaces: Vec<std::path::PathBuf>,
+    confirmation: ConfirmationConfig,
+    read_only: bool,
+    /// Decides which tools are registered: all of them only in code mode.
+    mode: AgentMode,
+    /// Shell command prefixes the user chose to always allow, shared by all guards.
+    allowed_prefixes: Arc<Mutex<Vec<String>>>,
+    /// Tools always allowed in this project, from [`ALLOWED_TOOLS_FILE`].
+    allowed_tools: Vec<String>,
+    /// Confirms the guarded calls of a model response together.
+    batch: Arc<Batch>,
+    /// Refuses repeated tool calls and ends turns stuck in a loop.
+    loops: Arc<LoopGuard>,
+    summaries: Option<ToolSummaries>,
+    /// Branch, changes and recent commits when the session started.
+    git_state: Option<GitState>,
+    /// Language the user reads, as named by `config::language_name`.
+    language: Option<String>,
+    /// Contents of `context_files`, attached as context documents.
+    context: Vec<String>,
+    max_output_tokens: Option<u64>,
+    /// Answers to `ask_user` when nobody is there to reply.
+    answers: Option<Arc<Answers>>,
 }
 
-fn is_tool_available(tool: &str) -> bool {
-    std::process::Command::new("which")
-        .arg(tool)
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
-        .status()
-        .map(|s| s.success())
-        .unwrap_or(false)
+impl AgentOptions {
+    fn tool_settings(&self, tool: &str) -> ToolSettings {
+        self.tool_config.get(tool).cloned().unwrap_or_default()
+    }
 }
 
+type AgentFactory<M> = Arc<dyn Fn(&AgentOptions) -> Agent<M> + Send + Sync>;
+
 pub struct CodeAgent<M: CompletionModel> {
     agent: Agent<M>,
+    factory: Option<(AgentFactory<M>, AgentOptions)>,
     output: Arc<dyn Output>,
+    audit: Option<Arc<AuditLog>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
     tool_call_limit: usize,
     provider: String,
     model: String,
     yolo: bool,
     persona_name: Option<String>,
+    session: Session,
+    session_store: Option<SessionStore>,
+    /// Conversation used by the interactive loop and by stateful `run_once`.
+    /// Its messages are moved here from `session`, which only keeps metadata.
+    history: Mutex<Vec<Message>>,
+    stateful: bool,
+    /// Prompts sent so far, numbering the turns in `telemetry` spans.
+    turns: AtomicUsize,
+    usage: Arc<Mutex<TokenUsage>>,
+    /// Tool-less agent used for cheap one-off completions such as session titles.
+    title_agent: Option<Agent<M>>,
+    /// Cheaper model and agent that handle turns classified as `Route::Simple`.
+    simple_agent: Option<(String, Agent<M>)>,
+    tool_stats: Arc<ToolStats>,
+    /// Timings of the turns so far, averaged in the session summary.
+    turn_stats: Mutex<TurnStats>,
+    /// Git state the model saw last, and whether it is cheap enough to re-read
+    /// before every turn.
+    git_state: Mutex<Option<GitState>>,
+    refresh_git: bool,
+    /// Start interactive sessions in plan mode until `/approve`.
+    require_plan_approval: bool,
+    /// Code mode is locked until the user approves a plan.
+    awaiting_approval: bool,
+    /// Sent before the first prompt of an interactive session.
+    first_message: Option<String>,
 }
 
 pub struct AgentConfig {
@@ -212,26 +828,314 @@ pub struct AgentConfig {
     pub persona_name: Option<String>,
     pub bash_auto_allow: Option<Vec<String>>,
     pub agent_prompt: Option<String>,
+    /// Path of the append-only tool audit log (JSONL). `None` disables auditing.
+    pub audit_log: Option<String>,
+    /// Requests/tokens per minute budget shared by all agents of this provider.
+    pub rate_limit: Option<crate::config::RateLimit>,
+    /// Routes simple turns to a cheaper model of the same provider.
+    pub routing: Option<crate::config::RoutingConfig>,
+    /// Condenses large tool results with a cheap model of the same provider.
+    pub tool_summaries: Option<crate::config::SummaryConfig>,
+    /// Enables the `web_search` tool with the given backend.
+    pub web_search: Option<crate::config::WebSearchConfig>,
+    /// GitLab instance and project for the `gl_*` tools, which are enabled when `GITLAB_TOKEN` is set.
+    pub gitlab: Option<crate::config::GitLabConfig>,
+    /// Runs `bash` commands in a container with the workspace mounted.
+    pub sandbox: Option<crate::sandbox::SandboxConfig>,
+    /// Per-tool settings keyed by tool name (e.g. `bash`), as in `picocode.yaml`.
+    pub tool_config: Option<HashMap<String, ToolSettings>>,
+    /// How long confirmations wait for an answer, and what happens when they time out.
+    pub confirmation: Option<ConfirmationConfig>,
+    /// Extra directories (relative to the current directory or absolute) that tools may access.
+    pub workspaces: Option<Vec<String>>,
+    /// Globs of files tools may not read, in addition to `.env` and `.env.*`.
+    pub protected_files: Option<Vec<String>>,
+    /// Directory where interactive sessions are saved after each turn. `None` disables persistence.
+    pub session_dir: Option<String>,
+    /// Id of a saved session (in `session_dir`) to continue.
+    pub resume: Option<String>,
+    /// Only register tools that don't change files or run commands, e.g. for untrusted workspaces.
+    pub read_only: bool,
+    /// Language for replies, commit messages and plans (a name or a locale like `ja_JP`).
+    pub language: Option<String>,
+    /// Sessions start in plan mode with read-only tools until the user accepts
+    /// a plan with `/approve`. Runs with no one to approve (`run_once`,
+    /// recipes) stay in plan mode.
+    pub require_plan_approval: bool,
+    /// Identical tool calls in a row that count as a loop; `None` uses the
+    /// default of 3 and 0 turns loop detection off.
+    pub tool_loop_limit: Option<usize>,
+    /// Files attached to every request as context documents, within a share
+    /// of the model's context window.
+    pub context_files: Option<Vec<String>>,
+    /// Proxy, CA bundle and timeout for provider and tool HTTP requests.
+    /// `None` keeps the settings of an earlier `network::configure`.
+    pub network: Option<crate::config::NetworkConfig>,
+    /// Write provider requests and responses to `.picocode/debug/`, secrets
+    /// redacted. Also enabled by `PICOCODE_DEBUG=1`.
+    pub debug_llm: bool,
+    /// Cap on the tokens of one response. Responses cut off at the cap are
+    /// continued automatically. `None` uses the provider's default.
+    pub max_output_tokens: Option<u64>,
+    /// How requests rotate over the keys in the provider's variable and its
+    /// numbered variants.
+    pub api_keys: Option<crate::config::ApiKeys>,
+    /// Deployments, endpoint and API version for the `azure` provider.
+    pub azure: Option<crate::config::AzureConfig>,
+    /// Answers to `ask_user` for runs without a user, such as recipes. `None`
+    /// asks the user through `output`.
+    pub answers: Option<HashMap<String, String>>,
+    /// Sent as the first prompt of an interactive session, as if typed.
+    pub first_message: Option<String>,
+}
+
+/// Anthropic's default model on the terminal, asking before changes, with
+/// the CLI's limit of 50 tool calls per prompt and everything optional off.
+/// Set the fields that matter and fill in the rest with `..Default::default()`.
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            provider: "anthropic".into(),
+            model: crate::models::default_model("anthropic")
+                .unwrap_or_default()
+                .into(),
+            output: Arc::new(crate::output::ConsoleOutput::new()),
+            yolo: false,
+            tool_call_limit: 50,
+            system_message_extension: None,
+            persona_prompt: None,
+            persona_name: None,
+            bash_auto_allow: None,
+            agent_prompt: None,
+            audit_log: None,
+            rate_limit: None,
+            routing: None,
+            tool_summaries: None,
+            web_search: None,
+            gitlab: None,
+            sandbox: None,
+            tool_config: None,
+            confirmation: None,
+            workspaces: None,
+            protected_files: None,
+            session_dir: None,
+            resume: None,
+            read_only: false,
+            language: None,
+            require_plan_approval: false,
+            tool_loop_limit: None,
+            context_files: None,
+            network: None,
+            debug_llm: false,
+            max_output_tokens: None,
+            api_keys: None,
+            azure: None,
+            answers: None,
+            first_message: None,
+        }
+    }
+}
+
+/// Changes the rig builder of the agents [`create_agent_with`] builds, for
+/// features picocode doesn't set up itself: context documents, dynamic tools,
+/// temperature or provider parameters. It runs after picocode sets the
+/// preamble and again whenever the agent is rebuilt (`/persona`, routing).
+pub trait BuilderHook: Send + Sync + 'static {
+    fn customize<M: CompletionModel>(&self, builder: AgentBuilder<M>) -> AgentBuilder<M>;
+}
+
+/// The hook of [`create_agent`], which leaves the builder as it is.
+pub struct NoHook;
+
+impl BuilderHook for NoHook {
+    fn customize<M: CompletionModel>(&self, builder: AgentBuilder<M>) -> AgentBuilder<M> {
+        builder
+    }
 }
 
 pub async fn create_agent(config: AgentConfig) -> Result<Box<dyn PicoAgent>> {
+    create_agent_with(config, NoHook).await
+}
+
+/// Like [`create_agent`], with `hook` applied to the rig agent builder.
+pub async fn create_agent_with(
+    config: AgentConfig,
+    hook: impl BuilderHook,
+) -> Result<Box<dyn PicoAgent>> {
     let provider = config.provider.to_lowercase();
     let model = config.model.clone();
 
+    let session_store = config.session_dir.map(|dir| {
+        storage::sessions()
+            .map_or_else(|| SessionStore::new(dir), SessionStore::with_storage)
+            .indexed()
+    });
+    let mut session = match (&config.resume, &session_store) {
+        (Some(id), Some(store)) => store.load(id)?,
+        (Some(_), None) => {
+            return Err(crate::PicocodeError::Other(
+                "Resuming a session requires a session directory".into(),
+            ))
+        }
+        (None, _) => Session::new(
+            new_session_id(),
+            &config.provider,
+            &model,
+            config.persona_name.as_deref(),
+        ),
+    };
+
+    let workspaces = config
+        .workspaces
+        .unwrap_or_default()
+        .iter()
+        .map(|dir| {
+            std::fs::canonicalize(dir).map_err(|e| {
+                crate::PicocodeError::Other(format!("Workspace root {}: {}", dir, e))
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    crate::tools::set_workspace_roots(workspaces.clone());
+    crate::secrets::set_protected_files(&config.protected_files.unwrap_or_default())?;
+    if let Some(network) = &config.network {
+        crate::network::configure(network)?;
+    }
+
+    if crate::models::lookup(&model).is_some_and(|info| !info.tools) {
+        config.output.display_system(&format!(
+            "⚠ {} does not support tool calls, so it can only answer from the conversation",
+            model
+        ));
+    }
+
+    let started = std::time::Instant::now();
+    let git_state = GitState::read_current().await;
+    let refresh_git = git_state.is_some() && started.elapsed() < git::REFRESH_BUDGET;
+
+    let (context, notes) = crate::context::load(
+        &config.context_files.unwrap_or_default(),
+        crate::context::budget(&model),
+    );
+    for note in notes {
+        config.output.display_system(&format!("⚠ {}", note));
+    }
+
+    let debug = (config.debug_llm || crate::debug::enabled_by_env()).then(|| {
+        let log = DebugLog::new(crate::debug::DEBUG_DIR, &session.id);
+        config.output.display_system(&format!(
+            "Debug: writing provider requests and responses to {}",
+            log.path().display()
+        ));
+        Arc::new(log)
+    });
+
+    let confirmation = config.confirmation.unwrap_or_default();
+    let options = AgentOptions {
+        model: model.clone(),
+        yolo: config.yolo,
+        output: config.output.clone(),
+        system_message_extension: config.system_message_extension,
+        persona_prompt: config.persona_prompt,
+        bash_auto_allow: config.bash_auto_allow.unwrap_or_default(),
+        agent_prompt: config.agent_prompt,
+        audit: config
+            .audit_log
+            .map(|path| Arc::new(AuditLog::new(path, session.id.clone()))),
+        web_search: config.web_search,
+        github: GitHub::from_env(),
+        gitlab: GitLab::from_env(&config.gitlab.unwrap_or_default()),
+        sandbox: config.sandbox,
+        tool_config: config.tool_config.unwrap_or_default(),
+        workspaces,
+        confirmation: confirmation.clone(),
+        read_only: config.read_only,
+        // Nothing but an interactive `/approve` leaves plan mode
+        mode: if config.require_plan_approval {
+            AgentMode::Plan
+        } else {
+            AgentMode::Code
+        },
+        allowed_prefixes: Arc::new(Mutex::new(crate::shell::load_allowed_prefixes(
+            crate::shell::ALLOWED_COMMANDS_FILE,
+        ))),
+        // Only a trusted project's choices apply; untrusted runs are read-only
+        allowed_tools: if config.read_only {
+            Vec::new()
+        } else {
+            crate::shell::load_allowed_prefixes(ALLOWED_TOOLS_FILE)
+        },
+        batch: Arc::new(Batch::new(confirmation)),
+        loops: Arc::new(LoopGuard::new(
+            config.tool_loop_limit.unwrap_or(DEFAULT_REPEAT_LIMIT),
+        )),
+        summaries: None,
+        git_state: git_state.clone(),
+        language: config
+            .language
+            .as_deref()
+            .map(crate::config::language_name),
+        context,
+        max_output_tokens: config.max_output_tokens,
+        answers: config.answers.map(|answers| Arc::new(Answers::new(answers))),
+    };
+    // Threshold and model of the tool result summarizer
+    let summary = config
+        .tool_summaries
+        .as_ref()
+        .map(|summaries| -> Result<_> {
+            let model = summaries
+                .model
+                .clone()
+                .or_else(|| config.routing.as_ref().map(|r| r.simple.clone()))
+                .unwrap_or_else(|| model.clone());
+            let results = Arc::new(ResultStore::for_session()?);
+            Ok((summaries.threshold_tokens, model, results))
+        })
+        .transpose()?;
+
+    // Deployment names only matter to Azure, where models rarely match them
+    let azure_config = if provider == "azure" {
+        let azure_config = config.azure.clone().unwrap_or_default();
+        configure_azure(&azure_config);
+        azure_config
+    } else {
+        crate::config::AzureConfig::default()
+    };
+
     macro_rules! build {
         ($client:expr) => {{
-            let builder = $client.agent(&model);
-            let rig_agent = build_rig_agent(
-                builder,
-                config.yolo,
-                config.output.clone(),
-                config.system_message_extension,
-                config.persona_prompt,
-                config.bash_auto_allow.unwrap_or_default(),
-                config.agent_prompt,
-            );
-
-            Box::new(CodeAgent::new(
+            let client = $client;
+            let title_model = azure_config.deployment(&model);
+            let title_agent = AgentBuilder::new(DebugModel::new(
+                client.completion_model(title_model),
+                title_model,
+                None,
+            ))
+            .preamble(session::TITLE_PROMPT)
+            .max_tokens(32)
+            .build();
+            let mut options = options;
+            if let Some((threshold_tokens, summary_model, results)) = &summary {
+                options.summaries = Some(ToolSummaries {
+                    summarizer: Arc::new(
+                        client
+                            .agent(azure_config.deployment(summary_model))
+                            .preamble(crate::summarize::SUMMARY_PROMPT)
+                            .build(),
+                    ),
+                    store: results.clone(),
+                    threshold_tokens: *threshold_tokens,
+                });
+            }
+            let azure_config = azure_config.clone();
+            let factory: AgentFactory<_> = Arc::new(move |options: &AgentOptions| {
+                let model = azure_config.deployment(&options.model);
+                let model = DebugModel::new(client.completion_model(model), model, debug.clone());
+                build_rig_agent(AgentBuilder::new(model), options, &hook)
+            });
+            let rig_agent = factory(&options);
+
+            let mut agent = CodeAgent::new(
                 rig_agent,
                 config.output,
                 config.tool_call_limit,
@@ -239,97 +1143,106 @@ pub async fn create_agent(config: AgentConfig) -> Result<Box<dyn PicoAgent>> {
                 model,
                 config.yolo,
                 config.persona_name,
-            ))
+            )
+            .with_factory(factory, options);
+            if let Some(limit) = &config.rate_limit {
+                agent.rate_limiter = Some(RateLimiter::for_provider(&provider, limit));
+            }
+            agent.usage = Arc::new(Mutex::new(session.usage));
+            agent.tool_stats = Arc::new(ToolStats::new(session.tool_stats.clone()));
+            agent.turn_stats = Mutex::new(session.turn_stats);
+            agent.history = Mutex::new(std::mem::take(&mut session.messages));
+            agent.session = session;
+            agent.session_store = session_store;
+            agent.title_agent = Some(title_agent);
+            agent.git_state = Mutex::new(git_state);
+            agent.refresh_git = refresh_git;
+            agent.require_plan_approval = config.require_plan_approval;
+            agent.first_message = config.first_message.clone();
+            if let Some(routing) = &config.routing {
+                agent.route_simple_to(routing.simple.clone());
+            }
+            Box::new(agent)
         }};
     }
 
-    macro_rules! check_env {
-        ($var:expr) => {
-            if std::env::var($var).is_err() {
-                return Err(crate::PicocodeError::MissingApiKey(
-                    provider.to_string(),
-                    $var.to_string(),
-                ));
+    // Each of the provider's keys is made into a client of its own
+    let keys = required_env_vars(&provider)
+        .first()
+        .copied()
+        .map(crate::keys::collect)
+        .unwrap_or_default();
+    let rotation = config
+        .api_keys
+        .as_ref()
+        .map(|k| k.rotation)
+        .unwrap_or_default();
+    if keys.len() > 1 {
+        config.output.display_system(&format!(
+            "Using {} API keys for {} ({})",
+            keys.len(),
+            provider,
+            match rotation {
+                KeyRotation::OnRateLimit => "next key on rate limits",
+                KeyRotation::RoundRobin => "round robin",
             }
+        ));
+    }
+
+    for (i, var) in required_env_vars(&provider).iter().enumerate() {
+        let missing = if i == 0 {
+            keys.is_empty()
+        } else {
+            std::env::var(var).is_err()
+        };
+        if missing {
+            return Err(crate::PicocodeError::MissingApiKey(
+                provider.to_string(),
+                var.to_string(),
+            ));
+        }
+    }
+
+    macro_rules! build_keyed {
+        ($client:ty) => {
+            build!(crate::keys::RotatingClient::new(
+                crate::keys::clients::<$client>(&keys),
+                rotation
+            ))
         };
     }
 
     let agent: Box<dyn PicoAgent> = match provider.as_str() {
-        "anthropic" => {
-            check_env!("ANTHROPIC_API_KEY");
-            build!(anthropic::Client::from_env())
-        }
-        "openai" => {
-            check_env!("OPENAI_API_KEY");
-            build!(openai::Client::from_env())
-        }
-        "azure" => {
-            check_env!("AZURE_OPENAI_API_KEY");
-            check_env!("AZURE_OPENAI_ENDPOINT");
-            build!(azure::Client::from_env())
-        }
-        "cohere" => {
-            check_env!("COHERE_API_KEY");
-            build!(cohere::Client::from_env())
-        }
-        "deepseek" => {
-            check_env!("DEEPSEEK_API_KEY");
-            build!(deepseek::Client::from_env())
-        }
-        "galadriel" => {
-            check_env!("GALADRIEL_API_KEY");
-            build!(galadriel::Client::from_env())
-        }
-        "gemini" | "google" => {
-            check_env!("GOOGLE_API_KEY");
-            build!(gemini::Client::from_env())
-        }
-        "groq" => {
-            check_env!("GROQ_API_KEY");
-            build!(groq::Client::from_env())
-        }
-        "huggingface" => {
-            check_env!("HF_TOKEN");
-            build!(huggingface::Client::from_env())
-        }
-        "hyperbolic" => {
-            check_env!("HYPERBOLIC_API_KEY");
-            build!(hyperbolic::Client::from_env())
-        }
-        "mira" => {
-            check_env!("MIRA_API_KEY");
-            build!(mira::Client::from_env())
-        }
-        "mistral" => {
-            check_env!("MISTRAL_API_KEY");
-            build!(mistral::Client::from_env())
-        }
-        "moonshot" => {
-            check_env!("MOONSHOT_API_KEY");
-            build!(moonshot::Client::from_env())
+        "anthropic" => build_keyed!(anthropic::Client),
+        "openai" => build_keyed!(openai::Client),
+        // rig's Azure client also needs the endpoint, so it still comes from the environment
+        "azure" if keys.len() > 1 || std::env::var("AZURE_OPENAI_API_KEY").is_err() => {
+            return Err(crate::PicocodeError::Other(
+                "The azure provider takes a single key, in AZURE_OPENAI_API_KEY".into(),
+            ))
         }
+        "azure" => build!(azure_client()),
+        "cohere" => build_keyed!(cohere::Client),
+        "deepseek" => build_keyed!(deepseek::Client),
+        "galadriel" => build_keyed!(galadriel::Client),
+        "gemini" | "google" => build_keyed!(gemini::Client),
+        "groq" => build_keyed!(groq::Client),
+        "huggingface" => build_keyed!(huggingface::Client),
+        "hyperbolic" => build_keyed!(hyperbolic::Client),
+        "mira" => build_keyed!(mira::Client),
+        "mistral" => build_keyed!(mistral::Client),
+        "moonshot" => build_keyed!(moonshot::Client),
         "ollama" => {
             if std::env::var("OLLAMA_API_BASE_URL").is_err() {
                 std::env::set_var("OLLAMA_API_BASE_URL", "http://localhost:11434");
             }
             build!(ollama::Client::from_env())
         }
-        "openrouter" => {
-            check_env!("OPENROUTER_API_KEY");
-            build!(openrouter::Client::from_env())
-        }
-        "perplexity" => {
-            check_env!("PERPLEXITY_API_KEY");
-            build!(perplexity::Client::from_env())
-        }
-        "together" => {
-            check_env!("TOGETHER_API_KEY");
-            build!(together::Client::from_env())
-        }
-        "xai" => {
-            check_env!("XAI_API_KEY");
-            build!(xai::Client::from_env())
-        }
+        "openrouter" => build_keyed!(openrouter::Client),
+        "perplexity" => build_keyed!(perplexity::Client),
+        "together" => build_keyed!(together::Client),
+        "xai" => build_keyed!(xai::Client),
+        "replay" => build!(crate::replay::ReplayModel::load(std::path::Path::new(&model))?),
         _ => {
             return Err(crate::PicocodeError::Other(format!(
                 "Unsupported provider: {}",
@@ -341,6 +1254,60 @@ pub async fn create_agent(config: AgentConfig) -> Result<Box<dyn PicoAgent>> {
     Ok(agent)
 }
 
+/// `api-version` of Azure OpenAI requests when none is configured.
+const AZURE_API_VERSION: &str = "2024-10-21";
+
+/// Sets the variables rig's Azure client reads from the `azure` settings and
+/// picocode's `AZURE_OPENAI_*` variables. A set `AZURE_OPENAI_ENDPOINT` wins
+/// over `azure.endpoint`, so a repository can't send the key elsewhere.
+fn configure_azure(azure: &crate::config::AzureConfig) {
+    let endpoint = std::env::var("AZURE_OPENAI_ENDPOINT")
+        .ok()
+        .or_else(|| azure.endpoint.clone());
+    if let Some(endpoint) = endpoint {
+        std::env::set_var("AZURE_OPENAI_ENDPOINT", &endpoint);
+        std::env::set_var("AZURE_ENDPOINT", endpoint);
+    }
+    let version = azure
+        .api_version
+        .clone()
+        .or_else(|| std::env::var("AZURE_OPENAI_API_VERSION").ok())
+        .unwrap_or_else(|| AZURE_API_VERSION.to_string());
+    std::env::set_var("AZURE_API_VERSION", version);
+}
+
+/// rig's Azure client, authenticated with the key in `AZURE_OPENAI_API_KEY`.
+fn azure_client() -> azure::Client {
+    if let Ok(key) = std::env::var("AZURE_OPENAI_API_KEY") {
+        std::env::set_var("AZURE_API_KEY", key);
+    }
+    azure::Client::from_env()
+}
+
+/// Environment variables a provider needs before a client can be created.
+pub fn required_env_vars(provider: &str) -> &'static [&'static str] {
+    match provider {
+        "anthropic" => &["ANTHROPIC_API_KEY"],
+        "openai" => &["OPENAI_API_KEY"],
+        "azure" => &["AZURE_OPENAI_API_KEY", "AZURE_OPENAI_ENDPOINT"],
+        "cohere" => &["COHERE_API_KEY"],
+        "deepseek" => &["DEEPSEEK_API_KEY"],
+        "galadriel" => &["GALADRIEL_API_KEY"],
+        "gemini" | "google" => &["GOOGLE_API_KEY"],
+        "groq" => &["GROQ_API_KEY"],
+        "huggingface" => &["HF_TOKEN"],
+        "hyperbolic" => &["HYPERBOLIC_API_KEY"],
+        "mira" => &["MIRA_API_KEY"],
+        "mistral" => &["MISTRAL_API_KEY"],
+        "moonshot" => &["MOONSHOT_API_KEY"],
+        "openrouter" => &["OPENROUTER_API_KEY"],
+        "perplexity" => &["PERPLEXITY_API_KEY"],
+        "together" => &["TOGETHER_API_KEY"],
+        "xai" => &["XAI_API_KEY"],
+        _ => &[],
+    }
+}
+
 pub fn load_agents_md() -> Option<String> {
     let path = std::path::Path::new("AGENTS.md");
     if path.exists() {
@@ -365,9 +1332,132 @@ pub fn load_claude_md() -> Option<String> {
 #[derive(Clone)]
 struct LoggingHook {
     output: Arc<dyn Output>,
+    audit: Option<Arc<AuditLog>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    usage: Arc<Mutex<TokenUsage>>,
+    tool_stats: Arc<ToolStats>,
+    tool_call_limit: usize,
+    /// Tool calls made by this prompt so far.
+    tool_calls: Arc<AtomicUsize>,
+    /// Session token total when the prompt started.
+    start_tokens: u64,
+    batch: Option<Arc<Batch>>,
+    loops: Option<Arc<LoopGuard>>,
+    answers: Option<Arc<Answers>>,
+    /// When the prompt started, and how long its first response took.
+    started: std::time::Instant,
+    first_token: Arc<OnceLock<Duration>>,
+    /// Whether the latest response was cut off at the output token limit.
+    truncated: Arc<AtomicBool>,
+    /// The model, its context window and the system prompt's estimated
+    /// tokens, to warn when tool results fill the window during the turn.
+    context: Option<(String, usize, usize)>,
+    /// Whether the context warning was shown for this prompt.
+    context_warned: AtomicBool,
+}
+
+impl LoggingHook {
+    /// Warns once per prompt when the request about to be sent nears the
+    /// context window, which long tool sequences can reach after `preflight`.
+    fn check_context(&self, prompt: &Message, history: &[Message]) {
+        let Some((model, window, preamble)) = &self.context else {
+            return;
+        };
+        let used = preamble
+            + tokens::estimate_message_tokens(prompt)
+            + tokens::estimate_history_tokens(history);
+        if used as f64 >= *window as f64 * tokens::WARN_RATIO
+            && !self.context_warned.swap(true, Ordering::Relaxed)
+        {
+            self.output
+                .display_system(&context_warning(used, *window, model));
+        }
+    }
+
+    fn update_status(&self, tool: Option<&str>) {
+        self.output.update_status(&Status {
+            tool: tool.map(str::to_string),
+            tool_calls: self.tool_calls.load(Ordering::Relaxed),
+            tool_call_limit: self.tool_call_limit,
+            tokens: self
+                .usage
+                .lock()
+                .unwrap()
+                .total_tokens
+                .saturating_sub(self.start_tokens),
+        });
+    }
+}
+
+fn context_warning(used: usize, window: usize, model: &str) -> String {
+    format!(
+        "⚠ Prompt is ~{} tokens ({}% of {}'s {} token context window)",
+        used,
+        used * 100 / window,
+        model,
+        window
+    )
 }
 
 impl<M: CompletionModel> PromptHook<M> for LoggingHook {
+    async fn on_completion_call(
+        &self,
+        prompt: &Message,
+        history: &[Message],
+        cancel_sig: CancelSignal,
+    ) {
+        // Ends the turn once the refused call's result is in the history
+        if self
+            .loops
+            .as_ref()
+            .is_some_and(|loops| loops.stopped().is_some())
+            || self
+                .answers
+                .as_ref()
+                .is_some_and(|answers| answers.unanswered().is_some())
+        {
+            cancel_sig.cancel();
+            return;
+        }
+        self.check_context(prompt, history);
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+        self.update_status(None);
+    }
+
+    async fn on_completion_response(
+        &self,
+        _prompt: &Message,
+        response: &CompletionResponse<M::Response>,
+        _cancel_sig: CancelSignal,
+    ) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.record_tokens(response.usage.total_tokens);
+        }
+        self.first_token.get_or_init(|| self.started.elapsed());
+        self.truncated.store(
+            serde_json::to_value(&response.raw_response).is_ok_and(|raw| is_truncated(&raw)),
+            Ordering::Relaxed,
+        );
+        self.usage.lock().unwrap().add(&response.usage);
+        telemetry::completion(&response.usage);
+        self.update_status(None);
+        if let Some(batch) = &self.batch {
+            let calls: Vec<(String, serde_json::Value)> = response
+                .choice
+                .iter()
+                .filter_map(|content| match content {
+                    rig::message::AssistantContent::ToolCall(call) => {
+                        Some((call.function.name.clone(), call.function.arguments.clone()))
+                    }
+                    _ => None,
+                })
+                .collect();
+            batch.confirm(&calls, &self.output).await;
+        }
+    }
+
     async fn on_tool_call(
         &self,
         tool_name: &str,
@@ -378,17 +1468,48 @@ impl<M: CompletionModel> PromptHook<M> for LoggingHook {
         let args_json =
             serde_json::from_str(args).unwrap_or(serde_json::Value::String(args.to_string()));
         self.output.display_tool_call(tool_name, &args_json);
-    }
+        telemetry::tool_call(tool_name, args);
+        crate::crash::tool_called(tool_name, args);
+        self.tool_stats.start(tool_name);
+        let calls = self.tool_calls.fetch_add(1, Ordering::Relaxed) + 1;
+        self.update_status(Some(tool_name));
+        if calls == call_budget_warning(self.tool_call_limit) && calls < self.tool_call_limit {
+            self.output.display_system(&format!(
+                "⚠ {} of {} tool calls used, the turn stops at {}. Raise --tool-call-limit for longer tasks",
+                calls, self.tool_call_limit, self.tool_call_limit
+            ));
+        }
+        match self.loops.as_ref().map(|loops| loops.call(tool_name, args)) {
+            Some(Verdict::Nudge(reason)) => self.output.display_system(&format!(
+                "⚠ Tool loop: {}, asking for another approach",
+                reason
+            )),
+            Some(Verdict::Stop(reason)) => self
+                .output
+                .display_system(&format!("⚠ Tool loop: {}, stopping", reason)),
+            _ => {}
+        }
+    }
 
     async fn on_tool_result(
         &self,
-        _tool_name: &str,
+        tool_name: &str,
         _tool_call_id: Option<String>,
-        _args: &str,
+        args: &str,
         result: &str,
         _cancel_sig: CancelSignal,
     ) {
+        let elapsed = self.tool_stats.finish(tool_name, result);
+        if let Some(loops) = &self.loops {
+            loops.result(tool_name, result);
+        }
+        telemetry::tool_result(tool_name, elapsed, result);
+        if let Some(audit) = &self.audit {
+            audit.record(tool_name, args, result);
+        }
         self.output.display_tool_result(result);
+        self.output.display_tool_timing(tool_name, elapsed);
+        self.update_status(None);
     }
 }
 
@@ -402,8 +1523,20 @@ Your mission is to assist the user in their development tasks by utilizing a set
 3. **Verify Everything**: After modifying code, verify the results. Run tests or build commands via `bash`. Read the modified file to ensure the change was applied correctly.
 4. **Tool Mastery**:
    - `read_file`: Use to read code. Note that it provides line numbers (e.g., `  10| code`). These are for your reference only; do not include them in your output or when writing files.
+   - `run_tests`: Run the test suite (optionally filtered) and get a pass/fail summary. Prefer it over running tests through `bash`.
+   - `run_linter` / `run_formatter`: Run the project's configured linters (optionally with auto-fix) and formatters instead of reconstructing their command lines.
+   - `replace_in_files`: For renames and other changes repeated across many files, instead of many `edit_file` calls. Run it with `dry_run: true` first and check the diff.
+   - `rename_symbol`: Rename a function, type or variable (`kind: item`) or a field, method or property (`kind: member`) in Rust/Python/JS/TS/Go code (available in builds with tree-sitter). Prefer it over `replace_in_files` for identifiers, since it skips strings, comments, longer names and local variables.
+   - `notebook_read` / `notebook_edit`: Use for `.ipynb` files instead of `read_file`/`edit_file`; they work on cells by index and keep the notebook JSON valid.
+   - `data_preview`: Inspect CSV/TSV/JSONL/Parquet files (schema, row count, first rows) instead of reading raw data.
+   - `download_file`: Fetch a schema, fixture or other file from a URL into the workspace (pass `sha256` when the expected digest is known) instead of `curl` through `bash`.
+   - `archive_extract` / `archive_create`: Unpack or build zip, tar and tar.gz archives instead of calling `unzip`/`tar` through `bash`.
+   - `git_diff` / `git_log`: Inspect uncommitted changes, the diff against a branch, or recent commits instead of running git through `bash`.
+   - `env_info`: Check the OS, memory, disk space and installed toolchain versions in one call instead of probing with `bash`.
    - `bash`: Your window to the system. Use it for compilation, testing, and complex automation.
    - `agent_browser`: Use for external documentation, searching for solutions, or web-related debugging.
+   - `gh_issue_get`, `gh_pr_create`, `gl_mr_create`, `gl_pipeline_status`, ...: Work with GitHub/GitLab issues, pull/merge requests and pipelines directly (available when a token is configured) instead of calling `gh` through `bash`.
+   - `remember`: Save a durable project convention or fact (e.g. how to run tests) so future sessions know it. Don't store secrets or one-off details.
 5. **Context**: You are working in the directory provided below. All paths are relative to this directory.
 
 ### GUIDING PRINCIPLES
@@ -413,12 +1546,132 @@ Your mission is to assist the user in their development tasks by utilizing a set
 - **Communication**: Keep explanations brief and focused on the "how" and "why" of your technical decisions.
 "#;
 
+const CONTINUE_PROMPT: &str = "You ran out of tool calls before finishing. Continue the task from where you left off; don't redo completed work.";
+
+const RESUME_PROMPT: &str = "The request failed partway through the task because the model provider returned an error. The tool results above are still valid: continue from where you left off without repeating completed steps.";
+
+const TRUNCATED_PROMPT: &str = "Your previous response was cut off at the output token limit. Continue exactly where it stopped, without repeating anything.";
+
+/// Prompts picocode sends on the user's behalf, which `/retry` and `/edit` skip.
+const SYNTHETIC_PROMPTS: &[&str] = &[CONTINUE_PROMPT, RESUME_PROMPT, TRUNCATED_PROMPT];
+
+/// Continuations requested for one prompt before the cut-off answer is returned as is.
+const MAX_CONTINUATIONS: usize = 3;
+
+/// Where raw responses give their stop reason: Anthropic, OpenAI-compatible
+/// providers, Gemini and Ollama.
+const STOP_REASON_KEYS: [&str; 4] = [
+    "stop_reason",
+    "finish_reason",
+    "finishReason",
+    "done_reason",
+];
+
+/// Whether a raw provider response stopped at the output token limit.
+fn is_truncated(raw: &serde_json::Value) -> bool {
+    let stopped = |value: &serde_json::Value| {
+        STOP_REASON_KEYS
+            .iter()
+            .filter_map(|key| value.get(key)?.as_str())
+            .any(|reason| matches!(reason, "max_tokens" | "length" | "MAX_TOKENS"))
+    };
+    stopped(raw)
+        || ["choices", "candidates"].iter().any(|key| {
+            raw.get(key)
+                .and_then(|choices| choices.as_array())
+                .is_some_and(|choices| choices.iter().any(stopped))
+        })
+}
+
+/// Model name, agent and simple-turn agent replaced by `CodeAgent::swap_model`.
+type SwappedModel<M> = (String, Agent<M>, Option<(String, Agent<M>)>);
+
+/// The text of a user prompt, as opposed to a message carrying tool results
+/// or a prompt picocode sent to continue a turn.
+fn prompt_text(message: &Message) -> Option<String> {
+    let Message::User { content } = message else {
+        return None;
+    };
+    let text: Vec<&str> = content
+        .iter()
+        .filter_map(|c| match c {
+            rig::message::UserContent::Text(t) => Some(t.text.as_str()),
+            _ => None,
+        })
+        .collect();
+    (!text.is_empty())
+        .then(|| {
+            let text = text.join("\n");
+            freshness::strip_note(git::strip_note(&text)).to_string()
+        })
+        .filter(|text| !SYNTHETIC_PROMPTS.contains(&text.as_str()))
+}
+
+/// `input` with a note on files read earlier that changed on disk since.
+fn with_file_changes(input: &str) -> String {
+    let changed = freshness::take_changed();
+    if changed.is_empty() {
+        input.to_string()
+    } else {
+        freshness::with_note(&changed, input)
+    }
+}
+
+/// Index of the message that started the last turn.
+fn last_prompt_index(history: &[Message]) -> Option<usize> {
+    history.iter().rposition(|m| prompt_text(m).is_some())
+}
+
+/// Assistant text written since the last user prompt, i.e. what a cut-off turn got done.
+fn turn_progress(history: &[Message]) -> Option<String> {
+    let start = last_prompt_index(history).map(|i| i + 1).unwrap_or(0);
+    let text: Vec<&str> = history[start..]
+        .iter()
+        .filter_map(|m| match m {
+            Message::Assistant { content, .. } => Some(content.iter()),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|c| match c {
+            rig::message::AssistantContent::Text(t) if !t.text.trim().is_empty() => {
+                Some(t.text.trim())
+            }
+            _ => None,
+        })
+        .collect();
+    (!text.is_empty()).then(|| text.join("\n\n"))
+}
+
+/// Tool results of a turn that failed before the model answered them, i.e.
+/// what `/resume-turn` can pick up. Zero unless the history ends with them.
+fn unanswered_tool_results(history: &[Message]) -> usize {
+    let start = last_prompt_index(history).map(|i| i + 1).unwrap_or(0);
+    let ends_with_results = history.last().is_some_and(|m| match m {
+        Message::User { content } => content
+            .iter()
+            .any(|c| matches!(c, rig::message::UserContent::ToolResult(_))),
+        _ => false,
+    });
+    if start >= history.len() || !ends_with_results {
+        return 0;
+    }
+    history[start..]
+        .iter()
+        .filter_map(|m| match m {
+            Message::User { content } => Some(content.iter()),
+            _ => None,
+        })
+        .flatten()
+        .filter(|c| matches!(c, rig::message::UserContent::ToolResult(_)))
+        .count()
+}
+
 const PLAN_MODE_PROMPT: &str = r#"You are picocode in PLANNING MODE. Your role is to explore, analyze, and design implementation plans before writing code.
 
 ### PLANNING MODE WORKFLOW
 
 1. **Deep Exploration**: Start by thoroughly understanding the codebase
-   - Use `list_dir` to understand project structure
+   - Use `list_dir` with `depth` 2-3 to see the project layout as a tree in one call
    - Use `read_file` to examine relevant files
    - Use `grep_text` to find patterns, functions, and related code
    - Use `glob_files` to locate files by name patterns
@@ -435,6 +1688,7 @@ const PLAN_MODE_PROMPT: &str = r#"You are picocode in PLANNING MODE. Your role i
    - Suggest code patterns that match the existing codebase
    - Consider testing and verification approaches
    - Present the plan as structured markdown in your response
+   - List the implementation as a `- [ ]` checklist; each item should be a self-contained step
 
 4. **Iteration**: Be ready to refine the plan
    - Answer questions about the approach
@@ -446,7 +1700,7 @@ const PLAN_MODE_PROMPT: &str = r#"You are picocode in PLANNING MODE. Your role i
 - **Present plans in chat**: Write your plan as markdown in your response, not to a file
 - **Exploration over execution**: Focus on reading and understanding, not editing
 - **Be thorough but concise**: Provide enough detail to implement, but stay focused
-- **Avoid premature implementation**: Don't edit files or run commands unless necessary for understanding
+- **Avoid premature implementation**: Editing tools and `bash` aren't available in planning mode; describe changes in the plan instead
 - **Ask clarifying questions**: If requirements are unclear, ask before finalizing the plan
 - **Think architecturally**: Consider how changes fit into the larger codebase
 
@@ -463,9 +1717,12 @@ When presenting a plan, use this structure:
 - Key files and components involved
 
 ### Approach
-1. Step-by-step breakdown
-2. Files to modify and why
-3. Functions/components to add or change
+- Files to modify and why
+- Functions/components to add or change
+
+### Steps
+- [ ] First self-contained step
+- [ ] Next step
 
 ### Verification
 - How to test the changes
@@ -475,75 +1732,537 @@ When presenting a plan, use this structure:
 Remember: You're in planning mode. The user will switch to code mode when ready to implement.
 "#;
 
-fn build_rig_agent<M: CompletionModel>(
-    builder: AgentBuilder<M>,
-    yolo: bool,
-    output: Arc<dyn Output>,
-    system_message_extension: Option<String>,
-    persona_prompt: Option<String>,
-    bash_auto_allow: Vec<String>,
-    agent_prompt: Option<String>,
-) -> Agent<M> {
+const REVIEW_MODE_PROMPT: &str = r#"You are picocode in REVIEW MODE. Your role is to review code changes, not to make them.
+
+### REVIEW MODE WORKFLOW
+
+1. **See the changes**: Use `git_diff` for uncommitted changes (`staged: true` for the index, `base` for a branch such as `main...HEAD`) and `git_log` for the commits involved
+2. **Read the context**: Use `read_file` and `grep_text` to understand the code around each change and its callers
+3. **Report findings**: For each problem give the file and line, its severity (error, warning, or nit), what is wrong, and a suggested fix
+   - Bugs, missing error handling, and edge cases first
+   - Then security issues, performance, and tests that are missing
+   - Style only where it departs from the codebase's conventions
+4. **Summarize**: End with an overall verdict and the most important fixes
+
+Editing tools and `bash` are not available in review mode. Describe fixes; the user will switch to code mode to apply them.
+"#;
+
+fn system_message(options: &AgentOptions) -> String {
     let cwd = std::env::current_dir()
         .map(|p| p.display().to_string())
         .unwrap_or_default();
-    let mut system_message = agent_prompt.unwrap_or_else(|| {
+    let mut system_message = options.agent_prompt.clone().unwrap_or_else(|| {
         format!("{}\n\nCurrent working directory: {}", DEFAULT_AGENT_PROMPT, cwd)
     });
-    if let Some(persona) = persona_prompt {
+    if !options.workspaces.is_empty() {
+        let roots: Vec<String> = options
+            .workspaces
+            .iter()
+            .map(|p| format!("- {}", p.display()))
+            .collect();
+        system_message.push_str(&format!(
+            "\n\nAdditional workspace roots (use absolute paths):\n{}",
+            roots.join("\n")
+        ));
+    }
+    if let Some(persona) = &options.persona_prompt {
         system_message = format!("{}\n\n{}", persona, system_message);
     }
-    if let Some(ext) = system_message_extension {
+    if let Some(ext) = &options.system_message_extension {
         system_message.push_str("\n\n");
-        system_message.push_str(&ext);
+        system_message.push_str(ext);
+    }
+    // Memory is repository-provided, so read-only (untrusted) runs leave it out
+    let facts = (!options.read_only)
+        .then(|| memory::load(&*storage::project()))
+        .flatten();
+    if let Some(facts) = facts {
+        system_message.push_str(&format!(
+            "\n\n### PROJECT MEMORY\nFacts remembered from earlier sessions ({}):\n\n{}",
+            memory::MEMORY_FILE,
+            facts.trim()
+        ));
+    }
+    if let Some(state) = &options.git_state {
+        system_message.push_str(&format!(
+            "\n\n### GIT STATE\nAt session start:\n{}",
+            state.render().trim_end()
+        ));
+    }
+    if let Some(language) = &options.language {
+        system_message.push_str(&format!(
+            "\n\n### LANGUAGE\nWrite replies, explanations, commit messages, plans and other documents in {}. Keep code, identifiers, file paths and commands as they are.",
+            language
+        ));
     }
+    if options.read_only {
+        system_message.push_str(
+            "\n\nThis workspace is not trusted, so only read-only tools are available. If the task needs changes, describe them instead of making them.",
+        );
+    } else if options.mode == AgentMode::Plan {
+        system_message.push_str(
+            "\n\nYou are in plan mode, so only read-only tools are available. Explore the code and propose a plan; editing tools are enabled when the user switches to code mode.",
+        );
+    } else if options.mode == AgentMode::Review {
+        system_message.push_str(
+            "\n\nYou are in review mode, so only read-only tools and git_diff/git_log are available. Report problems and suggested fixes instead of making changes.",
+        );
+    }
+    system_message
+}
+
+fn build_rig_agent<M: CompletionModel>(
+    builder: AgentBuilder<M>,
+    options: &AgentOptions,
+    hook: &impl BuilderHook,
+) -> Agent<M> {
+    let system_message = system_message(options);
+    let mut builder = options
+        .context
+        .iter()
+        .fold(builder.preamble(&system_message), |builder, document| {
+            builder.context(document)
+        });
+    if let Some(max_tokens) = options.max_output_tokens {
+        builder = builder.max_tokens(max_tokens);
+    }
+
+    let mut builder = Tools {
+        builder: hook.customize(builder),
+        config: &options.tool_config,
+        loops: options.loops.clone(),
+    }
+    .tool(ReadFile)
+    .tool(summarized(GlobFiles, options))
+    .tool(summarized(GrepText, options))
+    .tool(summarized(ListDir, options))
+    .tool(NotebookRead)
+    .tool(DataPreview)
+    .tool(EnvInfo)
+    .tool(AskUser {
+        output: options.output.clone(),
+        answers: options.answers.clone(),
+    });
 
-    let mut builder = builder
-        .preamble(&system_message)
-        .tool(ReadFile)
-        .tool(WriteFile)
-        .tool(EditFile)
-        .tool(GlobFiles)
-        .tool(GrepText)
-        .tool(ListDir);
+    if let Some(summaries) = &options.summaries {
+        builder = builder.tool(FetchFullResult(summaries.store.clone()));
+    }
+    if let Some(search) = &options.web_search {
+        builder = builder.tool(summarized(
+            guard(WebSearch::new(search.clone()), options, None),
+            options,
+        ));
+    }
+    if let Some(github) = &options.github {
+        builder = builder
+            .tool(summarized(GhIssueGet(github.clone()), options))
+            .tool(summarized(GhIssueList(github.clone()), options));
+    }
+    if let Some(gitlab) = &options.gitlab {
+        builder = builder
+            .tool(summarized(GlIssueGet(gitlab.clone()), options))
+            .tool(summarized(GlIssueList(gitlab.clone()), options))
+            .tool(summarized(GlPipelineStatus(gitlab.clone()), options));
+    }
+    if options.read_only || options.mode == AgentMode::Plan {
+        return builder.build();
+    }
+    builder = builder.tool(summarized(GitDiff, options)).tool(GitLog);
+    if options.mode == AgentMode::Review {
+        return builder.build();
+    }
 
     builder = builder
-        .tool(guard(MakeDir, yolo, output.clone(), None))
-        .tool(guard(Remove, yolo, output.clone(), None))
-        .tool(guard(MoveFile, yolo, output.clone(), None))
-        .tool(guard(CopyFile, yolo, output.clone(), None));
+        .confirmable(WriteFile, options)
+        .confirmable(EditFile, options)
+        .confirmable(ReplaceInFiles, options)
+        .confirmable(NotebookEdit, options)
+        .tool(Remember)
+        .tool(guard(MakeDir, options, None))
+        .tool(guard(Remove, options, None))
+        .tool(guard(MoveFile, options, None))
+        .tool(guard(CopyFile, options, None))
+        .tool(guard(ArchiveExtract, options, None))
+        .tool(guard(ArchiveCreate, options, None));
+    #[cfg(feature = "tree-sitter")]
+    {
+        builder = builder.confirmable(crate::tools::RenameSymbol, options);
+    }
 
-    let auto_allow = bash_auto_allow.clone();
+    let bash_settings = options.tool_settings("bash");
+    let network = bash_settings.network;
+    // Downloads follow the network policy of `bash`
     builder = builder.tool(guard(
-        Bash,
-        yolo,
-        output.clone(),
-        Some(Arc::new(move |args| {
-            auto_allow.iter().any(|pattern| {
-                regex::Regex::new(pattern)
-                    .map(|re| re.is_match(&args.cmd))
-                    .unwrap_or(false)
-            })
+        DownloadFile::new(network),
+        options,
+        Some(Arc::new(move |_| match network {
+            NetworkPolicy::Ask => Approval::Confirm("uses network"),
+            _ => Approval::Default,
         })),
     ));
+    let shell = bash_settings.shell;
+    let auto_allow = options.bash_auto_allow.clone();
+    let output = options.output.clone();
+    let bash = guard(
+        Bash {
+            sandbox: options.sandbox.clone(),
+            network,
+            shell,
+            progress: Some(Arc::new(move |line: &str| {
+                output.display_tool_progress(line)
+            })),
+        },
+        options,
+        Some(Arc::new(move |args| {
+            let class = crate::shell::classify(&args.cmd);
+            if let CommandClass::Dangerous(reason) = class {
+                return Approval::Confirm(reason);
+            }
+            if network == NetworkPolicy::Ask && crate::shell::uses_network(&args.cmd) {
+                return Approval::Confirm("uses network");
+            }
+            // Commands are classified as sh; other shells get no fast path
+            if args.shell.is_some_and(|shell| !shell.is_posix()) {
+                return Approval::Confirm("runs in a non-POSIX shell");
+            }
+            if !shell.unwrap_or_else(Shell::detect).is_posix() {
+                return Approval::Default;
+            }
+            let allowed = class == CommandClass::Safe
+                || auto_allow.iter().any(|pattern| {
+                    regex::Regex::new(pattern)
+                        .map(|re| re.is_match(&args.cmd))
+                        .unwrap_or(false)
+                });
+            if allowed {
+                Approval::Allow
+            } else {
+                Approval::Default
+            }
+        })),
+    )
+    .with_command(|args| Some(args.cmd.clone()));
+    let run_tests = guard(
+        RunTests {
+            sandbox: options.sandbox.clone(),
+            network,
+            shell,
+        },
+        options,
+        Some(command_auto_allow(
+            options.tool_settings("run_tests").auto_allow,
+            |args| RunTests::command_for(args).map(|(_, cmd)| cmd),
+        )),
+    )
+    .with_command(|args| RunTests::command_for(args).map(|(_, cmd)| cmd));
+    let run_linter = guard(
+        RunLinter {
+            sandbox: options.sandbox.clone(),
+            network,
+            shell,
+        },
+        options,
+        Some(command_auto_allow(
+            options.tool_settings("run_linter").auto_allow,
+            RunLinter::command_for,
+        )),
+    )
+    .with_command(RunLinter::command_for);
+    let run_formatter = guard(
+        RunFormatter {
+            sandbox: options.sandbox.clone(),
+            network,
+            shell,
+        },
+        options,
+        Some(command_auto_allow(
+            options.tool_settings("run_formatter").auto_allow,
+            RunFormatter::command_for,
+        )),
+    )
+    .with_command(RunFormatter::command_for);
+
+    builder = builder
+        .tool(summarized(bash, options))
+        .tool(summarized(run_tests, options))
+        .tool(summarized(run_linter, options))
+        .tool(run_formatter);
+
+    if let Some(github) = &options.github {
+        builder = builder
+            .tool(guard(GhPrCreate(github.clone()), options, None))
+            .tool(guard(GhPrComment(github.clone()), options, None));
+    }
+
+    if let Some(gitlab) = &options.gitlab {
+        builder = builder
+            .tool(guard(GlMrCreate(gitlab.clone()), options, None))
+            .tool(guard(GlMrComment(gitlab.clone()), options, None));
+    }
 
     if is_tool_available("agent-browser") {
-        builder = builder.tool(guard(AgentBrowser, yolo, output.clone(), None));
+        builder = builder.tool(summarized(guard(AgentBrowser, options, None), options));
     }
     builder.build()
 }
 
 use rig::tool::Tool;
 
+/// Per-call approval policy for a guarded tool, decided from its args.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Approval {
+    /// Confirm unless yolo or "always" was chosen for the session.
+    Default,
+    /// Run without confirmation (e.g. matched `auto_allow`).
+    Allow,
+    /// Always confirm, even in yolo mode, giving the reason.
+    Confirm(&'static str),
+}
+
+type ApprovalPolicy<A> = Arc<dyn Fn(&A) -> Approval + Send + Sync>;
+
+/// Allows calls whose resolved shell command matches one of the `auto_allow` patterns.
+fn command_auto_allow<A: 'static>(
+    patterns: Vec<String>,
+    command: fn(&A) -> Option<String>,
+) -> ApprovalPolicy<A> {
+    Arc::new(move |args| {
+        let allowed = command(args).is_some_and(|cmd| {
+            patterns.iter().any(|pattern| {
+                regex::Regex::new(pattern)
+                    .map(|re| re.is_match(&cmd))
+                    .unwrap_or(false)
+            })
+        });
+        if allowed {
+            Approval::Allow
+        } else {
+            Approval::Default
+        }
+    })
+}
+
+/// What decides whether a guarded call is confirmed, shared with the [`Batch`]
+/// so it can tell which calls of a response would ask.
+struct Rules<A> {
+    yolo: bool,
+    always: Arc<AtomicBool>,
+    policy: Option<ApprovalPolicy<A>>,
+    /// The shell command a call runs, for allowing commands by prefix.
+    command: Option<fn(&A) -> Option<String>>,
+    allowed_prefixes: Arc<Mutex<Vec<String>>>,
+    /// Listed in `.picocode/allowed_tools`. Ignored for tools with a `command`,
+    /// which are allowed per command prefix instead.
+    allowed_tool: bool,
+}
+
+impl<A> Clone for Rules<A> {
+    fn clone(&self) -> Self {
+        Self {
+            yolo: self.yolo,
+            always: self.always.clone(),
+            policy: self.policy.clone(),
+            command: self.command,
+            allowed_prefixes: self.allowed_prefixes.clone(),
+            allowed_tool: self.allowed_tool,
+        }
+    }
+}
+
+impl<A> Rules<A> {
+    fn approval(&self, args: &A) -> Approval {
+        self.policy
+            .as_ref()
+            .map(|f| f(args))
+            .unwrap_or(Approval::Default)
+    }
+
+    /// The decision for a call that runs without asking, or `None` when it
+    /// must be confirmed.
+    fn unasked(&self, args: &A, approval: Approval) -> Option<&'static str> {
+        let forced = matches!(approval, Approval::Confirm(_));
+        let prefix_allowed = || {
+            self.command.and_then(|f| f(args)).is_some_and(|cmd| {
+                self.allowed_prefixes
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|prefix| crate::shell::matches_prefix(&cmd, prefix))
+            })
+        };
+        let listed = self.allowed_tool && self.command.is_none();
+        if self.yolo && !forced {
+            Some("yolo")
+        } else if (self.always.load(Ordering::Relaxed) || listed) && !forced {
+            Some("always")
+        } else if approval == Approval::Allow {
+            Some("auto_allow")
+        } else if !forced && prefix_allowed() {
+            Some("allowed_prefix")
+        } else {
+            None
+        }
+    }
+}
+
+/// Tools the user chose to always allow in the project, one name per line.
+pub(crate) const ALLOWED_TOOLS_FILE: &str = ".picocode/allowed_tools";
+
 struct Guard<T: Tool> {
     tool: T,
-    yolo: bool,
     output: Arc<dyn Output>,
-    always: Arc<AtomicBool>,
-    auto_approve: Option<Arc<dyn Fn(&T::Args) -> bool + Send + Sync>>,
+    audit: Option<Arc<AuditLog>>,
+    rules: Rules<T::Args>,
+    confirmation: ConfirmationConfig,
+    batch: Arc<Batch>,
 }
 
-impl<T: Tool<Error = crate::tools::ToolError>> Tool for Guard<T> {
+impl<T: Tool> Guard<T>
+where
+    T::Args: serde::Serialize + 'static,
+{
+    fn with_command(mut self, command: fn(&T::Args) -> Option<String>) -> Self {
+        self.rules.command = Some(command);
+        self.register();
+        self
+    }
+
+    /// Lets calls be confirmed in a batch. Calls confirmed for a reason
+    /// (`Approval::Confirm`) are always asked about on their own.
+    fn register(&self) {
+        let rules = self.rules.clone();
+        self.batch.register(
+            T::NAME,
+            Arc::new(move |args| {
+                let args = serde_json::from_value::<T::Args>(args.clone()).ok()?;
+                let approval = rules.approval(&args);
+                if matches!(approval, Approval::Confirm(_))
+                    || rules.unasked(&args, approval).is_some()
+                {
+                    return None;
+                }
+                serde_json::to_value(&args).ok()
+            }),
+        );
+    }
+}
+
+impl<T: Tool> Guard<T>
+where
+    T::Args: serde::Serialize,
+{
+    /// Asks about a call until the user decides, showing its diff or editing
+    /// its args in between. Returns the decision and the args to run with.
+    async fn ask(&self, mut args: T::Args) -> (&'static str, T::Args) {
+        let mut edited = false;
+        loop {
+            let message = match self.rules.approval(&args) {
+                Approval::Confirm(reason) => {
+                    format!("Confirm tool {} call ({})?", T::NAME.to_uppercase(), reason)
+                }
+                _ => format!("Confirm tool {} call?", T::NAME.to_uppercase()),
+            };
+            let command = self.rules.command.and_then(|f| f(&args));
+            let prefix = command.as_deref().and_then(crate::shell::command_prefix);
+            let value = serde_json::to_value(&args).ok();
+            let diff = value
+                .as_ref()
+                .and_then(|value| crate::batch::preview(T::NAME, value));
+            let options = ConfirmOptions {
+                prefix: prefix.clone(),
+                project: self.rules.command.is_none(),
+                edit: value.is_some(),
+                diff: diff.is_some(),
+            };
+            let answer = crate::output::confirm_within(
+                &self.output,
+                &message,
+                Some(&options),
+                self.confirmation.timeout,
+            )
+            .await;
+            let decision = match answer {
+                Some(Confirmation::ShowDiff) => {
+                    let diff = diff.unwrap_or_default();
+                    self.output
+                        .display_text(&format!("```diff\n{}\n```", diff.trim_end()));
+                    continue;
+                }
+                Some(Confirmation::EditArgs) => {
+                    let initial = value.map(|v| v.to_string()).unwrap_or_default();
+                    let input = self.output.edit_input("args> ", &initial).await;
+                    match serde_json::from_str(&input) {
+                        Ok(changed) => {
+                            args = changed;
+                            edited = true;
+                        }
+                        Err(e) => self.output.display_error(&format!(
+                            "Invalid {} arguments: {}",
+                            T::NAME,
+                            e
+                        )),
+                    }
+                    continue;
+                }
+                Some(Confirmation::Always) => {
+                    self.rules.always.store(true, Ordering::Relaxed);
+                    "always"
+                }
+                Some(Confirmation::AlwaysInProject) => {
+                    if let Err(e) = crate::shell::save_allowed_prefix(ALLOWED_TOOLS_FILE, T::NAME) {
+                        self.output.display_error(&format!(
+                            "Could not save to {}: {}",
+                            ALLOWED_TOOLS_FILE, e
+                        ));
+                    }
+                    self.rules.always.store(true, Ordering::Relaxed);
+                    "always"
+                }
+                Some(
+                    choice @ (Confirmation::AlwaysCommand | Confirmation::AlwaysCommandInProject),
+                ) => {
+                    let prefix = prefix.unwrap_or_default();
+                    if choice == Confirmation::AlwaysCommandInProject {
+                        if let Err(e) = crate::shell::save_allowed_prefix(
+                            crate::shell::ALLOWED_COMMANDS_FILE,
+                            &prefix,
+                        ) {
+                            self.output.display_error(&format!(
+                                "Could not save to {}: {}",
+                                crate::shell::ALLOWED_COMMANDS_FILE,
+                                e
+                            ));
+                        }
+                    }
+                    self.rules.allowed_prefixes.lock().unwrap().push(prefix);
+                    "always_command"
+                }
+                Some(Confirmation::Yes) if edited => "edited",
+                Some(Confirmation::Yes) => "yes",
+                Some(Confirmation::No) => "denied",
+                None => {
+                    let (decision, outcome) = match self.confirmation.default {
+                        ConfirmDefault::Deny => ("timeout_denied", "denied"),
+                        ConfirmDefault::Allow => ("timeout_allowed", "allowed"),
+                    };
+                    self.output.display_error(&format!(
+                        "No answer for {} within {}s, {}",
+                        T::NAME,
+                        self.confirmation.timeout.unwrap_or_default(),
+                        outcome
+                    ));
+                    decision
+                }
+            };
+            return (decision, args);
+        }
+    }
+}
+
+impl<T: Tool<Error = crate::tools::ToolError>> Tool for Guard<T>
+where
+    T::Args: serde::Serialize,
+{
     type Args = T::Args;
     type Output = T::Output;
     type Error = T::Error;
@@ -555,48 +2274,199 @@ impl<T: Tool<Error = crate::tools::ToolError>> Tool for Guard<T> {
     }
 
     async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
-        let should_auto_approve = self
-            .auto_approve
-            .as_ref()
-            .map(|f| f(&args))
-            .unwrap_or(false);
+        let approval = self.rules.approval(&args);
+        // Calls confirmed for a reason are never covered by a batch answer
+        let batched = || {
+            let value = serde_json::to_value(&args).ok()?;
+            self.batch.take(Self::NAME, &value)
+        };
+        let (decision, args) = if let Some(decision) = self.rules.unasked(&args, approval) {
+            (decision, args)
+        } else if let Some(decision) = (!matches!(approval, Approval::Confirm(_)))
+            .then(batched)
+            .flatten()
+        {
+            (decision, args)
+        } else {
+            self.ask(args).await
+        };
+        if let Some(audit) = &self.audit {
+            audit.record_decision(Self::NAME, decision);
+        }
+        if decision == "denied" {
+            return Err(crate::tools::ToolError::Generic(
+                "Action cancelled by user".into(),
+            ));
+        }
+        if decision == "timeout_denied" {
+            return Err(crate::tools::ToolError::Generic(
+                "Action denied: confirmation timed out".into(),
+            ));
+        }
+        self.tool.call(args).await
+    }
+}
 
-        if !self.yolo && !self.always.load(Ordering::Relaxed) && !should_auto_approve {
-            match self
-                .output
-                .confirm(&format!("Confirm tool {} call?", Self::NAME.to_uppercase()))
-            {
-                Confirmation::Always => {
-                    self.always.store(true, Ordering::Relaxed);
-                }
-                Confirmation::Yes => {}
-                Confirmation::No => {
-                    return Err(crate::tools::ToolError::Generic(
-                        "Action cancelled by user".into(),
-                    ));
-                }
-            }
+/// A tool whose description is replaced or extended by
+/// `tool_config.<name>.description_override` and `extra_instructions`.
+struct Described<T: Tool> {
+    tool: T,
+    description: Option<String>,
+    extra: Option<String>,
+}
+
+impl<T: Tool> Tool for Described<T> {
+    type Args = T::Args;
+    type Output = T::Output;
+    type Error = T::Error;
+
+    const NAME: &'static str = T::NAME;
+
+    async fn definition(&self, prompt: String) -> ToolDefinition {
+        let mut definition = self.tool.definition(prompt).await;
+        if let Some(description) = &self.description {
+            definition.description = description.trim().to_string();
         }
+        if let Some(extra) = &self.extra {
+            definition.description = format!("{}\n\n{}", definition.description, extra.trim());
+        }
+        definition
+    }
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
         self.tool.call(args).await
     }
 }
 
-fn guard<T: Tool>(
+/// A tool that isn't run when [`LoopGuard`] refused its call, returning the
+/// reason to the model instead.
+struct Watched<T: Tool> {
     tool: T,
-    yolo: bool,
-    output: Arc<dyn Output>,
-    auto_approve: Option<Arc<dyn Fn(&T::Args) -> bool + Send + Sync>>,
-) -> Guard<T> {
-    Guard {
+    loops: Arc<LoopGuard>,
+}
+
+impl<T: Tool<Error = crate::tools::ToolError>> Tool for Watched<T> {
+    type Args = T::Args;
+    type Output = T::Output;
+    type Error = T::Error;
+
+    const NAME: &'static str = T::NAME;
+
+    async fn definition(&self, prompt: String) -> ToolDefinition {
+        self.tool.definition(prompt).await
+    }
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        if let Some(refusal) = self.loops.refusal(T::NAME) {
+            return Err(crate::tools::ToolError::Generic(refusal));
+        }
+        self.tool.call(args).await
+    }
+}
+
+/// Adds tools to an agent, applying the description settings in `tool_config`
+/// and refusing calls that [`LoopGuard`] caught repeating.
+struct Tools<'a, M: CompletionModel> {
+    builder: AgentBuilder<M>,
+    config: &'a HashMap<String, ToolSettings>,
+    loops: Arc<LoopGuard>,
+}
+
+impl<M: CompletionModel> Tools<'_, M> {
+    fn tool<T: Tool<Error = crate::tools::ToolError> + 'static>(mut self, tool: T) -> Self {
+        let tool = Watched {
+            tool,
+            loops: self.loops.clone(),
+        };
+        let settings = self.config.get(T::NAME);
+        let description = settings.and_then(|s| s.description_override.clone());
+        let extra = settings.and_then(|s| s.extra_instructions.clone());
+        self.builder = if description.is_none() && extra.is_none() {
+            self.builder.tool(tool)
+        } else {
+            self.builder.tool(Described {
+                tool,
+                description,
+                extra,
+            })
+        };
+        self
+    }
+
+    /// Adds a tool that runs without confirmation unless
+    /// `tool_config.<name>.confirm` is set.
+    fn confirmable<T>(self, tool: T, options: &AgentOptions) -> Self
+    where
+        T: Tool<Error = crate::tools::ToolError> + 'static,
+        T::Args: serde::Serialize + 'static,
+    {
+        if options.tool_settings(T::NAME).confirm {
+            self.tool(guard(tool, options, None))
+        } else {
+            self.tool(tool)
+        }
+    }
+
+    fn build(self) -> Agent<M> {
+        self.builder.build()
+    }
+}
+
+/// Wraps a tool whose large results are summarized when `tool_summaries` is set.
+fn summarized<T: Tool>(tool: T, options: &AgentOptions) -> Summarized<T> {
+    Summarized {
         tool,
-        yolo,
-        output,
-        always: Arc::new(AtomicBool::new(false)),
-        auto_approve,
+        summaries: options.summaries.clone(),
     }
 }
 
+fn guard<T: Tool>(
+    tool: T,
+    options: &AgentOptions,
+    policy: Option<ApprovalPolicy<T::Args>>,
+) -> Guard<T>
+where
+    T::Args: serde::Serialize + 'static,
+{
+    let guard = Guard {
+        tool,
+        output: options.output.clone(),
+        audit: options.audit.clone(),
+        rules: Rules {
+            yolo: options.yolo,
+            always: options.batch.always(T::NAME),
+            policy,
+            command: None,
+            allowed_prefixes: options.allowed_prefixes.clone(),
+            allowed_tool: options.allowed_tools.iter().any(|tool| tool == T::NAME),
+        },
+        confirmation: options.confirmation.clone(),
+        batch: options.batch.clone(),
+    };
+    guard.register();
+    guard
+}
+
 impl<M: CompletionModel + 'static> CodeAgent<M> {
+    /// Runs `prompt` through the tool loop of `agent`, adding the exchange to
+    /// `history` when there is one.
+    async fn send(
+        &self,
+        agent: &Agent<M>,
+        prompt: &str,
+        hook: &LoggingHook,
+        history: Option<&mut Vec<Message>>,
+    ) -> std::result::Result<String, PromptError> {
+        let mut builder = agent
+            .prompt(prompt)
+            .with_hook(hook.clone())
+            .multi_turn(self.tool_call_limit);
+        if let Some(h) = history {
+            builder = builder.with_history(h);
+        }
+        builder.into_future().await
+    }
+
     pub fn new(
         agent: Agent<M>,
         output: Arc<dyn Output>,
@@ -606,35 +2476,910 @@ impl<M: CompletionModel + 'static> CodeAgent<M> {
         yolo: bool,
         persona_name: Option<String>,
     ) -> Self {
+        let session = Session::new(new_session_id(), &provider, &model, persona_name.as_deref());
         Self {
             agent,
+            factory: None,
             output,
+            audit: None,
+            rate_limiter: None,
             tool_call_limit,
             provider,
             model,
             yolo,
             persona_name,
+            session,
+            session_store: None,
+            history: Mutex::new(Vec::new()),
+            stateful: false,
+            turns: AtomicUsize::new(0),
+            usage: Arc::new(Mutex::new(TokenUsage::default())),
+            title_agent: None,
+            simple_agent: None,
+            tool_stats: Arc::new(ToolStats::default()),
+            turn_stats: Mutex::new(TurnStats::default()),
+            git_state: Mutex::new(None),
+            refresh_git: false,
+            require_plan_approval: false,
+            awaiting_approval: false,
+            first_message: None,
         }
     }
 
-    async fn prompt(&self, input: &str, history: Option<&mut Vec<Message>>) -> Result<String> {
-        self.output.display_thinking("Thinking...");
-        let mut builder = self
-            .agent
-            .prompt(input)
-            .with_hook(LoggingHook {
-                output: self.output.clone(),
-            })
-            .multi_turn(self.tool_call_limit);
+    fn with_factory(mut self, factory: AgentFactory<M>, options: AgentOptions) -> Self {
+        self.audit = options.audit.clone();
+        self.factory = Some((factory, options));
+        self
+    }
 
-        if let Some(h) = history {
-            builder = builder.with_history(h);
+    /// Rebuilds the agent with the tools of `mode`. Returns false when it
+    /// cannot be rebuilt and keeps all its tools.
+    fn set_mode(&mut self, mode: AgentMode) -> bool {
+        self.rebuild(|options| options.mode = mode)
+    }
+
+    /// Rebuilds the rig agent after `update` changes its options. Returns false
+    /// when the agent was constructed without a factory and cannot be rebuilt.
+    fn rebuild(&mut self, update: impl FnOnce(&mut AgentOptions)) -> bool {
+        let Some((factory, options)) = self.factory.as_mut() else {
+            return false;
+        };
+        update(options);
+        self.agent = factory(options);
+        if let Some((model, simple)) = self.simple_agent.as_mut() {
+            *simple = factory(&AgentOptions {
+                model: model.clone(),
+                ..options.clone()
+            });
+        }
+        true
+    }
+
+    /// Builds a second agent on `model` for turns `routing::classify` deems simple.
+    fn route_simple_to(&mut self, model: String) {
+        if model == self.model {
+            return;
+        }
+        if let Some((factory, options)) = &self.factory {
+            let agent = factory(&AgentOptions {
+                model: model.clone(),
+                ..options.clone()
+            });
+            self.simple_agent = Some((model, agent));
         }
+    }
 
-        let response = builder
+    /// Opens the memory file in `$EDITOR` and reloads it into the system prompt.
+    /// Memory kept in a database is edited in a temporary file and saved back.
+    fn edit_memory(&mut self) {
+        let storage = storage::project();
+        let local = storage.local_path(memory::MEMORY_FILE);
+        // Removed when dropped, at the end of the edit
+        let temp = match local {
+            Some(_) => None,
+            None => match tempfile::Builder::new()
+                .prefix("picocode-memory-")
+                .suffix(".md")
+                .tempfile()
+            {
+                Ok(file) => Some(file.into_temp_path()),
+                Err(e) => {
+                    self.output.display_error(&e.to_string());
+                    return;
+                }
+            },
+        };
+        let path = local
+            .clone()
+            .or_else(|| temp.as_deref().map(std::path::Path::to_path_buf))
+            .unwrap_or_default()
+            .display()
+            .to_string();
+        if let Err(e) = plan::save(
+            &path,
+            &memory::load(&*storage).unwrap_or_else(|| "# Project memory\n\n".into()),
+        ) {
+            self.output.display_error(&e.to_string());
+            return;
+        }
+        let program = editor::configured_editor().unwrap_or_else(|| "vi".to_string());
+        let location = editor::Location::parse(&path);
+        if let Err(e) = editor::command(&program, &location).status() {
+            self.output
+                .display_error(&format!("Failed to run {}: {}", program, e));
+            return;
+        }
+        if local.is_none() {
+            let saved = std::fs::read_to_string(&path)
+                .map_err(crate::PicocodeError::from)
+                .and_then(|content| storage.write(memory::MEMORY_FILE, &content));
+            if let Err(e) = saved {
+                self.output.display_error(&e.to_string());
+                return;
+            }
+        }
+        self.rebuild(|_| {});
+        self.output.display_system("Memory reloaded");
+    }
+
+    /// `/history` lists recent sessions, `/history search <words>` the
+    /// sessions mentioning every word.
+    fn show_history(&self, args: &str) {
+        const LIMIT: usize = 10;
+        let Some(store) = &self.session_store else {
+            self.output
+                .display_system("Sessions aren't saved in this run");
+            return;
+        };
+        let (subcommand, query) = args.split_once(' ').unwrap_or((args, ""));
+        let lines: Result<Vec<String>> = match (subcommand, query.trim()) {
+            ("", _) => store.list().map(|sessions| {
+                sessions
+                    .iter()
+                    .take(LIMIT)
+                    .map(|session| {
+                        format!(
+                            "{:<24} {}  {}",
+                            session.id,
+                            session
+                                .updated_at
+                                .with_timezone(&chrono::Local)
+                                .format("%Y-%m-%d %H:%M"),
+                            session.title.as_deref().unwrap_or("(untitled)")
+                        )
+                    })
+                    .collect()
+            }),
+            ("search", query) if !query.is_empty() => store
+                .search(query, LIMIT)
+                .map(|hits| hits.iter().map(|hit| hit.display()).collect()),
+            _ => {
+                self.output
+                    .display_system("Usage: /history or /history search <words>");
+                return;
+            }
+        };
+        match lines {
+            Ok(lines) if lines.is_empty() => self.output.display_system("No sessions found"),
+            Ok(lines) => {
+                for line in lines {
+                    self.output.display_system(&line);
+                }
+                self.output
+                    .display_system("Resume one with: picocode chat --resume <id>");
+            }
+            Err(e) => self.output.display_error(&e.to_string()),
+        }
+    }
+
+    fn switch_persona(&mut self, name: &str) {
+        if name.is_empty() {
+            self.output.display_system(&format!(
+                "Current persona: {}",
+                self.persona_name.as_deref().unwrap_or("default")
+            ));
+            return;
+        }
+
+        let (persona_name, persona_prompt) = if name == "off" {
+            (None, None)
+        } else {
+            match crate::persona::get_persona(name) {
+                Some(prompt) => (Some(name.to_string()), Some(prompt)),
+                None => {
+                    self.output
+                        .display_error(&format!("Unknown persona: {}", name));
+                    return;
+                }
+            }
+        };
+
+        if !self.rebuild(|options| options.persona_prompt = persona_prompt) {
+            self.output
+                .display_error("Persona switching is not supported for this agent");
+            return;
+        }
+        self.persona_name = persona_name;
+        match &self.persona_name {
+            Some(p) => self
+                .output
+                .display_system(&format!("Switched persona to: {}", p)),
+            None => self.output.display_system("Persona disabled"),
+        }
+    }
+
+    /// Runs one interactive turn while collecting anything the user types in the
+    /// meantime. Queued lines are fed as the following messages; `/queue`
+    /// commands typed during the turn are applied right away.
+    async fn run_turn(
+        &self,
+        input: &str,
+        history: &mut Vec<Message>,
+        queue: &mut VecDeque<String>,
+    ) -> Result<String> {
+        self.output.start_input_queue();
+        crate::crash::turn_started(input);
+        let response = self.prompt(input, Some(history)).await;
+        let typed = self.output.take_queued_input();
+        if !typed.is_empty() {
+            for line in typed {
+                if line == "/queue" || line.starts_with("/queue ") {
+                    self.handle_queue_command(line.strip_prefix("/queue").unwrap().trim(), queue);
+                } else {
+                    queue.push_back(line);
+                }
+            }
+            if !queue.is_empty() {
+                self.output
+                    .display_system(&format!("{} message(s) queued", queue.len()));
+            }
+        }
+        // Keep the prompt of a failed turn so `/retry` can send it again
+        let last_prompt = last_prompt_index(history).and_then(|i| prompt_text(&history[i]));
+        if response.is_err() && last_prompt.as_deref() != Some(input) {
+            history.push(Message::user(input));
+        }
+        self.save_session(history);
+        response
+    }
+
+    /// Runs a turn and shows its response. A turn cut off by the tool call limit
+    /// shows its progress instead and returns `None`; the history keeps that
+    /// progress so `/continue` can pick it up. Other failures are reported and
+    /// also return `None`, leaving the prompt in the history for `/retry`.
+    async fn run_and_display(
+        &self,
+        input: &str,
+        history: &mut Vec<Message>,
+        queue: &mut VecDeque<String>,
+        responses: &mut Vec<String>,
+    ) -> Option<String> {
+        match self.run_turn(input, history, queue).await {
+            Ok(response) => {
+                responses.push(response.clone());
+                self.output.display_text(&response);
+                Some(response)
+            }
+            Err(crate::PicocodeError::ToolCallLimit(limit)) => {
+                if let Some(progress) = turn_progress(history) {
+                    responses.push(progress.clone());
+                    self.output.display_text(&progress);
+                }
+                self.output.display_error(&format!(
+                    "Stopped after {} tool calls before the task was finished. Progress is kept; /continue resumes with a fresh budget.",
+                    limit
+                ));
+                None
+            }
+            Err(crate::PicocodeError::ToolLoop(reason)) => {
+                if let Some(progress) = turn_progress(history) {
+                    responses.push(progress.clone());
+                    self.output.display_text(&progress);
+                }
+                self.output.display_error(&format!(
+                    "Stopped the turn: {}. Progress is kept; tell the agent how to proceed or /continue.",
+                    reason
+                ));
+                None
+            }
+            Err(e) => {
+                let hint = match unanswered_tool_results(history) {
+                    0 => "Use /retry to send the prompt again.".to_string(),
+                    results => format!(
+                        "The turn's {} tool result(s) are kept: /resume-turn continues from them, /retry starts over.",
+                        results
+                    ),
+                };
+                self.output.display_error(&format!("{}\n{}", e, hint));
+                None
+            }
+        }
+    }
+
+    /// Swaps in an agent on `model` for a single turn, returning what
+    /// `restore_model` needs to undo it. Routing is off while swapped.
+    fn swap_model(&mut self, model: &str) -> Option<SwappedModel<M>> {
+        let (factory, options) = self.factory.as_ref()?;
+        let agent = factory(&AgentOptions {
+            model: model.to_string(),
+            ..options.clone()
+        });
+        Some((
+            std::mem::replace(&mut self.model, model.to_string()),
+            std::mem::replace(&mut self.agent, agent),
+            self.simple_agent.take(),
+        ))
+    }
+
+    fn restore_model(&mut self, (model, agent, simple_agent): SwappedModel<M>) {
+        self.model = model;
+        self.agent = agent;
+        self.simple_agent = simple_agent;
+    }
+
+    /// Writes the conversation so far to the session store, if persistence is enabled.
+    /// Also keeps the crash report of an interactive session up to date.
+    fn save_session(&self, history: &[Message]) {
+        let session = Session {
+            updated_at: chrono::Utc::now(),
+            persona: self.persona_name.clone(),
+            usage: *self.usage.lock().unwrap(),
+            tool_stats: self.tool_stats.snapshot(),
+            turn_stats: *self.turn_stats.lock().unwrap(),
+            messages: history.to_vec(),
+            ..self.session.clone()
+        };
+        crate::crash::update_session(&session);
+        let Some(store) = &self.session_store else {
+            return;
+        };
+        if let Err(e) = store.save(&session) {
+            self.output
+                .display_error(&format!("Failed to save session: {}", e));
+        }
+    }
+
+    /// Sends `input` as a prompt in `mode`, naming the session after the
+    /// first one.
+    async fn send_prompt(
+        &mut self,
+        input: &str,
+        mode: AgentMode,
+        needs_title: &mut bool,
+        history: &mut Vec<Message>,
+        queue: &mut VecDeque<String>,
+        responses: &mut Vec<String>,
+    ) {
+        self.output.display_separator();
+
+        if self.session.title.is_none() {
+            self.session.title = Some(session::title_from_prompt(input));
+        }
+
+        // Inject mode-specific context into the prompt
+        let prompt_with_mode = match mode.instructions() {
+            Some(instructions) => format!("{}\n\nUser Request: {}", instructions, input),
+            None => input.to_string(),
+        };
+
+        let Some(response) = self
+            .run_and_display(&prompt_with_mode, history, queue, responses)
             .await
-            .map_err(|e| crate::PicocodeError::Other(e.to_string()))?;
+        else {
+            return;
+        };
+
+        if *needs_title {
+            *needs_title = false;
+            self.generate_title(input, &response, history).await;
+        }
+    }
+
+    async fn generate_title(&mut self, input: &str, response: &str, history: &[Message]) {
+        let Some(title_agent) = &self.title_agent else {
+            return;
+        };
+        if self.session_store.is_none() {
+            return;
+        }
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+        let excerpt: String = response.chars().take(1000).collect();
+        let request = format!("User: {}\n\nAssistant: {}", input, excerpt);
+        let result = title_agent.prompt(request).await;
+        match result {
+            Ok(raw) => {
+                if let Some(title) = session::clean_title(&raw) {
+                    self.session.title = Some(title);
+                    self.save_session(history);
+                }
+            }
+            Err(e) => tracing::debug!(target: "picocode", "Session title generation failed: {}", e),
+        }
+    }
+
+    /// Implements the unchecked steps of the plan file one prompt at a time,
+    /// checking each off once its turn completes. Stops early when the user
+    /// queues a message, so `/go` can resume where it left off.
+    async fn execute_plan(
+        &self,
+        history: &mut Vec<Message>,
+        queue: &mut VecDeque<String>,
+        responses: &mut Vec<String>,
+    ) -> Result<()> {
+        loop {
+            let content = plan::load(plan::PLAN_FILE).unwrap_or_default();
+            let Some((index, step)) = plan::next_step(&content) else {
+                self.output.display_system("All plan steps completed");
+                return Ok(());
+            };
+            let total = plan::parse_steps(&content).len();
+
+            self.output.display_separator();
+            self.output
+                .display_system(&format!("Step {}/{}: {}", index, total, step.text));
+            let prompt = format!(
+                "Implement step {} of the plan in {}:\n\n{}\n\nOnly work on this step; the remaining steps will follow.",
+                index,
+                plan::PLAN_FILE,
+                step.text
+            );
+            if self
+                .run_and_display(&prompt, history, queue, responses)
+                .await
+                .is_none()
+            {
+                self.output.display_system(
+                    "Plan paused. Use /continue or /retry to finish this step, then /go to resume the plan.",
+                );
+                return Ok(());
+            }
+
+            // Re-read in case the agent touched the plan during the step
+            let content = plan::load(plan::PLAN_FILE).unwrap_or(content);
+            match plan::mark_done(&content, &step.text) {
+                Some(updated) => plan::save(plan::PLAN_FILE, &updated)?,
+                // Already checked off by the agent
+                None if plan::parse_steps(&content)
+                    .iter()
+                    .any(|s| s.done && s.text == step.text) => {}
+                // The step was reworded or removed: running on would repeat it
+                None => {
+                    self.output.display_system(&format!(
+                        "Plan paused: step {} is no longer in {}. Check off the finished steps, then /go to resume.",
+                        index,
+                        plan::PLAN_FILE
+                    ));
+                    return Ok(());
+                }
+            }
+
+            if !queue.is_empty() {
+                self.output
+                    .display_system("Plan paused for queued messages. Run /go to resume.");
+                return Ok(());
+            }
+        }
+    }
+
+    /// `/diff`: the files changed this session, or the diff of one of them.
+    fn show_diff(&self, file: &str) {
+        if file.is_empty() {
+            let changes = checkpoint::changes();
+            if changes.is_empty() {
+                self.output
+                    .display_system("No files changed by the agent this session");
+            } else {
+                self.output
+                    .display_text(&format!("```diff\n{}\n```", checkpoint::summary(&changes)));
+            }
+            return;
+        }
+        let diff = crate::tools::get_path(file)
+            .map_err(|e| e.to_string())
+            .map(|path| checkpoint::diff(&path));
+        match diff {
+            Ok(Some(diff)) => self
+                .output
+                .display_text(&format!("```diff\n{}\n```", diff.trim_end())),
+            Ok(None) => self.output.display_system(&format!(
+                "{} was not changed by the agent this session",
+                file
+            )),
+            Err(e) => self.output.display_error(&e),
+        }
+    }
+
+    /// `/keep <file>`: accepts the agent's changes to a file, leaving `/diff`.
+    fn keep_changes(&self, file: &str) {
+        if file.is_empty() {
+            self.output.display_system("Usage: /keep <file>");
+            return;
+        }
+        match crate::tools::get_path(file) {
+            Ok(path) if checkpoint::keep(&path) => self
+                .output
+                .display_system(&format!("Kept the changes to {}", file)),
+            Ok(_) => self.output.display_system(&format!(
+                "{} was not changed by the agent this session",
+                file
+            )),
+            Err(e) => self.output.display_error(&e.to_string()),
+        }
+    }
+
+    /// `/discard <file>` or `/discard --all`: reverts the agent's changes.
+    async fn discard_changes(&self, file: &str) {
+        if file.is_empty() {
+            self.output.display_system("Usage: /discard <file> | --all");
+            return;
+        }
+        if file != "--all" {
+            let discarded = crate::tools::get_path(file)
+                .map_err(|e| crate::PicocodeError::Other(e.to_string()))
+                .and_then(|path| checkpoint::discard(&path));
+            match discarded {
+                Ok(true) => self
+                    .output
+                    .display_system(&format!("Discarded the changes to {}", file)),
+                Ok(false) => self.output.display_system(&format!(
+                    "{} was not changed by the agent this session",
+                    file
+                )),
+                Err(e) => self.output.display_error(&e.to_string()),
+            }
+            return;
+        }
+        let changes = checkpoint::changes();
+        if changes.is_empty() {
+            self.output
+                .display_system("No files changed by the agent this session");
+            return;
+        }
+        let message = format!(
+            "Discard the agent's changes to {} file(s)?\n{}",
+            changes.len(),
+            checkpoint::summary(&changes)
+        );
+        if self.output.confirm(&message).await != Confirmation::Yes {
+            self.output.display_system("Nothing discarded");
+            return;
+        }
+        let mut discarded = 0;
+        for change in &changes {
+            match checkpoint::discard(&change.path) {
+                Ok(_) => discarded += 1,
+                Err(e) => self.output.display_error(&e.to_string()),
+            }
+        }
+        self.output
+            .display_system(&format!("Discarded the changes to {} file(s)", discarded));
+    }
+
+    fn handle_queue_command(&self, args: &str, queue: &mut VecDeque<String>) {
+        let parts: Vec<&str> = args.split_whitespace().collect();
+        let index = |s: &str| {
+            s.parse::<usize>()
+                .ok()
+                .filter(|n| *n >= 1 && *n <= queue.len())
+                .map(|n| n - 1)
+        };
+        match parts.as_slice() {
+            [] => {
+                if queue.is_empty() {
+                    self.output.display_system("Queue is empty");
+                }
+                for (i, message) in queue.iter().enumerate() {
+                    self.output
+                        .display_system(&format!("  {}. {}", i + 1, message));
+                }
+            }
+            ["clear"] => {
+                queue.clear();
+                self.output.display_system("Queue cleared");
+            }
+            ["drop", n] => match index(n) {
+                Some(i) => {
+                    queue.remove(i);
+                    self.output.display_system(&format!("Dropped message {}", n));
+                }
+                None => self.output.display_error(&format!("No queued message {}", n)),
+            },
+            ["move", from, to] => match (index(from), index(to)) {
+                (Some(from), Some(to)) => {
+                    if let Some(message) = queue.remove(from) {
+                        queue.insert(to, message);
+                    }
+                    self.output.display_system("Queue reordered");
+                }
+                _ => self.output.display_error("Usage: /queue move <from> <to>"),
+            },
+            _ => self
+                .output
+                .display_error("Usage: /queue [clear | drop <n> | move <from> <to>]"),
+        }
+    }
+
+    /// Estimates the assembled prompt size before sending it. Warns when it nears
+    /// the model's context window, and compacts older history when it would overflow.
+    /// Estimated tokens of the system prompt and context documents.
+    fn preamble_tokens(&self) -> usize {
+        self.factory
+            .as_ref()
+            .map(|(_, options)| {
+                tokens::estimate_tokens(&system_message(options))
+                    + options
+                        .context
+                        .iter()
+                        .map(|document| tokens::estimate_tokens(document))
+                        .sum::<usize>()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Compacts `history` when the prompt would nearly fill the context
+    /// window. Returns whether the user was warned about its size.
+    fn preflight(&self, input: &str, history: &mut Vec<Message>) -> bool {
+        let Some(window) = crate::models::context_window(&self.model) else {
+            return false;
+        };
+        let fixed = self.preamble_tokens() + tokens::estimate_tokens(input);
+        let mut used = fixed + tokens::estimate_history_tokens(history);
+
+        if used as f64 >= window as f64 * tokens::COMPACT_RATIO {
+            let budget = ((window as f64 * tokens::WARN_RATIO) as usize).saturating_sub(fixed);
+            let removed = tokens::compact_history(history, budget);
+            if removed > 0 {
+                self.output.display_system(&format!(
+                    "Context nearly full: compacted {} older messages to fit {}'s window",
+                    removed, self.model
+                ));
+                used = fixed + tokens::estimate_history_tokens(history);
+            }
+        }
+
+        let warn = used as f64 >= window as f64 * tokens::WARN_RATIO;
+        if warn {
+            self.output
+                .display_system(&context_warning(used, window, &self.model));
+        }
+        warn
+    }
+
+    /// `input` with the current git state in front when it changed since the
+    /// model last saw it, e.g. after the agent committed or edited files.
+    async fn with_git_changes(&self, input: &str) -> String {
+        if !self.refresh_git {
+            return input.to_string();
+        }
+        let Some(state) = GitState::read_current().await else {
+            return input.to_string();
+        };
+        let mut last = self.git_state.lock().unwrap();
+        if last.as_ref() == Some(&state) {
+            return input.to_string();
+        }
+        let prompt = git::with_note(&state, input);
+        *last = Some(state);
+        prompt
+    }
+
+    async fn prompt(&self, input: &str, mut history: Option<&mut Vec<Message>>) -> Result<String> {
+        let warned = match history.as_mut() {
+            Some(h) => self.preflight(input, h),
+            None => self.preflight(input, &mut Vec::new()),
+        };
+        let (model, agent) = match &self.simple_agent {
+            Some((model, agent)) if routing::classify(input) == Route::Simple => {
+                self.output.display_system(&format!("↳ {}", model));
+                (model, agent)
+            }
+            _ => (&self.model, &self.agent),
+        };
+        let turn = self.turns.fetch_add(1, Ordering::Relaxed) + 1;
+        let span = telemetry::turn_span(&self.session.id, turn, &self.provider, model);
+        let usage_before = *self.usage.lock().unwrap();
+        let tool_calls = Arc::new(AtomicUsize::new(0));
+        let started = std::time::Instant::now();
+        let first_token = Arc::new(OnceLock::new());
+        let tools_before = self.tool_stats.total_time();
+        let loops = self
+            .factory
+            .as_ref()
+            .map(|(_, options)| options.loops.clone());
+        if let Some(loops) = &loops {
+            loops.reset();
+        }
+        let answers = self
+            .factory
+            .as_ref()
+            .and_then(|(_, options)| options.answers.clone());
+        if let Some(answers) = &answers {
+            answers.reset();
+        }
+        let prompt = self.with_git_changes(&with_file_changes(input)).await;
+        self.output.display_thinking("Thinking...");
+        let truncated = Arc::new(AtomicBool::new(false));
+        let hook = LoggingHook {
+            output: self.output.clone(),
+            audit: self.audit.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            usage: self.usage.clone(),
+            tool_stats: self.tool_stats.clone(),
+            tool_call_limit: self.tool_call_limit,
+            tool_calls: tool_calls.clone(),
+            start_tokens: usage_before.total_tokens,
+            batch: self
+                .factory
+                .as_ref()
+                .map(|(_, options)| options.batch.clone()),
+            loops: loops.clone(),
+            answers: answers.clone(),
+            started,
+            first_token: first_token.clone(),
+            truncated: truncated.clone(),
+            context: crate::models::context_window(model)
+                .map(|window| (model.clone(), window, self.preamble_tokens())),
+            context_warned: AtomicBool::new(warned),
+        };
+        let mut response = self
+            .send(agent, &prompt, &hook, history.as_deref_mut())
+            .instrument(span.clone())
+            .await;
+        // Stitch the rest of an answer cut off at the output token limit
+        let mut continuations = 0;
+        while response.is_ok()
+            && history.is_some()
+            && continuations < MAX_CONTINUATIONS
+            && truncated.swap(false, Ordering::Relaxed)
+        {
+            continuations += 1;
+            self.output
+                .display_system("↳ Response hit the output token limit, continuing");
+            let rest = self
+                .send(agent, TRUNCATED_PROMPT, &hook, history.as_deref_mut())
+                .instrument(span.clone())
+                .await;
+            response = response.and_then(|text| rest.map(|rest| text + &rest));
+        }
         self.output.stop_thinking();
+        let usage = *self.usage.lock().unwrap();
+        telemetry::finish_turn(
+            &span,
+            &TokenUsage {
+                input_tokens: usage.input_tokens - usage_before.input_tokens,
+                output_tokens: usage.output_tokens - usage_before.output_tokens,
+                total_tokens: usage.total_tokens - usage_before.total_tokens,
+            },
+            tool_calls.load(Ordering::Relaxed),
+            response.as_ref().err().map(|e| e.to_string()).as_deref(),
+        );
+        let response = response.map_err(|e| {
+            let message = e.to_string();
+            if let Some(reason) = loops.as_ref().and_then(|loops| loops.stopped()) {
+                crate::PicocodeError::ToolLoop(reason)
+            } else if let Some(question) = answers.as_ref().and_then(|a| a.unanswered()) {
+                crate::PicocodeError::Unanswered(question)
+            } else if crate::ratelimit::is_rate_limit_error(&message) {
+                crate::PicocodeError::RateLimited(message)
+            } else if message.contains("MaxDepth") || message.contains("MaxTurn") {
+                crate::PicocodeError::ToolCallLimit(self.tool_call_limit)
+            } else {
+                crate::PicocodeError::Other(message)
+            }
+        })?;
+        let timing = TurnTiming {
+            total: started.elapsed(),
+            tools: self.tool_stats.total_time().saturating_sub(tools_before),
+            first_token: first_token.get().copied(),
+        };
+        self.turn_stats.lock().unwrap().record(&timing);
+        self.output.display_turn_timing(&timing);
         Ok(response.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockCompletionModel;
+    use crate::output::NoOutput;
+    use serde_json::json;
+
+    fn mock_agent(
+        model: &MockCompletionModel,
+        tool_call_limit: usize,
+    ) -> CodeAgent<MockCompletionModel> {
+        CodeAgent::new(
+            model.agent("mock").tool(ReadFile).build(),
+            Arc::new(NoOutput),
+            tool_call_limit,
+            "mock".into(),
+            "mock".into(),
+            true,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_last_prompt_skips_continuations() {
+        let history = vec![
+            Message::user("write the parser"),
+            Message::assistant("fn parse("),
+            Message::user(TRUNCATED_PROMPT),
+            Message::assistant("input: &str)"),
+            Message::user(git::with_note(
+                &git::GitState {
+                    branch: "main".into(),
+                    changed: Vec::new(),
+                    commits: Vec::new(),
+                },
+                CONTINUE_PROMPT,
+            )),
+        ];
+        assert_eq!(last_prompt_index(&history), Some(0));
+        assert_eq!(
+            prompt_text(&history[0]).as_deref(),
+            Some("write the parser")
+        );
+        assert_eq!(prompt_text(&Message::user(RESUME_PROMPT)), None);
+    }
+
+    #[tokio::test]
+    async fn test_tool_round_trip() {
+        let model = MockCompletionModel::new()
+            .tool_call(
+                "read_file",
+                json!({"path": "Cargo.toml", "offset": 0, "limit": 1}),
+            )
+            .text("A Rust crate.");
+        let agent = mock_agent(&model, 5);
+        let answer = agent
+            .run_once("What is this project?".into())
+            .await
+            .unwrap();
+        assert_eq!(answer, "A Rust crate.");
+
+        let requests = model.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].tools, ["read_file"]);
+        assert_eq!(unanswered_tool_results(&requests[1].messages), 1);
+        assert_eq!(model.remaining(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_limit() {
+        let mut model = MockCompletionModel::new();
+        for _ in 0..4 {
+            model = model.tool_call(
+                "read_file",
+                json!({"path": "README.md", "offset": 0, "limit": 1}),
+            );
+        }
+        let agent = mock_agent(&model, 1);
+        let err = agent.run_once("Read forever".into()).await.unwrap_err();
+        assert!(
+            matches!(err, crate::PicocodeError::ToolCallLimit(1)),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_allowed_tool() {
+        let rules = Rules::<String> {
+            yolo: false,
+            always: Arc::new(AtomicBool::new(false)),
+            policy: None,
+            command: None,
+            allowed_prefixes: Arc::new(Mutex::new(Vec::new())),
+            allowed_tool: true,
+        };
+        let args = "rm -rf target".to_string();
+        assert_eq!(rules.unasked(&args, Approval::Default), Some("always"));
+        assert_eq!(rules.unasked(&args, Approval::Confirm("dangerous")), None);
+        let command = Rules {
+            command: Some(|args: &String| Some(args.clone())),
+            ..rules
+        };
+        assert_eq!(command.unasked(&args, Approval::Default), None);
+    }
+
+    #[test]
+    fn test_is_truncated() {
+        assert!(is_truncated(&json!({"stop_reason": "max_tokens"})));
+        assert!(!is_truncated(&json!({"stop_reason": "end_turn"})));
+        assert!(is_truncated(
+            &json!({"choices": [{"finish_reason": "length"}]})
+        ));
+        assert!(!is_truncated(
+            &json!({"choices": [{"finish_reason": "tool_calls"}]})
+        ));
+        assert!(is_truncated(
+            &json!({"candidates": [{"finishReason": "MAX_TOKENS"}]})
+        ));
+        assert!(is_truncated(&json!({"done_reason": "length"})));
+        assert!(!is_truncated(&json!(null)));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::storage::{self, Storage};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+pub const DEFAULT_AUDIT_LOG: &str = ".picocode/audit.jsonl";
+
+const RESULT_SUMMARY_LEN: usize = 500;
+
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    timestamp: String,
+    session_id: &'a str,
+    tool: &'a str,
+    args: serde_json::Value,
+    result: String,
+    decision: &'a str,
+}
+
+/// Append-only JSONL log of every tool invocation, written regardless of the
+/// `Output` implementation in use, to the project's storage.
+pub struct AuditLog {
+    storage: Arc<dyn Storage>,
+    path: String,
+    session_id: String,
+    // Confirmation decisions reported by `Guard`, consumed in call order when
+    // the matching tool result is recorded.
+    decisions: Mutex<HashMap<String, VecDeque<&'static str>>>,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<String>, session_id: impl Into<String>) -> Self {
+        Self {
+            storage: storage::project(),
+            path: path.into(),
+            session_id: session_id.into(),
+            decisions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    pub fn record_decision(&self, tool: &str, decision: &'static str) {
+        self.decisions
+            .lock()
+            .unwrap()
+            .entry(tool.to_string())
+            .or_default()
+            .push_back(decision);
+    }
+
+    pub fn record(&self, tool: &str, args: &str, result: &str) {
+        let decision = self
+            .decisions
+            .lock()
+            .unwrap()
+            .get_mut(tool)
+            .and_then(|q| q.pop_front())
+            .unwrap_or("not_required");
+        let record = AuditRecord {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            session_id: &self.session_id,
+            tool,
+            args: serde_json::from_str(args)
+                .unwrap_or_else(|_| serde_json::Value::String(args.to_string())),
+            result: summarize(result),
+            decision,
+        };
+        if let Err(e) = self.append(&record) {
+            tracing::warn!(target: "picocode", "Failed to write audit log: {}", e);
+        }
+    }
+
+    fn append(&self, record: &AuditRecord) -> crate::Result<()> {
+        let line = format!("{}\n", serde_json::to_string(record)?);
+        self.storage.append(&self.path, &line)
+    }
+}
+
+fn summarize(result: &str) -> String {
+    match result.char_indices().nth(RESULT_SUMMARY_LEN) {
+        Some((idx, _)) => format!("{}... ({} bytes)", &result[..idx], result.len()),
+        None => result.to_string(),
+    }
+}
@@ -0,0 +1,322 @@
+//! Confirm-once batching: when a model response asks for several tool calls
+//! that each need confirmation, they are listed together (file edits as diffs)
+//! and confirmed with one answer instead of one prompt per call.
+
+use crate::config::{ConfirmDefault, ConfirmationConfig};
+use crate::output::Confirmation;
+use crate::Output;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Diff lines shown per operation before cutting off.
+const MAX_DIFF_LINES: usize = 40;
+
+/// The args of a call, as its guard would run it, when the guard would ask
+/// before running it; `None` when it wouldn't, or would ask on its own.
+pub type NeedsConfirmation = Arc<dyn Fn(&Value) -> Option<Value> + Send + Sync>;
+
+/// Answer to the current batch, handed out once per call it covered. Calls
+/// are told apart by tool and args, so a call that wasn't in the batch can't
+/// use the answer meant for another call of the same tool.
+struct Decided {
+    remaining: HashMap<(String, String), usize>,
+    decision: &'static str,
+}
+
+pub struct Batch {
+    confirmation: ConfirmationConfig,
+    tools: Mutex<HashMap<&'static str, NeedsConfirmation>>,
+    /// Per-tool "always" answers, shared by every build of the agent.
+    always: Mutex<HashMap<&'static str, Arc<AtomicBool>>>,
+    decided: Mutex<Option<Decided>>,
+}
+
+impl Batch {
+    pub fn new(confirmation: ConfirmationConfig) -> Self {
+        Self {
+            confirmation,
+            tools: Mutex::new(HashMap::new()),
+            always: Mutex::new(HashMap::new()),
+            decided: Mutex::new(None),
+        }
+    }
+
+    /// Makes calls of `tool` part of batches when `needs` says they would be
+    /// confirmed.
+    pub fn register(&self, tool: &'static str, needs: NeedsConfirmation) {
+        self.tools.lock().unwrap().insert(tool, needs);
+    }
+
+    /// Set when the user answered "always" for `tool`.
+    pub fn always(&self, tool: &'static str) -> Arc<AtomicBool> {
+        self.always.lock().unwrap().entry(tool).or_default().clone()
+    }
+
+    /// The calls, of those a response asked for, that would be confirmed,
+    /// with their args as the guards will see them.
+    fn pending(&self, calls: &[(String, Value)]) -> Vec<(String, Value)> {
+        let tools = self.tools.lock().unwrap();
+        calls
+            .iter()
+            .filter_map(|(tool, args)| {
+                let needs = tools.get(tool.as_str())?;
+                needs(args).map(|args| (tool.clone(), args))
+            })
+            .collect()
+    }
+
+    fn decide(&self, calls: &[(String, Value)], decision: &'static str) {
+        let mut remaining = HashMap::new();
+        for (tool, args) in calls {
+            *remaining
+                .entry((tool.clone(), args.to_string()))
+                .or_default() += 1;
+        }
+        *self.decided.lock().unwrap() = Some(Decided {
+            remaining,
+            decision,
+        });
+    }
+
+    /// Asks once about the `calls` of a model response that need confirmation,
+    /// when there are at least two. Their guards then use the answer via
+    /// [`Batch::take`] instead of asking again.
+    pub async fn confirm(&self, calls: &[(String, Value)], output: &Arc<dyn Output>) {
+        *self.decided.lock().unwrap() = None;
+        let pending = self.pending(calls);
+        if pending.len() < 2 {
+            return;
+        }
+        let message = format!(
+            "{} operations pending:\n{}Confirm all {}?",
+            pending.len(),
+            render(&pending),
+            pending.len()
+        );
+        let answer =
+            crate::output::confirm_within(output, &message, None, self.confirmation.timeout).await;
+        let decision = match answer {
+            Some(Confirmation::Always) => {
+                let tools = self.tools.lock().unwrap();
+                for (tool, _) in &pending {
+                    if let Some((name, _)) = tools.get_key_value(tool.as_str()) {
+                        self.always(name).store(true, Ordering::Relaxed);
+                    }
+                }
+                "always"
+            }
+            Some(Confirmation::No) => "denied",
+            Some(_) => "batch",
+            None => {
+                let (decision, outcome) = match self.confirmation.default {
+                    ConfirmDefault::Deny => ("timeout_denied", "denied"),
+                    ConfirmDefault::Allow => ("timeout_allowed", "allowed"),
+                };
+                output.display_error(&format!(
+                    "No answer for {} operations within {}s, {}",
+                    pending.len(),
+                    self.confirmation.timeout.unwrap_or_default(),
+                    outcome
+                ));
+                decision
+            }
+        };
+        self.decide(&pending, decision);
+    }
+
+    /// The batch decision for a call of `tool` with `args`, or `None` when the
+    /// call wasn't part of a batch and must be confirmed on its own.
+    pub fn take(&self, tool: &str, args: &Value) -> Option<&'static str> {
+        let mut decided = self.decided.lock().unwrap();
+        let decided = decided.as_mut()?;
+        let remaining = decided
+            .remaining
+            .get_mut(&(tool.to_string(), args.to_string()))
+            .filter(|n| **n > 0)?;
+        *remaining -= 1;
+        Some(decided.decision)
+    }
+}
+
+/// The operations of a batch, numbered, with diffs for file writes and edits.
+fn render(calls: &[(String, Value)]) -> String {
+    calls
+        .iter()
+        .enumerate()
+        .map(|(i, (tool, args))| format!("{}. {}\n", i + 1, describe(tool, args)))
+        .collect()
+}
+
+fn describe(tool: &str, args: &Value) -> String {
+    let text = |key: &str| args.get(key).and_then(Value::as_str).unwrap_or_default();
+    let path = text("path");
+    match tool {
+        "write_file" => match read(path) {
+            Ok(Some(before)) => format!(
+                "write_file {}\n{}",
+                path,
+                diff(path, &before, text("content"))
+            ),
+            Ok(None) => format!(
+                "write_file {} (new file, {} lines)",
+                path,
+                text("content").lines().count()
+            ),
+            Err(reason) => format!("write_file {} ({})", path, reason),
+        },
+        "edit_file" => match read(path) {
+            Ok(Some(before)) => match edited(&before, args) {
+                Some(after) => format!("edit_file {}\n{}", path, diff(path, &before, &after)),
+                None => format!("edit_file {} (text to replace not found)", path),
+            },
+            Ok(None) => format!("edit_file {} (text to replace not found)", path),
+            Err(reason) => format!("edit_file {} ({})", path, reason),
+        },
+        "bash" => format!("bash: {}", text("cmd")),
+        _ => format!("{} {}", tool, args),
+    }
+}
+
+/// `before` with the replacement of an `edit_file` call made, `None` when the
+/// text to replace isn't there.
+fn edited(before: &str, args: &Value) -> Option<String> {
+    let text = |key: &str| args.get(key).and_then(Value::as_str).unwrap_or_default();
+    let (old, new) = (text("old"), text("new"));
+    if old.is_empty() || !before.contains(old) {
+        return None;
+    }
+    Some(if args.get("all").and_then(Value::as_bool) == Some(true) {
+        before.replace(old, new)
+    } else {
+        before.replacen(old, new, 1)
+    })
+}
+
+/// The whole diff a `write_file` or `edit_file` call would make, for "Show
+/// diff" when confirming it. `None` for other tools and when there's nothing
+/// to show.
+pub fn preview(tool: &str, args: &Value) -> Option<String> {
+    let path = args.get("path").and_then(Value::as_str)?;
+    let before = read(path).ok()?.unwrap_or_default();
+    let after = match tool {
+        "write_file" => args.get("content").and_then(Value::as_str)?.to_string(),
+        "edit_file" => edited(&before, args)?,
+        _ => return None,
+    };
+    Some(unified_diff(path, &before, &after))
+}
+
+/// Current content of a file, `None` if it doesn't exist yet. Protected files
+/// aren't read, so their content doesn't end up in logs.
+fn read(path: &str) -> Result<Option<String>, &'static str> {
+    if crate::secrets::is_protected(Path::new(path)) {
+        return Err("protected file, diff not shown");
+    }
+    match std::fs::read_to_string(path) {
+        Ok(text) => Ok(Some(text.replace("\r\n", "\n"))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(_) => Err("unreadable, diff not shown"),
+    }
+}
+
+fn unified_diff(path: &str, before: &str, after: &str) -> String {
+    similar::TextDiff::from_lines(before, after)
+        .unified_diff()
+        .context_radius(2)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string()
+}
+
+fn diff(path: &str, before: &str, after: &str) -> String {
+    let diff = unified_diff(path, before, after);
+    let lines: Vec<&str> = diff.lines().collect();
+    if lines.len() <= MAX_DIFF_LINES {
+        return diff.trim_end().to_string();
+    }
+    format!(
+        "{}\n... {} more diff lines",
+        lines[..MAX_DIFF_LINES].join("\n"),
+        lines.len() - MAX_DIFF_LINES
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        std::fs::write(&file, "one\ntwo\n").unwrap();
+        let path = file.to_str().unwrap();
+
+        let batch = Batch::new(ConfirmationConfig::default());
+        batch.register("edit_file", Arc::new(|args| Some(args.clone())));
+        batch.register(
+            "bash",
+            Arc::new(|args| (args["cmd"] != "ls").then(|| args.clone())),
+        );
+        let calls = vec![
+            (
+                "edit_file".to_string(),
+                json!({"path": path, "old": "two", "new": "2", "all": false}),
+            ),
+            ("bash".to_string(), json!({"cmd": "ls"})),
+            ("bash".to_string(), json!({"cmd": "make"})),
+            ("read_file".to_string(), json!({"path": path})),
+        ];
+        let pending = batch.pending(&calls);
+        assert_eq!(pending.len(), 2);
+        let rendered = render(&pending);
+        assert!(rendered.starts_with(&format!("1. edit_file {}\n", path)));
+        assert!(rendered.contains("-two\n+2\n"));
+        assert!(rendered.ends_with("2. bash: make\n"));
+
+        batch.decide(&pending, "batch");
+        assert_eq!(batch.take("bash", &calls[1].1), None);
+        assert_eq!(batch.take("bash", &calls[2].1), Some("batch"));
+        assert_eq!(batch.take("bash", &calls[2].1), None);
+        assert_eq!(batch.take("edit_file", &calls[0].1), Some("batch"));
+        assert_eq!(batch.take("remove", &json!({"path": path})), None);
+
+        let missing = dir.path().join("new.txt");
+        let missing = missing.to_str().unwrap();
+        assert_eq!(
+            describe("write_file", &json!({"path": missing, "content": "a\nb\n"})),
+            format!("write_file {} (new file, 2 lines)", missing)
+        );
+        let edit = json!({"path": path, "old": "one", "new": "1", "all": false});
+        assert!(preview("edit_file", &edit).unwrap().contains("-one\n+1\n"));
+        assert!(
+            preview("write_file", &json!({"path": missing, "content": "a\n"}))
+                .unwrap()
+                .contains("+a\n")
+        );
+        assert!(preview("bash", &json!({"cmd": "ls"})).is_none());
+    }
+
+    #[test]
+    fn test_forced_call_not_batched() {
+        let batch = Batch::new(ConfirmationConfig::default());
+        // Guards leave out calls they confirm for a reason, such as dangerous commands
+        batch.register(
+            "bash",
+            Arc::new(|args| (args["cmd"] != "rm -rf ~").then(|| args.clone())),
+        );
+        let calls: Vec<(String, Value)> = ["make", "rm -rf ~", "make test"]
+            .iter()
+            .map(|cmd| ("bash".to_string(), json!({ "cmd": cmd })))
+            .collect();
+        let pending = batch.pending(&calls);
+        assert_eq!(pending.len(), 2);
+        batch.decide(&pending, "batch");
+        assert_eq!(batch.take("bash", &calls[1].1), None);
+        assert_eq!(batch.take("bash", &calls[0].1), Some("batch"));
+        assert_eq!(batch.take("bash", &calls[2].1), Some("batch"));
+    }
+}
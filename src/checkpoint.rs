@@ -0,0 +1,293 @@
+//! The contents files had before the agent first changed them, kept in memory
+//! for the rest of the run, so `/diff` can show the cumulative effect of a
+//! session before it is committed and `/discard` can revert it per file. File
+//! tools save a file before writing it; changes made through `bash` aren't seen.
+
+use crate::{PicocodeError, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+/// Larger files are reported as changed without keeping their contents.
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Original {
+    Missing,
+    Content(Vec<u8>),
+    /// Too large or unreadable: the change is known, the contents aren't.
+    Unsaved,
+}
+
+static ORIGINALS: LazyLock<Mutex<BTreeMap<PathBuf, Original>>> = LazyLock::new(Default::default);
+
+fn read(path: &Path) -> Original {
+    match std::fs::metadata(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Original::Missing,
+        Ok(metadata) if metadata.is_file() && metadata.len() <= MAX_FILE_BYTES => {
+            std::fs::read(path).map_or(Original::Unsaved, Original::Content)
+        }
+        _ => Original::Unsaved,
+    }
+}
+
+/// Files under `dir`, skipping those ignored by git like tools do.
+fn files(dir: &Path) -> Vec<PathBuf> {
+    ignore::WalkBuilder::new(dir)
+        .hidden(false)
+        .require_git(false)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|ft| ft.is_file()))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+fn save_file(path: &Path) {
+    let mut originals = ORIGINALS.lock().unwrap();
+    if !originals.contains_key(path) {
+        originals.insert(path.to_path_buf(), read(path));
+    }
+}
+
+/// Saves `path`, or the files under it, before a tool changes it. Files saved
+/// earlier keep their first contents.
+pub fn save(path: &Path) {
+    if path.is_dir() {
+        files(path).iter().for_each(|file| save_file(file));
+    } else {
+        save_file(path);
+    }
+}
+
+/// Saves what copying or moving `src` to `dst` creates or replaces.
+pub fn save_destination(src: &Path, dst: &Path) {
+    save(dst);
+    if src.is_dir() {
+        for file in files(src) {
+            if let Ok(relative) = file.strip_prefix(src) {
+                save_file(&dst.join(relative));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+    /// Lines added and removed, 0 for binary and unsaved files.
+    pub added: usize,
+    pub removed: usize,
+}
+
+fn text(original: &Original) -> Option<&str> {
+    match original {
+        Original::Missing => Some(""),
+        Original::Content(bytes) => std::str::from_utf8(bytes).ok(),
+        Original::Unsaved => None,
+    }
+}
+
+fn change(path: &Path, before: &Original) -> Option<FileChange> {
+    let after = read(path);
+    let kind = match (before, &after) {
+        (Original::Missing, Original::Missing) => return None,
+        (Original::Missing, _) => ChangeKind::Created,
+        (_, Original::Missing) => ChangeKind::Deleted,
+        (Original::Content(a), Original::Content(b)) if a == b => return None,
+        _ => ChangeKind::Modified,
+    };
+    let (mut added, mut removed) = (0, 0);
+    if let (Some(before), Some(after)) = (text(before), text(&after)) {
+        for change in similar::TextDiff::from_lines(before, after).iter_all_changes() {
+            match change.tag() {
+                similar::ChangeTag::Insert => added += 1,
+                similar::ChangeTag::Delete => removed += 1,
+                similar::ChangeTag::Equal => {}
+            }
+        }
+    }
+    Some(FileChange {
+        path: path.to_path_buf(),
+        kind,
+        added,
+        removed,
+    })
+}
+
+/// Saved files that differ from their saved contents, by path.
+pub fn changes() -> Vec<FileChange> {
+    ORIGINALS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|(path, original)| change(path, original))
+        .collect()
+}
+
+/// `path` relative to the current directory where possible.
+pub fn display_path(path: &Path) -> String {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    path.strip_prefix(&cwd)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+/// One line per change, marked `+` (created), `!` (modified) or `-`
+/// (deleted), and a total.
+pub fn summary(changes: &[FileChange]) -> String {
+    let mut lines: Vec<String> = changes
+        .iter()
+        .map(|change| {
+            let (mark, note) = match change.kind {
+                ChangeKind::Created => ('+', "new, "),
+                ChangeKind::Modified => ('!', ""),
+                ChangeKind::Deleted => ('-', "deleted, "),
+            };
+            format!(
+                "{} {} ({}+{} -{})",
+                mark,
+                display_path(&change.path),
+                note,
+                change.added,
+                change.removed
+            )
+        })
+        .collect();
+    lines.push(format!(
+        "{} file(s) changed, +{} -{}",
+        changes.len(),
+        changes.iter().map(|c| c.added).sum::<usize>(),
+        changes.iter().map(|c| c.removed).sum::<usize>()
+    ));
+    lines.join("\n")
+}
+
+/// Unified diff of `path` from its saved contents, `None` if the agent didn't
+/// change it.
+pub fn diff(path: &Path) -> Option<String> {
+    let original = ORIGINALS.lock().unwrap().get(path).cloned()?;
+    change(path, &original)?;
+    let name = display_path(path);
+    let current = read(path);
+    let (Some(before), Some(after)) = (text(&original), text(&current)) else {
+        return Some(format!("{} changed (binary or too large to diff)", name));
+    };
+    Some(
+        similar::TextDiff::from_lines(before, after)
+            .unified_diff()
+            .header(&format!("a/{}", name), &format!("b/{}", name))
+            .to_string(),
+    )
+}
+
+/// Accepts the agent's changes to `path`: it is no longer listed or
+/// restored. Returns whether the agent had changed it.
+pub fn keep(path: &Path) -> bool {
+    let mut originals = ORIGINALS.lock().unwrap();
+    let changed = originals
+        .get(path)
+        .is_some_and(|original| change(path, original).is_some());
+    originals.remove(path);
+    changed
+}
+
+/// Restores `path` to its saved contents, removing it if the agent created
+/// it. Returns whether the agent had changed it.
+pub fn discard(path: &Path) -> Result<bool> {
+    let mut originals = ORIGINALS.lock().unwrap();
+    let Some(original) = originals.get(path) else {
+        return Ok(false);
+    };
+    if change(path, original).is_none() {
+        originals.remove(path);
+        return Ok(false);
+    }
+    match original {
+        Original::Missing => std::fs::remove_file(path)?,
+        Original::Content(bytes) => {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, bytes)?;
+        }
+        Original::Unsaved => {
+            return Err(PicocodeError::Other(format!(
+                "{} was too large or unreadable to save, so it can't be restored",
+                display_path(path)
+            )))
+        }
+    }
+    originals.remove(path);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("lib.rs"), "fn a() {}\nfn b() {}\n").unwrap();
+        std::fs::write(root.join("old.rs"), "fn old() {}\n").unwrap();
+        std::fs::write(root.join("same.rs"), "fn same() {}\n").unwrap();
+
+        save(&root.join("lib.rs"));
+        std::fs::write(root.join("lib.rs"), "fn a() {}\nfn c() {}\n").unwrap();
+        // Later saves keep the contents from before the first change
+        save(&root.join("lib.rs"));
+        save(&root.join("new.rs"));
+        std::fs::write(root.join("new.rs"), "fn new() {}\n").unwrap();
+        save(root);
+        std::fs::remove_file(root.join("old.rs")).unwrap();
+
+        let ours: Vec<FileChange> = changes()
+            .into_iter()
+            .filter(|c| c.path.starts_with(root))
+            .collect();
+        let kinds: Vec<ChangeKind> = ours.iter().map(|c| c.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                ChangeKind::Modified,
+                ChangeKind::Created,
+                ChangeKind::Deleted
+            ]
+        );
+        assert_eq!((ours[0].added, ours[0].removed), (1, 1));
+        assert!(summary(&ours).ends_with("3 file(s) changed, +2 -2"));
+
+        let patch = diff(&root.join("lib.rs")).unwrap();
+        assert!(patch.contains("-fn b() {}"));
+        assert!(patch.contains("+fn c() {}"));
+        assert!(diff(&root.join("same.rs")).is_none());
+
+        assert!(discard(&root.join("lib.rs")).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(root.join("lib.rs")).unwrap(),
+            "fn a() {}\nfn b() {}\n"
+        );
+        assert!(discard(&root.join("new.rs")).unwrap());
+        assert!(!root.join("new.rs").exists());
+        assert!(discard(&root.join("old.rs")).unwrap());
+        assert!(root.join("old.rs").exists());
+        assert!(!discard(&root.join("same.rs")).unwrap());
+
+        save(&root.join("lib.rs"));
+        std::fs::write(root.join("lib.rs"), "fn kept() {}\n").unwrap();
+        assert!(keep(&root.join("lib.rs")));
+        assert!(!keep(&root.join("lib.rs")));
+        assert!(diff(&root.join("lib.rs")).is_none());
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{PicocodeError, Result};
+use regex::Regex;
+use std::sync::LazyLock;
+
+static FENCE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^```[^\n]*\n([\s\S]*?)^```\s*$").unwrap());
+
+/// Kept open for the whole process: on X11 the clipboard is served by its owner,
+/// so copied text would vanish if the handle was dropped right after `/copy`.
+#[cfg(feature = "clipboard")]
+static CLIPBOARD: std::sync::Mutex<Option<arboard::Clipboard>> = std::sync::Mutex::new(None);
+
+#[cfg(feature = "clipboard")]
+fn with_clipboard<T>(
+    f: impl FnOnce(&mut arboard::Clipboard) -> std::result::Result<T, arboard::Error>,
+) -> Result<T> {
+    let mut guard = CLIPBOARD.lock().unwrap();
+    if guard.is_none() {
+        *guard = Some(
+            arboard::Clipboard::new()
+                .map_err(|e| PicocodeError::Other(format!("Clipboard unavailable: {}", e)))?,
+        );
+    }
+    f(guard.as_mut().unwrap()).map_err(|e| PicocodeError::Other(format!("Clipboard error: {}", e)))
+}
+
+#[cfg(feature = "clipboard")]
+pub fn copy(text: &str) -> Result<()> {
+    with_clipboard(|c| c.set_text(text.to_string()))
+}
+
+#[cfg(feature = "clipboard")]
+pub fn paste() -> Result<String> {
+    with_clipboard(|c| c.get_text())
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn copy(_text: &str) -> Result<()> {
+    Err(unavailable())
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn paste() -> Result<String> {
+    Err(unavailable())
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn unavailable() -> PicocodeError {
+    PicocodeError::Other("The clipboard needs picocode built with `--features clipboard`".into())
+}
+
+/// Contents of the last fenced code block in a markdown response.
+pub fn last_code_block(text: &str) -> Option<String> {
+    FENCE_RE
+        .captures_iter(text)
+        .last()
+        .map(|cap| cap[1].to_string())
+}
+
+/// Prompt for `/paste [text]`: the typed text, followed by the clipboard contents.
+pub fn paste_prompt(text: &str, clipboard: &str) -> String {
+    let text = text.trim();
+    if text.is_empty() {
+        clipboard.to_string()
+    } else {
+        format!("{}\n\n```\n{}\n```", text, clipboard.trim_end())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_code_block() {
+        let response =
+            "Try this:\n```rust\nfn a() {}\n```\nor:\n```\nfn b() {}\nfn c() {}\n```\nDone.";
+        assert_eq!(
+            last_code_block(response).as_deref(),
+            Some("fn b() {}\nfn c() {}\n")
+        );
+        assert_eq!(last_code_block("no code"), None);
+        assert_eq!(
+            paste_prompt(" explain ", "x = 1\n"),
+            "explain\n\n```\nx = 1\n```"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! User-defined slash commands: each markdown file in `.picocode/commands/`
+//! is a prompt template run as `/<file name> [args]`, with `$ARGUMENTS`
+//! replaced by the arguments.
+
+use std::path::Path;
+
+/// Directory custom commands are loaded from.
+pub const COMMANDS_DIR: &str = ".picocode/commands";
+
+/// Replaced by the text typed after the command name.
+const ARGUMENTS: &str = "$ARGUMENTS";
+
+/// Built-in commands, which take precedence over custom ones of the same name.
+const BUILTIN: &[&str] = &[
+    "plan",
+    "code",
+    "review",
+    "go",
+    "approve",
+    "continue",
+    "resume-turn",
+    "retry",
+    "edit",
+    "write",
+    "copy",
+    "paste",
+    "compose",
+    "load",
+    "open",
+    "diff",
+    "keep",
+    "discard",
+    "persona",
+    "remember",
+    "memory",
+    "history",
+    "queue",
+    "help",
+    "q",
+    "exit",
+];
+
+#[derive(Debug, Clone)]
+pub struct CustomCommand {
+    pub name: String,
+    /// The `description:` front matter, or else the first line of the prompt.
+    pub description: String,
+    template: String,
+}
+
+impl CustomCommand {
+    fn parse(name: &str, text: &str) -> Self {
+        let (description, template) = match front_matter(text) {
+            Some((header, body)) => (
+                header
+                    .lines()
+                    .find_map(|line| line.strip_prefix("description:"))
+                    .map(|d| d.trim().trim_matches('"').to_string()),
+                body,
+            ),
+            None => (None, text),
+        };
+        let template = template.trim().to_string();
+        let description = description.unwrap_or_else(|| {
+            template
+                .lines()
+                .find(|line| !line.trim().is_empty())
+                .unwrap_or_default()
+                .trim_start_matches('#')
+                .trim()
+                .to_string()
+        });
+        Self {
+            name: name.to_string(),
+            description,
+            template,
+        }
+    }
+
+    /// The prompt for `args`. Templates without `$ARGUMENTS` get the arguments
+    /// appended.
+    pub fn expand(&self, args: &str) -> String {
+        let args = args.trim();
+        if self.template.contains(ARGUMENTS) {
+            self.template.replace(ARGUMENTS, args)
+        } else if args.is_empty() {
+            self.template.clone()
+        } else {
+            format!("{}\n\n{}", self.template, args)
+        }
+    }
+}
+
+/// The header and body of text starting with a `---` delimited block.
+fn front_matter(text: &str) -> Option<(&str, &str)> {
+    let rest = text.strip_prefix("---")?.trim_start_matches([' ', '\t']);
+    let rest = rest
+        .strip_prefix('\n')
+        .or_else(|| rest.strip_prefix("\r\n"))?;
+    let end = rest.find("\n---")?;
+    let body = &rest[end + 4..];
+    Some((&rest[..end], body.split_once('\n').map_or("", |(_, b)| b)))
+}
+
+/// Loads the `*.md` files in `dir`, sorted by name, with `{{include}}`
+/// directives resolved. Also returns notes about files that were skipped.
+pub fn load(dir: &Path) -> (Vec<CustomCommand>, Vec<String>) {
+    let mut commands = Vec::new();
+    let mut notes = Vec::new();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return (commands, notes);
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            notes.push(format!(
+                "{}: command names can't contain spaces",
+                path.display()
+            ));
+            continue;
+        }
+        if BUILTIN.contains(&name) {
+            notes.push(format!(
+                "{}: /{} is a built-in command",
+                path.display(),
+                name
+            ));
+            continue;
+        }
+        let text = std::fs::read_to_string(&path)
+            .map_err(crate::PicocodeError::Io)
+            .and_then(|text| crate::config::resolve_includes(&text, dir));
+        match text {
+            Ok(text) => commands.push(CustomCommand::parse(name, &text)),
+            Err(e) => notes.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    (commands, notes)
+}
+
+/// The custom command `input` invokes, and its arguments.
+pub fn find<'a>(
+    commands: &'a [CustomCommand],
+    input: &'a str,
+) -> Option<(&'a CustomCommand, &'a str)> {
+    let rest = input.strip_prefix('/')?;
+    let (name, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    commands
+        .iter()
+        .find(|command| command.name == name)
+        .map(|command| (command, args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_and_expand() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("fix-tests.md"),
+            "---\ndescription: Fix failing tests\n---\nRun the tests for $ARGUMENTS and fix failures.\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("explain.md"),
+            "# Explain code\nExplain it simply.",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("help.md"), "Shadowed").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "Not a command").unwrap();
+
+        let (commands, notes) = load(dir.path());
+        let names: Vec<&str> = commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["explain", "fix-tests"]);
+        assert_eq!(notes.len(), 1);
+        assert_eq!(commands[0].description, "Explain code");
+        assert_eq!(commands[1].description, "Fix failing tests");
+
+        let (command, args) = find(&commands, "/fix-tests src/parser").unwrap();
+        assert_eq!(
+            command.expand(args),
+            "Run the tests for src/parser and fix failures."
+        );
+        let (command, args) = find(&commands, "/explain").unwrap();
+        assert_eq!(command.expand(args), "# Explain code\nExplain it simply.");
+        assert_eq!(
+            command.expand("lib.rs"),
+            "# Explain code\nExplain it simply.\n\nlib.rs"
+        );
+        assert!(find(&commands, "/fix").is_none());
+        assert!(find(&commands, "fix-tests").is_none());
+    }
+}
@@ -1,9 +1,15 @@
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
 
-#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+static INCLUDE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\{\{\s*include\s+"([^"]+)"\s*\}\}"#).unwrap());
+
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     #[serde(default)]
     pub agent_prompt: Option<String>,
@@ -13,20 +19,430 @@ pub struct Config {
     pub tool_config: HashMap<String, ToolSettings>,
     #[serde(default)]
     pub recipes: HashMap<String, Recipe>,
+    /// Where tool invocations are audited (JSONL). Set to `null` to disable.
+    #[serde(default = "default_audit_log")]
+    pub audit_log: Option<String>,
+    /// Transcript of everything displayed, as text or JSONL (by extension). Also set with `--log-file`.
+    #[serde(default)]
+    pub log_file: Option<String>,
+    /// Per-provider request pacing, keyed by provider name.
+    #[serde(default)]
+    pub rate_limits: HashMap<String, RateLimit>,
+    /// Per-provider cap on the tokens of one response. Responses cut off at
+    /// the cap are continued automatically.
+    #[serde(default)]
+    pub max_output_tokens: HashMap<String, u64>,
+    /// How requests rotate over a provider's API keys. The keys themselves
+    /// only come from the environment, e.g. `ANTHROPIC_API_KEY_1`.
+    #[serde(default)]
+    pub api_keys: HashMap<String, ApiKeys>,
+    #[serde(default)]
+    pub web_search: WebSearchConfig,
+    /// GitLab instance and project for the `gl_*` tools (enabled by `GITLAB_TOKEN`).
+    #[serde(default)]
+    pub gitlab: GitLabConfig,
+    /// Deployments, endpoint and API version for the `azure` provider.
+    #[serde(default)]
+    pub azure: AzureConfig,
+    /// Run the bash tool inside a container. Also enabled with `--sandbox`.
+    #[serde(default)]
+    pub sandbox: Option<crate::sandbox::SandboxConfig>,
+    /// Extra directories tools may access besides the current one (e.g. `../shared-lib`).
+    #[serde(default)]
+    pub workspaces: Vec<String>,
+    /// Timeout and fallback decision for tool confirmations.
+    #[serde(default)]
+    pub confirmation: ConfirmationConfig,
+    /// Bell and desktop notifications when picocode is waiting for you.
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+    /// Reviewer used by `--verify` and recipes with `verify: true`.
+    #[serde(default)]
+    pub verifier: VerifierConfig,
+    /// Send simple turns to a cheaper model.
+    #[serde(default)]
+    pub routing: Option<RoutingConfig>,
+    /// Summarize large tool results with a cheap model.
+    #[serde(default)]
+    pub tool_summaries: Option<SummaryConfig>,
+    /// Globs of files tools may not read, besides `.env` and `.env.*`.
+    #[serde(default)]
+    pub protected_files: Vec<String>,
+    /// Where `picocode models refresh` gets the model manifest, and the key it is signed with.
+    #[serde(default)]
+    pub models_manifest: ManifestConfig,
+    /// Language for replies, commit messages and plans: a name like `Japanese`
+    /// or a locale like `ja_JP.UTF-8`. Also set with `--language`.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Sessions start in plan mode without editing tools, which become
+    /// available once the plan is accepted with `/approve`. One-shot runs and
+    /// recipes, where nobody can approve, only plan.
+    #[serde(default)]
+    pub require_plan_approval: bool,
+    /// Identical tool calls in a row that count as a loop (default 3, 0 turns
+    /// loop detection off).
+    #[serde(default)]
+    pub tool_loop_limit: Option<usize>,
+    /// Files attached to every request as context documents, e.g.
+    /// `docs/ARCHITECTURE.md`, within a budget of the model's context window.
+    #[serde(default)]
+    pub context_files: Vec<String>,
+    /// Proxy, CA bundle and timeout for provider and tool HTTP requests.
+    #[serde(default)]
+    pub network: NetworkConfig,
+    /// Where sessions, project memory and the audit log are kept.
+    #[serde(default)]
+    pub storage: StorageConfig,
+    /// Prompt history of the interactive input editor.
+    #[serde(default)]
+    pub input: InputConfig,
+}
+
+fn default_audit_log() -> Option<String> {
+    Some(crate::audit::DEFAULT_AUDIT_LOG.to_string())
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            agent_prompt: None,
+            agent_prompt_file: None,
+            tool_config: HashMap::new(),
+            recipes: HashMap::new(),
+            audit_log: default_audit_log(),
+            log_file: None,
+            rate_limits: HashMap::new(),
+            max_output_tokens: HashMap::new(),
+            api_keys: HashMap::new(),
+            web_search: WebSearchConfig::default(),
+            gitlab: GitLabConfig::default(),
+            azure: AzureConfig::default(),
+            sandbox: None,
+            workspaces: Vec::new(),
+            confirmation: ConfirmationConfig::default(),
+            notifications: NotificationsConfig::default(),
+            verifier: VerifierConfig::default(),
+            routing: None,
+            tool_summaries: None,
+            protected_files: Vec::new(),
+            models_manifest: ManifestConfig::default(),
+            language: None,
+            require_plan_approval: false,
+            tool_loop_limit: None,
+            context_files: Vec::new(),
+            network: NetworkConfig::default(),
+            storage: StorageConfig::default(),
+            input: InputConfig::default(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct ToolSettings {
     #[serde(default)]
     pub auto_allow: Vec<String>,
+    /// Network access for `bash` commands.
+    #[serde(default)]
+    pub network: NetworkPolicy,
+    /// Shell for `bash` commands: sh, bash, zsh, fish, pwsh, powershell or cmd.
+    /// Detected for the platform when unset.
+    #[serde(default)]
+    pub shell: Option<crate::platform::Shell>,
+    /// Replaces the tool's built-in description.
+    #[serde(default)]
+    pub description_override: Option<String>,
+    /// Appended to the tool's description, e.g. project conventions for `bash`.
+    #[serde(default)]
+    pub extra_instructions: Option<String>,
+    /// Confirm calls of a tool that otherwise runs without asking, such as
+    /// `write_file` and `edit_file`.
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkPolicy {
+    /// No restrictions.
+    #[default]
+    Allow,
+    /// Commands that look like they use the network always need confirmation, even in yolo mode.
+    Ask,
+    /// Commands run without network access (firejail/unshare on Linux); elsewhere,
+    /// commands that look like they use the network are refused.
+    Deny,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct ManifestConfig {
+    /// Manifest URL; its signature is read from `<url>.minisig`. Required by
+    /// `picocode models refresh`, like `public_key`.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Minisign public key (the second line of `minisign.pub`) the manifest
+    /// must be signed with.
+    #[serde(default)]
+    pub public_key: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct NetworkConfig {
+    /// Proxy for all HTTP(S) requests, e.g. `http://proxy.corp:3128`. Falls
+    /// back to the `HTTPS_PROXY`/`HTTP_PROXY` environment variables.
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// PEM file with extra CA certificates to trust, for proxies that
+    /// re-sign TLS traffic.
+    #[serde(default)]
+    pub ca_bundle: Option<String>,
+    /// Timeout of each request made by picocode's own HTTP clients.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct VerifierConfig {
+    /// Reviewer model. Defaults to the main model.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Reviewer persona (or comma-separated personas).
+    #[serde(default)]
+    pub persona: Option<String>,
+}
+
+/// Large tool results are condensed by `model` before they enter the history;
+/// the full output stays retrievable with `fetch_full_result`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SummaryConfig {
+    /// Results above this many estimated tokens are summarized.
+    #[serde(default = "default_summary_threshold")]
+    pub threshold_tokens: usize,
+    /// Model of the active provider. Defaults to `routing.simple`, then `--model`.
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+fn default_summary_threshold() -> usize {
+    4000
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct ConfirmationConfig {
+    /// Seconds to wait for an answer. Unset waits forever, except in quiet runs.
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// Decision applied when the timeout expires.
+    #[serde(default)]
+    pub default: ConfirmDefault,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfirmDefault {
+    #[default]
+    Deny,
+    Allow,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NotificationsConfig {
+    /// Ring the terminal bell when a confirmation is pending or a long turn
+    /// finished.
+    #[serde(default)]
+    pub bell: bool,
+    /// Also show a desktop notification (`notify-send`, or macOS notifications).
+    #[serde(default)]
+    pub desktop: bool,
+    /// Turns shorter than this many seconds finish without a notification.
+    #[serde(default = "default_long_turn_secs")]
+    pub long_turn_secs: u64,
+}
+
+fn default_long_turn_secs() -> u64 {
+    30
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            bell: false,
+            desktop: false,
+            long_turn_secs: default_long_turn_secs(),
+        }
+    }
+}
+
+impl NotificationsConfig {
+    pub fn enabled(&self) -> bool {
+        self.bell || self.desktop
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// Files in the project and under `~/.picocode`.
+    #[default]
+    File,
+    /// One SQLite database, shared by the projects of a machine. Needs the
+    /// `sqlite` feature.
+    Sqlite,
+    /// A Redis server, shared by every machine using it. Needs the `redis`
+    /// feature.
+    Redis,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct StorageConfig {
+    #[serde(default)]
+    pub backend: StorageBackend,
+    /// SQLite database file. Defaults to `~/.picocode/picocode.db`.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Redis URL. Falls back to REDIS_URL.
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryMode {
+    /// Nothing is kept, not even for the arrow keys.
+    Off,
+    /// Kept for the arrow keys until picocode exits.
+    Session,
+    /// Saved to the history file and shared with later sessions.
+    #[default]
+    Persistent,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct InputConfig {
+    #[serde(default)]
+    pub history: HistoryMode,
+    /// History file instead of `~/.picocode_history`. A relative path such as
+    /// `.picocode/history` keeps a history per project.
+    #[serde(default)]
+    pub history_file: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct RateLimit {
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    #[serde(default)]
+    pub tokens_per_minute: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct ApiKeys {
+    #[serde(default)]
+    pub rotation: KeyRotation,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyRotation {
+    /// Keep using a key until it is rate limited, then move to the next.
+    #[default]
+    OnRateLimit,
+    /// Use the next key for every request.
+    RoundRobin,
+}
+
+/// Models picked per turn by `routing::classify`. Both must belong to the active provider.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RoutingConfig {
+    /// Model for questions and lookups.
+    pub simple: String,
+    /// Model for edits and everything else. Defaults to `--model`.
+    #[serde(default)]
+    pub complex: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchBackend {
+    Brave,
+    Serpapi,
+    Searx,
+    #[default]
+    Duckduckgo,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct WebSearchConfig {
+    #[serde(default)]
+    pub backend: SearchBackend,
+    /// API key for Brave/SerpAPI. Falls back to BRAVE_API_KEY / SERPAPI_API_KEY.
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Base URL of the Searx instance.
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub max_results: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct GitLabConfig {
+    /// Instance URL. Falls back to CI_SERVER_URL, then https://gitlab.com.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Project path such as `group/project`. Falls back to CI_PROJECT_PATH, then the `origin` remote.
+    #[serde(default)]
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct AzureConfig {
+    /// Deployment per model name, e.g. `gpt-4o: prod-gpt4o`. Other models are
+    /// requested under their own name.
+    #[serde(default)]
+    pub deployments: HashMap<String, String>,
+    /// Resource endpoint such as `https://my-resource.openai.azure.com`, used
+    /// when AZURE_OPENAI_ENDPOINT isn't set.
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// `api-version` of the requests. Falls back to AZURE_OPENAI_API_VERSION,
+    /// then a recent GA version.
+    #[serde(default)]
+    pub api_version: Option<String>,
+}
+
+impl AzureConfig {
+    /// The deployment serving `model`.
+    pub fn deployment<'a>(&'a self, model: &'a str) -> &'a str {
+        self.deployments.get(model).map_or(model, String::as_str)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RecipeMode {
+    /// Runs the prompt once without a user, for scripts and CI.
+    #[default]
+    Run,
+    /// Starts an interactive session, with the prompt as its first message.
+    Chat,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Recipe {
     pub prompt: Option<String>,
     pub prompt_file: Option<String>,
+    #[serde(default)]
+    pub mode: RecipeMode,
+    /// System prompt for this recipe, instead of the `agent_prompt` of picocode.yaml.
+    #[serde(default)]
+    pub agent_prompt: Option<String>,
+    #[serde(default)]
+    pub agent_prompt_file: Option<String>,
     pub provider: Option<String>,
     pub model: Option<String>,
+    /// A persona name, or a list of personas whose prompts are combined in order.
+    #[serde(default, deserialize_with = "deserialize_persona")]
     pub persona: Option<String>,
     pub yolo: Option<bool>,
     #[serde(default)]
@@ -34,9 +450,138 @@ pub struct Recipe {
     /// If set, response is treated as error when it matches this regex. Process exits with error.
     #[serde(default)]
     pub error_if: Option<String>,
+    /// If set, response is treated as error unless it matches this regex.
+    #[serde(default)]
+    pub success_if: Option<String>,
+    /// Runs allowed in total; failed checks re-prompt the agent with the failure until they run out.
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+    /// Instruction added to the failure context when re-prompting (e.g. "Fix the failing tests").
+    #[serde(default)]
+    pub retry_prompt: Option<String>,
+    /// Have a reviewer agent check the resulting diff and request one revision.
+    #[serde(default)]
+    pub verify: bool,
+    /// Cron expression (e.g. `0 9 * * 1`) for running the recipe under `picocode cron`.
+    #[serde(default)]
+    pub schedule: Option<String>,
+    /// Narrows the response to the payload a script needs, before `error_if` and printing.
+    #[serde(default)]
+    pub post_process: Option<PostProcess>,
+    /// Files attached as context documents for this recipe, after the
+    /// `context_files` of picocode.yaml.
+    #[serde(default)]
+    pub context_files: Vec<String>,
+    /// Answers to the agent's `ask_user` questions, by a word or phrase of the
+    /// question (`"*"` for any other). An unanswered question stops the run.
+    #[serde(default)]
+    pub answers: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Extract {
+    /// The last fenced code block.
+    CodeBlock,
+}
+
+/// Steps applied to a recipe's response in order: `extract`, then `json_path`, then `regex`.
+/// A step that finds nothing is an error, so pipelines never get prose by accident.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct PostProcess {
+    #[serde(default)]
+    pub extract: Option<Extract>,
+    /// Selector such as `$.result.items[0].name` into a JSON response.
+    #[serde(default)]
+    pub json_path: Option<String>,
+    /// Keeps the first capture group of the first match (or the whole match without groups).
+    #[serde(default)]
+    pub regex: Option<String>,
+}
+
+impl PostProcess {
+    pub fn apply(&self, response: &str) -> crate::Result<String> {
+        let fail = |msg: String| crate::PicocodeError::Other(format!("post_process: {}", msg));
+        let mut text = response.to_string();
+        if self.extract == Some(Extract::CodeBlock) {
+            text = crate::clipboard::last_code_block(&text)
+                .ok_or_else(|| fail("no code block in the response".into()))?;
+        }
+        if let Some(path) = &self.json_path {
+            let value: serde_json::Value = serde_json::from_str(text.trim())
+                .map_err(|e| fail(format!("response is not JSON: {}", e)))?;
+            let pointer = json_pointer(path).map_err(fail)?;
+            text = match value.pointer(&pointer) {
+                Some(serde_json::Value::String(s)) => s.clone(),
+                Some(v) => v.to_string(),
+                None => return Err(fail(format!("{} not found", path))),
+            };
+        }
+        if let Some(pattern) = &self.regex {
+            let cap = Regex::new(pattern)?
+                .captures(&text)
+                .ok_or_else(|| fail(format!("no match for {}", pattern)))?;
+            text = cap.get(1).unwrap_or_else(|| cap.get(0).unwrap()).as_str().to_string();
+        }
+        Ok(text)
+    }
+}
+
+/// Converts a JSONPath subset (`$`, `.key`, `[n]`, `['key']`) to a JSON Pointer.
+pub fn json_pointer(path: &str) -> Result<String, String> {
+    let invalid = || format!("unsupported json_path {} (use $.key, [n] or ['key'])", path);
+    let mut rest = path.trim().strip_prefix('$').ok_or_else(invalid)?;
+    let mut pointer = String::new();
+    while !rest.is_empty() {
+        let key = if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            rest = &after[end..];
+            &after[..end]
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(invalid)?;
+            rest = &after[end + 1..];
+            after[..end].trim_matches(|c| c == '\'' || c == '"')
+        } else {
+            return Err(invalid());
+        };
+        if key.is_empty() {
+            return Err(invalid());
+        }
+        pointer.push('/');
+        pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+    }
+    Ok(pointer)
+}
+
+/// Accepts `persona: name` or `persona: [a, b]`, normalizing lists to the
+/// comma-separated form understood by `persona::get_persona`.
+fn deserialize_persona<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum PersonaField {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match Option::<PersonaField>::deserialize(deserializer)? {
+        Some(PersonaField::One(name)) => Some(name),
+        Some(PersonaField::Many(names)) => Some(names.join(",")),
+        None => None,
+    })
 }
 
 impl Recipe {
+    /// The response after the recipe's `post_process` steps.
+    pub fn post_process(&self, response: &str) -> crate::Result<String> {
+        match &self.post_process {
+            Some(post) => post.apply(response),
+            None => Ok(response.to_string()),
+        }
+    }
+
     /// Returns true if the response matches the recipe's error_if regex.
     pub fn is_error(&self, response: &str) -> crate::Result<bool> {
         let Some(pattern) = &self.error_if else {
@@ -45,6 +590,39 @@ impl Recipe {
         let re = Regex::new(pattern)?;
         Ok(re.is_match(response))
     }
+
+    /// Why the response fails the recipe's `error_if`/`success_if` checks, if it does.
+    pub fn check(&self, response: &str) -> crate::Result<Option<String>> {
+        if self.is_error(response)? {
+            return Ok(Some(format!(
+                "response matched error_if pattern `{}`",
+                self.error_if.as_deref().unwrap_or_default()
+            )));
+        }
+        if let Some(pattern) = &self.success_if {
+            if !Regex::new(pattern)?.is_match(response) {
+                return Ok(Some(format!(
+                    "response did not match success_if pattern `{}`",
+                    pattern
+                )));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Prompt for another attempt after `response` to `request` failed a check.
+    pub fn retry_request(&self, request: &str, response: &str, failure: &str) -> String {
+        format!(
+            "You already worked on this request:\n\n{}\n\nYour answer was:\n\n{}\n\n\
+It failed an automated check: {}.\n\n{}",
+            request,
+            response,
+            failure,
+            self.retry_prompt
+                .as_deref()
+                .unwrap_or("Find out what went wrong, fix it, and answer again.")
+        )
+    }
 }
 
 impl Config {
@@ -70,6 +648,22 @@ impl Config {
         Ok(Config::default())
     }
 
+    pub fn get_rate_limit(&self, provider: &str) -> Option<RateLimit> {
+        self.rate_limits.get(provider).cloned()
+    }
+
+    pub fn get_max_output_tokens(&self, provider: &str) -> Option<u64> {
+        self.max_output_tokens.get(provider).copied()
+    }
+
+    pub fn get_api_keys(&self, provider: &str) -> Option<ApiKeys> {
+        self.api_keys.get(provider).cloned()
+    }
+
+    pub fn get_tool_settings(&self, tool: &str) -> ToolSettings {
+        self.tool_config.get(tool).cloned().unwrap_or_default()
+    }
+
     pub fn get_bash_auto_allow(&self) -> Vec<String> {
         self.tool_config
             .get("bash")
@@ -78,12 +672,162 @@ impl Config {
     }
 }
 
+/// Names of the languages most often given as a locale, by ISO 639-1 code.
+const LANGUAGES: &[(&str, &str)] = &[
+    ("ar", "Arabic"),
+    ("cs", "Czech"),
+    ("de", "German"),
+    ("en", "English"),
+    ("es", "Spanish"),
+    ("fr", "French"),
+    ("he", "Hebrew"),
+    ("hi", "Hindi"),
+    ("id", "Indonesian"),
+    ("it", "Italian"),
+    ("ja", "Japanese"),
+    ("ko", "Korean"),
+    ("nl", "Dutch"),
+    ("pl", "Polish"),
+    ("pt", "Portuguese"),
+    ("ru", "Russian"),
+    ("sv", "Swedish"),
+    ("tr", "Turkish"),
+    ("uk", "Ukrainian"),
+    ("vi", "Vietnamese"),
+    ("zh", "Chinese"),
+];
+
+/// The language named by a `language` setting, for the model: locales such as
+/// `ja_JP.UTF-8` or `pt-BR` become `Japanese` or `Portuguese (BR)`, anything
+/// else is taken as a language name.
+pub fn language_name(setting: &str) -> String {
+    let setting = setting.trim();
+    let locale = setting.split('.').next().unwrap_or(setting);
+    let (code, region) = locale
+        .split_once(['_', '-'])
+        .map_or((locale, None), |(code, region)| (code, Some(region)));
+    match LANGUAGES.iter().find(|(c, _)| c.eq_ignore_ascii_case(code)) {
+        Some((_, name)) => match region {
+            Some(region) => format!("{} ({})", name, region.to_uppercase()),
+            None => name.to_string(),
+        },
+        None => setting.to_string(),
+    }
+}
+
+/// Reads an inline or file prompt, expanding `{{include "path"}}` directives.
 pub fn read_prompt(prompt: Option<String>, prompt_file: Option<String>) -> crate::Result<Option<String>> {
     if let Some(file_path) = prompt_file {
         let path = Path::new(&file_path);
         let content = std::fs::read_to_string(path).map_err(crate::PicocodeError::Io)?;
-        Ok(Some(content))
+        let base = path.parent().unwrap_or(Path::new(""));
+        Ok(Some(resolve_includes(&content, base)?))
     } else {
-        Ok(prompt)
+        prompt.map(|p| resolve_includes(&p, Path::new(""))).transpose()
+    }
+}
+
+/// Expands `{{include "path"}}` directives, recursively. Paths are relative to
+/// `base`, and nested includes are relative to the file that contains them.
+pub fn resolve_includes(text: &str, base: &Path) -> crate::Result<String> {
+    expand_includes(text, base, &mut Vec::new())
+}
+
+fn expand_includes(text: &str, base: &Path, stack: &mut Vec<PathBuf>) -> crate::Result<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for cap in INCLUDE_RE.captures_iter(text) {
+        let whole = cap.get(0).unwrap();
+        result.push_str(&text[last..whole.start()]);
+        last = whole.end();
+
+        let path = base.join(&cap[1]);
+        if stack.contains(&path) || stack.len() >= MAX_INCLUDE_DEPTH {
+            return Err(crate::PicocodeError::Other(format!(
+                "Include cycle or nesting too deep at {}",
+                path.display()
+            )));
+        }
+        let content = std::fs::read_to_string(&path).map_err(|e| {
+            crate::PicocodeError::Other(format!("Cannot include {}: {}", path.display(), e))
+        })?;
+        stack.push(path.clone());
+        let nested_base = path.parent().unwrap_or(Path::new(""));
+        result.push_str(expand_includes(&content, nested_base, stack)?.trim_end_matches('\n'));
+        stack.pop();
+    }
+    result.push_str(&text[last..]);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let snippets = dir.path().join("snippets");
+        std::fs::create_dir(&snippets).unwrap();
+        std::fs::write(snippets.join("security.md"), "- check auth\n{{include \"common.md\"}}\n").unwrap();
+        std::fs::write(snippets.join("common.md"), "- be brief\n").unwrap();
+
+        let prompt = "Review.\n{{ include \"snippets/security.md\" }}\nDone.";
+        assert_eq!(
+            resolve_includes(prompt, dir.path()).unwrap(),
+            "Review.\n- check auth\n- be brief\nDone."
+        );
+        assert!(resolve_includes("{{include \"missing.md\"}}", dir.path()).is_err());
+
+        std::fs::write(snippets.join("loop.md"), "{{include \"loop.md\"}}").unwrap();
+        assert!(resolve_includes("{{include \"snippets/loop.md\"}}", dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_language_name() {
+        assert_eq!(language_name("ja"), "Japanese");
+        assert_eq!(language_name("ja_JP.UTF-8"), "Japanese (JP)");
+        assert_eq!(language_name("pt-br"), "Portuguese (BR)");
+        assert_eq!(language_name(" Brazilian Portuguese "), "Brazilian Portuguese");
+    }
+
+    #[test]
+    fn test_post_process() {
+        let response = "Here you go:\n```json\n{\"result\": {\"items\": [{\"version\": \"VERSION=1.2.3\"}]}}\n```";
+        let post = PostProcess {
+            extract: Some(Extract::CodeBlock),
+            json_path: Some("$.result.items[0]['version']".into()),
+            regex: Some(r"VERSION=(\S+)".into()),
+        };
+        assert_eq!(post.apply(response).unwrap(), "1.2.3");
+        assert!(post.apply("no code here").is_err());
+        assert_eq!(json_pointer("$.a/b[2]").unwrap(), "/a~1b/2");
+        assert!(json_pointer("result").is_err());
+    }
+
+    #[test]
+    fn test_recipe_check() {
+        let recipe: Recipe =
+            serde_yaml::from_str("prompt: go\nerror_if: FAIL\nsuccess_if: PASS\nmax_attempts: 2").unwrap();
+        assert_eq!(recipe.check("all tests PASS").unwrap(), None);
+        assert!(recipe.check("1 FAIL").unwrap().unwrap().contains("error_if"));
+        assert!(recipe.check("done").unwrap().unwrap().contains("success_if"));
+        assert!(recipe
+            .retry_request("go", "done", "nope")
+            .ends_with("Find out what went wrong, fix it, and answer again."));
+        assert_eq!(recipe.mode, RecipeMode::Run);
+        let chat: Recipe = serde_yaml::from_str("mode: chat\nagent_prompt: Guide me").unwrap();
+        assert_eq!(chat.mode, RecipeMode::Chat);
+        assert!(chat.prompt.is_none());
+    }
+
+    #[test]
+    fn test_azure_deployment() {
+        let azure: AzureConfig =
+            serde_yaml::from_str("deployments: { gpt-4o: prod-gpt4o }\napi_version: 2024-10-21")
+                .unwrap();
+        assert_eq!(azure.deployment("gpt-4o"), "prod-gpt4o");
+        assert_eq!(azure.deployment("gpt-4o-mini"), "gpt-4o-mini");
+        assert_eq!(azure.api_version.as_deref(), Some("2024-10-21"));
     }
 }
@@ -0,0 +1,111 @@
+//! Standing project documents listed in `context_files` (picocode.yaml or a
+//! recipe). They are attached to every request as context documents, so the
+//! model knows them without the user mentioning them, and together they may
+//! take at most a share of the model's context window.
+
+use crate::tokens::estimate_tokens;
+
+/// Share of the model's context window the documents may take.
+pub const CONTEXT_RATIO: f64 = 0.25;
+/// Token budget when the model's context window is unknown.
+pub const DEFAULT_BUDGET_TOKENS: usize = 16_000;
+/// A document is skipped instead of cut when less than this remains.
+const MIN_PART_TOKENS: usize = 200;
+const TRUNCATED: &str = "\n[... truncated to fit the context_files budget]";
+
+/// Tokens the documents may take with `model`.
+pub fn budget(model: &str) -> usize {
+    crate::models::context_window(model)
+        .map(|window| (window as f64 * CONTEXT_RATIO) as usize)
+        .unwrap_or(DEFAULT_BUDGET_TOKENS)
+}
+
+/// Reads `paths` in order as context documents, cutting off the one that
+/// exceeds `budget` tokens and skipping the rest. Also returns notes about
+/// files that couldn't be read or didn't fit. Files outside the workspace and
+/// protected files are refused, as they are for tools.
+pub fn load(paths: &[String], budget: usize) -> (Vec<String>, Vec<String>) {
+    let mut documents = Vec::new();
+    let mut notes = Vec::new();
+    let mut remaining = budget;
+    for path in paths {
+        let text = match crate::tools::get_readable_path(path)
+            .and_then(|p| std::fs::read_to_string(p).map_err(Into::into))
+        {
+            Ok(text) => text,
+            Err(e) => {
+                notes.push(format!("{} not attached: {}", path, e));
+                continue;
+            }
+        };
+        match fit(path, &text, &mut remaining) {
+            Ok(document) => {
+                if document.ends_with(TRUNCATED) {
+                    notes.push(format!(
+                        "{} cut to ~{} tokens to fit the context_files budget of {}",
+                        path,
+                        estimate_tokens(&document),
+                        budget
+                    ));
+                }
+                documents.push(document);
+            }
+            Err(note) => notes.push(note),
+        }
+    }
+    (documents, notes)
+}
+
+/// The document for the file at `path`, counted against `remaining` tokens:
+/// whole, cut to what remains, or an `Err` note when too little remains.
+fn fit(path: &str, text: &str, remaining: &mut usize) -> Result<String, String> {
+    let document = format!("File: {}\n\n{}", path, text.trim_end());
+    let tokens = estimate_tokens(&document);
+    if tokens <= *remaining {
+        *remaining -= tokens;
+        return Ok(document);
+    }
+    if *remaining < MIN_PART_TOKENS {
+        return Err(format!(
+            "{} not attached: the context_files budget is used up",
+            path
+        ));
+    }
+    // ~4 characters per token, as in `estimate_tokens`
+    let chars = (*remaining - estimate_tokens(TRUNCATED)) * 4;
+    *remaining = 0;
+    Ok(format!(
+        "{}{}",
+        document.chars().take(chars).collect::<String>(),
+        TRUNCATED
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit() {
+        let mut remaining = 1000;
+        let doc = fit("docs/ARCHITECTURE.md", "Layers\n", &mut remaining).unwrap();
+        assert_eq!(doc, "File: docs/ARCHITECTURE.md\n\nLayers");
+        assert_eq!(remaining, 1000 - estimate_tokens(&doc));
+
+        let long = "x".repeat(8000);
+        let doc = fit("CONTRIBUTING.md", &long, &mut remaining).unwrap();
+        assert!(doc.ends_with(TRUNCATED));
+        assert!(estimate_tokens(&doc) <= 1000);
+        assert_eq!(remaining, 0);
+        assert!(fit("NOTES.md", "short", &mut remaining).is_err());
+    }
+
+    #[test]
+    fn test_load() {
+        let paths = ["README.md", "missing.md", "../outside.md"].map(String::from);
+        let (documents, notes) = load(&paths, 1_000_000);
+        assert_eq!(documents.len(), 1);
+        assert!(documents[0].starts_with("File: README.md\n\n"));
+        assert_eq!(notes.len(), 2);
+    }
+}
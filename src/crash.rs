@@ -0,0 +1,191 @@
+//! Crash reports for interactive sessions. While a session is tracked, a panic
+//! or a termination signal writes the conversation so far and what the agent
+//! was doing (provider, model, pending prompt, last tool call) to
+//! `~/.picocode/crash/`, and flushes the session so it can be resumed.
+//!
+//! Nothing is reported until the application calls [`install`]: the panic hook
+//! and signal handlers are process-wide, so a library doesn't set them up.
+
+use crate::session::{Session, SessionStore};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, Once};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolCallRecord {
+    pub tool: String,
+    pub args: String,
+}
+
+struct Context {
+    session: Session,
+    store: Option<SessionStore>,
+    /// Prompt of the turn in progress, not yet in the session messages.
+    input: Option<String>,
+    last_tool_call: Option<ToolCallRecord>,
+}
+
+#[derive(Serialize)]
+struct CrashReport<'a> {
+    reason: &'a str,
+    time: String,
+    version: &'static str,
+    provider: &'a str,
+    model: &'a str,
+    pending_input: Option<&'a str>,
+    last_tool_call: Option<&'a ToolCallRecord>,
+    session: &'a Session,
+}
+
+static CONTEXT: Mutex<Option<Context>> = Mutex::new(None);
+static INSTALL: Once = Once::new();
+
+pub fn crash_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".picocode").join("crash"))
+}
+
+/// Starts tracking `session`, saved to `store` (if given) on a crash.
+pub fn track(session: Session, store: Option<SessionStore>) {
+    *CONTEXT.lock().unwrap() = Some(Context {
+        session,
+        store,
+        input: None,
+        last_tool_call: None,
+    });
+}
+
+/// Stops tracking, e.g. when the session ends normally.
+pub fn untrack() {
+    *CONTEXT.lock().unwrap() = None;
+}
+
+fn with_context(f: impl FnOnce(&mut Context)) {
+    if let Some(context) = CONTEXT.lock().unwrap().as_mut() {
+        f(context);
+    }
+}
+
+/// Replaces the tracked session, whose messages end with the last finished turn.
+pub fn update_session(session: &Session) {
+    with_context(|c| {
+        c.session = session.clone();
+        c.input = None;
+    });
+}
+
+pub fn turn_started(input: &str) {
+    with_context(|c| c.input = Some(input.to_string()));
+}
+
+pub fn tool_called(tool: &str, args: &str) {
+    with_context(|c| {
+        c.last_tool_call = Some(ToolCallRecord {
+            tool: tool.to_string(),
+            args: args.to_string(),
+        })
+    });
+}
+
+/// Writes the crash report for the tracked session and returns its path.
+/// Does nothing when no session is tracked.
+pub fn write_report(reason: &str) -> Option<PathBuf> {
+    write_report_to(&crash_dir()?, reason)
+}
+
+fn write_report_to(dir: &Path, reason: &str) -> Option<PathBuf> {
+    // A panic may happen while the context is locked; skip rather than deadlock
+    let guard = CONTEXT.try_lock().ok()?;
+    let context = guard.as_ref()?;
+    let mut session = context.session.clone();
+    session.updated_at = chrono::Utc::now();
+    if let Some(store) = &context.store {
+        let _ = store.save(&session);
+    }
+    let report = CrashReport {
+        reason,
+        time: session.updated_at.to_rfc3339(),
+        version: env!("CARGO_PKG_VERSION"),
+        provider: &session.provider,
+        model: &session.model,
+        pending_input: context.input.as_deref(),
+        last_tool_call: context.last_tool_call.as_ref(),
+        session: &session,
+    };
+    std::fs::create_dir_all(dir).ok()?;
+    let path = dir.join(format!(
+        "{}-{}.json",
+        session.id,
+        session.updated_at.format("%Y%m%d%H%M%S")
+    ));
+    std::fs::write(&path, serde_json::to_string_pretty(&report).ok()?).ok()?;
+    Some(path)
+}
+
+fn report_and_notify(reason: &str) {
+    if let Some(path) = write_report(reason) {
+        eprintln!("\npicocode stopped unexpectedly ({}).", reason);
+        eprintln!("Crash report and transcript saved to {}", path.display());
+    }
+}
+
+/// Installs the panic hook (after any hook already set) and, on Unix, the
+/// `SIGTERM`/`SIGHUP` handlers that report a tracked session and exit. Call it
+/// once from `main`, inside the tokio runtime so the handlers can be spawned.
+pub fn install() {
+    INSTALL.call_once(install_handlers);
+}
+
+fn install_handlers() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+        report_and_notify(&format!("panic: {}", info));
+    }));
+
+    #[cfg(unix)]
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        use tokio::signal::unix::{signal, SignalKind};
+        for (kind, name) in [
+            (SignalKind::terminate(), "SIGTERM"),
+            (SignalKind::hangup(), "SIGHUP"),
+        ] {
+            handle.spawn(async move {
+                if let Ok(mut stream) = signal(kind) {
+                    if stream.recv().await.is_some() {
+                        report_and_notify(&format!("terminated by {}", name));
+                        std::process::exit(128 + kind.as_raw_value());
+                    }
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_report() {
+        let dir = tempfile::tempdir().unwrap();
+        untrack();
+        assert!(write_report_to(dir.path(), "test").is_none());
+
+        let sessions = dir.path().join("sessions");
+        let session = Session::new("s1".into(), "openai", "gpt-4o", None);
+        track(session, Some(SessionStore::new(&sessions)));
+        turn_started("fix the build");
+        tool_called("bash", r#"{"cmd":"cargo build"}"#);
+        let path = write_report_to(dir.path(), "terminated by SIGTERM").unwrap();
+        untrack();
+
+        let report: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(report["reason"], "terminated by SIGTERM");
+        assert_eq!(report["model"], "gpt-4o");
+        assert_eq!(report["pending_input"], "fix the build");
+        assert_eq!(report["last_tool_call"]["tool"], "bash");
+        // The session was flushed so it can be resumed
+        assert!(SessionStore::new(&sessions).load("s1").is_ok());
+    }
+}
@@ -0,0 +1,221 @@
+//! `--debug-llm` (or `PICOCODE_DEBUG=1`): each completion request picocode
+//! makes for a turn and the response it gets are appended to
+//! `.picocode/debug/<session id>.jsonl` with credentials redacted, to diagnose
+//! prompt construction and provider quirks.
+
+use rig::completion::{CompletionError, CompletionModel, CompletionRequest, CompletionResponse};
+use rig::streaming::StreamingCompletionResponse;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+pub const DEBUG_DIR: &str = ".picocode/debug";
+/// Enables debug logging when set to `1` or `true`.
+pub const DEBUG_ENV: &str = "PICOCODE_DEBUG";
+
+pub fn enabled_by_env() -> bool {
+    std::env::var(DEBUG_ENV).is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+pub struct DebugLog {
+    path: PathBuf,
+    /// Credential values from the environment, redacted from every record.
+    secrets: Vec<String>,
+    /// Requests logged so far, pairing each response with its request.
+    requests: AtomicUsize,
+}
+
+impl DebugLog {
+    pub fn new(dir: impl AsRef<Path>, session_id: &str) -> Self {
+        Self {
+            path: dir.as_ref().join(format!("{}.jsonl", session_id)),
+            secrets: crate::secrets::secret_values(),
+            requests: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records a request as it is sent to the provider, with the tool
+    /// definitions and settings, and returns its number.
+    pub fn request(&self, model: &str, request: &CompletionRequest) -> usize {
+        self.record_request(
+            model,
+            json!({
+                "preamble": request.preamble,
+                "history": request.chat_history,
+                "tools": request.tools,
+                "temperature": request.temperature,
+                "max_tokens": request.max_tokens,
+                "additional_params": request.additional_params,
+            }),
+        )
+    }
+
+    fn record_request(&self, model: &str, request: Value) -> usize {
+        let n = self.requests.fetch_add(1, Ordering::Relaxed) + 1;
+        self.append("request", n, model, request);
+        n
+    }
+
+    /// Records the response to request `n`.
+    pub fn response(
+        &self,
+        n: usize,
+        model: &str,
+        content: &impl Serialize,
+        usage: &impl Serialize,
+    ) {
+        self.append(
+            "response",
+            n,
+            model,
+            json!({ "content": content, "usage": usage }),
+        );
+    }
+
+    fn error(&self, n: usize, model: &str, error: &CompletionError) {
+        self.append("error", n, model, json!({ "error": error.to_string() }));
+    }
+
+    fn append(&self, kind: &str, n: usize, model: &str, fields: Value) {
+        let mut record = json!({
+            "timestamp": chrono::Local::now().to_rfc3339(),
+            "kind": kind,
+            "n": n,
+            "model": model,
+        });
+        if let (Some(record), Value::Object(fields)) = (record.as_object_mut(), fields) {
+            record.extend(fields);
+        }
+        // Before serializing, so that escaping can't hide a secret from `redact`
+        redact(&mut record, &self.secrets);
+        let written = self
+            .path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| {
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)
+            })
+            .and_then(|mut file| writeln!(file, "{}", record));
+        if let Err(e) = written {
+            tracing::warn!(target: "picocode", "Failed to write debug log: {}", e);
+        }
+    }
+}
+
+/// Redacts `secrets` from every string in `value`.
+fn redact(value: &mut Value, secrets: &[String]) {
+    match value {
+        Value::String(text) => *text = crate::secrets::redact(text, secrets),
+        Value::Array(items) => items.iter_mut().for_each(|item| redact(item, secrets)),
+        Value::Object(fields) => fields.values_mut().for_each(|field| redact(field, secrets)),
+        _ => {}
+    }
+}
+
+/// A model whose requests and responses go to the debug log, if there is one.
+/// Session titles are made without a log, so captures replay only turns.
+#[derive(Clone)]
+pub struct DebugModel<M> {
+    model: M,
+    name: String,
+    log: Option<Arc<DebugLog>>,
+}
+
+impl<M> DebugModel<M> {
+    pub fn new(model: M, name: &str, log: Option<Arc<DebugLog>>) -> Self {
+        Self {
+            model,
+            name: name.to_string(),
+            log,
+        }
+    }
+}
+
+impl<M: CompletionModel> CompletionModel for DebugModel<M> {
+    type Response = M::Response;
+    type StreamingResponse = M::StreamingResponse;
+    type Client = DebugModel<M>;
+
+    fn make(client: &Self::Client, _model: impl Into<String>) -> Self {
+        client.clone()
+    }
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> std::result::Result<CompletionResponse<M::Response>, CompletionError> {
+        let Some(log) = &self.log else {
+            return self.model.completion(request).await;
+        };
+        let n = log.request(&self.name, &request);
+        let response = self.model.completion(request).await;
+        match &response {
+            Ok(response) => {
+                let mut usage = crate::session::TokenUsage::default();
+                usage.add(&response.usage);
+                log.response(n, &self.name, &response.choice, &usage);
+            }
+            Err(e) => log.error(n, &self.name, e),
+        }
+        response
+    }
+
+    async fn stream(
+        &self,
+        request: CompletionRequest,
+    ) -> std::result::Result<StreamingCompletionResponse<M::StreamingResponse>, CompletionError>
+    {
+        if let Some(log) = &self.log {
+            log.request(&self.name, &request);
+        }
+        self.model.stream(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = DebugLog {
+            secrets: vec!["hunter2-secret".into(), r#"pa"ss\word"#.into()],
+            ..DebugLog::new(dir.path(), "s1")
+        };
+        let n = log.record_request(
+            "claude-x",
+            json!({
+                "history": ["use key hunter2-secret"],
+                "tools": [{"name": "bash", "description": r#"login with pa"ss\word"#}],
+            }),
+        );
+        log.response(n, "claude-x", &"done", &json!({"total_tokens": 3}));
+
+        let text = std::fs::read_to_string(log.path()).unwrap();
+        assert!(!text.contains("hunter2") && !text.contains("ss\\\\word"));
+        let records: Vec<Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["kind"], "request");
+        assert_eq!(records[0]["history"][0], "use key <redacted>");
+        assert_eq!(
+            records[0]["tools"][0]["description"],
+            "login with <redacted>"
+        );
+        assert_eq!(records[1]["n"], 1);
+        assert_eq!(records[1]["content"], "done");
+    }
+}
@@ -0,0 +1,455 @@
+//! `picocode doctor`: checks API keys, configuration, provider connectivity and
+//! optional binaries, printing a suggested fix for every problem found.
+
+use crate::agent::{create_agent, required_env_vars, AgentConfig};
+use crate::config::{Config, NetworkPolicy, RecipeMode};
+use crate::tools::is_tool_available;
+use crate::NoOutput;
+use console::style;
+use regex::Regex;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Top-level keys understood by `Config`.
+const CONFIG_KEYS: &[&str] = &[
+    "agent_prompt",
+    "agent_prompt_file",
+    "tool_config",
+    "recipes",
+    "audit_log",
+    "log_file",
+    "rate_limits",
+    "gitlab",
+    "web_search",
+    "sandbox",
+    "workspaces",
+    "confirmation",
+    "verifier",
+    "routing",
+    "tool_summaries",
+    "protected_files",
+    "models_manifest",
+    "language",
+    "require_plan_approval",
+    "tool_loop_limit",
+    "context_files",
+    "network",
+    "max_output_tokens",
+    "api_keys",
+    "azure",
+    "notifications",
+    "storage",
+    "input",
+];
+
+const PROVIDERS: &[&str] = &[
+    "anthropic",
+    "openai",
+    "azure",
+    "cohere",
+    "deepseek",
+    "galadriel",
+    "gemini",
+    "groq",
+    "huggingface",
+    "hyperbolic",
+    "mira",
+    "mistral",
+    "moonshot",
+    "openrouter",
+    "perplexity",
+    "together",
+    "xai",
+];
+
+const CONNECTIVITY_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+struct Report {
+    failures: usize,
+    warnings: usize,
+}
+
+impl Report {
+    fn section(&self, title: &str) {
+        println!("\n{}", style(title).bold());
+    }
+
+    fn ok(&mut self, message: &str) {
+        println!("  {} {}", style("✓").green(), message);
+    }
+
+    fn warn(&mut self, message: &str, fix: &str) {
+        self.warnings += 1;
+        println!("  {} {}", style("!").yellow(), message);
+        println!("    {} {}", style("fix:").dim(), fix);
+    }
+
+    fn fail(&mut self, message: &str, fix: &str) {
+        self.failures += 1;
+        println!("  {} {}", style("✗").red(), message);
+        println!("    {} {}", style("fix:").dim(), fix);
+    }
+}
+
+/// Runs all checks and returns false if any of them failed.
+pub async fn run(config_path: Option<&str>, provider: &str, model: &str) -> bool {
+    let mut report = Report::default();
+    let provider = provider.to_lowercase();
+
+    report.section("Configuration");
+    let config = check_config(&mut report, config_path);
+
+    report.section("API keys");
+    let keys_ok = check_api_keys(&mut report, &provider);
+
+    report.section("Provider connectivity");
+    if keys_ok {
+        check_connectivity(&mut report, &provider, model, &config).await;
+    } else {
+        report.warn(
+            &format!("Skipped {} connectivity test", provider),
+            "Set the missing API key and run `picocode doctor` again",
+        );
+    }
+
+    report.section("Binaries");
+    check_binaries(&mut report, &config);
+
+    println!();
+    if report.failures == 0 {
+        println!(
+            "{} No problems found ({} warning(s))",
+            style("✓").green(),
+            report.warnings
+        );
+    } else {
+        println!(
+            "{} {} problem(s), {} warning(s)",
+            style("✗").red(),
+            report.failures,
+            report.warnings
+        );
+    }
+    report.failures == 0
+}
+
+fn check_config(report: &mut Report, config_path: Option<&str>) -> Config {
+    let path = match config_path {
+        Some(p) => Some(p.to_string()),
+        None => ["picocode.yaml", "picocode.yml"]
+            .into_iter()
+            .find(|p| Path::new(p).exists())
+            .map(str::to_string),
+    };
+    let Some(path) = path else {
+        report.ok("No picocode.yaml found, using defaults");
+        return Config::default();
+    };
+
+    let content = match std::fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) => {
+            report.fail(
+                &format!("Cannot read {}: {}", path, e),
+                "Check the path passed to --config",
+            );
+            return Config::default();
+        }
+    };
+
+    if let Ok(serde_yaml::Value::Mapping(map)) = serde_yaml::from_str::<serde_yaml::Value>(&content)
+    {
+        for key in map.keys().filter_map(|k| k.as_str()) {
+            if !CONFIG_KEYS.contains(&key) {
+                report.warn(
+                    &format!("Unknown key '{}' in {}", key, path),
+                    &format!(
+                        "Remove it or check its spelling; known keys: {}",
+                        CONFIG_KEYS.join(", ")
+                    ),
+                );
+            }
+        }
+    }
+
+    let config = match serde_yaml::from_str::<Config>(&content) {
+        Ok(config) => config,
+        Err(e) => {
+            report.fail(
+                &format!("{} is invalid: {}", path, e),
+                "Compare with picocode.yaml.example",
+            );
+            return Config::default();
+        }
+    };
+    report.ok(&format!("{} is valid", path));
+
+    if let Some(file) = &config.agent_prompt_file {
+        if !Path::new(file).exists() {
+            report.fail(
+                &format!("agent_prompt_file '{}' does not exist", file),
+                "Create the file or fix the path",
+            );
+        }
+    }
+    for dir in &config.workspaces {
+        if !Path::new(dir).is_dir() {
+            report.fail(
+                &format!("Workspace root '{}' does not exist", dir),
+                "Fix or remove the entry under `workspaces`",
+            );
+        }
+    }
+    // Also makes the checks below go through the configured proxy
+    if let Err(e) = crate::network::configure(&config.network) {
+        report.fail(&e.to_string(), "Fix `network.proxy` or `network.ca_bundle`");
+    }
+    if let Err(e) = crate::storage::configure(&config.storage) {
+        report.fail(
+            &e.to_string(),
+            "Fix `storage`, or build picocode with the backend's feature",
+        );
+    }
+    for file in &config.context_files {
+        if !Path::new(file).is_file() {
+            report.fail(
+                &format!("context_files entry '{}' does not exist", file),
+                "Create the file or fix the path",
+            );
+        }
+    }
+    for (tool, settings) in &config.tool_config {
+        for pattern in &settings.auto_allow {
+            if let Err(e) = Regex::new(pattern) {
+                report.fail(
+                    &format!(
+                        "tool_config.{}.auto_allow pattern '{}' is invalid: {}",
+                        tool, pattern, e
+                    ),
+                    "Fix the regular expression",
+                );
+            }
+        }
+    }
+    for (name, recipe) in &config.recipes {
+        match (&recipe.prompt, &recipe.prompt_file) {
+            // A chat recipe may leave the first message to the user
+            (None, None) if recipe.mode == RecipeMode::Chat => {}
+            (None, None) => report.fail(
+                &format!("Recipe '{}' has no prompt", name),
+                "Add `prompt` or `prompt_file` to the recipe",
+            ),
+            (_, Some(file)) if !Path::new(file).exists() => report.fail(
+                &format!("Recipe '{}' prompt_file '{}' does not exist", name, file),
+                "Create the file or fix the path",
+            ),
+            _ => {}
+        }
+        if let Some(file) = &recipe.agent_prompt_file {
+            if !Path::new(file).exists() {
+                report.fail(
+                    &format!(
+                        "Recipe '{}' agent_prompt_file '{}' does not exist",
+                        name, file
+                    ),
+                    "Create the file or fix the path",
+                );
+            }
+        }
+        if recipe.mode == RecipeMode::Chat && recipe.schedule.is_some() {
+            report.fail(
+                &format!("Recipe '{}' is a chat recipe with a schedule", name),
+                "Remove `schedule`, or `mode: chat` to run it unattended",
+            );
+        }
+        for file in &recipe.context_files {
+            if !Path::new(file).is_file() {
+                report.fail(
+                    &format!(
+                        "Recipe '{}' context_files entry '{}' does not exist",
+                        name, file
+                    ),
+                    "Create the file or fix the path",
+                );
+            }
+        }
+        if let Some(schedule) = &recipe.schedule {
+            if let Err(e) = crate::schedule::Schedule::parse(schedule) {
+                report.fail(
+                    &format!("Recipe '{}': {}", name, e),
+                    "Use a five-field cron expression such as \"0 9 * * 1\"",
+                );
+            }
+        }
+        if let Some(pattern) = &recipe.error_if {
+            if let Err(e) = Regex::new(pattern) {
+                report.fail(
+                    &format!("Recipe '{}' error_if pattern is invalid: {}", name, e),
+                    "Fix the regular expression",
+                );
+            }
+        }
+        if let Some(pattern) = &recipe.success_if {
+            if let Err(e) = Regex::new(pattern) {
+                report.fail(
+                    &format!("Recipe '{}' success_if pattern is invalid: {}", name, e),
+                    "Fix the regular expression",
+                );
+            }
+        }
+        if let Some(post) = &recipe.post_process {
+            if let Some(Err(e)) = post.regex.as_deref().map(Regex::new) {
+                report.fail(
+                    &format!("Recipe '{}' post_process.regex is invalid: {}", name, e),
+                    "Fix the regular expression",
+                );
+            }
+            if let Some(Err(e)) = post.json_path.as_deref().map(crate::config::json_pointer) {
+                report.fail(
+                    &format!("Recipe '{}': {}", name, e),
+                    "Use a path such as $.result.items[0]",
+                );
+            }
+        }
+    }
+    config
+}
+
+fn check_api_keys(report: &mut Report, provider: &str) -> bool {
+    let mut ok = true;
+    if provider == "ollama" || provider == "replay" {
+        report.ok(&format!("{} does not need an API key", provider));
+    } else if !PROVIDERS.contains(&provider) && provider != "google" {
+        report.fail(
+            &format!("Unsupported provider '{}'", provider),
+            &format!("Use one of: {}, ollama", PROVIDERS.join(", ")),
+        );
+        return false;
+    }
+    for var in required_env_vars(provider) {
+        let keys = crate::keys::collect(var).len();
+        if keys > 1 {
+            report.ok(&format!(
+                "{} and its numbered variants hold {} keys",
+                var, keys
+            ));
+        } else if keys == 1 && std::env::var(var).is_ok_and(|v| !v.is_empty()) {
+            report.ok(&format!("{} is set", var));
+        } else if keys == 1 {
+            report.ok(&format!("{}_1 is set", var));
+        } else {
+            ok = false;
+            report.fail(
+                &format!("{} is not set (needed for {})", var, provider),
+                &format!("export {}=...", var),
+            );
+        }
+    }
+
+    let others: Vec<&str> = PROVIDERS
+        .iter()
+        .copied()
+        .filter(|p| *p != provider)
+        .filter(|p| {
+            required_env_vars(p)
+                .iter()
+                .all(|v| std::env::var(v).is_ok())
+        })
+        .collect();
+    if !others.is_empty() {
+        report.ok(&format!("Also available: {}", others.join(", ")));
+    }
+    ok
+}
+
+async fn check_connectivity(report: &mut Report, provider: &str, model: &str, config: &Config) {
+    let agent = create_agent(AgentConfig {
+        provider: provider.to_string(),
+        model: model.to_string(),
+        output: Arc::new(NoOutput),
+        yolo: false,
+        tool_call_limit: 1,
+        agent_prompt: Some("Reply with the single word OK.".to_string()),
+        api_keys: config.get_api_keys(provider),
+        ..Default::default()
+    })
+    .await;
+    let agent = match agent {
+        Ok(agent) => agent,
+        Err(e) => {
+            report.fail(
+                &format!("Cannot create {} client: {}", provider, e),
+                "Check the provider name and API key",
+            );
+            return;
+        }
+    };
+
+    let started = Instant::now();
+    match tokio::time::timeout(CONNECTIVITY_TIMEOUT, agent.run_once("ping".to_string())).await {
+        Ok(Ok(_)) => report.ok(&format!(
+            "{}/{} responded in {:.1}s",
+            provider,
+            model,
+            started.elapsed().as_secs_f64()
+        )),
+        Ok(Err(e)) => report.fail(
+            &format!("{}/{} request failed: {}", provider, model, e),
+            "Check the API key, the model name (--model), and network/proxy settings",
+        ),
+        Err(_) => report.fail(
+            &format!(
+                "{}/{} did not respond within {}s",
+                provider,
+                model,
+                CONNECTIVITY_TIMEOUT.as_secs()
+            ),
+            "Check network/proxy settings or the provider's status page",
+        ),
+    }
+}
+
+fn check_binaries(report: &mut Report, config: &Config) {
+    let optional = [
+        ("git", "Install git to let the agent inspect history and diffs"),
+        ("rg", "Install ripgrep for faster searches from bash (https://github.com/BurntSushi/ripgrep)"),
+        (
+            "agent-browser",
+            "Install agent-browser to enable the agent_browser tool (https://github.com/jondot/agent-browser)",
+        ),
+    ];
+    for (binary, fix) in optional {
+        if is_tool_available(binary) {
+            report.ok(&format!("{} found", binary));
+        } else {
+            report.warn(&format!("{} not found", binary), fix);
+        }
+    }
+
+    if let Some(sandbox) = &config.sandbox {
+        if is_tool_available(&sandbox.runtime) {
+            report.ok(&format!("{} found (sandbox runtime)", sandbox.runtime));
+        } else {
+            report.fail(
+                &format!("Sandbox runtime '{}' not found", sandbox.runtime),
+                &format!(
+                    "Install {} or remove `sandbox` from picocode.yaml",
+                    sandbox.runtime
+                ),
+            );
+        }
+    }
+
+    if config.get_tool_settings("bash").network == NetworkPolicy::Deny {
+        if crate::shell::without_network(crate::platform::Shell::Sh, "true").is_some() {
+            report.ok("Network isolation available for bash (network: deny)");
+        } else {
+            report.warn(
+                "No firejail or unprivileged user namespaces; network-looking bash commands will be refused",
+                "Install firejail, or enable unprivileged user namespaces",
+            );
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// A file, optionally at a line, as in `src/main.rs:42`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+}
+
+impl Location {
+    /// Parses `file`, `file:line` or `file:line:col` (the column is ignored).
+    pub fn parse(spec: &str) -> Self {
+        let mut parts = spec.rsplitn(3, ':');
+        let last = parts.next().unwrap_or_default();
+        let middle = parts.next();
+        let rest = parts.next();
+        match (rest, middle, last.parse::<usize>()) {
+            (Some(path), Some(line), Ok(_)) if line.parse::<usize>().is_ok() => Self {
+                path: path.into(),
+                line: line.parse().ok(),
+            },
+            (_, Some(_), Ok(line)) => Self {
+                path: spec.rsplit_once(':').unwrap().0.into(),
+                line: Some(line),
+            },
+            _ => Self {
+                path: spec.into(),
+                line: None,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}", self.path.display(), line),
+            None => write!(f, "{}", self.path.display()),
+        }
+    }
+}
+
+/// `$VISUAL`, then `$EDITOR`.
+pub fn configured_editor() -> Option<String> {
+    std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+}
+
+/// `editor` with its arguments, such as `code --wait`, ready for a file to be added.
+pub fn base_command(editor: &str) -> std::process::Command {
+    let mut parts = editor.split_whitespace();
+    let mut cmd = std::process::Command::new(parts.next().unwrap_or("vi"));
+    cmd.args(parts);
+    cmd
+}
+
+/// Command that opens `location` in `editor`, using the editor's syntax for jumping to a line.
+pub fn command(editor: &str, location: &Location) -> std::process::Command {
+    let program = editor.split_whitespace().next().unwrap_or("vi");
+    let mut cmd = base_command(editor);
+
+    let name = Path::new(program)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(program);
+    let path = location.path.display().to_string();
+    match (location.line, name) {
+        (None, _) => cmd.arg(path),
+        (Some(line), "code" | "code-insiders" | "codium" | "cursor" | "windsurf") => {
+            cmd.arg("-g").arg(format!("{}:{}", path, line))
+        }
+        (Some(line), "subl" | "zed" | "hx" | "helix") => cmd.arg(format!("{}:{}", path, line)),
+        (Some(line), _) => cmd.arg(format!("+{}", line)).arg(path),
+    };
+    cmd
+}
+
+/// Opens `location` in the configured editor, VS Code (`code -g`) when none is set
+/// and it is installed, or `vi`.
+pub fn open(location: &Location) -> crate::Result<()> {
+    let editor = configured_editor()
+        .or_else(|| crate::tools::is_tool_available("code").then(|| "code".to_string()))
+        .unwrap_or_else(|| "vi".to_string());
+    command(&editor, location)
+        .status()
+        .map_err(|e| crate::PicocodeError::Other(format!("Failed to run {}: {}", editor, e)))?;
+    Ok(())
+}
+
+/// New temporary file for writing a long prompt in, for `/compose` and
+/// Ctrl+X. Only the user can read it, and it is removed when dropped.
+pub fn compose_file() -> std::io::Result<tempfile::TempPath> {
+    Ok(tempfile::Builder::new()
+        .prefix("picocode-prompt-")
+        .suffix(".md")
+        .tempfile()?
+        .into_temp_path())
+}
+
+/// Opens a temporary file holding `initial` in the configured editor (or
+/// `vi`) and returns what was saved, `None` when it was left empty.
+pub fn compose(initial: &str) -> crate::Result<Option<String>> {
+    let path = compose_file()?;
+    std::fs::write(&path, initial)?;
+    let editor = configured_editor().unwrap_or_else(|| "vi".to_string());
+    let status = base_command(&editor).arg(&path).status();
+    let text = std::fs::read_to_string(&path);
+    drop(path);
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => {
+            return Err(crate::PicocodeError::Other(format!(
+                "{} exited with {}, prompt discarded",
+                editor, status
+            )))
+        }
+        Err(e) => {
+            return Err(crate::PicocodeError::Other(format!(
+                "Failed to run {}: {}",
+                editor, e
+            )))
+        }
+    }
+    let text = text?;
+    Ok(Some(text.trim_end().to_string()).filter(|t| !t.trim().is_empty()))
+}
+
+/// Whether the terminal renders OSC 8 hyperlinks.
+pub fn supports_hyperlinks() -> bool {
+    if !console::Term::stdout().is_term() {
+        return false;
+    }
+    let env = |k: &str| std::env::var(k).unwrap_or_default();
+    matches!(
+        env("TERM_PROGRAM").as_str(),
+        "iTerm.app" | "WezTerm" | "vscode" | "ghostty" | "Hyper"
+    ) || env("TERM") == "xterm-kitty"
+        || !env("WT_SESSION").is_empty()
+        || env("VTE_VERSION").parse::<u32>().is_ok_and(|v| v >= 5000)
+}
+
+/// `text` linked to the absolute file of `location` with an OSC 8 escape.
+pub fn hyperlink(location: &Location, text: &str) -> String {
+    let path = std::path::absolute(&location.path).unwrap_or_else(|_| location.path.clone());
+    format!(
+        "\x1b]8;;file://{}\x1b\\{}\x1b]8;;\x1b\\",
+        path.display(),
+        text
+    )
+}
+
+/// Where a successful file-changing tool call left its mark, for linking in the output.
+pub fn edited_location(tool: &str, args: &Value) -> Option<Location> {
+    let path = args["path"].as_str()?;
+    let line = match tool {
+        "write_file" => Some(1),
+        "edit_file" => {
+            let new = args["new"].as_str().filter(|n| !n.is_empty())?;
+            let content = std::fs::read_to_string(path).ok()?;
+            content
+                .find(new)
+                .map(|pos| content[..pos].matches('\n').count() + 1)
+        }
+        "notebook_edit" => None,
+        _ => return None,
+    };
+    Some(Location {
+        path: path.into(),
+        line,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_location_and_command() {
+        assert_eq!(Location::parse("src/main.rs:42").to_string(), "src/main.rs:42");
+        assert_eq!(Location::parse("src/main.rs:42:7").line, Some(42));
+        assert_eq!(Location::parse("src/main.rs").line, None);
+        assert_eq!(Location::parse("C:notes").line, None);
+
+        let location = Location::parse("a.rs:3");
+        let args = |editor| {
+            command(editor, &location)
+                .get_args()
+                .map(|a| a.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(args("code --reuse-window"), vec!["--reuse-window", "-g", "a.rs:3"]);
+        assert_eq!(args("/usr/bin/nvim"), vec!["+3", "a.rs"]);
+        assert_eq!(args("zed"), vec!["a.rs:3"]);
+    }
+}
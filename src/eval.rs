@@ -0,0 +1,334 @@
+//! `picocode eval <suite.yaml>`: runs task prompts against fresh copies of
+//! fixture repositories, checks assertions on the result, and reports pass
+//! rates, tokens and cost per model, to compare models and catch prompt
+//! regressions.
+
+use crate::models;
+use crate::platform::Shell;
+use crate::session::TokenUsage;
+use crate::{PicocodeError, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Output kept from a failing `command` assertion.
+const MAX_COMMAND_OUTPUT_CHARS: usize = 2_000;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Suite {
+    /// `provider/model` entries to compare. Empty runs the `--provider` and
+    /// `--model` of the command line.
+    #[serde(default)]
+    pub models: Vec<String>,
+    pub tasks: Vec<Task>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Task {
+    pub name: String,
+    /// Directory, relative to the suite file, copied for each run. Without
+    /// one the task starts in an empty directory.
+    #[serde(default)]
+    pub fixture: Option<String>,
+    pub prompt: String,
+    #[serde(default)]
+    pub assert: Vec<Assertion>,
+}
+
+/// One entry of a task's `assert` list. Every check it sets must pass.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Assertion {
+    /// A file the run should leave in the copy.
+    pub file_exists: Option<String>,
+    /// A file whose contents should match a regex.
+    pub file_matches: Option<FileMatches>,
+    /// A shell command run in the copy that should exit successfully, such
+    /// as the test suite.
+    pub command: Option<String>,
+    /// A regex the agent's final answer should match.
+    pub output_matches: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileMatches {
+    pub path: String,
+    pub regex: String,
+}
+
+impl Suite {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| PicocodeError::Other(format!("Eval suite {}: {}", path.display(), e)))?;
+        let suite: Suite = serde_yaml::from_str(&text)?;
+        for task in &suite.tasks {
+            let empty = task.assert.iter().any(|a| {
+                a.file_exists.is_none()
+                    && a.file_matches.is_none()
+                    && a.command.is_none()
+                    && a.output_matches.is_none()
+            });
+            if empty {
+                return Err(PicocodeError::Other(format!(
+                    "Task '{}' has an assertion without a check",
+                    task.name
+                )));
+            }
+        }
+        if suite.tasks.is_empty() {
+            return Err(PicocodeError::Other(format!(
+                "Eval suite {} has no tasks",
+                path.display()
+            )));
+        }
+        Ok(suite)
+    }
+
+    /// The `(provider, model)` pairs to run.
+    pub fn targets(&self, provider: &str, model: &str) -> Result<Vec<(String, String)>> {
+        if self.models.is_empty() {
+            return Ok(vec![(provider.to_string(), model.to_string())]);
+        }
+        self.models
+            .iter()
+            .map(|entry| match entry.split_once('/') {
+                Some((provider, model)) if !provider.is_empty() && !model.is_empty() => {
+                    Ok((provider.to_string(), model.to_string()))
+                }
+                _ => Err(PicocodeError::Other(format!(
+                    "Eval model '{}' should be provider/model, e.g. anthropic/claude-sonnet-4-5",
+                    entry
+                ))),
+            })
+            .collect()
+    }
+}
+
+/// Copies the fixture of `task` into a new temporary directory and returns it.
+pub fn prepare(task: &Task, suite_dir: &Path) -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!(
+        "picocode-eval-{}-{}-{}",
+        std::process::id(),
+        task.name.replace(|c: char| !c.is_ascii_alphanumeric(), "-"),
+        chrono::Local::now().format("%H%M%S%3f")
+    ));
+    match &task.fixture {
+        Some(fixture) => copy_dir(&suite_dir.join(fixture), &dir).map_err(|e| {
+            PicocodeError::Other(format!("Task '{}' fixture {}: {}", task.name, fixture, e))
+        })?,
+        None => std::fs::create_dir_all(&dir)?,
+    }
+    Ok(dir)
+}
+
+fn copy_dir(src: &Path, dst: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dst)?;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks the assertions of `task` against the run in `dir` that answered
+/// `output`, returning a description of each one that failed.
+pub fn check(task: &Task, dir: &Path, output: &str) -> Vec<String> {
+    let mut failures = Vec::new();
+    for assertion in &task.assert {
+        if let Some(path) = &assertion.file_exists {
+            if !dir.join(path).exists() {
+                failures.push(format!("{} does not exist", path));
+            }
+        }
+        if let Some(FileMatches { path, regex }) = &assertion.file_matches {
+            let matched = std::fs::read_to_string(dir.join(path))
+                .map_err(|e| format!("{}: {}", path, e))
+                .and_then(|text| is_match(regex, &text));
+            match matched {
+                Ok(true) => {}
+                Ok(false) => failures.push(format!("{} does not match `{}`", path, regex)),
+                Err(e) => failures.push(e),
+            }
+        }
+        if let Some(command) = &assertion.command {
+            if let Err(e) = run_command(command, dir) {
+                failures.push(e);
+            }
+        }
+        if let Some(regex) = &assertion.output_matches {
+            match is_match(regex, output) {
+                Ok(true) => {}
+                Ok(false) => failures.push(format!("the answer does not match `{}`", regex)),
+                Err(e) => failures.push(e),
+            }
+        }
+    }
+    failures
+}
+
+fn is_match(pattern: &str, text: &str) -> std::result::Result<bool, String> {
+    Regex::new(pattern)
+        .map(|re| re.is_match(text))
+        .map_err(|e| format!("invalid regex `{}`: {}", pattern, e))
+}
+
+/// Runs `command` in `dir`, failing with the end of its output.
+fn run_command(command: &str, dir: &Path) -> std::result::Result<(), String> {
+    let result = Shell::detect()
+        .command(command)
+        .dir(dir)
+        .stderr_to_stdout()
+        .stdout_capture()
+        .unchecked()
+        .run()
+        .map_err(|e| format!("`{}`: {}", command, e))?;
+    if result.status.success() {
+        return Ok(());
+    }
+    let out = String::from_utf8_lossy(&result.stdout);
+    let start = out
+        .char_indices()
+        .rev()
+        .nth(MAX_COMMAND_OUTPUT_CHARS)
+        .map_or(0, |(i, _)| i);
+    Err(format!(
+        "`{}` failed ({}):\n{}",
+        command,
+        result.status,
+        out[start..].trim_end()
+    ))
+}
+
+/// The outcome of one task with one model.
+#[derive(Debug, Clone)]
+pub struct TaskResult {
+    pub provider: String,
+    pub model: String,
+    pub task: String,
+    /// Failed assertions, or the error that ended the run.
+    pub failures: Vec<String>,
+    pub usage: TokenUsage,
+    pub elapsed: Duration,
+}
+
+impl TaskResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// A table with a row per model: tasks passed, pass rate, tokens, cost and time.
+pub fn summary(results: &[TaskResult]) -> String {
+    let mut targets: Vec<(&str, &str)> = Vec::new();
+    for result in results {
+        let target = (result.provider.as_str(), result.model.as_str());
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    let mut table = format!(
+        "{:<40} {:>7} {:>6} {:>10} {:>9} {:>8}\n",
+        "MODEL", "PASSED", "RATE", "TOKENS", "COST", "TIME"
+    );
+    for (provider, model) in targets {
+        let runs: Vec<&TaskResult> = results
+            .iter()
+            .filter(|r| r.provider == provider && r.model == model)
+            .collect();
+        let passed = runs.iter().filter(|r| r.passed()).count();
+        let mut usage = TokenUsage::default();
+        for run in &runs {
+            usage.input_tokens += run.usage.input_tokens;
+            usage.output_tokens += run.usage.output_tokens;
+            usage.total_tokens += run.usage.total_tokens;
+        }
+        let cost = models::cost(provider, model, &usage)
+            .map(models::format_cost)
+            .unwrap_or_else(|| "-".into());
+        let elapsed: Duration = runs.iter().map(|r| r.elapsed).sum();
+        table.push_str(&format!(
+            "{:<40} {:>7} {:>5.0}% {:>10} {:>9} {:>8}\n",
+            format!("{}/{}", provider, model),
+            format!("{}/{}", passed, runs.len()),
+            pass_rate(&runs),
+            usage.total_tokens,
+            cost,
+            crate::stats::format_duration(elapsed)
+        ));
+    }
+    table
+}
+
+/// Percentage of `runs` that passed.
+pub fn pass_rate(runs: &[&TaskResult]) -> f64 {
+    if runs.is_empty() {
+        return 0.0;
+    }
+    runs.iter().filter(|r| r.passed()).count() as f64 * 100.0 / runs.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suite_and_checks() {
+        let suite: Suite = serde_yaml::from_str(
+            r#"
+models: [anthropic/claude-sonnet-4-5, openai/gpt-4o]
+tasks:
+  - name: add
+    fixture: calculator
+    prompt: Fix add
+    assert:
+      - file_exists: src/lib.rs
+      - file_matches: { path: src/lib.rs, regex: "a \\+ b" }
+      - command: "true"
+      - output_matches: "(?i)fixed"
+"#,
+        )
+        .unwrap();
+        assert_eq!(suite.targets("x", "y").unwrap()[1].0, "openai");
+        assert!(Suite {
+            models: vec!["gpt-4o".into()],
+            ..suite.clone()
+        }
+        .targets("x", "y")
+        .is_err());
+
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("calculator/src")).unwrap();
+        std::fs::write(
+            root.path().join("calculator/src/lib.rs"),
+            "fn add(a: i32, b: i32) -> i32 { a - b }",
+        )
+        .unwrap();
+        let task = &suite.tasks[0];
+        let dir = prepare(task, root.path()).unwrap();
+        assert!(check(task, &dir, "Fixed it").len() == 1);
+        std::fs::write(dir.join("src/lib.rs"), "a + b").unwrap();
+        assert!(check(task, &dir, "Fixed it").is_empty());
+        assert_eq!(check(task, &dir, "Gave up").len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let result = |task: &str, failures: Vec<String>| TaskResult {
+            provider: "openai".into(),
+            model: "gpt-4o".into(),
+            task: task.into(),
+            failures,
+            usage: TokenUsage::default(),
+            elapsed: Duration::from_secs(1),
+        };
+        let results = [result("a", vec![]), result("b", vec!["failed".into()])];
+        let table = summary(&results);
+        assert!(table.contains("openai/gpt-4o"));
+        assert!(table.contains("1/2"));
+        assert!(table.contains("50%"));
+    }
+}
@@ -0,0 +1,122 @@
+//! Files the agent has read this session, and whether they changed on disk
+//! since (the user edited them in another window). The tools that write files
+//! refuse to change a stale file until it is read again, and the next prompt
+//! mentions it, so edits aren't based on outdated contents.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::SystemTime;
+
+/// Wraps the list of changed files sent with a prompt.
+const NOTE_START: &str = "<files-changed-externally>\n";
+const NOTE_END: &str = "</files-changed-externally>\n\n";
+
+/// Modification time and size, `None` once the file is gone.
+type Stamp = Option<(SystemTime, u64)>;
+
+struct Seen {
+    stamp: Stamp,
+    /// The change was already mentioned in a prompt.
+    noted: bool,
+}
+
+static SEEN: LazyLock<Mutex<HashMap<PathBuf, Seen>>> = LazyLock::new(Default::default);
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Records that the agent knows the current contents of `path`, after reading
+/// or writing it.
+pub fn record(path: &Path) {
+    SEEN.lock().unwrap().insert(
+        path.to_path_buf(),
+        Seen {
+            stamp: stamp(path),
+            noted: false,
+        },
+    );
+}
+
+/// Whether `path` was read this session and changed on disk since.
+pub fn is_stale(path: &Path) -> bool {
+    SEEN.lock()
+        .unwrap()
+        .get(path)
+        .is_some_and(|seen| seen.stamp != stamp(path))
+}
+
+/// Files read this session that changed on disk since and weren't mentioned
+/// yet, sorted. They are mentioned from now on.
+pub fn take_changed() -> Vec<PathBuf> {
+    let mut seen = SEEN.lock().unwrap();
+    let mut changed: Vec<PathBuf> = seen
+        .iter_mut()
+        .filter(|(path, seen)| !seen.noted && seen.stamp != stamp(path))
+        .map(|(path, seen)| {
+            seen.noted = true;
+            path.clone()
+        })
+        .collect();
+    changed.sort();
+    changed
+}
+
+/// `input` preceded by a note listing `changed`, relative to the current
+/// directory where possible.
+pub fn with_note(changed: &[PathBuf], input: &str) -> String {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let files: Vec<String> = changed
+        .iter()
+        .map(|path| {
+            let path = path.strip_prefix(&cwd).unwrap_or(path);
+            format!("- {}\n", path.display())
+        })
+        .collect();
+    format!(
+        "{}These files changed on disk since you read them. Read them again before editing:\n{}{}{}",
+        NOTE_START,
+        files.concat(),
+        NOTE_END,
+        input
+    )
+}
+
+/// A prompt without the note added by `with_note`.
+pub fn strip_note(text: &str) -> &str {
+    text.strip_prefix(NOTE_START)
+        .and_then(|rest| rest.split_once(NOTE_END))
+        .map_or(text, |(_, input)| input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lib.rs");
+        std::fs::write(&path, "fn a() {}").unwrap();
+        assert!(!is_stale(&path));
+        record(&path);
+        assert!(!is_stale(&path));
+
+        std::fs::write(&path, "fn a() {}\nfn b() {}").unwrap();
+        assert!(is_stale(&path));
+        assert!(take_changed().contains(&path));
+        assert!(!take_changed().contains(&path));
+        assert!(is_stale(&path));
+
+        record(&path);
+        assert!(!is_stale(&path));
+        std::fs::remove_file(&path).unwrap();
+        assert!(is_stale(&path));
+
+        let prompt = with_note(&[path], "Fix it");
+        assert!(prompt.contains("lib.rs"));
+        assert_eq!(strip_note(&prompt), "Fix it");
+    }
+}
@@ -0,0 +1,148 @@
+//! Git state for the system message: branch, uncommitted changes and recent
+//! commits, so the agent doesn't spend its first turns running `git status`.
+
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+/// Changed files listed before the rest are summarized as a count.
+const MAX_FILES: usize = 30;
+const MAX_COMMITS: usize = 5;
+
+/// Reading the state is repeated before each turn only if it took less than
+/// this at session start, so large repositories don't slow every prompt.
+pub const REFRESH_BUDGET: Duration = Duration::from_millis(200);
+
+/// Wraps the state sent with a prompt when it changed during the session.
+const NOTE_START: &str = "<git-state-changed>\n";
+const NOTE_END: &str = "</git-state-changed>\n\n";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitState {
+    /// Branch and upstream as git prints them, e.g. `main...origin/main [ahead 1]`.
+    pub branch: String,
+    /// `git status --porcelain` lines, e.g. ` M src/lib.rs`.
+    pub changed: Vec<String>,
+    /// `<short hash> <subject>`, most recent first.
+    pub commits: Vec<String>,
+}
+
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Branch line and changed files from `git status --porcelain --branch`.
+fn parse_status(status: &str) -> (String, Vec<String>) {
+    let mut lines = status.lines();
+    let branch = lines
+        .next()
+        .and_then(|line| line.strip_prefix("## "))
+        .unwrap_or("(unknown)")
+        .to_string();
+    let changed = lines
+        .filter(|l| !l.trim().is_empty())
+        .map(String::from)
+        .collect();
+    (branch, changed)
+}
+
+impl GitState {
+    /// State of the repository containing `dir`, or `None` outside a
+    /// repository or without git.
+    pub fn read(dir: &Path) -> Option<Self> {
+        let status = git(dir, &["status", "--porcelain=v1", "--branch"])?;
+        let (branch, changed) = parse_status(&status);
+        // Fails in a repository without commits
+        let commits = git(
+            dir,
+            &["log", &format!("-n{}", MAX_COMMITS), "--format=%h %s"],
+        )
+        .map(|log| log.lines().map(String::from).collect())
+        .unwrap_or_default();
+        Some(Self {
+            branch,
+            changed,
+            commits,
+        })
+    }
+
+    /// State of the current directory's repository, with git run on a
+    /// blocking thread so it doesn't hold up the async runtime.
+    pub async fn read_current() -> Option<Self> {
+        tokio::task::spawn_blocking(|| Self::read(Path::new(".")))
+            .await
+            .ok()
+            .flatten()
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = format!("Branch: {}\n", self.branch);
+        if self.changed.is_empty() {
+            out.push_str("Working tree clean\n");
+        } else {
+            out.push_str(&format!("Uncommitted changes ({}):\n", self.changed.len()));
+            for line in self.changed.iter().take(MAX_FILES) {
+                out.push_str(&format!("{}\n", line));
+            }
+            if self.changed.len() > MAX_FILES {
+                out.push_str(&format!("... {} more\n", self.changed.len() - MAX_FILES));
+            }
+        }
+        if !self.commits.is_empty() {
+            out.push_str("Recent commits:\n");
+            out.push_str(&self.commits.join("\n"));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// `input` preceded by `state`, for a turn where the state changed since the
+/// model last saw it.
+pub fn with_note(state: &GitState, input: &str) -> String {
+    format!("{}{}{}{}", NOTE_START, state.render(), NOTE_END, input)
+}
+
+/// A prompt without the note added by `with_note`.
+pub fn strip_note(text: &str) -> &str {
+    text.strip_prefix(NOTE_START)
+        .and_then(|rest| rest.split_once(NOTE_END))
+        .map_or(text, |(_, input)| input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_render() {
+        let (branch, changed) =
+            parse_status("## main...origin/main [ahead 1]\n M src/lib.rs\n?? notes.md\n");
+        assert_eq!(branch, "main...origin/main [ahead 1]");
+        let state = GitState {
+            branch,
+            changed,
+            commits: vec!["abc1234 Add parser".into()],
+        };
+        assert_eq!(
+            state.render(),
+            "Branch: main...origin/main [ahead 1]\nUncommitted changes (2):\n M src/lib.rs\n?? notes.md\nRecent commits:\nabc1234 Add parser\n"
+        );
+
+        let (_, changed) = parse_status("## No commits yet on main\n");
+        assert!(changed.is_empty());
+
+        let prompt = with_note(&state, "fix the parser");
+        assert!(prompt.starts_with("<git-state-changed>\nBranch: main"));
+        assert_eq!(strip_note(&prompt), "fix the parser");
+        assert_eq!(strip_note("fix the parser"), "fix the parser");
+    }
+}
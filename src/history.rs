@@ -0,0 +1,264 @@
+//! Searching past conversations: `picocode sessions search` and `/history
+//! search`. Built with the `sqlite` feature, saved sessions are indexed with
+//! SQLite FTS5 in the storage database; otherwise a search reads every
+//! session.
+
+use crate::session::Session;
+use chrono::{DateTime, Utc};
+use rig::message::{AssistantContent, Message, UserContent};
+
+/// Characters of context around the first match.
+const SNIPPET_CHARS: usize = 120;
+
+/// A session matching a search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub id: String,
+    pub title: Option<String>,
+    pub updated_at: DateTime<Utc>,
+    /// The text around the first match, on one line.
+    pub snippet: String,
+}
+
+impl Hit {
+    /// Two lines: id, date and title, then the snippet.
+    pub fn display(&self) -> String {
+        format!(
+            "{:<24} {}  {}\n    {}",
+            self.id,
+            self.updated_at
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M"),
+            self.title.as_deref().unwrap_or("-"),
+            self.snippet
+        )
+    }
+}
+
+/// The searchable text of a session: its title and the text of user and
+/// assistant messages, without tool calls and results.
+pub fn session_text(session: &Session) -> String {
+    let mut parts: Vec<&str> = session.title.as_deref().into_iter().collect();
+    for message in &session.messages {
+        match message {
+            Message::User { content } => {
+                parts.extend(content.iter().filter_map(|item| match item {
+                    UserContent::Text(text) => Some(text.text.as_str()),
+                    _ => None,
+                }))
+            }
+            Message::Assistant { content, .. } => {
+                parts.extend(content.iter().filter_map(|item| match item {
+                    AssistantContent::Text(text) => Some(text.text.as_str()),
+                    _ => None,
+                }))
+            }
+        }
+    }
+    parts.join("\n")
+}
+
+fn terms(query: &str) -> Vec<String> {
+    query.split_whitespace().map(str::to_lowercase).collect()
+}
+
+/// Sessions whose text contains every word of `query`, ignoring case, in
+/// the order given.
+pub fn scan(sessions: &[Session], query: &str, limit: usize) -> Vec<Hit> {
+    let terms = terms(query);
+    if terms.is_empty() {
+        return Vec::new();
+    }
+    sessions
+        .iter()
+        .filter_map(|session| {
+            let text = session_text(session);
+            let lower = text.to_lowercase();
+            if !terms.iter().all(|term| lower.contains(term.as_str())) {
+                return None;
+            }
+            Some(Hit {
+                id: session.id.clone(),
+                title: session.title.clone(),
+                updated_at: session.updated_at,
+                snippet: snippet(&text, &terms[0]),
+            })
+        })
+        .take(limit)
+        .collect()
+}
+
+/// The text around the first occurrence of `term`, on one line.
+fn snippet(text: &str, term: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = text.to_lowercase().chars().collect();
+    let term: Vec<char> = term.chars().collect();
+    // Lowercasing may change the length of a few characters; then the
+    // snippet starts at the beginning
+    let position = (lower.len() == chars.len())
+        .then(|| lower.windows(term.len()).position(|w| w == term.as_slice()))
+        .flatten()
+        .unwrap_or(0);
+    let start = position.saturating_sub(SNIPPET_CHARS / 3);
+    let end = (start + SNIPPET_CHARS).min(chars.len());
+    let excerpt: String = chars[start..end].iter().collect();
+    format!(
+        "{}{}{}",
+        if start > 0 { "…" } else { "" },
+        excerpt.split_whitespace().collect::<Vec<_>>().join(" "),
+        if end < chars.len() { "…" } else { "" }
+    )
+}
+
+/// `query` as an FTS5 query matching every word, with each word quoted so
+/// punctuation such as `-` or `:` isn't read as query syntax.
+pub fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Saved sessions indexed with SQLite FTS5, ranked by relevance.
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+pub struct SearchIndex {
+    connection: std::sync::Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite")]
+fn index_error(e: rusqlite::Error) -> crate::PicocodeError {
+    crate::PicocodeError::Other(format!("Session search index: {}", e))
+}
+
+#[cfg(feature = "sqlite")]
+impl SearchIndex {
+    pub fn open(path: &std::path::Path) -> crate::Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let connection = rusqlite::Connection::open(path).map_err(index_error)?;
+        connection
+            .execute_batch(
+                "PRAGMA journal_mode = WAL;
+                 PRAGMA busy_timeout = 5000;
+                 CREATE VIRTUAL TABLE IF NOT EXISTS session_search USING fts5(
+                     id UNINDEXED, title, updated_at UNINDEXED, text,
+                     tokenize = 'porter unicode61'
+                 );",
+            )
+            .map_err(index_error)?;
+        Ok(Self {
+            connection: std::sync::Mutex::new(connection),
+        })
+    }
+
+    /// Indexes `session`, replacing what was indexed for it before.
+    pub fn update(&self, session: &Session) -> crate::Result<()> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute("DELETE FROM session_search WHERE id = ?1", [&session.id])
+            .map_err(index_error)?;
+        connection
+            .execute(
+                "INSERT INTO session_search (id, title, updated_at, text) VALUES (?1, ?2, ?3, ?4)",
+                [
+                    session.id.as_str(),
+                    session.title.as_deref().unwrap_or_default(),
+                    &session.updated_at.to_rfc3339(),
+                    &session_text(session),
+                ],
+            )
+            .map_err(index_error)?;
+        Ok(())
+    }
+
+    pub fn remove(&self, id: &str) -> crate::Result<()> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM session_search WHERE id = ?1", [id])
+            .map_err(index_error)?;
+        Ok(())
+    }
+
+    /// Ids of the indexed sessions.
+    pub fn ids(&self) -> crate::Result<std::collections::HashSet<String>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare("SELECT id FROM session_search")
+            .map_err(index_error)?;
+        let ids = statement
+            .query_map([], |row| row.get(0))
+            .map_err(index_error)?
+            .collect::<rusqlite::Result<_>>()
+            .map_err(index_error)?;
+        Ok(ids)
+    }
+
+    /// Sessions matching every word of `query`, most relevant first.
+    pub fn search(&self, query: &str, limit: usize) -> crate::Result<Vec<Hit>> {
+        let query = fts_query(query);
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare(
+                "SELECT id, title, updated_at, snippet(session_search, 3, '', '', '…', 20)
+                 FROM session_search WHERE session_search MATCH ?1 ORDER BY rank LIMIT ?2",
+            )
+            .map_err(index_error)?;
+        let hits = statement
+            .query_map(rusqlite::params![query, limit as i64], |row| {
+                let title: String = row.get(1)?;
+                let updated_at: String = row.get(2)?;
+                let snippet: String = row.get(3)?;
+                Ok(Hit {
+                    id: row.get(0)?,
+                    title: (!title.is_empty()).then_some(title),
+                    updated_at: DateTime::parse_from_rfc3339(&updated_at)
+                        .map(|t| t.with_timezone(&Utc))
+                        .unwrap_or_default(),
+                    snippet: snippet.split_whitespace().collect::<Vec<_>>().join(" "),
+                })
+            })
+            .map_err(index_error)?
+            .collect::<rusqlite::Result<_>>()
+            .map_err(index_error)?;
+        Ok(hits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan() {
+        let mut jwt = Session::new("jwt".into(), "openai", "gpt-4o", None);
+        jwt.title = Some("Fix token refresh".into());
+        jwt.messages = vec![
+            Message::user("The JWT refresh fails after an hour"),
+            Message::assistant("The refresh token is never rotated."),
+        ];
+        let mut css = Session::new("css".into(), "openai", "gpt-4o", None);
+        css.messages = vec![Message::user("Center the login form")];
+        let sessions = [jwt, css];
+
+        let hits = scan(&sessions, "jwt REFRESH", 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, "jwt");
+        assert!(hits[0].snippet.contains("The JWT refresh fails"));
+        assert!(scan(&sessions, "jwt login", 10).is_empty());
+        assert!(scan(&sessions, "  ", 10).is_empty());
+        assert_eq!(scan(&sessions, "the", 1).len(), 1);
+
+        assert_eq!(
+            snippet(&"word ".repeat(100), "word").chars().last(),
+            Some('…')
+        );
+        assert_eq!(fts_query("jwt  say\"hi\""), "\"jwt\" \"say\"\"hi\"\"\"");
+    }
+}
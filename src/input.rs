@@ -1,9 +1,22 @@
+use crate::config::{HistoryMode, InputConfig};
+use console::style;
 use reedline::{
-    default_emacs_keybindings, EditCommand, Emacs, FileBackedHistory, KeyCode, KeyModifiers,
-    Prompt, PromptEditMode, PromptHistorySearch, PromptHistorySearchStatus, Reedline,
-    ReedlineEvent, Signal,
+    default_emacs_keybindings, EditCommand, Emacs, FileBackedHistory, History, HistoryItem,
+    HistoryItemId, HistorySessionId, KeyCode, KeyModifiers, Prompt, PromptEditMode,
+    PromptHistorySearch, PromptHistorySearchStatus, Reedline, ReedlineEvent, SearchQuery, Signal,
 };
 use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use termimad::crossterm::event::{
+    self, Event, KeyCode as TermKeyCode, KeyEventKind, KeyModifiers as TermKeyModifiers,
+};
+use termimad::crossterm::{cursor, execute, terminal};
 
 #[derive(Debug)]
 pub enum ReadlineError {
@@ -45,14 +58,165 @@ impl Prompt for SimplePrompt {
     }
 }
 
+/// Entries kept in the history.
+const HISTORY_SIZE: usize = 1000;
+
+/// `input` of picocode.yaml, for the line editors created after `configure`.
+static SETTINGS: OnceLock<InputConfig> = OnceLock::new();
+
+/// Sets the history mode and file of the input editor for the rest of the run.
+pub fn configure(config: &InputConfig) {
+    let _ = SETTINGS.set(config.clone());
+}
+
+/// The history file, `None` unless history is persistent.
+pub fn history_path() -> Option<PathBuf> {
+    let settings = SETTINGS.get().cloned().unwrap_or_default();
+    if settings.history != HistoryMode::Persistent {
+        return None;
+    }
+    match settings.history_file {
+        Some(file) => Some(PathBuf::from(file)),
+        None => default_history_path(),
+    }
+}
+
+fn default_history_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".picocode_history"))
+}
+
+/// Whether `path` can be picocode's history, which is rewritten when loaded:
+/// the default file or one in a `.picocode` directory, and not a symlink.
+fn is_history_path(path: &Path) -> bool {
+    let picocode_dir = path
+        .parent()
+        .is_some_and(|dir| dir.components().any(|c| c.as_os_str() == ".picocode"));
+    let symlink = std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+    (picocode_dir || Some(path) == default_history_path().as_deref()) && !symlink
+}
+
+/// Entries of a history file, one per line, keeping only the last copy of a
+/// repeated entry and dropping entries that look like they hold a credential.
+fn compact(lines: &[String]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut kept: Vec<String> = lines
+        .iter()
+        .rev()
+        .filter(|line| !line.trim().is_empty() && !crate::secrets::contains_secret(line))
+        .filter(|line| seen.insert(line.as_str()))
+        .cloned()
+        .collect();
+    kept.reverse();
+    kept
+}
+
+/// Rewrites the history file at `path` with its [`compact`] entries, also
+/// removing secrets saved by earlier versions.
+fn compact_file(path: &Path) -> std::io::Result<()> {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return Ok(());
+    };
+    let lines: Vec<String> = content.lines().map(String::from).collect();
+    let kept = compact(&lines);
+    if kept.len() < lines.len() {
+        let content: String = kept.iter().map(|line| format!("{}\n", line)).collect();
+        std::fs::write(path, content)?;
+    }
+    Ok(())
+}
+
+/// A history that only saves the entries `keep` accepts; the others can't be
+/// recalled with the arrow keys and never reach the history file.
+struct FilteredHistory {
+    inner: Box<dyn History>,
+    keep: fn(&str) -> bool,
+}
+
+impl History for FilteredHistory {
+    fn save(&mut self, h: HistoryItem) -> reedline::Result<HistoryItem> {
+        if (self.keep)(&h.command_line) {
+            self.inner.save(h)
+        } else {
+            Ok(h)
+        }
+    }
+
+    fn load(&self, id: HistoryItemId) -> reedline::Result<HistoryItem> {
+        self.inner.load(id)
+    }
+
+    fn count(&self, query: SearchQuery) -> reedline::Result<i64> {
+        self.inner.count(query)
+    }
+
+    fn search(&self, query: SearchQuery) -> reedline::Result<Vec<HistoryItem>> {
+        self.inner.search(query)
+    }
+
+    fn update(
+        &mut self,
+        id: HistoryItemId,
+        updater: &dyn Fn(HistoryItem) -> HistoryItem,
+    ) -> reedline::Result<()> {
+        self.inner.update(id, updater)
+    }
+
+    fn clear(&mut self) -> reedline::Result<()> {
+        self.inner.clear()
+    }
+
+    fn delete(&mut self, h: HistoryItemId) -> reedline::Result<()> {
+        self.inner.delete(h)
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.inner.sync()
+    }
+
+    fn session(&self) -> Option<HistorySessionId> {
+        self.inner.session()
+    }
+}
+
+/// The history of `mode`: nothing, in memory, or the history file without
+/// lines that look like they hold a credential.
+fn history(mode: HistoryMode) -> Result<Box<dyn History>, String> {
+    let in_memory = || Box::new(FileBackedHistory::default()) as Box<dyn History>;
+    Ok(match (mode, history_path()) {
+        (HistoryMode::Off, _) => Box::new(FilteredHistory {
+            inner: in_memory(),
+            keep: |_| false,
+        }),
+        (HistoryMode::Persistent, Some(path)) => {
+            if !is_history_path(&path) {
+                return Err(format!(
+                    "{} is not a picocode history file; input.history_file must be in a .picocode directory",
+                    path.display()
+                ));
+            }
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            compact_file(&path).map_err(|e| e.to_string())?;
+            let history =
+                FileBackedHistory::with_file(HISTORY_SIZE, path).map_err(|e| e.to_string())?;
+            Box::new(FilteredHistory {
+                inner: Box::new(history),
+                keep: |line| !crate::secrets::contains_secret(line),
+            })
+        }
+        _ => in_memory(),
+    })
+}
+
 pub struct InputEditor {
     editor: Reedline,
+    /// File Ctrl+X opens in the editor, removed with the `InputEditor`.
+    _prompt_file: tempfile::TempPath,
 }
 
 impl InputEditor {
     pub fn new() -> Result<Self, String> {
-        let history_path = dirs::home_dir().map(|h| h.join(".picocode_history"));
-
         let mut keybindings = default_emacs_keybindings();
 
         // Enter always submits
@@ -69,24 +233,39 @@ impl InputEditor {
             ReedlineEvent::Edit(vec![EditCommand::InsertNewline]),
         );
 
+        // Ctrl+X opens the line in $EDITOR, like the start of bash's Ctrl+X Ctrl+E
+        keybindings.add_binding(
+            KeyModifiers::CONTROL,
+            KeyCode::Char('x'),
+            ReedlineEvent::OpenEditor,
+        );
+
         let edit_mode = Box::new(Emacs::new(keybindings));
 
+        let program = crate::editor::configured_editor().unwrap_or_else(|| "vi".to_string());
+        let prompt_file = crate::editor::compose_file().map_err(|e| e.to_string())?;
         let mut editor = Reedline::create()
             .with_edit_mode(edit_mode)
+            .with_buffer_editor(
+                crate::editor::base_command(&program),
+                prompt_file.to_path_buf(),
+            )
             .use_kitty_keyboard_enhancement(true);
 
-        if let Some(ref path) = history_path {
-            match FileBackedHistory::with_file(1000, path.clone()) {
-                Ok(history) => {
-                    editor = editor.with_history(Box::new(history));
-                }
-                Err(e) => {
-                    eprintln!("Warning: could not load history: {}", e);
-                }
+        let mode = SETTINGS.get().map(|s| s.history).unwrap_or_default();
+        match history(mode) {
+            Ok(history) => {
+                editor = editor.with_history(history);
+            }
+            Err(e) => {
+                eprintln!("Warning: could not load history: {}", e);
             }
         }
 
-        Ok(Self { editor })
+        Ok(Self {
+            editor,
+            _prompt_file: prompt_file,
+        })
     }
 
     pub fn readline(&mut self, prompt: &str) -> Result<String, ReadlineError> {
@@ -102,7 +281,234 @@ impl InputEditor {
         }
     }
 
+    /// Like `readline`, with the buffer pre-filled with `initial` for editing.
+    pub fn readline_with(
+        &mut self,
+        prompt: &str,
+        initial: &str,
+    ) -> Result<String, ReadlineError> {
+        self.editor.run_edit_commands(&[
+            EditCommand::Clear,
+            EditCommand::InsertString(initial.to_string()),
+        ]);
+        self.readline(prompt)
+    }
+
     pub fn save_history(&mut self) {
         let _ = self.editor.sync_history();
     }
 }
+
+/// Collects lines typed while the agent is busy and the line editor is not
+/// active, so they can be queued as the next messages.
+pub struct TypeAhead {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<Vec<String>>,
+}
+
+impl TypeAhead {
+    pub fn start() -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let handle = std::thread::spawn(move || {
+            let mut lines = Vec::new();
+            let mut current = String::new();
+            while !flag.load(Ordering::Relaxed) {
+                match event::poll(Duration::from_millis(50)) {
+                    Ok(true) => match event::read() {
+                        Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                            TermKeyCode::Enter => {
+                                let line = std::mem::take(&mut current);
+                                if !line.trim().is_empty() {
+                                    lines.push(line.trim().to_string());
+                                }
+                            }
+                            TermKeyCode::Char(c) => current.push(c),
+                            TermKeyCode::Backspace => {
+                                current.pop();
+                            }
+                            _ => {}
+                        },
+                        Ok(Event::Paste(text)) => current.push_str(&text),
+                        Ok(_) => {}
+                        Err(_) => break,
+                    },
+                    Ok(false) => {}
+                    Err(_) => break,
+                }
+            }
+            if !current.trim().is_empty() {
+                lines.push(current.trim().to_string());
+            }
+            lines
+        });
+        Self { stop, handle }
+    }
+
+    /// Stops collecting and returns the complete lines typed so far.
+    pub fn stop(self) -> Vec<String> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.join().unwrap_or_default()
+    }
+}
+
+/// What a key does in [`select`].
+#[derive(Debug, PartialEq)]
+enum Selection {
+    Move(usize),
+    Pick(usize),
+    Cancel,
+}
+
+fn select_key(code: TermKeyCode, selected: usize, keys: &[char]) -> Option<Selection> {
+    let last = keys.len().saturating_sub(1);
+    match code {
+        TermKeyCode::Up | TermKeyCode::Char('k') => {
+            Some(Selection::Move(selected.saturating_sub(1)))
+        }
+        TermKeyCode::Down | TermKeyCode::Char('j') => {
+            Some(Selection::Move((selected + 1).min(last)))
+        }
+        TermKeyCode::Home => Some(Selection::Move(0)),
+        TermKeyCode::End => Some(Selection::Move(last)),
+        TermKeyCode::Enter => Some(Selection::Pick(selected)),
+        TermKeyCode::Esc => Some(Selection::Cancel),
+        TermKeyCode::Char(c) => keys
+            .iter()
+            .position(|key| *key == c.to_ascii_lowercase())
+            .map(Selection::Pick),
+        _ => None,
+    }
+}
+
+fn render_choices(choices: &[(char, String)], selected: usize) -> String {
+    choices
+        .iter()
+        .enumerate()
+        .map(|(i, (key, label))| {
+            if i == selected {
+                format!(
+                    "  {} {} {}",
+                    style("❯").cyan(),
+                    style(label).cyan().bold(),
+                    style(key).dim()
+                )
+            } else {
+                format!("    {} {}", label, style(key).dim())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+/// Menu of `choices`, each with a shortcut key, moved through with the arrow
+/// keys (or `j`/`k`). Returns the index picked with Enter or its key, `None`
+/// on Esc, Ctrl-C, once `cancelled` returns true or when the terminal can't
+/// be put in raw mode.
+pub fn select(choices: &[(char, String)], cancelled: impl Fn() -> bool) -> Option<usize> {
+    if choices.is_empty() || terminal::enable_raw_mode().is_err() {
+        return None;
+    }
+    let keys: Vec<char> = choices.iter().map(|(key, _)| *key).collect();
+    let mut out = std::io::stdout();
+    let mut selected = 0;
+    let _ = write!(out, "{}", render_choices(choices, selected));
+    let _ = out.flush();
+    let picked = loop {
+        let polled = match event::poll(crate::output::confirm::POLL_INTERVAL) {
+            Ok(true) => event::read().map(Some),
+            Ok(false) => Ok(None),
+            Err(e) => Err(e),
+        };
+        let key = match polled {
+            Ok(Some(Event::Key(key))) if key.kind == KeyEventKind::Press => key,
+            Ok(None) if cancelled() => break None,
+            Ok(_) => continue,
+            Err(_) => break None,
+        };
+        if key.modifiers.contains(TermKeyModifiers::CONTROL) && key.code == TermKeyCode::Char('c') {
+            break None;
+        }
+        match select_key(key.code, selected, &keys) {
+            Some(Selection::Move(i)) => {
+                selected = i;
+                let _ = execute!(
+                    out,
+                    cursor::MoveToPreviousLine(choices.len() as u16 - 1),
+                    cursor::MoveToColumn(0),
+                    terminal::Clear(terminal::ClearType::FromCursorDown)
+                );
+                let _ = write!(out, "{}", render_choices(choices, selected));
+                let _ = out.flush();
+            }
+            Some(Selection::Pick(i)) => break Some(i),
+            Some(Selection::Cancel) => break None,
+            None => {}
+        }
+    };
+    // Replace the menu with the answer
+    let _ = execute!(
+        out,
+        cursor::MoveToPreviousLine(choices.len() as u16 - 1),
+        cursor::MoveToColumn(0),
+        terminal::Clear(terminal::ClearType::FromCursorDown)
+    );
+    let _ = terminal::disable_raw_mode();
+    let answer = picked.map_or("No", |i| choices[i].1.as_str());
+    println!("  {} {}", style("❯").cyan(), answer);
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_history() {
+        let lines: Vec<String> = [
+            "cargo test",
+            "fix the parser",
+            "export OPENAI_API_KEY=abcdef123456",
+            "",
+            "cargo test",
+        ]
+        .map(String::from)
+        .to_vec();
+        assert_eq!(compact(&lines), ["fix the parser", "cargo test"]);
+
+        assert!(is_history_path(Path::new(".picocode/history")));
+        assert!(is_history_path(Path::new("/work/app/.picocode/prompts")));
+        assert!(!is_history_path(Path::new(".bashrc")));
+        assert!(!is_history_path(Path::new("/home/me/.ssh/authorized_keys")));
+    }
+
+    #[test]
+    fn test_select_keys() {
+        let keys = ['y', 'n', 's'];
+        assert_eq!(
+            select_key(TermKeyCode::Down, 0, &keys),
+            Some(Selection::Move(1))
+        );
+        assert_eq!(
+            select_key(TermKeyCode::Down, 2, &keys),
+            Some(Selection::Move(2))
+        );
+        assert_eq!(
+            select_key(TermKeyCode::Up, 0, &keys),
+            Some(Selection::Move(0))
+        );
+        assert_eq!(
+            select_key(TermKeyCode::Enter, 1, &keys),
+            Some(Selection::Pick(1))
+        );
+        assert_eq!(
+            select_key(TermKeyCode::Char('S'), 0, &keys),
+            Some(Selection::Pick(2))
+        );
+        assert_eq!(
+            select_key(TermKeyCode::Esc, 0, &keys),
+            Some(Selection::Cancel)
+        );
+        assert_eq!(select_key(TermKeyCode::Char('x'), 0, &keys), None);
+    }
+}
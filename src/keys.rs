@@ -0,0 +1,196 @@
+//! Several API keys for one provider, for teams sharing quota-limited keys:
+//! `ANTHROPIC_API_KEY_1`, `ANTHROPIC_API_KEY_2`, ... next to the usual
+//! variable. Keys only come from the environment, so a repository's
+//! picocode.yaml can't add one of its own. A client is made per key and
+//! [`RotatingModel`] spreads requests over them, moving to the next key when
+//! one is rate limited or, with `api_keys.<provider>.rotation: round_robin`,
+//! on every request.
+
+use crate::config::KeyRotation;
+use rig::agent::AgentBuilder;
+use rig::client::{CompletionClient, ProviderClient};
+use rig::completion::{CompletionError, CompletionModel, CompletionRequest, CompletionResponse};
+use rig::streaming::StreamingCompletionResponse;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// The keys for a provider whose key is read from `var`: the variable and
+/// its numbered variants up to the first missing one, without duplicates.
+pub fn collect(var: &str) -> Vec<String> {
+    collect_from(|name| std::env::var(name).ok(), var)
+}
+
+fn collect_from(env: impl Fn(&str) -> Option<String>, var: &str) -> Vec<String> {
+    let numbered = (1..).map_while(|n| env(&format!("{}_{}", var, n)));
+    let mut keys: Vec<String> = Vec::new();
+    for key in env(var).into_iter().chain(numbered) {
+        if !key.is_empty() && !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    keys
+}
+
+/// A client per key, made from the key itself. Setting the provider's
+/// variable for `from_env` instead would race with threads reading the
+/// environment.
+pub fn clients<C>(keys: &[String]) -> Vec<C>
+where
+    C: ProviderClient,
+    C::Input: From<String>,
+{
+    keys.iter()
+        .map(|key| C::from_val(key.clone().into()))
+        .collect()
+}
+
+/// The clients of every key of a provider. Models made from it share the
+/// position in the rotation.
+#[derive(Clone)]
+pub struct RotatingClient<C> {
+    clients: Arc<Vec<C>>,
+    rotation: KeyRotation,
+    next: Arc<AtomicUsize>,
+}
+
+impl<C: CompletionClient> RotatingClient<C> {
+    pub fn new(clients: Vec<C>, rotation: KeyRotation) -> Self {
+        Self {
+            clients: Arc::new(clients),
+            rotation,
+            next: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// `model` with every key, like a provider client's `completion_model`.
+    pub fn completion_model(&self, model: &str) -> RotatingModel<C::CompletionModel> {
+        RotatingModel {
+            models: Arc::new(
+                self.clients
+                    .iter()
+                    .map(|client| client.completion_model(model))
+                    .collect(),
+            ),
+            rotation: self.rotation,
+            next: self.next.clone(),
+        }
+    }
+
+    /// An agent builder on `model` with every key, like a provider client's `agent`.
+    pub fn agent(&self, model: &str) -> AgentBuilder<RotatingModel<C::CompletionModel>> {
+        AgentBuilder::new(self.completion_model(model))
+    }
+}
+
+/// One model per key. A rate limited request is retried with the next key
+/// until every key has been tried.
+#[derive(Clone)]
+pub struct RotatingModel<M> {
+    models: Arc<Vec<M>>,
+    rotation: KeyRotation,
+    next: Arc<AtomicUsize>,
+}
+
+impl<M> RotatingModel<M> {
+    /// Position of the key for the next request, modulo the number of keys.
+    fn pick(&self) -> usize {
+        match self.rotation {
+            KeyRotation::OnRateLimit => self.next.load(Ordering::Relaxed),
+            KeyRotation::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Moves past the key at `position` after it was rate limited, unless a
+    /// concurrent request already did.
+    fn rotate(&self, position: usize) {
+        if self.rotation == KeyRotation::OnRateLimit {
+            let _ = self.next.compare_exchange(
+                position,
+                position + 1,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            );
+        }
+    }
+}
+
+impl<M: CompletionModel> CompletionModel for RotatingModel<M> {
+    type Response = M::Response;
+    type StreamingResponse = M::StreamingResponse;
+    type Client = RotatingModel<M>;
+
+    fn make(client: &Self::Client, _model: impl Into<String>) -> Self {
+        client.clone()
+    }
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> std::result::Result<CompletionResponse<M::Response>, CompletionError> {
+        let mut last = None;
+        for _ in 0..self.models.len() {
+            let position = self.pick();
+            let key = position % self.models.len();
+            match self.models[key].completion(request.clone()).await {
+                Err(e) if crate::ratelimit::is_rate_limit_error(&e.to_string()) => {
+                    tracing::warn!(
+                        target: "picocode",
+                        "API key {} of {} is rate limited, trying the next",
+                        key + 1,
+                        self.models.len()
+                    );
+                    self.rotate(position);
+                    last = Some(e);
+                }
+                result => return result,
+            }
+        }
+        Err(last.unwrap_or_else(|| CompletionError::ProviderError("No API keys".into())))
+    }
+
+    async fn stream(
+        &self,
+        request: CompletionRequest,
+    ) -> std::result::Result<StreamingCompletionResponse<M::StreamingResponse>, CompletionError>
+    {
+        let key = self.pick() % self.models.len();
+        self.models[key].stream(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_collect() {
+        let env: HashMap<&str, &str> = [
+            ("ANTHROPIC_API_KEY", "a"),
+            ("ANTHROPIC_API_KEY_1", "b"),
+            ("ANTHROPIC_API_KEY_2", "a"),
+            ("ANTHROPIC_API_KEY_4", "skipped"),
+        ]
+        .into();
+        let get = |name: &str| env.get(name).map(|v| v.to_string());
+        assert_eq!(collect_from(get, "ANTHROPIC_API_KEY"), ["a", "b"]);
+        assert!(collect_from(get, "OPENAI_API_KEY").is_empty());
+    }
+
+    #[test]
+    fn test_rotation() {
+        let model = |rotation| RotatingModel {
+            models: Arc::new(vec![(), (), ()]),
+            rotation,
+            next: Arc::new(AtomicUsize::new(0)),
+        };
+        let sticky = model(KeyRotation::OnRateLimit);
+        assert_eq!((sticky.pick(), sticky.pick()), (0, 0));
+        sticky.rotate(0);
+        sticky.rotate(0);
+        assert_eq!(sticky.pick(), 1);
+
+        let round_robin = model(KeyRotation::RoundRobin);
+        assert_eq!((round_robin.pick(), round_robin.pick()), (0, 1));
+    }
+}
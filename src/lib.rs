@@ -1,22 +1,77 @@
 use thiserror::Error;
 
 pub mod agent;
+pub mod audit;
+pub mod batch;
+pub mod checkpoint;
+pub mod clipboard;
+pub mod commands;
+pub mod editor;
+pub mod eval;
 pub mod input;
+pub mod keys;
+pub mod load;
+pub mod loops;
+pub mod memory;
+#[cfg(any(test, feature = "test-util"))]
+pub mod mock;
+pub mod models;
+pub mod network;
 pub mod output;
 pub mod tools;
 pub mod persona;
+pub mod plan;
+pub mod platform;
 pub mod config;
+pub mod context;
+pub mod crash;
+pub mod debug;
+pub mod doctor;
+pub mod freshness;
+pub mod git;
+pub mod history;
+pub mod ratelimit;
+pub mod replay;
+pub mod review;
+pub mod roundtable;
+pub mod routing;
+pub mod sandbox;
+pub mod schedule;
+pub mod secrets;
+pub mod session;
+pub mod shell;
+pub mod stats;
+pub mod storage;
+pub mod summarize;
+pub mod telemetry;
+pub mod tokens;
+pub mod trash;
+pub mod trust;
+pub mod verify;
 
-pub use config::{Config, Recipe, ToolSettings};
+pub use config::{
+    Config, ConfirmDefault, ConfirmationConfig, Extract, GitLabConfig, NetworkConfig, NetworkPolicy,
+    PostProcess, RateLimit, Recipe, RecipeMode, RoutingConfig, SummaryConfig, ToolSettings,
+    WebSearchConfig,
+};
 
 // Re-export core rig types for library users
 pub use rig::agent::AgentBuilder;
 pub use rig::client::{CompletionClient, ProviderClient};
 pub use rig::completion::CompletionModel;
+pub use rig::message::Message;
+pub use tokio_util::sync::CancellationToken;
 pub use rig::providers;
 
-pub use agent::{create_agent, load_agents_md, AgentConfig, CodeAgent, PicoAgent};
-pub use output::{Confirmation, ConsoleOutput, LogOutput, NoOutput, Output, QuietOutput};
+pub use agent::{
+    create_agent, create_agent_with, load_agents_md, AgentConfig, BuilderHook, CodeAgent, NoHook,
+    PicoAgent,
+};
+pub use output::{
+    ConfirmOptions, Confirmation, ConsoleOutput, FilterOutput, LogFileOutput, LogOutput, NoOutput,
+    NotifyingOutput, Output, OutputExt, QuietOutput, RedactingOutput, Status, SyncOutput,
+    TeeOutput,
+};
 
 #[derive(Error, Debug)]
 pub enum PicocodeError {
@@ -35,12 +90,27 @@ pub enum PicocodeError {
     #[error("LLM error: {0}")]
     Llm(String),
 
+    #[error("Rate limited by provider: {0}. Consider setting rate_limits in picocode.yaml.")]
+    RateLimited(String),
+
     #[error("Missing API key for provider {0}. Please set the {1} environment variable.")]
     MissingApiKey(String, String),
 
     #[error("Regex error: {0}")]
     Regex(#[from] regex::Error),
 
+    #[error("Tool call limit of {0} reached before the task was finished (raise it with --tool-call-limit)")]
+    ToolCallLimit(usize),
+
+    #[error("Stopped a tool loop: {0}")]
+    ToolLoop(String),
+
+    #[error("The agent asked \"{0}\", and nobody can answer in this run (add an answer under the recipe's `answers:`)")]
+    Unanswered(String),
+
+    #[error("Cancelled")]
+    Cancelled,
+
     #[error("Other error: {0}")]
     Other(String),
 }
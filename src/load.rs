@@ -0,0 +1,80 @@
+//! `/load <file> [instruction]`: a file's contents sent as the prompt, for
+//! issue exports, logs or specs that are awkward to paste or retype.
+
+use crate::{PicocodeError, Result};
+use std::path::Path;
+
+/// Larger files would crowd the conversation out of the context window.
+const MAX_BYTES: u64 = 256 * 1024;
+
+/// Splits `/load` arguments into the path and the instruction after it. A
+/// path containing spaces can be put in double quotes.
+pub fn parse_args(args: &str) -> Option<(&str, &str)> {
+    let args = args.trim();
+    let (path, instruction) = match args.strip_prefix('"') {
+        Some(quoted) => quoted.split_once('"')?,
+        None => args.split_once(char::is_whitespace).unwrap_or((args, "")),
+    };
+    (!path.is_empty()).then(|| (path, instruction.trim()))
+}
+
+/// `instruction` followed by the contents of `path`, fenced so that code
+/// blocks inside the file don't end it early.
+pub fn prompt(path: &Path, instruction: &str) -> Result<String> {
+    let name = path.display();
+    if crate::secrets::is_protected(path) {
+        return Err(PicocodeError::Other(format!(
+            "{} is a protected file and isn't sent to the model",
+            name
+        )));
+    }
+    let size = std::fs::metadata(path)?.len();
+    if size > MAX_BYTES {
+        return Err(PicocodeError::Other(format!(
+            "{} is {} KB, more than the {} KB /load sends",
+            name,
+            size / 1024,
+            MAX_BYTES / 1024
+        )));
+    }
+    let content = String::from_utf8(std::fs::read(path)?)
+        .map_err(|_| PicocodeError::Other(format!("{} is not a text file", name)))?;
+    let longest_run = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    let file = format!("`{}`:\n{}\n{}\n{}", name, fence, content.trim_end(), fence);
+    Ok(match instruction.trim() {
+        "" => file,
+        instruction => format!("{}\n\n{}", instruction, file),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_prompt() {
+        assert_eq!(
+            parse_args(" issue.md summarize it "),
+            Some(("issue.md", "summarize it"))
+        );
+        assert_eq!(
+            parse_args("\"my notes.txt\" fix"),
+            Some(("my notes.txt", "fix"))
+        );
+        assert_eq!(parse_args("log.txt"), Some(("log.txt", "")));
+        assert_eq!(parse_args("  "), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spec.md");
+        std::fs::write(&path, "Use this:\n```rust\nfn a() {}\n```\n").unwrap();
+        let prompt = prompt(&path, "Implement the spec").unwrap();
+        assert!(prompt.starts_with("Implement the spec\n\n`"));
+        assert!(prompt.contains("spec.md`:\n````\nUse this:\n```rust\n"));
+        assert!(prompt.ends_with("```\n````"));
+
+        std::fs::write(dir.path().join(".env"), "KEY=secret").unwrap();
+        assert!(super::prompt(&dir.path().join(".env"), "").is_err());
+        assert!(super::prompt(&dir.path().join("missing.md"), "").is_err());
+    }
+}
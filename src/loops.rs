@@ -0,0 +1,194 @@
+//! Catches a model stuck in a tool loop: the same call repeated with identical
+//! arguments, or two calls that keep failing in turn. The first time, the
+//! looping call isn't run and its result tells the model to change course; if
+//! it loops again, the turn is stopped instead of spending the whole tool call
+//! limit.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Identical calls in a row that count as a loop, unless configured.
+pub const DEFAULT_REPEAT_LIMIT: usize = 3;
+
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    Run,
+    /// Refuse the call and let the model try something else.
+    Nudge(String),
+    /// Refuse the call and end the turn.
+    Stop(String),
+}
+
+struct Call {
+    tool: String,
+    args: String,
+    failed: bool,
+}
+
+impl Call {
+    fn same(&self, other: &Call) -> bool {
+        self.tool == other.tool && self.args == other.args
+    }
+}
+
+#[derive(Default)]
+struct State {
+    recent: VecDeque<Call>,
+    nudged: bool,
+    stopped: Option<String>,
+    /// Messages returned instead of running the next call of a tool.
+    refusals: HashMap<String, String>,
+}
+
+pub struct LoopGuard {
+    /// Identical calls in a row that count as a loop; 0 disables detection.
+    limit: usize,
+    state: Mutex<State>,
+}
+
+/// Whether a tool result reports a failure, including tools that return
+/// `error: ...` as their output.
+fn failed(result: &str) -> bool {
+    crate::stats::is_failure(result)
+        || result
+            .trim_start_matches('"')
+            .to_lowercase()
+            .starts_with("error")
+}
+
+impl LoopGuard {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Forgets the calls of the previous prompt.
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = State::default();
+    }
+
+    /// Records a call the model made, before it runs.
+    pub fn call(&self, tool: &str, args: &str) -> Verdict {
+        if self.limit == 0 {
+            return Verdict::Run;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.recent.push_back(Call {
+            tool: tool.to_string(),
+            args: args.to_string(),
+            failed: false,
+        });
+        if state.recent.len() > self.limit.max(4) {
+            state.recent.pop_front();
+        }
+        let Some(reason) = self.detect(&state.recent) else {
+            return Verdict::Run;
+        };
+        if state.nudged {
+            state.refusals.insert(
+                tool.to_string(),
+                format!("Not run: {}. Stopping the turn.", reason),
+            );
+            state.stopped = Some(reason.clone());
+            return Verdict::Stop(reason);
+        }
+        state.nudged = true;
+        state.refusals.insert(
+            tool.to_string(),
+            format!(
+                "Not run: {}. Repeating it won't change the outcome. Re-read the relevant code or error and try a different approach, or explain to the user what is blocking you.",
+                reason
+            ),
+        );
+        Verdict::Nudge(reason)
+    }
+
+    fn detect(&self, recent: &VecDeque<Call>) -> Option<String> {
+        let n = recent.len();
+        let last = recent.back()?;
+        if n >= self.limit && recent.iter().skip(n - self.limit).all(|c| c.same(last)) {
+            return Some(format!(
+                "{} was called with the same arguments {} times in a row",
+                last.tool, self.limit
+            ));
+        }
+        // A, B, A failed and B is being tried again
+        if n >= 4 {
+            let [a, b, a2, b2] = [&recent[n - 4], &recent[n - 3], &recent[n - 2], last];
+            if !a.same(b) && a.same(a2) && b.same(b2) && a.failed && b.failed && a2.failed {
+                return Some(format!(
+                    "{} and {} keep failing in turn with the same arguments",
+                    a.tool, b.tool
+                ));
+            }
+        }
+        None
+    }
+
+    /// Records the result of the latest call of `tool`.
+    pub fn result(&self, tool: &str, result: &str) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(call) = state.recent.iter_mut().rev().find(|c| c.tool == tool) {
+            call.failed = failed(result);
+        }
+    }
+
+    /// The message to return instead of running this call of `tool`, if it
+    /// was refused.
+    pub fn refusal(&self, tool: &str) -> Option<String> {
+        self.state.lock().unwrap().refusals.remove(tool)
+    }
+
+    /// Why the turn was stopped, once a loop continued after the nudge.
+    pub fn stopped(&self) -> Option<String> {
+        self.state.lock().unwrap().stopped.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeated_call() {
+        let guard = LoopGuard::new(3);
+        let args = r#"{"cmd":"cargo build"}"#;
+        assert_eq!(guard.call("bash", args), Verdict::Run);
+        assert_eq!(guard.call("bash", args), Verdict::Run);
+        assert!(matches!(guard.call("bash", args), Verdict::Nudge(_)));
+        assert!(guard
+            .refusal("bash")
+            .unwrap()
+            .starts_with("Not run: bash was called"));
+        assert_eq!(guard.refusal("bash"), None);
+        assert_eq!(guard.stopped(), None);
+        assert!(matches!(guard.call("bash", args), Verdict::Stop(_)));
+        assert!(guard.stopped().is_some());
+
+        guard.reset();
+        assert_eq!(guard.call("bash", args), Verdict::Run);
+        assert_eq!(guard.call("read_file", "{}"), Verdict::Run);
+        assert_eq!(guard.call("bash", args), Verdict::Run);
+        assert_eq!(LoopGuard::new(0).call("bash", args), Verdict::Run);
+    }
+
+    #[test]
+    fn test_alternating_failures() {
+        let guard = LoopGuard::new(3);
+        let (a, b) = (r#"{"old":"x"}"#, r#"{"old":"y"}"#);
+        for args in [a, b, a] {
+            assert_eq!(guard.call("edit_file", args), Verdict::Run);
+            guard.result("edit_file", "\"error: old_string not found\"");
+        }
+        assert!(matches!(guard.call("edit_file", b), Verdict::Nudge(_)));
+
+        let guard = LoopGuard::new(3);
+        for args in [a, b, a] {
+            assert_eq!(guard.call("edit_file", args), Verdict::Run);
+            guard.result("edit_file", "\"ok\"");
+        }
+        assert_eq!(guard.call("edit_file", b), Verdict::Run);
+    }
+}
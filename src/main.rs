@@ -1,8 +1,19 @@
-use clap::{Parser, Subcommand};
-use picocode::{config::Config, create_agent, AgentConfig, ConsoleOutput};
+use clap::{Parser, Subcommand, ValueEnum};
+use picocode::session::{Session, SessionStore};
+use picocode::review::{self, Severity};
+use picocode::roundtable::Participant;
+use picocode::schedule::{Schedule, REPORT_DIR};
+use picocode::verify;
+use picocode::{
+    config::Config, create_agent, AgentConfig, ConsoleOutput, LogFileOutput, OutputExt, PicoAgent,
+    Recipe, RecipeMode,
+};
 use std::sync::Arc;
 
-#[derive(Parser, Debug)]
+/// Quiet runs (recipes, cron, `-q`) usually have nobody to answer a confirmation.
+const UNATTENDED_CONFIRM_TIMEOUT_SECS: u64 = 60;
+
+#[derive(Parser, Debug, Clone)]
 #[command(author, version, about = "Minimal coding assistant")]
 struct Args {
     #[command(subcommand)]
@@ -28,27 +39,201 @@ struct Args {
     #[arg(short, long, global = true)]
     quiet: bool,
 
+    /// In quiet mode, print a line to stderr for every tool call
+    #[arg(long, global = true)]
+    progress: bool,
+
     /// Maximum number of tool calls per prompt
     #[arg(long, default_value = "50", global = true)]
     tool_call_limit: usize,
 
     /// Choose a persona for the agent
-    #[arg(long, help = format!("Choose a persona for the agent. Available built-in personas:\n{}", picocode::persona::list_personas()), global = true)]
+    #[arg(long, help = format!("Choose a persona for the agent (comma-separated to combine). Available built-in personas:\n{}", picocode::persona::list_personas()), global = true)]
     persona: Option<String>,
 
+    /// Run bash commands in a container: docker, docker:<image>, podman[:<image>], or none
+    #[arg(long, global = true)]
+    sandbox: Option<String>,
+
+    /// Have a reviewer agent check the changes and request one revision
+    #[arg(long, global = true)]
+    verify: bool,
+
+    /// Only give the agent tools that read (no edits, no shell commands)
+    #[arg(long, global = true)]
+    read_only: bool,
+
+    /// Trust the workspace for this run without asking (needed by quiet and non-interactive runs)
+    #[arg(long, global = true)]
+    trust: bool,
+
+    /// Append a timestamped transcript of everything displayed to this file (JSONL if it ends in .jsonl)
+    #[arg(long, global = true)]
+    log_file: Option<String>,
+
+    /// Reply, and write commit messages and plans, in this language (e.g. Japanese or ja_JP)
+    #[arg(long, global = true)]
+    language: Option<String>,
+
+    /// Write provider requests and responses, secrets redacted, to .picocode/debug/ (also PICOCODE_DEBUG=1)
+    #[arg(long, global = true)]
+    debug_llm: bool,
+
+    /// Run as if started in this directory
+    #[arg(long, global = true)]
+    cwd: Option<String>,
+
     /// Path to config file (default: picocode.yaml or picocode.yml in current directory)
     #[arg(short, long, global = true)]
     config: Option<String>,
 }
 
-#[derive(Subcommand, Debug)]
+#[derive(Subcommand, Debug, Clone)]
 enum Commands {
     /// Start an interactive chat session (default)
-    Chat,
+    Chat {
+        /// Continue a saved session by id
+        #[arg(long)]
+        resume: Option<String>,
+    },
     /// Run a single prompt
     Input { prompt: String },
     /// Run a pre-defined recipe from picocode.yaml
     Recipe { name: String },
+    /// Answer a prompt with several personas/models in parallel, then synthesize with a moderator
+    Roundtable {
+        prompt: String,
+        /// Comma-separated personas, one agent each (e.g. architect,security,tester)
+        #[arg(long)]
+        personas: Option<String>,
+        /// Comma-separated models, one agent each, or one per persona
+        #[arg(long)]
+        models: Option<String>,
+        /// Also print each participant's answer
+        #[arg(long)]
+        show_all: bool,
+    },
+    /// Review the changed hunks of a git range (for CI / pre-commit gates)
+    Review {
+        /// Git range to review, e.g. origin/main..HEAD
+        #[arg(long)]
+        diff: String,
+        #[arg(long, value_enum, default_value = "text")]
+        format: ReviewFormat,
+        /// Exit with an error when a finding has at least this severity
+        #[arg(long, value_enum, default_value = "error")]
+        fail_on: FailOn,
+    },
+    /// Run recipes that have a `schedule` until interrupted
+    Cron,
+    /// Run an eval suite: task prompts on copies of fixture repos, checked by assertions, per model
+    Eval {
+        /// Suite file (YAML) listing the models and tasks
+        suite: String,
+        /// Exit with an error when a model passes fewer than this percentage of tasks
+        #[arg(long)]
+        fail_under: Option<f64>,
+        /// Keep the task directories instead of deleting them after the checks
+        #[arg(long)]
+        keep: bool,
+    },
+    /// Check API keys, configuration, provider connectivity and optional binaries
+    Doctor,
+    /// Manage saved sessions in ~/.picocode/sessions (list, show, delete, prune, export, import)
+    Sessions {
+        #[command(subcommand)]
+        command: SessionsCommand,
+    },
+    /// Restore files the agent removed, from .picocode/trash (list, restore, empty)
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommand,
+    },
+    /// Show known models: context window, tool/vision/streaming support and price
+    Models {
+        #[command(subcommand)]
+        command: ModelsCommand,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum ModelsCommand {
+    /// List known models and provider defaults (only for --provider, if given)
+    List,
+    /// Install the signed manifest at models_manifest.url in ~/.picocode/models.json
+    Refresh,
+    /// Ask --provider which models it serves now, with context sizes and prices
+    Available,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum TrashCommand {
+    /// List removed files, most recent first
+    List,
+    /// Put back the files of a batch (default: the most recent one)
+    Restore {
+        batch: Option<String>,
+        /// Replace files that exist again at the original path
+        #[arg(long)]
+        force: bool,
+    },
+    /// Delete everything in the trash
+    Empty,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum SessionsCommand {
+    /// List saved sessions, most recent first
+    List,
+    /// Show a session's metadata
+    Show { id: String },
+    /// Delete a saved session
+    Delete { id: String },
+    /// Delete sessions not updated recently
+    Prune {
+        /// Age such as 30d, 12h or 2w
+        #[arg(long, default_value = "30d")]
+        older_than: String,
+    },
+    /// Print a saved session as markdown or JSON
+    Export {
+        id: String,
+        #[arg(long, value_enum, default_value = "md")]
+        format: ExportFormat,
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Import a session JSON file (as produced by `export --format json`)
+    Import { path: String },
+    /// Find past sessions mentioning every word of a query
+    Search {
+        query: String,
+        /// Most sessions to show
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ReviewFormat {
+    Text,
+    Github,
+    Sarif,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum FailOn {
+    Error,
+    Warning,
+    Notice,
+    Never,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ExportFormat {
+    Md,
+    Json,
 }
 
 #[tokio::main]
@@ -73,6 +258,8 @@ async fn main() {
         eprintln!("Please report it at: https://github.com/jondot/picocode/issues");
         eprintln!("--------------------------------------------------------------------------------\n");
     }));
+    // Runs after the hook above, writing a crash report for a tracked session
+    picocode::crash::install();
 
     if let Err(e) = run().await {
         eprintln!("Error: {}", e);
@@ -81,8 +268,104 @@ async fn main() {
 }
 
 async fn run() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
-    let config = Config::load(args.config.as_deref())?;
+    let mut args = Args::parse();
+    if let Some(cwd) = &args.cwd {
+        std::env::set_current_dir(cwd).map_err(|e| format!("--cwd {}: {}", cwd, e))?;
+    }
+    // Commands that don't ask for trust only use the keys of a trusted project
+    if matches!(
+        args.command,
+        Some(Commands::Models { .. } | Commands::Doctor)
+    ) {
+        let config = Config::load(args.config.as_deref()).unwrap_or_default();
+        if args.trust || picocode::trust::is_trusted(&std::env::current_dir()?, &config) {
+            picocode::secrets::load_dotenv();
+        }
+    }
+    if let Some(Commands::Sessions { command }) = &args.command {
+        picocode::storage::configure(&load_unasked(&args)?.storage)?;
+        return run_sessions(command);
+    }
+    if let Some(Commands::Trash { command }) = &args.command {
+        return run_trash(command);
+    }
+    if let Some(Commands::Models { command }) = &args.command {
+        return run_models(&args, command).await;
+    }
+    if let Some(Commands::Doctor) = &args.command {
+        let provider = args.provider.clone().unwrap_or_else(|| "anthropic".to_string());
+        let model = args.model.clone().unwrap_or_else(|| default_model(&provider));
+        if !picocode::doctor::run(args.config.as_deref(), &provider, &model).await {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    let mut config = Config::load(args.config.as_deref())?;
+    // Only the interactive commands can ask; everything else needs --trust
+    let asks = !args.quiet
+        && match &args.command {
+            None | Some(Commands::Chat { .. } | Commands::Input { .. }) => true,
+            Some(Commands::Recipe { name }) => config
+                .recipes
+                .get(name)
+                .is_none_or(|r| r.mode == RecipeMode::Chat || !r.quiet),
+            Some(_) => false,
+        };
+    let trusted = args.trust
+        || if args.read_only {
+            picocode::trust::is_trusted(&std::env::current_dir()?, &config)
+        } else {
+            confirm_workspace_trust(&config, asks)?
+        };
+    if trusted {
+        // Provider keys may live in the project; tools can't read these files
+        picocode::secrets::load_dotenv();
+    }
+    if args.read_only || !trusted {
+        args.read_only = true;
+        // A --config file is the user's own; only the workspace's is restricted
+        if args.config.is_none() {
+            picocode::trust::restrict(&mut config);
+        }
+    }
+    picocode::storage::configure(&config.storage)?;
+    // The history file is rewritten when loaded, so only the user's own
+    // --config can move it
+    let mut input = config.input.clone();
+    if args.config.is_none() {
+        input.history_file = None;
+    }
+    picocode::input::configure(&input);
+    if let Some(Commands::Cron) = &args.command {
+        return run_cron(&args, &config).await;
+    }
+    if let Some(Commands::Eval {
+        suite,
+        fail_under,
+        keep,
+    }) = &args.command
+    {
+        return run_eval(&args, &config, suite, *fail_under, *keep).await;
+    }
+    if let Some(Commands::Review {
+        diff,
+        format,
+        fail_on,
+    }) = &args.command
+    {
+        return run_review(&args, &config, diff, *format, *fail_on).await;
+    }
+    if let Some(Commands::Roundtable {
+        prompt,
+        personas,
+        models,
+        show_all,
+    }) = &args.command
+    {
+        let participants =
+            picocode::roundtable::participants(personas.as_deref(), models.as_deref())?;
+        return run_roundtable(&args, &config, prompt, participants, *show_all).await;
+    }
 
     let (command, prompt, recipe_name) = match (&args.command, &args.prompt) {
         (Some(Commands::Recipe { name }), _) => (
@@ -95,48 +378,228 @@ async fn run() -> Result<(), Box<dyn std::error::Error>> {
             Some(prompt.clone()),
             None,
         ),
-        (Some(Commands::Chat), _) => (Commands::Chat, None, None),
+        (Some(Commands::Chat { resume }), _) => (Commands::Chat { resume: resume.clone() }, None, None),
+        (Some(Commands::Sessions { .. } | Commands::Trash { .. } | Commands::Models { .. } | Commands::Doctor | Commands::Cron | Commands::Eval { .. } | Commands::Roundtable { .. } | Commands::Review { .. }), _) => unreachable!("handled above"),
         (None, Some(p)) => (Commands::Input { prompt: p.clone() }, Some(p.clone()), None),
-        (None, None) => (Commands::Chat, None, None),
+        (None, None) => (Commands::Chat { resume: None }, None, None),
     };
 
     let recipe = recipe_name
         .as_ref()
         .and_then(|name| config.recipes.get(name).cloned());
 
+    // Chat recipes start an interactive session instead of running once
+    let chat = recipe.as_ref().is_some_and(|r| r.mode == RecipeMode::Chat);
+    let quiet = !chat && (args.quiet || recipe.as_ref().map(|r| r.quiet).unwrap_or(false));
+    let resume = match &command {
+        Commands::Chat { resume } => resume.clone(),
+        _ => None,
+    };
+    let agent = build_agent(&args, &config, recipe.as_ref(), quiet, resume).await?;
+
+    match command {
+        Commands::Recipe { name: _ } if chat => agent.run_interactive().await?,
+        Commands::Recipe { name: _ } => {
+            if let Some(r) = recipe {
+                let prompt = picocode::config::read_prompt(r.prompt.clone(), r.prompt_file.clone())?
+                    .ok_or("Recipe must have either 'prompt' or 'prompt_file'")?;
+                let (response, failure) =
+                    run_recipe(&args, &config, &r, agent.as_ref(), prompt, args.verify || r.verify)
+                        .await?;
+                if let Some(failure) = failure {
+                    return Err(Box::new(picocode::PicocodeError::Other(format!(
+                        "Recipe failed: {}",
+                        failure
+                    ))));
+                }
+                if args.quiet || r.quiet {
+                    println!("{}", response);
+                }
+            } else {
+                eprintln!("Error: Recipe not found");
+                std::process::exit(1);
+            }
+        }
+        Commands::Input { prompt } => {
+            let response =
+                run_verified(&args, &config, None, agent.as_ref(), prompt, args.verify).await?;
+            if args.quiet {
+                println!("{}", response);
+            }
+        }
+        Commands::Sessions { .. } | Commands::Trash { .. } | Commands::Models { .. } | Commands::Doctor | Commands::Cron | Commands::Eval { .. } | Commands::Roundtable { .. } | Commands::Review { .. } => unreachable!("handled above"),
+        Commands::Chat { .. } => {
+            if let Some(p) = prompt {
+                let response = agent.run_once(p).await?;
+                if args.quiet {
+                    println!("{}", response);
+                }
+            } else {
+                agent.run_interactive().await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs a recipe's prompt and post-processing, re-prompting with the failure while the
+/// result fails its checks and `max_attempts` allows. Returns the processed response and
+/// the last failure, if any.
+async fn run_recipe(
+    args: &Args,
+    config: &Config,
+    recipe: &Recipe,
+    agent: &dyn PicoAgent,
+    prompt: String,
+    verify: bool,
+) -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
+    let attempts = recipe.max_attempts.unwrap_or(1).max(1);
+    let mut request = prompt.clone();
+    let mut attempt = 1;
+    loop {
+        let response = run_verified(args, config, Some(recipe), agent, request, verify).await?;
+        let (output, failure) = match recipe.post_process(&response) {
+            Ok(output) => {
+                let failure = recipe.check(&output)?;
+                (output, failure)
+            }
+            Err(e) => (response.clone(), Some(e.to_string())),
+        };
+        match failure {
+            Some(failure) if attempt < attempts => {
+                eprintln!("Attempt {}/{} failed: {}. Retrying.", attempt, attempts, failure);
+                request = recipe.retry_request(&prompt, &response, &failure);
+                attempt += 1;
+            }
+            failure => return Ok((output, failure)),
+        }
+    }
+}
+
+/// Runs the prompt, then (when `verify` is set) has a reviewer agent check the
+/// changes the run made and gives its findings back to the agent for one revision.
+async fn run_verified(
+    args: &Args,
+    config: &Config,
+    recipe: Option<&Recipe>,
+    agent: &dyn PicoAgent,
+    prompt: String,
+    verify: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let baseline = verify.then(verify::Baseline::record).flatten();
+    let response = agent.run_once(prompt.clone()).await?;
+    if !verify {
+        return Ok(response);
+    }
+    let Some(diff) = baseline.as_ref().and_then(verify::workspace_diff) else {
+        eprintln!("Verify: no changes to review");
+        return Ok(response);
+    };
+
+    let mut reviewer_args = args.clone();
+    reviewer_args.yolo = Some(false);
+    reviewer_args.read_only = true;
+    if config.verifier.model.is_some() {
+        reviewer_args.model = config.verifier.model.clone();
+    }
+    if config.verifier.persona.is_some() {
+        reviewer_args.persona = config.verifier.persona.clone();
+    }
+    let reviewer = build_agent(&reviewer_args, config, recipe, true, None).await?;
+    let review = reviewer
+        .run_once(verify::review_prompt(&prompt, &diff))
+        .await?;
+    if verify::approved(&review) {
+        eprintln!("Verify: reviewer approved the changes");
+        return Ok(response);
+    }
+
+    if args.quiet {
+        eprintln!("Verify: reviewer feedback:\n{}", review.trim());
+    } else {
+        println!("\n── Reviewer ──\n\n{}\n", review.trim());
+    }
+    Ok(agent
+        .run_once(verify::revision_prompt(&prompt, &response, &review))
+        .await?)
+}
+
+/// Creates the agent for a run, resolving settings from flags, then the recipe, then picocode.yaml.
+async fn build_agent(
+    args: &Args,
+    config: &Config,
+    recipe: Option<&Recipe>,
+    quiet: bool,
+    resume: Option<String>,
+) -> Result<Box<dyn PicoAgent>, Box<dyn std::error::Error>> {
     let provider = args
         .provider
-        .or_else(|| recipe.as_ref().and_then(|r| r.provider.clone()))
+        .clone()
+        .or_else(|| recipe.and_then(|r| r.provider.clone()))
         .unwrap_or_else(|| "anthropic".to_string());
 
     let model = args
         .model
-        .or_else(|| recipe.as_ref().and_then(|r| r.model.clone()))
+        .clone()
+        .or_else(|| recipe.and_then(|r| r.model.clone()))
+        .or_else(|| config.routing.as_ref().and_then(|r| r.complex.clone()))
         .unwrap_or_else(|| default_model(&provider));
 
     let yolo = args
         .yolo
-        .or_else(|| recipe.as_ref().and_then(|r| r.yolo))
+        .or_else(|| recipe.and_then(|r| r.yolo))
         .unwrap_or(false);
 
     let persona_name = args
         .persona
-        .or_else(|| recipe.as_ref().and_then(|r| r.persona.clone()));
+        .clone()
+        .or_else(|| recipe.and_then(|r| r.persona.clone()));
+
+    let sandbox = match &args.sandbox {
+        Some(arg) => picocode::sandbox::SandboxConfig::from_arg(arg, config.sandbox.clone())?,
+        None => config.sandbox.clone(),
+    };
 
-    let output: Arc<dyn picocode::Output> = if args.quiet || recipe.as_ref().map(|r| r.quiet).unwrap_or(false) {
-        Arc::new(picocode::QuietOutput::new())
+    let mut confirmation = config.confirmation.clone();
+    if quiet && confirmation.timeout.is_none() {
+        confirmation.timeout = Some(UNATTENDED_CONFIRM_TIMEOUT_SECS);
+    }
+
+    let output: Arc<dyn picocode::Output> = if quiet {
+        Arc::new(picocode::QuietOutput::new().with_progress(args.progress))
     } else {
         Arc::new(ConsoleOutput::new())
     };
+    let output = match args.log_file.as_ref().or(config.log_file.as_ref()) {
+        Some(path) => output.tee(Arc::new(LogFileOutput::open(path)?).redact()),
+        None => output,
+    };
+    let output = if config.notifications.enabled() {
+        output.notify(config.notifications.clone())
+    } else {
+        output
+    };
 
-    let agents_md = picocode::agent::load_agents_md();
-    let claude_md = picocode::agent::load_claude_md();
+    // Untrusted repositories' instructions aren't loaded
+    let (agents_md, claude_md) = if args.read_only {
+        (None, None)
+    } else {
+        (
+            picocode::agent::load_agents_md(),
+            picocode::agent::load_claude_md(),
+        )
+    };
     let system_message_extension = match (agents_md, claude_md) {
         (Some(a), Some(c)) => Some(format!("{}\n\n{}", a, c)),
         (Some(a), None) => Some(a),
         (None, Some(c)) => Some(c),
         (None, None) => None,
     };
+    let mut context_files = config.context_files.clone();
+    if let Some(recipe) = recipe {
+        context_files.extend(recipe.context_files.iter().cloned());
+    }
     let persona_prompt = persona_name
         .as_ref()
         .and_then(|p| picocode::persona::get_persona(p));
@@ -151,73 +614,590 @@ async fn run() -> Result<(), Box<dyn std::error::Error>> {
         persona_prompt,
         persona_name,
         bash_auto_allow: Some(config.get_bash_auto_allow()),
-        agent_prompt: picocode::config::read_prompt(
-            config.agent_prompt.clone(),
-            config.agent_prompt_file.clone(),
-        )?,
+        agent_prompt: match recipe {
+            Some(r) if r.agent_prompt.is_some() || r.agent_prompt_file.is_some() => {
+                picocode::config::read_prompt(r.agent_prompt.clone(), r.agent_prompt_file.clone())?
+            }
+            _ => picocode::config::read_prompt(
+                config.agent_prompt.clone(),
+                config.agent_prompt_file.clone(),
+            )?,
+        },
+        audit_log: config.audit_log.clone(),
+        rate_limit: config.get_rate_limit(&provider),
+        routing: config.routing.clone(),
+        tool_summaries: config.tool_summaries.clone(),
+        web_search: Some(config.web_search.clone()),
+        gitlab: Some(config.gitlab.clone()),
+        sandbox,
+        tool_config: Some(config.tool_config.clone()),
+        workspaces: Some(config.workspaces.clone()),
+        protected_files: Some(config.protected_files.clone()),
+        confirmation: Some(confirmation),
+        session_dir: SessionStore::default_dir().map(|d| d.display().to_string()),
+        resume,
+        read_only: args.read_only,
+        language: args.language.clone().or_else(|| config.language.clone()),
+        require_plan_approval: config.require_plan_approval,
+        tool_loop_limit: config.tool_loop_limit,
+        context_files: Some(context_files),
+        network: Some(config.network.clone()),
+        debug_llm: args.debug_llm,
+        max_output_tokens: config.get_max_output_tokens(&provider),
+        api_keys: config.get_api_keys(&provider),
+        azure: Some(config.azure.clone()),
+        // Recipes and quiet runs don't ask the user: questions go to the recipe's answers
+        answers: (quiet || recipe.is_some_and(|r| r.mode == RecipeMode::Run))
+            .then(|| recipe.map(|r| r.answers.clone()).unwrap_or_default()),
+        first_message: match recipe {
+            Some(r) if r.mode == RecipeMode::Chat => {
+                picocode::config::read_prompt(r.prompt.clone(), r.prompt_file.clone())?
+            }
+            _ => None,
+        },
     })
     .await?;
+    Ok(agent)
+}
 
-    match command {
-        Commands::Recipe { name: _ } => {
-            if let Some(r) = recipe {
-                let prompt = picocode::config::read_prompt(r.prompt.clone(), r.prompt_file.clone())?
-                    .ok_or("Recipe must have either 'prompt' or 'prompt_file'")?;
-                let response = agent.run_once(prompt).await?;
-                if r.is_error(&response)? {
-                    return Err(Box::new(picocode::PicocodeError::Other(
-                        "Response matched error_if pattern".to_string(),
-                    )));
+/// The config for commands that don't ask for trust, restricted when it is an
+/// untrusted workspace's `picocode.yaml`.
+fn load_unasked(args: &Args) -> Result<Config, Box<dyn std::error::Error>> {
+    let mut config = Config::load(args.config.as_deref())?;
+    if args.config.is_none()
+        && !args.trust
+        && !picocode::trust::is_trusted(&std::env::current_dir()?, &config)
+    {
+        picocode::trust::restrict(&mut config);
+    }
+    Ok(config)
+}
+
+/// Asks whether to trust a directory the first time picocode runs in it with
+/// repository-provided config or instructions. Returns false when the user
+/// declines, in which case the run only gets read-only tools. Runs that can't
+/// ask (quiet, or without a terminal) are untrusted.
+fn confirm_workspace_trust(config: &Config, ask: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    use std::io::{IsTerminal, Write};
+
+    let cwd = std::env::current_dir()?;
+    let contents = picocode::trust::workspace_contents(&cwd, config);
+    if contents.is_empty() {
+        return Ok(true);
+    }
+    let store = picocode::trust::TrustStore::open_default()?;
+    if store.is_trusted(&cwd) {
+        return Ok(true);
+    }
+    if !ask || !std::io::stdin().is_terminal() {
+        eprintln!(
+            "This workspace has not been trusted yet, so this run only gets read-only tools (pass --trust to trust it)"
+        );
+        return Ok(false);
+    }
+
+    eprintln!("This workspace has not been trusted yet: {}", cwd.display());
+    eprintln!("Trusting it loads:");
+    for line in &contents {
+        eprintln!("  - {}", line);
+    }
+    eprint!("Trust this workspace? Untrusted workspaces only get read-only tools. [y/N] ");
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        store.trust(&cwd)?;
+        eprintln!("Trusted; remembered in ~/.picocode/trusted.json");
+        Ok(true)
+    } else {
+        eprintln!("Continuing with read-only tools");
+        Ok(false)
+    }
+}
+
+/// Reviews the changed hunks of `range`, prints findings in `format`, and fails
+/// the process when a finding reaches `fail_on`.
+async fn run_review(
+    args: &Args,
+    config: &Config,
+    range: &str,
+    format: ReviewFormat,
+    fail_on: FailOn,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let diff = review::git_diff(range)?;
+    if diff.trim().is_empty() {
+        eprintln!("No changes in {}", range);
+        return Ok(());
+    }
+    let mut review_args = args.clone();
+    review_args.yolo = Some(false);
+    let agent = build_agent(&review_args, config, None, true, None).await?;
+    let response = agent.run_once(review::review_prompt(range, &diff)).await?;
+    let findings =
+        review::on_changed_lines(review::parse_findings(&response)?, &review::changed_lines(&diff));
+
+    match format {
+        ReviewFormat::Text => print!("{}", review::to_text(&findings)),
+        ReviewFormat::Github => print!("{}", review::to_github(&findings)),
+        ReviewFormat::Sarif => println!("{}", review::to_sarif(&findings)),
+    }
+    eprintln!("{} finding(s) in {}", findings.len(), range);
+
+    let threshold = match fail_on {
+        FailOn::Error => Some(Severity::Error),
+        FailOn::Warning => Some(Severity::Warning),
+        FailOn::Notice => Some(Severity::Notice),
+        FailOn::Never => None,
+    };
+    if threshold.is_some_and(|t| findings.iter().any(|f| f.severity >= t)) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Runs the prompt through every participant concurrently, then asks a moderator
+/// (the default persona and model) to merge the answers.
+async fn run_roundtable(
+    args: &Args,
+    config: &Config,
+    prompt: &str,
+    participants: Vec<Participant>,
+    show_all: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let labels: Vec<String> = participants.iter().map(Participant::label).collect();
+    eprintln!("Roundtable: consulting {}...", labels.join(", "));
+
+    let mut tasks = Vec::new();
+    for participant in &participants {
+        // Participants run at once on the same workspace, so they only read it
+        let mut participant_args = args.clone();
+        participant_args.read_only = true;
+        if participant.persona.is_some() {
+            participant_args.persona = participant.persona.clone();
+        }
+        if participant.model.is_some() {
+            participant_args.model = participant.model.clone();
+        }
+        let agent = build_agent(&participant_args, config, None, true, None).await?;
+        let prompt = prompt.to_string();
+        tasks.push(tokio::spawn(async move { agent.run_once(prompt).await }));
+    }
+
+    let mut answers = Vec::new();
+    for (label, task) in labels.into_iter().zip(tasks) {
+        match task.await? {
+            Ok(answer) => {
+                eprintln!("  ✓ {}", label);
+                answers.push((label, answer));
+            }
+            Err(e) => eprintln!("  ✗ {}: {}", label, e),
+        }
+    }
+    if answers.is_empty() {
+        return Err("Every roundtable participant failed".into());
+    }
+    if show_all && !args.quiet {
+        for (label, answer) in &answers {
+            println!("\n## {}\n\n{}", label, answer.trim());
+        }
+    }
+
+    let mut moderator_args = args.clone();
+    moderator_args.persona = None;
+    moderator_args.read_only = true;
+    let moderator = build_agent(&moderator_args, config, None, args.quiet, None).await?;
+    let response = moderator
+        .run_once(picocode::roundtable::moderator_prompt(prompt, &answers))
+        .await?;
+    if args.quiet {
+        println!("{}", response);
+    }
+    Ok(())
+}
+
+/// Runs every task of an eval suite with each of its models, in a fresh copy of
+/// the task's fixture, and prints the pass rate, tokens and cost per model.
+async fn run_eval(
+    args: &Args,
+    config: &Config,
+    suite_path: &str,
+    fail_under: Option<f64>,
+    keep: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use picocode::eval::{self, Suite, TaskResult};
+
+    let suite_path =
+        std::fs::canonicalize(suite_path).map_err(|e| format!("{}: {}", suite_path, e))?;
+    let suite = Suite::load(&suite_path)?;
+    let suite_dir = suite_path.parent().unwrap_or(std::path::Path::new("."));
+    let provider = args
+        .provider
+        .clone()
+        .unwrap_or_else(|| "anthropic".to_string());
+    let model = args
+        .model
+        .clone()
+        .unwrap_or_else(|| default_model(&provider));
+    let targets = suite.targets(&provider, &model)?;
+    // Commands run in a container unless --sandbox turns it off
+    let sandbox = match &args.sandbox {
+        Some(arg) => picocode::sandbox::SandboxConfig::from_arg(arg, config.sandbox.clone())?,
+        None => Some(config.sandbox.clone().unwrap_or_default()),
+    };
+    // Tasks only see their own copy
+    let config = Config {
+        workspaces: Vec::new(),
+        sandbox: sandbox.clone(),
+        ..config.clone()
+    };
+    let home = std::env::current_dir()?;
+    eprintln!(
+        "picocode eval: {} task(s) × {} model(s)",
+        suite.tasks.len(),
+        targets.len()
+    );
+
+    let mut results = Vec::new();
+    for (provider, model) in &targets {
+        for task in &suite.tasks {
+            let dir = eval::prepare(task, suite_dir)?;
+            std::env::set_current_dir(&dir)?;
+            let mut task_args = args.clone();
+            task_args.provider = Some(provider.clone());
+            task_args.model = Some(model.clone());
+            task_args.sandbox = None;
+            // The copy is thrown away and commands stay in the container, so
+            // nobody needs to confirm; on the host they are confirmed as usual
+            task_args.yolo = args.yolo.or(Some(sandbox.is_some()));
+            let started = std::time::Instant::now();
+            let (failures, usage) = match build_agent(&task_args, &config, None, true, None).await {
+                Ok(agent) => match agent.run_once(task.prompt.clone()).await {
+                    Ok(answer) => (eval::check(task, &dir, &answer), agent.usage()),
+                    Err(e) => (vec![e.to_string()], agent.usage()),
+                },
+                Err(e) => (vec![e.to_string()], Default::default()),
+            };
+            std::env::set_current_dir(&home)?;
+            let result = TaskResult {
+                provider: provider.clone(),
+                model: model.clone(),
+                task: task.name.clone(),
+                failures,
+                usage,
+                elapsed: started.elapsed(),
+            };
+            if result.passed() {
+                eprintln!("  ✓ {} ({}/{})", task.name, provider, model);
+            } else {
+                eprintln!("  ✗ {} ({}/{})", task.name, provider, model);
+                for failure in &result.failures {
+                    eprintln!("      {}", failure.replace('\n', "\n      "));
                 }
-                if args.quiet || r.quiet {
-                    println!("{}", response);
+            }
+            if keep {
+                eprintln!("      kept in {}", dir.display());
+            } else if let Err(e) = std::fs::remove_dir_all(&dir) {
+                eprintln!("      could not remove {}: {}", dir.display(), e);
+            }
+            results.push(result);
+        }
+    }
+
+    println!("\n{}", eval::summary(&results));
+    if let Some(threshold) = fail_under {
+        let below: Vec<String> = targets
+            .iter()
+            .filter(|(provider, model)| {
+                let runs: Vec<&TaskResult> = results
+                    .iter()
+                    .filter(|r| &r.provider == provider && &r.model == model)
+                    .collect();
+                eval::pass_rate(&runs) < threshold
+            })
+            .map(|(provider, model)| format!("{}/{}", provider, model))
+            .collect();
+        if !below.is_empty() {
+            return Err(format!("Pass rate under {}%: {}", threshold, below.join(", ")).into());
+        }
+    }
+    Ok(())
+}
+
+/// Runs scheduled recipes until interrupted, writing a report file per run.
+async fn run_cron(args: &Args, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let mut jobs = Vec::new();
+    for (name, recipe) in &config.recipes {
+        if let Some(expr) = &recipe.schedule {
+            let schedule = Schedule::parse(expr)
+                .map_err(|e| format!("Recipe '{}': {}", name, e))?;
+            jobs.push((name.clone(), schedule));
+        }
+    }
+    if jobs.is_empty() {
+        return Err("No recipes with a `schedule` in picocode.yaml".into());
+    }
+    jobs.sort_by(|a, b| a.0.cmp(&b.0));
+    eprintln!("picocode cron: {} scheduled recipe(s), reports in {}", jobs.len(), REPORT_DIR);
+
+    loop {
+        let now = chrono::Local::now().naive_local();
+        let Some(next) = jobs.iter().filter_map(|(_, s)| s.next_after(now)).min() else {
+            return Err("No upcoming runs for the scheduled recipes".into());
+        };
+        let due: Vec<&String> = jobs
+            .iter()
+            .filter(|(_, s)| s.matches(next))
+            .map(|(name, _)| name)
+            .collect();
+        eprintln!(
+            "Next run at {}: {}",
+            next.format("%Y-%m-%d %H:%M"),
+            due.iter().map(|n| n.as_str()).collect::<Vec<_>>().join(", ")
+        );
+        tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+
+        for name in due {
+            let (status, body) = match run_scheduled(args, config, name).await {
+                Ok((None, response)) => ("ok", response),
+                Ok((Some(failure), response)) => ("check failed", format!("{}\n\n{}", failure, response)),
+                Err(e) => ("failed", e.to_string()),
+            };
+            match picocode::schedule::write_report(name, status, &body) {
+                Ok(path) => eprintln!("{}: {} ({})", name, status, path.display()),
+                Err(e) => eprintln!("{}: {} (could not write report: {})", name, status, e),
+            }
+        }
+    }
+}
+
+/// Runs one scheduled recipe, returning why it failed its checks (if it did) and the response.
+async fn run_scheduled(
+    args: &Args,
+    config: &Config,
+    name: &str,
+) -> Result<(Option<String>, String), Box<dyn std::error::Error>> {
+    let recipe = config.recipes.get(name).ok_or("Recipe not found")?;
+    let prompt = picocode::config::read_prompt(recipe.prompt.clone(), recipe.prompt_file.clone())?
+        .ok_or("Recipe must have either 'prompt' or 'prompt_file'")?;
+    let agent = build_agent(args, config, Some(recipe), true, None).await?;
+    let (response, failure) =
+        run_recipe(args, config, recipe, agent.as_ref(), prompt, recipe.verify).await?;
+    Ok((failure, response))
+}
+
+fn run_sessions(command: &SessionsCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let store = SessionStore::open_default()?;
+    match command {
+        SessionsCommand::List => {
+            let sessions = store.list()?;
+            if sessions.is_empty() {
+                println!("No saved sessions in {}", store.location());
+            }
+            for session in sessions {
+                println!(
+                    "{:<24} {}  {:>4} msgs  {:>8} tokens  {}",
+                    session.id,
+                    session.updated_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+                    session.messages.len(),
+                    session.usage.total_tokens,
+                    session.title.as_deref().unwrap_or("(untitled)")
+                );
+            }
+        }
+        SessionsCommand::Show { id } => {
+            let session = store.load(id)?;
+            println!("Id:        {}", session.id);
+            println!("Title:     {}", session.title.as_deref().unwrap_or("(untitled)"));
+            println!("Directory: {}", session.cwd);
+            println!("Provider:  {} ({})", session.provider, session.model);
+            if let Some(persona) = &session.persona {
+                println!("Persona:   {}", persona);
+            }
+            println!("Created:   {}", session.created_at.with_timezone(&chrono::Local));
+            println!("Updated:   {}", session.updated_at.with_timezone(&chrono::Local));
+            println!("Messages:  {}", session.messages.len());
+            println!(
+                "Tokens:    {} ({} in, {} out)",
+                session.usage.total_tokens, session.usage.input_tokens, session.usage.output_tokens
+            );
+            if let Some(cost) =
+                picocode::models::cost(&session.provider, &session.model, &session.usage)
+            {
+                println!("Cost:      ~{} at list price", picocode::models::format_cost(cost));
+            }
+        }
+        SessionsCommand::Delete { id } => {
+            store.delete(id)?;
+            println!("Deleted session {}", id);
+        }
+        SessionsCommand::Prune { older_than } => {
+            let removed = store.prune(picocode::session::parse_age(older_than)?)?;
+            println!("Pruned {} session(s) older than {}", removed.len(), older_than);
+        }
+        SessionsCommand::Export { id, format, output } => {
+            let session = store.load(id)?;
+            let content = match format {
+                ExportFormat::Md => session.to_markdown(),
+                ExportFormat::Json => session.to_json()?,
+            };
+            match output {
+                Some(path) => {
+                    std::fs::write(path, content)?;
+                    eprintln!("Session {} exported to {}", id, path);
                 }
-            } else {
-                eprintln!("Error: Recipe not found");
-                std::process::exit(1);
+                None => println!("{}", content),
             }
         }
-        Commands::Input { prompt } => {
-            let response = agent.run_once(prompt).await?;
-            if args.quiet {
-                println!("{}", response);
+        SessionsCommand::Import { path } => {
+            let session = Session::from_json(&std::fs::read_to_string(path)?)?;
+            if store.exists(&session.id) {
+                return Err(format!("Session {} already exists", session.id).into());
             }
+            store.save(&session)?;
+            println!(
+                "Imported session {} ({} messages). Continue it with: picocode chat --resume {}",
+                session.id,
+                session.messages.len(),
+                session.id
+            );
         }
-        Commands::Chat => {
-            if let Some(p) = prompt {
-                let response = agent.run_once(p).await?;
-                if args.quiet {
-                    println!("{}", response);
+        SessionsCommand::Search { query, limit } => {
+            let hits = store.search(query, *limit)?;
+            if hits.is_empty() {
+                println!("No sessions match '{}'", query);
+            }
+            for hit in hits {
+                println!("{}", hit.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_trash(command: &TrashCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let trash = picocode::trash::Trash::open_default()?;
+    match command {
+        TrashCommand::List => {
+            let batches = trash.batches()?;
+            if batches.is_empty() {
+                println!("The trash in {} is empty", trash.dir().display());
+            }
+            for batch in batches {
+                println!("{}", batch.name);
+                for entry in batch.entries {
+                    println!("  {}", entry.original.display());
                 }
-            } else {
-                agent.run_interactive().await?;
             }
         }
+        TrashCommand::Restore { batch, force } => {
+            for path in trash.restore(batch.as_deref(), *force)? {
+                println!("Restored {}", path.display());
+            }
+        }
+        TrashCommand::Empty => {
+            let removed = trash.empty()?;
+            println!("Deleted {} batch(es) from the trash", removed);
+        }
     }
+    Ok(())
+}
 
+async fn run_models(
+    args: &Args,
+    command: &ModelsCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use picocode::models;
+    match command {
+        ModelsCommand::List => {
+            let provider = args.provider.as_deref();
+            println!(
+                "{:<11} {:<20} {:>9}  {:<22} $/Mtok in/out",
+                "PROVIDER", "MODEL", "CONTEXT", "SUPPORTS"
+            );
+            for info in models::all()
+                .iter()
+                .filter(|m| provider.is_none_or(|p| m.provider == p))
+            {
+                let supports: Vec<&str> = [
+                    (info.tools, "tools"),
+                    (info.vision, "vision"),
+                    (info.streaming, "streaming"),
+                ]
+                .into_iter()
+                .filter_map(|(yes, name)| yes.then_some(name))
+                .collect();
+                let price = info
+                    .price
+                    .map(|p| format!("{} / {}", p.input, p.output))
+                    .unwrap_or_else(|| "-".into());
+                println!(
+                    "{:<11} {:<20} {:>9}  {:<22} {}",
+                    info.provider,
+                    info.id,
+                    info.context_window,
+                    supports.join(","),
+                    price
+                );
+            }
+            println!(
+                "\nModels match by prefix, e.g. claude- covers every Claude model.\nDefaults:"
+            );
+            for (name, model) in models::defaults()
+                .iter()
+                .filter(|(name, _)| provider.is_none_or(|p| name == p))
+            {
+                println!("  {:<11} {}", name, model);
+            }
+        }
+        ModelsCommand::Refresh => {
+            let config = load_unasked(args)?;
+            picocode::network::configure(&config.network)?;
+            let (Some(url), Some(public_key)) = (
+                config.models_manifest.url.as_deref(),
+                config.models_manifest.public_key.as_deref(),
+            ) else {
+                return Err("Set models_manifest.url and models_manifest.public_key in picocode.yaml to refresh the model manifest".into());
+            };
+            let (path, manifest) = models::refresh(url, public_key).await?;
+            println!(
+                "Installed {} model(s) and {} default(s) from {} in {}",
+                manifest.models.len(),
+                manifest.defaults.len(),
+                url,
+                path.display()
+            );
+        }
+        ModelsCommand::Available => {
+            let provider = args
+                .provider
+                .as_deref()
+                .ok_or("Pass --provider, e.g. picocode models available --provider openrouter")?;
+            picocode::network::configure(&load_unasked(args)?.network)?;
+            let available = models::available(provider).await?;
+            println!("{:<50} {:>9}  $/Mtok in/out", "MODEL", "CONTEXT");
+            for model in &available {
+                let context = model
+                    .context_window
+                    .map(|n| n.to_string())
+                    .unwrap_or_else(|| "-".into());
+                let price = model
+                    .price
+                    .map(|p| format!("{} / {}", p.input, p.output))
+                    .unwrap_or_else(|| "-".into());
+                println!("{:<50} {:>9}  {}", model.id, context, price);
+            }
+            println!(
+                "\n{} model(s). Use one with --provider {} --model <MODEL>.",
+                available.len(),
+                provider
+            );
+        }
+    }
     Ok(())
 }
 
 fn default_model(provider: &str) -> String {
-    match provider {
-        "anthropic" => "claude-sonnet-4-6".to_string(),
-        "openai" => "gpt-4o-mini".to_string(),
-        "azure" => "gpt-4o".to_string(),
-        "cohere" => "command-r-plus".to_string(),
-        "deepseek" => "deepseek-chat".to_string(),
-        "galadriel" => "llama3-70b".to_string(),
-        "groq" => "llama3-70b-8192".to_string(),
-        "huggingface" => "meta-llama/Llama-3-70b-chat-hf".to_string(),
-        "hyperbolic" => "meta-llama/Llama-3-70b-instruct".to_string(),
-        "mira" => "mira-v1".to_string(),
-        "mistral" => "mistral-large-latest".to_string(),
-        "moonshot" => "moonshot-v1-8k".to_string(),
-        "ollama" => "llama3".to_string(),
-        "openrouter" => "meta-llama/llama-3-70b-instruct".to_string(),
-        "perplexity" => "llama-3-sonar-large-32k-online".to_string(),
-        "together" => "meta-llama/Llama-3-70b-chat-hf".to_string(),
-        "xai" => "grok-1".to_string(),
-        "gemini" | "google" => "gemini-1.5-pro".to_string(),
-        _ => "unknown".to_string(),
-    }
+    picocode::models::default_model(provider)
+        .unwrap_or("unknown")
+        .to_string()
 }
@@ -0,0 +1,63 @@
+use crate::storage::Storage;
+
+/// Project facts remembered across sessions, injected into the system prompt.
+pub const MEMORY_FILE: &str = ".picocode/memory.md";
+
+const HEADER: &str = "# Project memory\n\n";
+
+/// Returns the memory file content, or None when nothing has been remembered.
+pub fn load(storage: &dyn Storage) -> Option<String> {
+    storage
+        .read(MEMORY_FILE)
+        .ok()
+        .flatten()
+        .filter(|content| !content.trim().is_empty())
+}
+
+/// Appends `fact` as a bullet, creating the file with a header if needed.
+/// Facts already present are not added twice.
+pub fn remember(storage: &dyn Storage, fact: &str) -> crate::Result<()> {
+    let fact = fact.trim().trim_start_matches("- ").replace('\n', " ");
+    if fact.is_empty() {
+        return Err(crate::PicocodeError::Other("Nothing to remember".into()));
+    }
+    let existing = storage.read(MEMORY_FILE)?.unwrap_or_default();
+    if existing
+        .lines()
+        .any(|line| line.trim_start_matches("- ").trim() == fact)
+    {
+        return Ok(());
+    }
+    let mut entry = String::new();
+    if existing.is_empty() {
+        entry.push_str(HEADER);
+    } else if !existing.ends_with('\n') {
+        entry.push('\n');
+    }
+    entry.push_str(&format!("- {}\n", fact));
+    storage.append(MEMORY_FILE, &entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FileStorage;
+
+    #[test]
+    fn test_remember() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FileStorage::new(dir.path());
+        assert!(load(&storage).is_none());
+
+        remember(&storage, "Use anyhow in binaries").unwrap();
+        remember(&storage, "- Tests live next to the code\n").unwrap();
+        remember(&storage, "Use anyhow in binaries").unwrap();
+        assert!(remember(&storage, "  ").is_err());
+
+        assert_eq!(
+            load(&storage).unwrap(),
+            "# Project memory\n\n- Use anyhow in binaries\n- Tests live next to the code\n"
+        );
+        assert!(dir.path().join(MEMORY_FILE).exists());
+    }
+}
@@ -0,0 +1,107 @@
+//! A scripted completion model for tests (the `test-util` feature). It
+//! answers each request with the next scripted response, text or tool calls,
+//! and keeps the requests it got, so a flow built on picocode can be tested
+//! without a provider. See "Use as a Library" in the README for an example.
+
+use crate::replay::{completion_response, NoStreaming, Recorded};
+use crate::session::TokenUsage;
+use rig::agent::AgentBuilder;
+use rig::completion::{CompletionError, CompletionModel, CompletionRequest, CompletionResponse};
+use rig::message::{AssistantContent, Message};
+use rig::streaming::StreamingCompletionResponse;
+use rig::OneOrMany;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// A request the model got.
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub preamble: Option<String>,
+    /// The history, ending with the new message.
+    pub messages: Vec<Message>,
+    /// Names of the tools the request offered.
+    pub tools: Vec<String>,
+}
+
+/// Clones share the script and the requests, so keep one to inspect them after
+/// the agent has run.
+#[derive(Clone, Default)]
+pub struct MockCompletionModel {
+    script: Arc<Mutex<VecDeque<Recorded>>>,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockCompletionModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scripts a text response.
+    pub fn text(self, text: &str) -> Self {
+        self.response(OneOrMany::one(AssistantContent::text(text)))
+    }
+
+    /// Scripts a response calling tool `name` with `args`.
+    pub fn tool_call(self, name: &str, args: serde_json::Value) -> Self {
+        let id = format!("call_{}", self.remaining() + 1);
+        self.response(OneOrMany::one(AssistantContent::tool_call(id, name, args)))
+    }
+
+    /// Scripts a response with any content, e.g. several tool calls at once.
+    pub fn response(self, content: OneOrMany<AssistantContent>) -> Self {
+        self.script.lock().unwrap().push_back(Recorded {
+            content,
+            usage: TokenUsage::default(),
+        });
+        self
+    }
+
+    /// An agent builder on this model, like a provider client's `agent`.
+    pub fn agent(&self, _model: &str) -> AgentBuilder<Self> {
+        AgentBuilder::new(self.clone())
+    }
+
+    /// The requests answered so far, oldest first.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Scripted responses not served yet.
+    pub fn remaining(&self) -> usize {
+        self.script.lock().unwrap().len()
+    }
+}
+
+impl CompletionModel for MockCompletionModel {
+    type Response = ();
+    type StreamingResponse = NoStreaming;
+    type Client = MockCompletionModel;
+
+    fn make(client: &Self::Client, _model: impl Into<String>) -> Self {
+        client.clone()
+    }
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> std::result::Result<CompletionResponse<()>, CompletionError> {
+        self.requests.lock().unwrap().push(MockRequest {
+            preamble: request.preamble.clone(),
+            messages: request.chat_history.iter().cloned().collect(),
+            tools: request.tools.iter().map(|tool| tool.name.clone()).collect(),
+        });
+        let next = self.script.lock().unwrap().pop_front();
+        let recorded =
+            next.ok_or_else(|| CompletionError::ProviderError("No scripted response left".into()))?;
+        Ok(completion_response(&recorded))
+    }
+
+    async fn stream(
+        &self,
+        _request: CompletionRequest,
+    ) -> std::result::Result<StreamingCompletionResponse<NoStreaming>, CompletionError> {
+        Err(CompletionError::ProviderError(
+            "MockCompletionModel does not stream".into(),
+        ))
+    }
+}
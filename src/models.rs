@@ -0,0 +1,507 @@
+//! Known models and what they support: context window, tool calling, image
+//! input, streaming and list price. Consulted for default models, context
+//! budgeting, cost estimates and `picocode models list`.
+//!
+//! The built-in table can be corrected without a release: `picocode models
+//! refresh` installs a manifest from `models_manifest.url`, signed with
+//! `models_manifest.public_key`, in `~/.picocode/models.json`, whose entries
+//! take precedence. `picocode models available` asks a provider's
+//! models API what it serves right now.
+
+use crate::session::TokenUsage;
+use crate::{PicocodeError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+/// US dollars per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Price {
+    pub input: f64,
+    pub output: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub provider: String,
+    /// Model id, or a prefix matching a family of models (e.g. `claude-`).
+    pub id: String,
+    pub context_window: usize,
+    pub tools: bool,
+    pub vision: bool,
+    pub streaming: bool,
+    #[serde(default)]
+    pub price: Option<Price>,
+}
+
+#[derive(Clone, Copy)]
+struct Caps {
+    tools: bool,
+    vision: bool,
+    streaming: bool,
+}
+
+const TEXT: Caps = Caps {
+    tools: true,
+    vision: false,
+    streaming: true,
+};
+const VISION: Caps = Caps {
+    tools: true,
+    vision: true,
+    streaming: true,
+};
+const NO_TOOLS: Caps = Caps {
+    tools: false,
+    vision: false,
+    streaming: true,
+};
+
+/// Provider, id, context window, capabilities and `(input, output)` price.
+type Row = (&'static str, &'static str, usize, Caps, Option<(f64, f64)>);
+
+/// Matched in order, so specific ids come before the prefixes that contain them.
+#[rustfmt::skip]
+const BUILTIN: &[Row] = &[
+    ("anthropic", "claude-opus-4-5", 200_000, VISION, Some((5.0, 25.0))),
+    ("anthropic", "claude-opus-4", 200_000, VISION, Some((15.0, 75.0))),
+    ("anthropic", "claude-sonnet-4", 200_000, VISION, Some((3.0, 15.0))),
+    ("anthropic", "claude-haiku-4-5", 200_000, VISION, Some((1.0, 5.0))),
+    ("anthropic", "claude-3-7-sonnet", 200_000, VISION, Some((3.0, 15.0))),
+    ("anthropic", "claude-3-5-sonnet", 200_000, VISION, Some((3.0, 15.0))),
+    ("anthropic", "claude-3-5-haiku", 200_000, VISION, Some((0.8, 4.0))),
+    ("anthropic", "claude-", 200_000, VISION, None),
+    ("openai", "gpt-4.1-nano", 1_047_576, VISION, Some((0.1, 0.4))),
+    ("openai", "gpt-4.1-mini", 1_047_576, VISION, Some((0.4, 1.6))),
+    ("openai", "gpt-4.1", 1_047_576, VISION, Some((2.0, 8.0))),
+    ("openai", "gpt-4o-mini", 128_000, VISION, Some((0.15, 0.6))),
+    ("openai", "gpt-4o", 128_000, VISION, Some((2.5, 10.0))),
+    ("openai", "gpt-4-turbo", 128_000, VISION, Some((10.0, 30.0))),
+    ("openai", "gpt-4", 8_192, TEXT, Some((30.0, 60.0))),
+    ("openai", "gpt-3.5", 16_385, TEXT, Some((0.5, 1.5))),
+    ("openai", "o1", 200_000, VISION, Some((15.0, 60.0))),
+    ("openai", "o3-mini", 200_000, TEXT, Some((1.1, 4.4))),
+    ("openai", "o3", 200_000, VISION, Some((2.0, 8.0))),
+    ("openai", "o4-mini", 200_000, VISION, Some((1.1, 4.4))),
+    ("gemini", "gemini-2.5-pro", 1_048_576, VISION, Some((1.25, 10.0))),
+    ("gemini", "gemini-2.5-flash", 1_048_576, VISION, Some((0.3, 2.5))),
+    ("gemini", "gemini-1.5-pro", 2_097_152, VISION, Some((1.25, 5.0))),
+    ("gemini", "gemini-", 1_048_576, VISION, None),
+    ("deepseek", "deepseek-reasoner", 64_000, NO_TOOLS, Some((0.55, 2.19))),
+    ("deepseek", "deepseek-", 64_000, TEXT, Some((0.27, 1.1))),
+    ("cohere", "command-r-plus", 128_000, TEXT, Some((2.5, 10.0))),
+    ("cohere", "command-r", 128_000, TEXT, Some((0.15, 0.6))),
+    ("mistral", "mistral-large", 128_000, TEXT, Some((2.0, 6.0))),
+    ("moonshot", "moonshot-v1-8k", 8_192, TEXT, None),
+    ("moonshot", "moonshot-v1-32k", 32_768, TEXT, None),
+    ("moonshot", "moonshot-v1-128k", 131_072, TEXT, None),
+    ("xai", "grok-4", 256_000, VISION, Some((3.0, 15.0))),
+    ("xai", "grok-3", 131_072, TEXT, Some((3.0, 15.0))),
+    ("xai", "grok", 131_072, TEXT, None),
+    ("groq", "llama3-70b-8192", 8_192, TEXT, Some((0.59, 0.79))),
+    ("perplexity", "llama-3-sonar", 32_768, NO_TOOLS, None),
+    ("ollama", "llama3", 8_192, TEXT, Some((0.0, 0.0))),
+    ("together", "llama-3", 8_192, TEXT, None),
+];
+
+/// Model used when neither `--model`, the recipe nor `routing.complex` names one.
+const DEFAULT_MODELS: &[(&str, &str)] = &[
+    ("anthropic", "claude-sonnet-4-6"),
+    ("openai", "gpt-4o-mini"),
+    ("azure", "gpt-4o"),
+    ("cohere", "command-r-plus"),
+    ("deepseek", "deepseek-chat"),
+    ("galadriel", "llama3-70b"),
+    ("groq", "llama3-70b-8192"),
+    ("huggingface", "meta-llama/Llama-3-70b-chat-hf"),
+    ("hyperbolic", "meta-llama/Llama-3-70b-instruct"),
+    ("mira", "mira-v1"),
+    ("mistral", "mistral-large-latest"),
+    ("moonshot", "moonshot-v1-8k"),
+    ("ollama", "llama3"),
+    ("openrouter", "meta-llama/llama-3-70b-instruct"),
+    ("perplexity", "llama-3-sonar-large-32k-online"),
+    ("together", "meta-llama/Llama-3-70b-chat-hf"),
+    ("xai", "grok-1"),
+    ("gemini", "gemini-1.5-pro"),
+    ("google", "gemini-1.5-pro"),
+];
+
+/// Models and default models that replace or extend the built-in ones.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Default model by provider.
+    #[serde(default)]
+    pub defaults: BTreeMap<String, String>,
+    /// Looked up before the built-in models.
+    #[serde(default)]
+    pub models: Vec<ModelInfo>,
+}
+
+struct Registry {
+    models: Vec<ModelInfo>,
+    defaults: Vec<(String, String)>,
+}
+
+impl Registry {
+    /// The built-in models with the manifest at `path`, if there is a valid one.
+    fn load(path: Option<PathBuf>) -> Self {
+        let manifest = path
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| match serde_json::from_str(&text) {
+                Ok(manifest) => Some(manifest),
+                Err(e) => {
+                    tracing::warn!(target: "picocode", "Ignoring invalid model manifest: {}", e);
+                    None
+                }
+            });
+        Self::new(manifest.unwrap_or_default())
+    }
+
+    fn new(manifest: Manifest) -> Self {
+        let builtin = BUILTIN
+            .iter()
+            .map(|&(provider, id, context_window, caps, price)| ModelInfo {
+                provider: provider.into(),
+                id: id.into(),
+                context_window,
+                tools: caps.tools,
+                vision: caps.vision,
+                streaming: caps.streaming,
+                price: price.map(|(input, output)| Price { input, output }),
+            });
+        let models = manifest
+            .models
+            .into_iter()
+            .map(|info| ModelInfo {
+                id: info.id.to_lowercase(),
+                ..info
+            })
+            .chain(builtin)
+            .collect();
+
+        let mut overrides = manifest.defaults;
+        let mut defaults: Vec<(String, String)> = DEFAULT_MODELS
+            .iter()
+            .map(|&(provider, model)| {
+                let model = overrides.remove(provider).unwrap_or_else(|| model.into());
+                (provider.into(), model)
+            })
+            .collect();
+        defaults.extend(overrides);
+        Self { models, defaults }
+    }
+
+    fn default_model(&self, provider: &str) -> Option<&str> {
+        self.defaults
+            .iter()
+            .find(|(p, _)| p == provider)
+            .map(|(_, model)| model.as_str())
+    }
+
+    fn lookup(&self, model: &str) -> Option<&ModelInfo> {
+        let model = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+        self.models.iter().find(|info| model.starts_with(&info.id))
+    }
+
+    fn cost(&self, provider: &str, model: &str, usage: &TokenUsage) -> Option<f64> {
+        let provider = if provider == "google" {
+            "gemini"
+        } else {
+            provider
+        };
+        let info = self
+            .lookup(model)
+            .filter(|info| info.provider == provider)?;
+        let price = info.price?;
+        Some(
+            (usage.input_tokens as f64 * price.input + usage.output_tokens as f64 * price.output)
+                / 1_000_000.0,
+        )
+    }
+}
+
+static REGISTRY: LazyLock<Registry> = LazyLock::new(|| Registry::load(manifest_path()));
+
+/// Where `picocode models refresh` installs the manifest.
+pub fn manifest_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".picocode").join("models.json"))
+}
+
+/// Every known model, in lookup order.
+pub fn all() -> &'static [ModelInfo] {
+    &REGISTRY.models
+}
+
+/// `(provider, model)` pairs used when no model is configured.
+pub fn defaults() -> &'static [(String, String)] {
+    &REGISTRY.defaults
+}
+
+pub fn default_model(provider: &str) -> Option<&'static str> {
+    REGISTRY.default_model(provider)
+}
+
+/// What is known about `model`, matched by id prefix and ignoring any
+/// `vendor/` part, so models served by several providers are found too.
+pub fn lookup(model: &str) -> Option<&'static ModelInfo> {
+    REGISTRY.lookup(model)
+}
+
+/// Context window of `model` in tokens, if known.
+pub fn context_window(model: &str) -> Option<usize> {
+    lookup(model).map(|info| info.context_window)
+}
+
+/// List-price cost of `usage` in US dollars. Only known when `model` is listed
+/// with a price for `provider`, since the same model costs differently elsewhere.
+pub fn cost(provider: &str, model: &str, usage: &TokenUsage) -> Option<f64> {
+    REGISTRY.cost(provider, model, usage)
+}
+
+/// Parses a manifest after checking its minisign signature (the contents of
+/// the `.minisig` file) against `public_key`.
+pub fn verify_manifest(bytes: &[u8], signature: &str, public_key: &str) -> Result<Manifest> {
+    let invalid = |e: minisign_verify::Error| {
+        PicocodeError::Other(format!("Model manifest signature: {}", e))
+    };
+    let signature = minisign_verify::Signature::decode(signature).map_err(invalid)?;
+    let key = minisign_verify::PublicKey::from_base64(public_key.trim()).map_err(invalid)?;
+    key.verify(bytes, &signature, false).map_err(invalid)?;
+    Ok(serde_json::from_slice(bytes)?)
+}
+
+/// Downloads the manifest at `url` and its signature at `<url>.minisig`, and
+/// installs it at [`manifest_path`] once verified with `public_key`. Takes
+/// effect on the next run.
+pub async fn refresh(url: &str, public_key: &str) -> Result<(PathBuf, Manifest)> {
+    let path = manifest_path()
+        .ok_or_else(|| PicocodeError::Other("Could not determine home directory".into()))?;
+    let fetch = |url: String| async move {
+        let response = crate::network::client()?
+            .get(&url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| PicocodeError::Other(format!("{}: {}", url, e)))?;
+        response
+            .bytes()
+            .await
+            .map_err(|e| PicocodeError::Other(format!("{}: {}", url, e)))
+    };
+    let bytes = fetch(url.to_string()).await?;
+    let signature = fetch(format!("{}.minisig", url)).await?;
+    let manifest = verify_manifest(&bytes, &String::from_utf8_lossy(&signature), public_key)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let part = path.with_extension("json.part");
+    std::fs::write(&part, &bytes)?;
+    std::fs::rename(&part, &path)?;
+    Ok((path, manifest))
+}
+
+/// Providers whose models API `available` can query, as `(provider, url, API key
+/// variable)`. OpenRouter lists its models without a key.
+#[rustfmt::skip]
+const MODELS_APIS: &[(&str, &str, Option<&str>)] = &[
+    ("openrouter", "https://openrouter.ai/api/v1/models", None),
+    ("groq", "https://api.groq.com/openai/v1/models", Some("GROQ_API_KEY")),
+    ("openai", "https://api.openai.com/v1/models", Some("OPENAI_API_KEY")),
+    ("deepseek", "https://api.deepseek.com/models", Some("DEEPSEEK_API_KEY")),
+    ("mistral", "https://api.mistral.ai/v1/models", Some("MISTRAL_API_KEY")),
+    ("xai", "https://api.x.ai/v1/models", Some("XAI_API_KEY")),
+];
+
+/// A model a provider currently serves, as reported by its models API.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AvailableModel {
+    pub id: String,
+    pub context_window: Option<usize>,
+    pub price: Option<Price>,
+}
+
+/// Models in an OpenAI-style `{"data": [...]}` response, sorted by id. The
+/// context window and price are read from whichever fields the provider uses.
+fn parse_available(body: &serde_json::Value) -> Vec<AvailableModel> {
+    // OpenRouter prices are strings in dollars per token, rounded here to
+    // hide float noise like 2.4999999999999996
+    let per_token = |v: &serde_json::Value| -> Option<f64> {
+        let price = v.as_f64().or_else(|| v.as_str()?.parse().ok())?;
+        Some((price * 1e10).round() / 1e4)
+    };
+    let mut models: Vec<AvailableModel> = body["data"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|m| {
+            let context_window = ["context_length", "context_window", "max_context_length"]
+                .iter()
+                .find_map(|key| m[key].as_u64())
+                .map(|n| n as usize);
+            let price = match (
+                per_token(&m["pricing"]["prompt"]),
+                per_token(&m["pricing"]["completion"]),
+            ) {
+                (Some(input), Some(output)) => Some(Price { input, output }),
+                _ => None,
+            };
+            Some(AvailableModel {
+                id: m["id"].as_str()?.to_string(),
+                context_window,
+                price,
+            })
+        })
+        .collect();
+    models.sort_by(|a, b| a.id.cmp(&b.id));
+    models
+}
+
+/// Models `provider` serves right now, from its models API.
+pub async fn available(provider: &str) -> Result<Vec<AvailableModel>> {
+    let &(_, url, key_var) = MODELS_APIS
+        .iter()
+        .find(|(name, _, _)| *name == provider)
+        .ok_or_else(|| {
+            let names: Vec<&str> = MODELS_APIS.iter().map(|(name, _, _)| *name).collect();
+            PicocodeError::Other(format!(
+                "Listing available models is supported for {} (not {})",
+                names.join(", "),
+                provider
+            ))
+        })?;
+    let mut request = crate::network::client()?.get(url);
+    if let Some(var) = key_var {
+        let key =
+            std::env::var(var).map_err(|_| PicocodeError::Other(format!("{} is not set", var)))?;
+        request = request.bearer_auth(key);
+    }
+    let error = |e: reqwest::Error| PicocodeError::Other(format!("{}: {}", url, e));
+    let body: serde_json::Value = request
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(error)?
+        .json()
+        .await
+        .map_err(error)?;
+    Ok(parse_available(&body))
+}
+
+pub fn format_cost(dollars: f64) -> String {
+    if dollars < 0.01 {
+        format!("${:.4}", dollars)
+    } else {
+        format!("${:.2}", dollars)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        // A registry of its own, whatever ~/.picocode/models.json holds
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("models.json");
+        let registry = Registry::load(Some(path.clone()));
+        let context_window = |model| registry.lookup(model).map(|info| info.context_window);
+        assert_eq!(context_window("claude-sonnet-4-6"), Some(200_000));
+        assert_eq!(context_window("gpt-4o-mini"), Some(128_000));
+        assert_eq!(
+            context_window("meta-llama/Llama-3-70b-chat-hf"),
+            Some(8_192)
+        );
+        assert_eq!(context_window("mystery-model"), None);
+        assert!(!registry.lookup("deepseek-reasoner").unwrap().tools);
+        assert!(registry.lookup("deepseek-chat").unwrap().tools);
+
+        assert_eq!(registry.default_model("google"), Some("gemini-1.5-pro"));
+        assert_eq!(registry.default_model("nope"), None);
+
+        let usage = TokenUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            total_tokens: 1_100_000,
+        };
+        assert_eq!(
+            registry.cost("anthropic", "claude-sonnet-4-6", &usage),
+            Some(4.5)
+        );
+        assert_eq!(
+            registry.cost("openrouter", "claude-sonnet-4-6", &usage),
+            None
+        );
+
+        std::fs::write(&path, r#"{"defaults": {"google": "gemini-2.5-pro"}}"#).unwrap();
+        let registry = Registry::load(Some(path));
+        assert_eq!(registry.default_model("google"), Some("gemini-2.5-pro"));
+        assert_eq!(format_cost(4.5), "$4.50");
+        assert_eq!(format_cost(0.00123), "$0.0012");
+    }
+
+    #[test]
+    fn test_parse_available() {
+        let body = serde_json::json!({"data": [
+            {"id": "openai/gpt-4o", "context_length": 128000,
+             "pricing": {"prompt": "0.0000025", "completion": "0.00001"}},
+            {"id": "llama-3.3-70b-versatile", "context_window": 131072},
+            {"object": "model"}
+        ]});
+        let models = parse_available(&body);
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[0].id, "llama-3.3-70b-versatile");
+        assert_eq!(models[0].context_window, Some(131_072));
+        assert_eq!(models[0].price, None);
+        assert_eq!(models[1].context_window, Some(128_000));
+        assert_eq!(
+            models[1].price,
+            Some(Price {
+                input: 2.5,
+                output: 10.0
+            })
+        );
+        assert!(parse_available(&serde_json::json!({"error": "nope"})).is_empty());
+    }
+
+    #[test]
+    fn test_manifest() {
+        let manifest: Manifest = serde_json::from_str(
+            r#"{"defaults": {"xai": "grok-4", "newco": "newco-1"},
+                "models": [{"provider": "xai", "id": "Grok-4-fast", "context_window": 2000000,
+                            "tools": true, "vision": true, "streaming": true}]}"#,
+        )
+        .unwrap();
+        let registry = Registry::new(manifest);
+        assert_eq!(registry.models[0].id, "grok-4-fast");
+        assert!(registry.models.len() > BUILTIN.len());
+        assert_eq!(registry.default_model("xai"), Some("grok-4"));
+        assert_eq!(registry.default_model("newco"), Some("newco-1"));
+        assert_eq!(
+            registry.default_model("anthropic"),
+            Some("claude-sonnet-4-6")
+        );
+
+        // Example key and signature of "test" from the minisign-verify docs
+        let key = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+        let signature = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==";
+        // The signature checks out, but "test" is not a manifest
+        assert!(matches!(
+            verify_manifest(b"test", signature, key),
+            Err(PicocodeError::Serialization(_))
+        ));
+        assert!(matches!(
+            verify_manifest(b"{}", signature, key),
+            Err(PicocodeError::Other(_))
+        ));
+        assert!(matches!(
+            verify_manifest(b"test", signature, "RW..."),
+            Err(PicocodeError::Other(_))
+        ));
+    }
+}
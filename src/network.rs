@@ -0,0 +1,107 @@
+//! The `network` settings of picocode.yaml: a proxy, extra CA certificates
+//! and a request timeout, for environments behind a corporate proxy.
+//! picocode's own HTTP clients (web search, downloads, GitHub/GitLab, model
+//! lists) are built with [`client_builder`]. Provider clients are created by
+//! rig from the environment, so they get the proxy and CA bundle through
+//! `HTTPS_PROXY`, `HTTP_PROXY` and `SSL_CERT_FILE`.
+
+use crate::config::NetworkConfig;
+use crate::{PicocodeError, Result};
+use std::sync::{LazyLock, RwLock};
+use std::time::Duration;
+
+#[derive(Clone, Default)]
+struct Settings {
+    proxy: Option<reqwest::Proxy>,
+    certificates: Vec<reqwest::Certificate>,
+    timeout: Option<Duration>,
+}
+
+static SETTINGS: LazyLock<RwLock<Settings>> = LazyLock::new(Default::default);
+
+/// Applies `config` to the HTTP clients built from now on. Fails on a proxy
+/// URL reqwest can't use or a CA bundle without readable certificates.
+pub fn configure(config: &NetworkConfig) -> Result<()> {
+    let proxy = config
+        .proxy
+        .as_deref()
+        .map(|url| {
+            reqwest::Proxy::all(url)
+                .map_err(|e| PicocodeError::Other(format!("network.proxy {}: {}", url, e)))
+        })
+        .transpose()?;
+    let certificates = match &config.ca_bundle {
+        Some(path) => read_certificates(path)?,
+        None => Vec::new(),
+    };
+    // Read by the clients rig creates for providers (and by commands `bash` runs)
+    if let Some(url) = &config.proxy {
+        std::env::set_var("HTTPS_PROXY", url);
+        std::env::set_var("HTTP_PROXY", url);
+    }
+    if let Some(path) = &config.ca_bundle {
+        std::env::set_var("SSL_CERT_FILE", path);
+    }
+    *SETTINGS.write().unwrap() = Settings {
+        proxy,
+        certificates,
+        timeout: config.timeout_secs.map(Duration::from_secs),
+    };
+    Ok(())
+}
+
+fn read_certificates(path: &str) -> Result<Vec<reqwest::Certificate>> {
+    let error = |e: String| PicocodeError::Other(format!("network.ca_bundle {}: {}", path, e));
+    let pem = std::fs::read(path).map_err(|e| error(e.to_string()))?;
+    let certificates =
+        reqwest::Certificate::from_pem_bundle(&pem).map_err(|e| error(e.to_string()))?;
+    if certificates.is_empty() {
+        return Err(error("no PEM certificates found".into()));
+    }
+    Ok(certificates)
+}
+
+/// A client builder with the configured proxy, CA certificates and timeout.
+pub fn client_builder() -> reqwest::ClientBuilder {
+    let settings = SETTINGS.read().unwrap().clone();
+    let mut builder =
+        reqwest::Client::builder().user_agent(concat!("picocode/", env!("CARGO_PKG_VERSION")));
+    if let Some(proxy) = settings.proxy {
+        builder = builder.proxy(proxy);
+    }
+    for certificate in settings.certificates {
+        builder = builder.add_root_certificate(certificate);
+    }
+    if let Some(timeout) = settings.timeout {
+        builder = builder.timeout(timeout);
+    }
+    builder
+}
+
+/// A client with the configured settings. Fails rather than falling back to
+/// a client that would bypass the proxy.
+pub fn client() -> Result<reqwest::Client> {
+    client_builder()
+        .build()
+        .map_err(|e| PicocodeError::Other(format!("HTTP client: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_certificates() {
+        let dir = tempfile::tempdir().unwrap();
+        let empty = dir.path().join("empty.pem");
+        std::fs::write(&empty, "not a certificate\n").unwrap();
+        let err = read_certificates(empty.to_str().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("network.ca_bundle"));
+        assert!(read_certificates("/nonexistent/ca.pem").is_err());
+        assert!(configure(&NetworkConfig {
+            proxy: Some("not a url".into()),
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
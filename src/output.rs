@@ -1,9 +1,15 @@
+use async_trait::async_trait;
 use console::{style, StyledObject, Term};
 use indicatif::{ProgressBar, ProgressStyle};
 use regex::Regex;
+use crate::editor;
+use crate::stats::{format_duration, ToolStat, TurnStats, TurnTiming};
 use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{LazyLock, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use syntect::easy::HighlightLines;
 use syntect::highlighting::ThemeSet;
 use syntect::parsing::SyntaxSet;
@@ -14,24 +20,251 @@ use termimad::{CompoundStyle, MadSkin};
 static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
 static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
 
+/// Faster tool calls don't get a timing line in the console.
+const MIN_SHOWN_TOOL_TIME: Duration = Duration::from_secs(1);
+
 use crate::input::InputEditor;
 use crate::input::ReadlineError;
+use crate::input::TypeAhead;
+
+pub mod confirm;
+pub mod log_file;
+pub mod middleware;
+pub use confirm::{confirm_within, ConfirmWait};
+pub use log_file::LogFileOutput;
+pub use middleware::{FilterOutput, Kind, NotifyingOutput, OutputExt, RedactingOutput, TeeOutput};
+
+/// Live progress of a multi-turn prompt, shown in place of a static spinner message.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Status {
+    /// Tool currently running, if any.
+    pub tool: Option<String>,
+    pub tool_calls: usize,
+    pub tool_call_limit: usize,
+    /// Tokens used by this prompt so far.
+    pub tokens: u64,
+}
+
+/// Tool calls after which a turn is warned that it is running out of
+/// `limit`: 80% of it.
+pub fn call_budget_warning(limit: usize) -> usize {
+    (limit * 4).div_ceil(5)
+}
+
+impl Status {
+    /// Whether the tool calls used reached [`call_budget_warning`].
+    pub fn near_limit(&self) -> bool {
+        self.tool_call_limit > 0 && self.tool_calls >= call_budget_warning(self.tool_call_limit)
+    }
+
+    pub fn message(&self) -> String {
+        let mut message = match &self.tool {
+            Some(tool) => format!("Running {}...", tool),
+            None => "Thinking...".to_string(),
+        };
+        if self.tool_calls > 0 {
+            let calls = format!("calls {}/{}", self.tool_calls, self.tool_call_limit);
+            if self.near_limit() {
+                message.push_str(&format!(" · {}", style(calls).yellow()));
+            } else {
+                message.push_str(&format!(" · {}", calls));
+            }
+        }
+        if self.tokens > 0 {
+            message.push_str(&format!(" · {} tokens", format_tokens(self.tokens)));
+        }
+        message
+    }
+}
 
-#[derive(Debug, PartialEq)]
+fn format_tokens(tokens: u64) -> String {
+    if tokens < 1000 {
+        tokens.to_string()
+    } else {
+        format!("{:.1}k", tokens as f64 / 1000.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Confirmation {
     Yes,
     No,
+    /// Allow every call of the tool for the rest of the session.
     Always,
+    /// Allow commands starting with the offered prefix for the rest of the session.
+    AlwaysCommand,
+    /// Allow commands starting with the offered prefix in this project from now on.
+    AlwaysCommandInProject,
+    /// Allow every call of the tool in this project from now on.
+    AlwaysInProject,
+    /// Edit the call's arguments, then ask again.
+    EditArgs,
+    /// Show the change the call would make, then ask again.
+    ShowDiff,
+}
+
+/// What a tool call confirmation offers besides yes, no and always for the
+/// session.
+#[derive(Debug, Clone, Default)]
+pub struct ConfirmOptions {
+    /// Commands starting with this prefix (e.g. `cargo test`) can be always
+    /// allowed, for the session or the project.
+    pub prefix: Option<String>,
+    /// The tool can be always allowed in this project.
+    pub project: bool,
+    /// The arguments can be edited before deciding.
+    pub edit: bool,
+    /// The call has a diff to show.
+    pub diff: bool,
+}
+
+/// One answer of a confirmation, picked with the arrow keys or its key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Choice {
+    pub key: char,
+    /// Typed in full as an alternative to the key.
+    pub word: &'static str,
+    pub label: String,
+    pub answer: Confirmation,
+}
+
+/// The answers a confirmation with `options` offers, in display order.
+pub fn choices(options: &ConfirmOptions) -> Vec<Choice> {
+    let choice = |key, word, label: &str, answer| Choice {
+        key,
+        word,
+        label: label.to_string(),
+        answer,
+    };
+    let mut choices = vec![
+        choice('y', "yes", "Yes", Confirmation::Yes),
+        choice('n', "no", "No", Confirmation::No),
+        choice('s', "session", "Always (session)", Confirmation::Always),
+    ];
+    if let Some(prefix) = &options.prefix {
+        choices.push(choice(
+            'c',
+            "command",
+            &format!("Always `{} ...` (session)", prefix),
+            Confirmation::AlwaysCommand,
+        ));
+        choices.push(choice(
+            'p',
+            "project",
+            &format!("Always `{} ...` (project)", prefix),
+            Confirmation::AlwaysCommandInProject,
+        ));
+    } else if options.project {
+        choices.push(choice(
+            'p',
+            "project",
+            "Always (project)",
+            Confirmation::AlwaysInProject,
+        ));
+    }
+    if options.edit {
+        choices.push(choice('e', "edit", "Edit args", Confirmation::EditArgs));
+    }
+    if options.diff {
+        choices.push(choice('d', "diff", "Show diff", Confirmation::ShowDiff));
+    }
+    choices
+}
+
+/// The answer typed as a choice's key or word, `No` for anything else.
+pub fn typed_choice(choices: &[Choice], input: &str) -> Confirmation {
+    let input = input.trim().to_lowercase();
+    choices
+        .iter()
+        .find(|c| input == c.word || input.chars().eq([c.key]))
+        .map_or(Confirmation::No, |c| c.answer)
 }
 
+/// Frontend for the agent. Prompting for input and confirmations is async so GUI
+/// and web embedders can wait on their own event loop; display methods stay sync.
+/// Blocking implementations can implement [`SyncOutput`] instead.
+#[async_trait]
 pub trait Output: Send + Sync {
     fn display_text(&self, text: &str);
     fn display_tool_call(&self, name: &str, args: &Value);
     fn display_tool_result(&self, result: &str);
+    /// Shows a line of output from a tool that is still running (e.g. a long
+    /// `bash` build). The full output still arrives with `display_tool_result`.
+    fn display_tool_progress(&self, _line: &str) {}
+    async fn get_user_input(&self, prompt: &str) -> String;
+    /// Reads input starting from `initial`, for revising an earlier prompt.
+    /// Frontends without an editable buffer read fresh input.
+    async fn edit_input(&self, prompt: &str, _initial: &str) -> String {
+        self.get_user_input(prompt).await
+    }
+    fn display_error(&self, error: &str);
+    fn display_system(&self, text: &str);
+    async fn confirm(&self, message: &str) -> Confirmation;
+    /// Like `confirm` for a shell command, also offering to always allow commands
+    /// starting with `prefix` (e.g. `cargo test`).
+    async fn confirm_command(&self, message: &str, _prefix: &str) -> Confirmation {
+        self.confirm(message).await
+    }
+    /// Confirms a tool call, offering the [`choices`] of `options`. Frontends
+    /// without their own menu fall back to `confirm_command` or `confirm`.
+    async fn confirm_call(&self, message: &str, options: &ConfirmOptions) -> Confirmation {
+        match &options.prefix {
+            Some(prefix) => self.confirm_command(message, prefix).await,
+            None => self.confirm(message).await,
+        }
+    }
+    fn display_separator(&self);
+    fn display_thinking(&self, message: &str);
+    fn stop_thinking(&self);
+    fn display_header(
+        &self,
+        provider: &str,
+        model: &str,
+        yolo: bool,
+        limit: usize,
+        persona: Option<&str>,
+    );
+    /// Starts collecting input typed while a turn is running.
+    fn start_input_queue(&self) {}
+    /// Stops collecting and returns the lines typed since `start_input_queue`.
+    fn take_queued_input(&self) -> Vec<String> {
+        Vec::new()
+    }
+    /// Shows per-tool call statistics at the end of a session.
+    fn display_summary(&self, _stats: &BTreeMap<String, ToolStat>) {}
+    /// Shows how long a tool call took, after its result.
+    fn display_tool_timing(&self, _name: &str, _elapsed: Duration) {}
+    /// Shows how long a turn took, split into model and tool time.
+    fn display_turn_timing(&self, _timing: &TurnTiming) {}
+    /// Shows average turn timings at the end of a session.
+    fn display_turn_stats(&self, _stats: &TurnStats) {}
+    /// Updates the in-progress indicator while a prompt runs tools and model calls.
+    fn update_status(&self, _status: &Status) {}
+}
+
+/// Compatibility shim for blocking frontends: the `Output` methods with sync
+/// `get_user_input` and `confirm`. Every `SyncOutput` is an `Output`.
+pub trait SyncOutput: Send + Sync {
+    fn display_text(&self, text: &str);
+    fn display_tool_call(&self, name: &str, args: &Value);
+    fn display_tool_result(&self, result: &str);
+    fn display_tool_progress(&self, _line: &str) {}
     fn get_user_input(&self, prompt: &str) -> String;
+    fn edit_input(&self, prompt: &str, _initial: &str) -> String {
+        self.get_user_input(prompt)
+    }
     fn display_error(&self, error: &str);
     fn display_system(&self, text: &str);
     fn confirm(&self, message: &str) -> Confirmation;
+    fn confirm_command(&self, message: &str, _prefix: &str) -> Confirmation {
+        self.confirm(message)
+    }
+    fn confirm_call(&self, message: &str, options: &ConfirmOptions) -> Confirmation {
+        match &options.prefix {
+            Some(prefix) => self.confirm_command(message, prefix),
+            None => self.confirm(message),
+        }
+    }
     fn display_separator(&self);
     fn display_thinking(&self, message: &str);
     fn stop_thinking(&self);
@@ -43,17 +276,122 @@ pub trait Output: Send + Sync {
         limit: usize,
         persona: Option<&str>,
     );
+    fn start_input_queue(&self) {}
+    fn take_queued_input(&self) -> Vec<String> {
+        Vec::new()
+    }
+    fn display_summary(&self, _stats: &BTreeMap<String, ToolStat>) {}
+    fn display_tool_timing(&self, _name: &str, _elapsed: Duration) {}
+    fn display_turn_timing(&self, _timing: &TurnTiming) {}
+    fn display_turn_stats(&self, _stats: &TurnStats) {}
+    fn update_status(&self, _status: &Status) {}
+}
+
+#[async_trait]
+impl<T: SyncOutput> Output for T {
+    fn display_text(&self, text: &str) {
+        SyncOutput::display_text(self, text)
+    }
+    fn display_tool_call(&self, name: &str, args: &Value) {
+        SyncOutput::display_tool_call(self, name, args)
+    }
+    fn display_tool_result(&self, result: &str) {
+        SyncOutput::display_tool_result(self, result)
+    }
+    fn display_tool_progress(&self, line: &str) {
+        SyncOutput::display_tool_progress(self, line)
+    }
+    async fn get_user_input(&self, prompt: &str) -> String {
+        SyncOutput::get_user_input(self, prompt)
+    }
+    async fn edit_input(&self, prompt: &str, initial: &str) -> String {
+        SyncOutput::edit_input(self, prompt, initial)
+    }
+    fn display_error(&self, error: &str) {
+        SyncOutput::display_error(self, error)
+    }
+    fn display_system(&self, text: &str) {
+        SyncOutput::display_system(self, text)
+    }
+    async fn confirm(&self, message: &str) -> Confirmation {
+        SyncOutput::confirm(self, message)
+    }
+    async fn confirm_command(&self, message: &str, prefix: &str) -> Confirmation {
+        SyncOutput::confirm_command(self, message, prefix)
+    }
+    async fn confirm_call(&self, message: &str, options: &ConfirmOptions) -> Confirmation {
+        SyncOutput::confirm_call(self, message, options)
+    }
+    fn display_separator(&self) {
+        SyncOutput::display_separator(self)
+    }
+    fn display_thinking(&self, message: &str) {
+        SyncOutput::display_thinking(self, message)
+    }
+    fn stop_thinking(&self) {
+        SyncOutput::stop_thinking(self)
+    }
+    fn display_header(
+        &self,
+        provider: &str,
+        model: &str,
+        yolo: bool,
+        limit: usize,
+        persona: Option<&str>,
+    ) {
+        SyncOutput::display_header(self, provider, model, yolo, limit, persona)
+    }
+    fn start_input_queue(&self) {
+        SyncOutput::start_input_queue(self)
+    }
+    fn take_queued_input(&self) -> Vec<String> {
+        SyncOutput::take_queued_input(self)
+    }
+    fn display_summary(&self, stats: &BTreeMap<String, ToolStat>) {
+        SyncOutput::display_summary(self, stats)
+    }
+    fn display_tool_timing(&self, name: &str, elapsed: Duration) {
+        SyncOutput::display_tool_timing(self, name, elapsed)
+    }
+    fn display_turn_timing(&self, timing: &TurnTiming) {
+        SyncOutput::display_turn_timing(self, timing)
+    }
+    fn display_turn_stats(&self, stats: &TurnStats) {
+        SyncOutput::display_turn_stats(self, stats)
+    }
+    fn update_status(&self, status: &Status) {
+        SyncOutput::update_status(self, status)
+    }
 }
 
 pub struct QuietOutput {
     spinner: Mutex<Option<ProgressBar>>,
+    /// Start of the run, when a line is printed to stderr for every tool call.
+    progress: Option<Instant>,
+    tool_calls: AtomicUsize,
+    /// Redacted from the progress lines, see [`crate::secrets::redact`].
+    secrets: Vec<String>,
 }
 
 impl QuietOutput {
     pub fn new() -> Self {
         Self {
             spinner: Mutex::new(None),
+            progress: None,
+            tool_calls: AtomicUsize::new(0),
+            secrets: Vec::new(),
+        }
+    }
+
+    /// Prints `[mm:ss] #n tool: args` to stderr for each tool call, so
+    /// unattended runs show they are making progress. Stdout stays clean, and
+    /// secrets in the arguments are redacted.
+    pub fn with_progress(mut self, progress: bool) -> Self {
+        self.progress = progress.then(Instant::now);
+        if progress {
+            self.secrets = crate::secrets::secret_values();
         }
+        self
     }
 
     fn create_spinner(message: &str) -> ProgressBar {
@@ -69,11 +407,36 @@ impl QuietOutput {
     }
 }
 
+impl Default for QuietOutput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
 impl Output for QuietOutput {
     fn display_text(&self, _text: &str) {}
-    fn display_tool_call(&self, _name: &str, _args: &Value) {}
+    fn display_tool_call(&self, name: &str, args: &Value) {
+        let Some(start) = self.progress else {
+            return;
+        };
+        let n = self.tool_calls.fetch_add(1, Ordering::Relaxed) + 1;
+        let secs = start.elapsed().as_secs();
+        let line = format!(
+            "[{:02}:{:02}] #{} {}: {}",
+            secs / 60,
+            secs % 60,
+            n,
+            name,
+            truncate(&crate::secrets::redact(&preview_text(args), &self.secrets), 50)
+        );
+        match self.spinner.lock().unwrap().as_ref() {
+            Some(pb) => pb.suspend(|| eprintln!("{}", line)),
+            None => eprintln!("{}", line),
+        }
+    }
     fn display_tool_result(&self, _result: &str) {}
-    fn get_user_input(&self, _prompt: &str) -> String {
+    async fn get_user_input(&self, _prompt: &str) -> String {
         String::new()
     }
     fn display_error(&self, error: &str) {
@@ -81,16 +444,17 @@ impl Output for QuietOutput {
         eprintln!("Error: {}", error);
     }
     fn display_system(&self, _text: &str) {}
-    fn confirm(&self, message: &str) -> Confirmation {
+    async fn confirm(&self, message: &str) -> Confirmation {
         self.stop_thinking();
         eprintln!("Confirm: {} [y/n/s]", message);
-        let mut input = String::new();
-        let _ = std::io::stdin().read_line(&mut input);
-        match input.trim().to_lowercase().as_str() {
-            "y" | "yes" => Confirmation::Yes,
-            "s" | "session" => Confirmation::Always,
-            _ => Confirmation::No,
-        }
+        // Read on a blocking thread that a confirmation timeout can cancel
+        let wait = ConfirmWait::start();
+        let input = tokio::task::spawn_blocking(move || confirm::read_line(Some(wait)))
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        typed_choice(&choices(&ConfirmOptions::default()), &input)
     }
     fn display_separator(&self) {}
     fn display_thinking(&self, message: &str) {
@@ -104,6 +468,13 @@ impl Output for QuietOutput {
             pb.finish_and_clear();
         }
     }
+    fn update_status(&self, status: &Status) {
+        let mut spinner = self.spinner.lock().unwrap();
+        match spinner.as_ref() {
+            Some(pb) => pb.set_message(status.message()),
+            None => *spinner = Some(Self::create_spinner(&status.message())),
+        }
+    }
     fn display_header(
         &self,
         _provider: &str,
@@ -117,7 +488,7 @@ impl Output for QuietOutput {
 
 pub struct NoOutput;
 
-impl Output for NoOutput {
+impl SyncOutput for NoOutput {
     fn display_text(&self, _text: &str) {}
     fn display_tool_call(&self, _name: &str, _args: &Value) {}
     fn display_tool_result(&self, _result: &str) {}
@@ -145,7 +516,7 @@ impl Output for NoOutput {
 
 pub struct LogOutput;
 
-impl Output for LogOutput {
+impl SyncOutput for LogOutput {
     fn display_text(&self, text: &str) {
         tracing::info!(target: "picocode", "{}", text);
     }
@@ -158,6 +529,46 @@ impl Output for LogOutput {
         tracing::info!(target: "picocode", "Tool result: {}", result);
     }
 
+    fn display_tool_timing(&self, name: &str, elapsed: Duration) {
+        tracing::info!(target: "picocode", "Tool timing: {} elapsed_ms={}", name, elapsed.as_millis());
+    }
+
+    fn display_turn_timing(&self, timing: &TurnTiming) {
+        tracing::info!(
+            target: "picocode",
+            "Turn timing: total_ms={} first_token_ms={} model_ms={} tools_ms={}",
+            timing.total.as_millis(),
+            timing.first_token.unwrap_or_default().as_millis(),
+            timing.model().as_millis(),
+            timing.tools.as_millis()
+        );
+    }
+
+    fn display_turn_stats(&self, stats: &TurnStats) {
+        tracing::info!(
+            target: "picocode",
+            "Turn stats: turns={} avg_total_ms={} avg_first_token_ms={} avg_model_ms={} avg_tools_ms={}",
+            stats.turns,
+            stats.avg(stats.total_ms).as_millis(),
+            stats.avg(stats.first_token_ms).as_millis(),
+            stats.avg(stats.model_ms).as_millis(),
+            stats.avg(stats.tools_ms).as_millis()
+        );
+    }
+
+    fn display_summary(&self, stats: &BTreeMap<String, ToolStat>) {
+        for (tool, stat) in stats {
+            tracing::info!(
+                target: "picocode",
+                "Tool stats: {} calls={} failures={} total_ms={}",
+                tool,
+                stat.calls,
+                stat.failures,
+                stat.total_ms
+            );
+        }
+    }
+
     fn get_user_input(&self, _prompt: &str) -> String {
         String::new()
     }
@@ -195,17 +606,30 @@ impl Output for LogOutput {
 pub struct ConsoleOutput {
     spinner: Mutex<Option<ProgressBar>>,
     editor: Mutex<Option<InputEditor>>,
+    typeahead: Mutex<Option<TypeAhead>>,
+    queued: Mutex<Vec<String>>,
+    /// Name and args of the tool whose result is displayed next, for file links.
+    last_tool_call: Mutex<Option<(String, Value)>>,
 }
 
 fn truncate(s: &str, max_len: usize) -> String {
     if s.len() > max_len {
-        format!("{}...", &s[..max_len])
+        let end = (0..=max_len)
+            .rev()
+            .find(|&i| s.is_char_boundary(i))
+            .unwrap_or(0);
+        format!("{}...", &s[..end])
     } else {
         s.to_string()
     }
 }
 
 fn get_preview(args: &Value) -> String {
+    truncate(&preview_text(args), 50)
+}
+
+/// The first argument of a tool call, on one line.
+fn preview_text(args: &Value) -> String {
     let s = if let Some(obj) = args.as_object() {
         obj.values()
             .next()
@@ -217,7 +641,7 @@ fn get_preview(args: &Value) -> String {
     } else {
         args.to_string()
     };
-    truncate(&s.replace('\n', " "), 50)
+    s.replace('\n', " ")
 }
 
 impl ConsoleOutput {
@@ -225,6 +649,9 @@ impl ConsoleOutput {
         Self {
             spinner: Mutex::new(None),
             editor: Mutex::new(None),
+            typeahead: Mutex::new(None),
+            queued: Mutex::new(Vec::new()),
+            last_tool_call: Mutex::new(None),
         }
     }
 
@@ -244,11 +671,11 @@ impl ConsoleOutput {
     }
 
     fn fallback_input() -> String {
-        use std::io::{self, Write};
-        let _ = io::stdout().flush();
-        let mut input = String::new();
-        let _ = io::stdin().read_line(&mut input);
-        input.trim().to_string()
+        let _ = std::io::stdout().flush();
+        confirm::read_line(None)
+            .unwrap_or_default()
+            .trim()
+            .to_string()
     }
 }
 
@@ -305,8 +732,50 @@ impl ConsoleOutput {
         pb
     }
 
-    fn get_user_input_impl(&self, prompt: &str) -> String {
-        self.stop_thinking();
+    /// Stops the type-ahead collector (if running) so the line editor can own
+    /// the terminal. Returns true when it should be restarted afterwards.
+    fn pause_typeahead(&self) -> bool {
+        match self.typeahead.lock().unwrap().take() {
+            Some(typeahead) => {
+                self.queued.lock().unwrap().extend(typeahead.stop());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Asks with an arrow-key menu of the [`choices`] of `options`, or by
+    /// typing a choice's key when the terminal can't show one.
+    fn ask_confirmation(&self, message: &str, options: &ConfirmOptions) -> Confirmation {
+        SyncOutput::stop_thinking(self);
+        let choices = choices(options);
+        println!("\n{} {}", style("⚠").yellow(), message);
+
+        let resume = self.pause_typeahead();
+        let wait = ConfirmWait::start();
+        let answer = if Term::stdout().is_term() && std::io::stdin().is_terminal() {
+            let items: Vec<(char, String)> =
+                choices.iter().map(|c| (c.key, c.label.clone())).collect();
+            crate::input::select(&items, || wait.cancelled())
+                .map_or(Confirmation::No, |i| choices[i].answer)
+        } else {
+            let legend: Vec<String> = choices
+                .iter()
+                .map(|c| format!("{} {}", style(c.key).bold(), c.label))
+                .collect();
+            println!("  {}", legend.join(" / "));
+            let _ = std::io::stdout().flush();
+            let input = confirm::read_line(Some(wait)).unwrap_or_default();
+            typed_choice(&choices, &input)
+        };
+        if resume {
+            SyncOutput::start_input_queue(self);
+        }
+        answer
+    }
+
+    fn get_user_input_impl(&self, prompt: &str, initial: &str) -> String {
+        SyncOutput::stop_thinking(self);
 
         if !self.init_editor_if_needed() {
             return Self::fallback_input();
@@ -314,7 +783,7 @@ impl ConsoleOutput {
 
         let mut editor_guard = self.editor.lock().unwrap();
         if let Some(ref mut editor) = *editor_guard {
-            match editor.readline(prompt) {
+            match editor.readline_with(prompt, initial) {
                 Ok(line) => {
                     editor.save_history();
                     line
@@ -337,9 +806,9 @@ impl ConsoleOutput {
     }
 }
 
-impl Output for ConsoleOutput {
+impl SyncOutput for ConsoleOutput {
     fn display_text(&self, text: &str) {
-        self.stop_thinking();
+        SyncOutput::stop_thinking(self);
         println!();
 
         let skin = Self::markdown_skin();
@@ -401,7 +870,7 @@ impl Output for ConsoleOutput {
     }
 
     fn display_tool_call(&self, name: &str, args: &Value) {
-        self.stop_thinking();
+        SyncOutput::stop_thinking(self);
         let preview = get_preview(args);
         let capitalized_name = name
             .chars()
@@ -414,10 +883,11 @@ impl Output for ConsoleOutput {
             style(capitalized_name).bold(),
             style(preview).dim()
         );
+        *self.last_tool_call.lock().unwrap() = Some((name.to_string(), args.clone()));
     }
 
     fn display_tool_result(&self, result: &str) {
-        self.stop_thinking();
+        SyncOutput::stop_thinking(self);
 
         let unquoted = serde_json::from_str::<Value>(result)
             .ok()
@@ -473,46 +943,93 @@ impl Output for ConsoleOutput {
                 style(format!("... +{} lines", lines.len() - show_max)).dim()
             );
         }
+
+        let last_call = self.last_tool_call.lock().unwrap().take();
+        if let Some(location) = last_call
+            .filter(|_| !is_error)
+            .and_then(|(name, args)| editor::edited_location(&name, &args))
+        {
+            let text = location.to_string();
+            let link = if editor::supports_hyperlinks() {
+                editor::hyperlink(&location, &text)
+            } else {
+                text
+            };
+            println!("  {}  {}", style("→").dim(), link);
+        }
+    }
+
+    fn display_tool_progress(&self, line: &str) {
+        let line = format!(
+            "  {}  {}",
+            style("│").dim(),
+            style(truncate(line, 100)).dim()
+        );
+        match self.spinner.lock().unwrap().as_ref() {
+            Some(pb) => pb.suspend(|| println!("{}", line)),
+            None => println!("{}", line),
+        }
     }
 
     fn get_user_input(&self, prompt: &str) -> String {
-        self.get_user_input_impl(prompt)
+        // Also asked mid-turn (`ask_user`), while typed lines are being queued
+        let resume = self.pause_typeahead();
+        let input = self.get_user_input_impl(prompt, "");
+        if resume {
+            SyncOutput::start_input_queue(self);
+        }
+        input
+    }
+
+    fn edit_input(&self, prompt: &str, initial: &str) -> String {
+        self.get_user_input_impl(prompt, initial)
     }
 
     fn display_error(&self, error: &str) {
-        self.stop_thinking();
+        SyncOutput::stop_thinking(self);
         println!("{} Error: {}", style("⏺").red(), error);
     }
 
     fn display_system(&self, text: &str) {
-        self.stop_thinking();
+        SyncOutput::stop_thinking(self);
         println!("{}", style(text).bold().dim());
     }
 
     fn confirm(&self, message: &str) -> Confirmation {
-        self.stop_thinking();
-        println!("\n{} {} [y/n/s]", style("⚠").yellow(), message);
-        println!(
-            "  {}es / {}o / {}ession",
-            style("y").bold(),
-            style("n").bold(),
-            style("s").bold()
-        );
+        self.ask_confirmation(message, &ConfirmOptions::default())
+    }
 
-        let input = self.get_user_input_impl("").to_lowercase();
+    fn confirm_command(&self, message: &str, prefix: &str) -> Confirmation {
+        self.ask_confirmation(
+            message,
+            &ConfirmOptions {
+                prefix: Some(prefix.to_string()),
+                ..Default::default()
+            },
+        )
+    }
 
-        match input.as_str() {
-            "y" | "yes" => Confirmation::Yes,
-            "s" | "session" => Confirmation::Always,
-            _ => Confirmation::No,
-        }
+    fn confirm_call(&self, message: &str, options: &ConfirmOptions) -> Confirmation {
+        self.ask_confirmation(message, options)
     }
 
     fn display_separator(&self) {
-        self.stop_thinking();
+        SyncOutput::stop_thinking(self);
         Self::separator();
     }
 
+    fn start_input_queue(&self) {
+        let mut typeahead = self.typeahead.lock().unwrap();
+        if typeahead.is_none() {
+            *typeahead = Some(TypeAhead::start());
+        }
+    }
+
+    fn take_queued_input(&self) -> Vec<String> {
+        self.pause_typeahead();
+        std::mem::take(&mut *self.queued.lock().unwrap())
+    }
+
     fn display_thinking(&self, message: &str) {
         let mut spinner_lock = self.spinner.lock().unwrap();
         if spinner_lock.is_none() {
@@ -526,6 +1043,14 @@ impl Output for ConsoleOutput {
         }
     }
 
+    fn update_status(&self, status: &Status) {
+        let mut spinner = self.spinner.lock().unwrap();
+        match spinner.as_ref() {
+            Some(pb) => pb.set_message(status.message()),
+            None => *spinner = Some(Self::create_spinner(&status.message())),
+        }
+    }
+
     fn display_header(
         &self,
         provider: &str,
@@ -584,4 +1109,99 @@ impl Output for ConsoleOutput {
         );
     }
 
+    fn display_summary(&self, stats: &BTreeMap<String, ToolStat>) {
+        if stats.is_empty() {
+            return;
+        }
+        SyncOutput::stop_thinking(self);
+        println!();
+        println!(
+            "{}",
+            style(format!(
+                "{:<16} {:>6} {:>8} {:>10} {:>10}",
+                "tool", "calls", "failed", "avg", "total"
+            ))
+            .bold()
+        );
+        for (tool, stat) in stats {
+            let failed = format!("{:.0}%", stat.failure_rate() * 100.0);
+            println!(
+                "{:<16} {:>6} {:>8} {:>10} {:>10}",
+                tool,
+                stat.calls,
+                if stat.failures > 0 {
+                    style(failed).red()
+                } else {
+                    style(failed).dim()
+                },
+                format!("{}ms", stat.avg_ms()),
+                format!("{:.1}s", stat.total_ms as f64 / 1000.0)
+            );
+        }
+    }
+
+    fn display_tool_timing(&self, _name: &str, elapsed: Duration) {
+        if elapsed >= MIN_SHOWN_TOOL_TIME {
+            println!("  {}", style(format!("… {}", format_duration(elapsed))).dim());
+        }
+    }
+
+    fn display_turn_timing(&self, timing: &TurnTiming) {
+        SyncOutput::stop_thinking(self);
+        let mut parts = Vec::new();
+        if let Some(first_token) = timing.first_token {
+            parts.push(format!("first token {}", format_duration(first_token)));
+        }
+        if !timing.tools.is_zero() {
+            parts.push(format!("model {}", format_duration(timing.model())));
+            parts.push(format!("tools {}", format_duration(timing.tools)));
+        }
+        let text = if parts.is_empty() {
+            format!("⏱ {}", format_duration(timing.total))
+        } else {
+            format!("⏱ {} ({})", format_duration(timing.total), parts.join(", "))
+        };
+        println!("\n{}", style(text).dim());
+    }
+
+    fn display_turn_stats(&self, stats: &TurnStats) {
+        if stats.turns == 0 {
+            return;
+        }
+        SyncOutput::stop_thinking(self);
+        println!(
+            "\n{}",
+            style(format!(
+                "{} turn(s), avg {} (first token {}, model {}, tools {})",
+                stats.turns,
+                format_duration(stats.avg(stats.total_ms)),
+                format_duration(stats.avg(stats.first_token_ms)),
+                format_duration(stats.avg(stats.model_ms)),
+                format_duration(stats.avg(stats.tools_ms))
+            ))
+            .dim()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status() {
+        let status = |tool_calls| Status {
+            tool: Some("bash".into()),
+            tool_calls,
+            tool_call_limit: 50,
+            tokens: 12_400,
+        };
+        assert_eq!(
+            console::strip_ansi_codes(&status(12).message()),
+            "Running bash... · calls 12/50 · 12.4k tokens"
+        );
+        assert_eq!(call_budget_warning(50), 40);
+        assert!(!status(39).near_limit());
+        assert!(status(40).near_limit());
+    }
 }
@@ -0,0 +1,173 @@
+//! Confirmations that can time out. A frontend may block while it waits for
+//! the answer, so the question runs on a blocking thread and its reader is
+//! told to give up when the timeout passes.
+
+use super::{ConfirmOptions, Confirmation, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often a waiting reader checks whether it was cancelled.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Bumped whenever a confirmation times out.
+static EPOCH: AtomicUsize = AtomicUsize::new(0);
+
+/// A confirmation being answered, cancelled once it times out.
+#[derive(Debug, Clone, Copy)]
+pub struct ConfirmWait(usize);
+
+impl ConfirmWait {
+    pub fn start() -> Self {
+        Self(EPOCH.load(Ordering::SeqCst))
+    }
+
+    pub fn cancelled(&self) -> bool {
+        EPOCH.load(Ordering::SeqCst) != self.0
+    }
+}
+
+/// Asks `output` to confirm `message` (a tool call with `options`, if given),
+/// giving up after `timeout` seconds. `None` means it timed out.
+pub async fn confirm_within(
+    output: &Arc<dyn Output>,
+    message: &str,
+    options: Option<&ConfirmOptions>,
+    timeout: Option<u64>,
+) -> Option<Confirmation> {
+    let Some(secs) = timeout else {
+        return Some(match options {
+            Some(options) => output.confirm_call(message, options).await,
+            None => output.confirm(message).await,
+        });
+    };
+    let (output, message, options) = (output.clone(), message.to_string(), options.cloned());
+    let ask = tokio::task::spawn_blocking(move || {
+        tokio::runtime::Handle::current().block_on(async {
+            match &options {
+                Some(options) => output.confirm_call(&message, options).await,
+                None => output.confirm(&message).await,
+            }
+        })
+    });
+    match tokio::time::timeout(Duration::from_secs(secs), ask).await {
+        Ok(answer) => answer.ok(),
+        Err(_) => {
+            EPOCH.fetch_add(1, Ordering::SeqCst);
+            None
+        }
+    }
+}
+
+/// Reads stdin on one thread for the whole process, a line at a time when
+/// asked, so a cancelled read doesn't leave a thread behind.
+struct StdinReader {
+    requests: Sender<()>,
+    lines: Receiver<(Instant, Option<String>)>,
+    /// A line was asked for and hasn't arrived yet.
+    pending: bool,
+}
+
+static STDIN: LazyLock<Mutex<StdinReader>> = LazyLock::new(|| {
+    let (requests, asked) = mpsc::channel::<()>();
+    let (sender, lines) = mpsc::channel();
+    std::thread::spawn(move || {
+        for () in asked {
+            let mut line = String::new();
+            let read = match std::io::stdin().read_line(&mut line) {
+                Ok(0) | Err(_) => None,
+                Ok(_) => Some(line),
+            };
+            if sender.send((Instant::now(), read)).is_err() {
+                break;
+            }
+        }
+    });
+    Mutex::new(StdinReader {
+        requests,
+        lines,
+        pending: false,
+    })
+});
+
+/// Reads a line from stdin, or `None` at end of input or once `wait` is
+/// cancelled. A line that arrives for an earlier, cancelled read is dropped
+/// rather than taken as the answer to this one.
+pub(crate) fn read_line(wait: Option<ConfirmWait>) -> Option<String> {
+    let asked = Instant::now();
+    let mut reader = STDIN.lock().unwrap();
+    loop {
+        if !reader.pending {
+            reader.requests.send(()).ok()?;
+            reader.pending = true;
+        }
+        match reader.lines.recv_timeout(POLL_INTERVAL) {
+            Ok((at, line)) => {
+                reader.pending = false;
+                if at >= asked || line.is_none() {
+                    return line;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) if wait.is_some_and(|w| w.cancelled()) => return None,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::SyncOutput;
+    use serde_json::Value;
+    use std::sync::atomic::AtomicBool;
+
+    /// Blocks in `confirm` until the confirmation is cancelled.
+    #[derive(Default)]
+    struct Blocking(AtomicBool);
+
+    impl SyncOutput for Blocking {
+        fn display_text(&self, _text: &str) {}
+        fn display_tool_call(&self, _name: &str, _args: &Value) {}
+        fn display_tool_result(&self, _result: &str) {}
+        fn get_user_input(&self, _prompt: &str) -> String {
+            String::new()
+        }
+        fn display_error(&self, _error: &str) {}
+        fn display_system(&self, _text: &str) {}
+        fn confirm(&self, _message: &str) -> Confirmation {
+            let wait = ConfirmWait::start();
+            while !wait.cancelled() {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            self.0.store(true, Ordering::SeqCst);
+            Confirmation::Yes
+        }
+        fn display_separator(&self) {}
+        fn display_thinking(&self, _message: &str) {}
+        fn stop_thinking(&self) {}
+        fn display_header(
+            &self,
+            _provider: &str,
+            _model: &str,
+            _yolo: bool,
+            _limit: usize,
+            _persona: Option<&str>,
+        ) {
+        }
+    }
+
+    #[tokio::test]
+    async fn test_confirm_within() {
+        let blocking = Arc::new(Blocking::default());
+        let output: Arc<dyn Output> = blocking.clone();
+        assert_eq!(
+            confirm_within(&output, "Delete?", None, Some(1)).await,
+            None
+        );
+        // The blocked frontend was told to stop waiting
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(blocking.0.load(Ordering::SeqCst));
+    }
+}
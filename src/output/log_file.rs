@@ -0,0 +1,233 @@
+//! Transcript of a session on disk for `--log-file`: everything displayed, one
+//! timestamped entry per call, as text or as JSONL when the path ends in `.jsonl`.
+//! Meant as the second output of a [`super::TeeOutput`].
+
+use super::{Confirmation, SyncOutput};
+use crate::stats::{format_duration, ToolStat, TurnStats, TurnTiming};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The log is rotated when a write would take it past this size.
+pub const MAX_LOG_BYTES: u64 = 10 * 1024 * 1024;
+/// Rotated logs kept as `<path>.1` (newest) to `<path>.3`.
+const KEEP_ROTATED: usize = 3;
+
+#[derive(Serialize)]
+struct Record<'a> {
+    timestamp: String,
+    kind: &'a str,
+    text: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    args: Option<&'a Value>,
+}
+
+struct Current {
+    file: File,
+    size: u64,
+}
+
+pub struct LogFileOutput {
+    path: PathBuf,
+    json: bool,
+    max_bytes: u64,
+    current: Mutex<Current>,
+}
+
+fn open(path: &Path) -> std::io::Result<Current> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok(Current { file, size })
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+impl LogFileOutput {
+    /// Appends to `path`, creating it and its directory if needed.
+    pub fn open(path: impl Into<PathBuf>) -> crate::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let current = open(&path)?;
+        Ok(Self {
+            json: path.extension().is_some_and(|ext| ext == "jsonl"),
+            max_bytes: MAX_LOG_BYTES,
+            current: Mutex::new(current),
+            path,
+        })
+    }
+
+    /// Rotates the log when it would grow past `max_bytes` instead of [`MAX_LOG_BYTES`].
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    fn rotate(&self, current: &mut Current) -> std::io::Result<()> {
+        for n in (1..KEEP_ROTATED).rev() {
+            let from = rotated(&self.path, n);
+            if from.exists() {
+                std::fs::rename(&from, rotated(&self.path, n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, rotated(&self.path, 1))?;
+        *current = open(&self.path)?;
+        Ok(())
+    }
+
+    fn entry(&self, kind: &str, text: &str, args: Option<&Value>) -> String {
+        if self.json {
+            let record = Record {
+                timestamp: chrono::Local::now().to_rfc3339(),
+                kind,
+                text,
+                args,
+            };
+            return serde_json::to_string(&record).unwrap_or_default() + "\n";
+        }
+        let mut text = text.replace('\n', "\n  ");
+        if let Some(args) = args {
+            text = format!("{} {}", text, args);
+        }
+        format!(
+            "{} [{}] {}\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+            kind,
+            text
+        )
+    }
+
+    fn write(&self, kind: &str, text: &str, args: Option<&Value>) {
+        let entry = self.entry(kind, text, args);
+        let mut current = self.current.lock().unwrap();
+        let result = (|| {
+            if current.size > 0 && current.size + entry.len() as u64 > self.max_bytes {
+                self.rotate(&mut current)?;
+            }
+            current.file.write_all(entry.as_bytes())?;
+            current.size += entry.len() as u64;
+            Ok::<_, std::io::Error>(())
+        })();
+        if let Err(e) = result {
+            tracing::warn!(target: "picocode", "Failed to write log file: {}", e);
+        }
+    }
+}
+
+impl SyncOutput for LogFileOutput {
+    fn display_text(&self, text: &str) {
+        self.write("text", text, None);
+    }
+    fn display_tool_call(&self, name: &str, args: &Value) {
+        self.write("tool_call", name, Some(args));
+    }
+    fn display_tool_result(&self, result: &str) {
+        self.write("tool_result", result, None);
+    }
+    fn get_user_input(&self, _prompt: &str) -> String {
+        String::new()
+    }
+    fn display_error(&self, error: &str) {
+        self.write("error", error, None);
+    }
+    fn display_system(&self, text: &str) {
+        self.write("system", text, None);
+    }
+    fn confirm(&self, _message: &str) -> Confirmation {
+        Confirmation::No
+    }
+    fn display_separator(&self) {}
+    fn display_thinking(&self, _message: &str) {}
+    fn stop_thinking(&self) {}
+    fn display_header(
+        &self,
+        provider: &str,
+        model: &str,
+        yolo: bool,
+        limit: usize,
+        persona: Option<&str>,
+    ) {
+        let header = format!(
+            "picocode | {} | {} | persona:{} | yolo:{} limit:{}",
+            provider,
+            model,
+            persona.unwrap_or("default"),
+            yolo,
+            limit
+        );
+        self.write("header", &header, None);
+    }
+    fn display_summary(&self, stats: &BTreeMap<String, ToolStat>) {
+        for (tool, stat) in stats {
+            let line = format!(
+                "{} calls={} failures={} total_ms={}",
+                tool, stat.calls, stat.failures, stat.total_ms
+            );
+            self.write("summary", &line, None);
+        }
+    }
+    fn display_tool_timing(&self, name: &str, elapsed: Duration) {
+        let line = format!("{} {}", name, format_duration(elapsed));
+        self.write("timing", &line, None);
+    }
+    fn display_turn_timing(&self, timing: &TurnTiming) {
+        let line = format!(
+            "turn {} (first token {}, model {}, tools {})",
+            format_duration(timing.total),
+            format_duration(timing.first_token.unwrap_or_default()),
+            format_duration(timing.model()),
+            format_duration(timing.tools)
+        );
+        self.write("timing", &line, None);
+    }
+    fn display_turn_stats(&self, stats: &TurnStats) {
+        let line = format!(
+            "turns={} avg_total_ms={} avg_first_token_ms={} avg_model_ms={} avg_tools_ms={}",
+            stats.turns,
+            stats.avg(stats.total_ms).as_millis(),
+            stats.avg(stats.first_token_ms).as_millis(),
+            stats.avg(stats.model_ms).as_millis(),
+            stats.avg(stats.tools_ms).as_millis()
+        );
+        self.write("summary", &line, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs/session.log");
+        let log = LogFileOutput::open(&path).unwrap().with_max_bytes(100);
+        log.display_text("first line\nsecond line");
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.ends_with(" [text] first line\n  second line\n"));
+
+        log.display_tool_call("bash", &serde_json::json!({"cmd": "cargo test"}));
+        assert!(rotated(&path, 1).exists());
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.ends_with(" [tool_call] bash {\"cmd\":\"cargo test\"}\n"));
+
+        let path = dir.path().join("session.jsonl");
+        let log = LogFileOutput::open(&path).unwrap();
+        log.display_error("boom");
+        let record: Value =
+            serde_json::from_str(std::fs::read_to_string(&path).unwrap().trim()).unwrap();
+        assert_eq!(record["kind"], "error");
+        assert_eq!(record["text"], "boom");
+        assert!(record.get("args").is_none());
+    }
+}
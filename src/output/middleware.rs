@@ -0,0 +1,637 @@
+//! `Output` decorators that wrap another frontend: [`TeeOutput`] copies
+//! everything displayed to a second output (e.g. a log), [`FilterOutput`] drops
+//! kinds of events, [`RedactingOutput`] hides secrets and [`NotifyingOutput`]
+//! rings when input is needed. [`OutputExt`] stacks them, e.g.
+//! `console.tee(log.redact())`.
+
+use super::{ConfirmOptions, Confirmation, Output, Status};
+use crate::config::NotificationsConfig;
+use crate::stats::{ToolStat, TurnStats, TurnTiming};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What a display call shows, for [`FilterOutput`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Text,
+    ToolCall,
+    ToolResult,
+    /// Output of a tool that is still running.
+    ToolProgress,
+    Error,
+    System,
+    Separator,
+    /// Spinner and status updates while a prompt runs.
+    Thinking,
+    Header,
+    Summary,
+    /// Tool and turn timings.
+    Timing,
+}
+
+/// Sends display calls to both outputs. Input and confirmations are answered
+/// by `primary`; `secondary` sees them as system lines, so a log records what
+/// was asked and answered.
+pub struct TeeOutput {
+    primary: Arc<dyn Output>,
+    secondary: Arc<dyn Output>,
+}
+
+impl TeeOutput {
+    pub fn new(primary: Arc<dyn Output>, secondary: Arc<dyn Output>) -> Self {
+        Self { primary, secondary }
+    }
+}
+
+#[async_trait]
+impl Output for TeeOutput {
+    fn display_text(&self, text: &str) {
+        self.primary.display_text(text);
+        self.secondary.display_text(text);
+    }
+    fn display_tool_call(&self, name: &str, args: &Value) {
+        self.primary.display_tool_call(name, args);
+        self.secondary.display_tool_call(name, args);
+    }
+    fn display_tool_result(&self, result: &str) {
+        self.primary.display_tool_result(result);
+        self.secondary.display_tool_result(result);
+    }
+    fn display_tool_progress(&self, line: &str) {
+        self.primary.display_tool_progress(line);
+        self.secondary.display_tool_progress(line);
+    }
+    async fn get_user_input(&self, prompt: &str) -> String {
+        let input = self.primary.get_user_input(prompt).await;
+        self.secondary
+            .display_system(&format!("{}{}", prompt, input));
+        input
+    }
+    async fn edit_input(&self, prompt: &str, initial: &str) -> String {
+        let input = self.primary.edit_input(prompt, initial).await;
+        self.secondary
+            .display_system(&format!("{}{}", prompt, input));
+        input
+    }
+    fn display_error(&self, error: &str) {
+        self.primary.display_error(error);
+        self.secondary.display_error(error);
+    }
+    fn display_system(&self, text: &str) {
+        self.primary.display_system(text);
+        self.secondary.display_system(text);
+    }
+    async fn confirm(&self, message: &str) -> Confirmation {
+        let answer = self.primary.confirm(message).await;
+        self.secondary
+            .display_system(&format!("{} {:?}", message, answer));
+        answer
+    }
+    async fn confirm_command(&self, message: &str, prefix: &str) -> Confirmation {
+        let answer = self.primary.confirm_command(message, prefix).await;
+        self.secondary
+            .display_system(&format!("{} {:?}", message, answer));
+        answer
+    }
+    async fn confirm_call(&self, message: &str, options: &ConfirmOptions) -> Confirmation {
+        let answer = self.primary.confirm_call(message, options).await;
+        self.secondary
+            .display_system(&format!("{} {:?}", message, answer));
+        answer
+    }
+    fn display_separator(&self) {
+        self.primary.display_separator();
+        self.secondary.display_separator();
+    }
+    fn display_thinking(&self, message: &str) {
+        self.primary.display_thinking(message);
+        self.secondary.display_thinking(message);
+    }
+    fn stop_thinking(&self) {
+        self.primary.stop_thinking();
+        self.secondary.stop_thinking();
+    }
+    fn display_header(
+        &self,
+        provider: &str,
+        model: &str,
+        yolo: bool,
+        limit: usize,
+        persona: Option<&str>,
+    ) {
+        self.primary
+            .display_header(provider, model, yolo, limit, persona);
+        self.secondary
+            .display_header(provider, model, yolo, limit, persona);
+    }
+    fn start_input_queue(&self) {
+        self.primary.start_input_queue();
+    }
+    fn take_queued_input(&self) -> Vec<String> {
+        self.primary.take_queued_input()
+    }
+    fn display_summary(&self, stats: &BTreeMap<String, ToolStat>) {
+        self.primary.display_summary(stats);
+        self.secondary.display_summary(stats);
+    }
+    fn display_tool_timing(&self, name: &str, elapsed: Duration) {
+        self.primary.display_tool_timing(name, elapsed);
+        self.secondary.display_tool_timing(name, elapsed);
+    }
+    fn display_turn_timing(&self, timing: &TurnTiming) {
+        self.primary.display_turn_timing(timing);
+        self.secondary.display_turn_timing(timing);
+    }
+    fn display_turn_stats(&self, stats: &TurnStats) {
+        self.primary.display_turn_stats(stats);
+        self.secondary.display_turn_stats(stats);
+    }
+    fn update_status(&self, status: &Status) {
+        self.primary.update_status(status);
+        self.secondary.update_status(status);
+    }
+}
+
+/// Passes on the display calls whose [`Kind`] `keep` accepts. Input and
+/// confirmations always go through.
+pub struct FilterOutput {
+    inner: Arc<dyn Output>,
+    keep: Box<dyn Fn(Kind) -> bool + Send + Sync>,
+}
+
+impl FilterOutput {
+    pub fn new(
+        inner: Arc<dyn Output>,
+        keep: impl Fn(Kind) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            inner,
+            keep: Box::new(keep),
+        }
+    }
+}
+
+#[async_trait]
+impl Output for FilterOutput {
+    fn display_text(&self, text: &str) {
+        if (self.keep)(Kind::Text) {
+            self.inner.display_text(text);
+        }
+    }
+    fn display_tool_call(&self, name: &str, args: &Value) {
+        if (self.keep)(Kind::ToolCall) {
+            self.inner.display_tool_call(name, args);
+        }
+    }
+    fn display_tool_result(&self, result: &str) {
+        if (self.keep)(Kind::ToolResult) {
+            self.inner.display_tool_result(result);
+        }
+    }
+    fn display_tool_progress(&self, line: &str) {
+        if (self.keep)(Kind::ToolProgress) {
+            self.inner.display_tool_progress(line);
+        }
+    }
+    async fn get_user_input(&self, prompt: &str) -> String {
+        self.inner.get_user_input(prompt).await
+    }
+    async fn edit_input(&self, prompt: &str, initial: &str) -> String {
+        self.inner.edit_input(prompt, initial).await
+    }
+    fn display_error(&self, error: &str) {
+        if (self.keep)(Kind::Error) {
+            self.inner.display_error(error);
+        }
+    }
+    fn display_system(&self, text: &str) {
+        if (self.keep)(Kind::System) {
+            self.inner.display_system(text);
+        }
+    }
+    async fn confirm(&self, message: &str) -> Confirmation {
+        self.inner.confirm(message).await
+    }
+    async fn confirm_command(&self, message: &str, prefix: &str) -> Confirmation {
+        self.inner.confirm_command(message, prefix).await
+    }
+    async fn confirm_call(&self, message: &str, options: &ConfirmOptions) -> Confirmation {
+        self.inner.confirm_call(message, options).await
+    }
+    fn display_separator(&self) {
+        if (self.keep)(Kind::Separator) {
+            self.inner.display_separator();
+        }
+    }
+    fn display_thinking(&self, message: &str) {
+        if (self.keep)(Kind::Thinking) {
+            self.inner.display_thinking(message);
+        }
+    }
+    fn stop_thinking(&self) {
+        self.inner.stop_thinking();
+    }
+    fn display_header(
+        &self,
+        provider: &str,
+        model: &str,
+        yolo: bool,
+        limit: usize,
+        persona: Option<&str>,
+    ) {
+        if (self.keep)(Kind::Header) {
+            self.inner
+                .display_header(provider, model, yolo, limit, persona);
+        }
+    }
+    fn start_input_queue(&self) {
+        self.inner.start_input_queue();
+    }
+    fn take_queued_input(&self) -> Vec<String> {
+        self.inner.take_queued_input()
+    }
+    fn display_summary(&self, stats: &BTreeMap<String, ToolStat>) {
+        if (self.keep)(Kind::Summary) {
+            self.inner.display_summary(stats);
+        }
+    }
+    fn display_tool_timing(&self, name: &str, elapsed: Duration) {
+        if (self.keep)(Kind::Timing) {
+            self.inner.display_tool_timing(name, elapsed);
+        }
+    }
+    fn display_turn_timing(&self, timing: &TurnTiming) {
+        if (self.keep)(Kind::Timing) {
+            self.inner.display_turn_timing(timing);
+        }
+    }
+    fn display_turn_stats(&self, stats: &TurnStats) {
+        if (self.keep)(Kind::Summary) {
+            self.inner.display_turn_stats(stats);
+        }
+    }
+    fn update_status(&self, status: &Status) {
+        if (self.keep)(Kind::Thinking) {
+            self.inner.update_status(status);
+        }
+    }
+}
+
+/// Replaces secrets in everything passed on: values of credential-looking
+/// environment variables (as set when it was created), recognizable API tokens
+/// and passwords in URLs. See [`crate::secrets::redact`].
+pub struct RedactingOutput {
+    inner: Arc<dyn Output>,
+    values: Vec<String>,
+}
+
+impl RedactingOutput {
+    pub fn new(inner: Arc<dyn Output>) -> Self {
+        Self {
+            inner,
+            values: crate::secrets::secret_values(),
+        }
+    }
+
+    /// Also redacts `value`, e.g. a token that isn't in the environment.
+    pub fn with_secret(mut self, value: impl Into<String>) -> Self {
+        self.values.push(value.into());
+        self.values.sort_by_key(|v| std::cmp::Reverse(v.len()));
+        self
+    }
+
+    fn redact(&self, text: &str) -> String {
+        crate::secrets::redact(text, &self.values)
+    }
+
+    fn redact_value(&self, value: &Value) -> Value {
+        match value {
+            Value::String(s) => Value::String(self.redact(s)),
+            Value::Array(items) => items.iter().map(|v| self.redact_value(v)).collect(),
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .map(|(k, v)| (k.clone(), self.redact_value(v)))
+                    .collect(),
+            ),
+            _ => value.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl Output for RedactingOutput {
+    fn display_text(&self, text: &str) {
+        self.inner.display_text(&self.redact(text));
+    }
+    fn display_tool_call(&self, name: &str, args: &Value) {
+        self.inner.display_tool_call(name, &self.redact_value(args));
+    }
+    fn display_tool_result(&self, result: &str) {
+        self.inner.display_tool_result(&self.redact(result));
+    }
+    fn display_tool_progress(&self, line: &str) {
+        self.inner.display_tool_progress(&self.redact(line));
+    }
+    async fn get_user_input(&self, prompt: &str) -> String {
+        self.inner.get_user_input(prompt).await
+    }
+    async fn edit_input(&self, prompt: &str, initial: &str) -> String {
+        self.inner.edit_input(prompt, initial).await
+    }
+    fn display_error(&self, error: &str) {
+        self.inner.display_error(&self.redact(error));
+    }
+    fn display_system(&self, text: &str) {
+        self.inner.display_system(&self.redact(text));
+    }
+    async fn confirm(&self, message: &str) -> Confirmation {
+        self.inner.confirm(&self.redact(message)).await
+    }
+    async fn confirm_command(&self, message: &str, prefix: &str) -> Confirmation {
+        self.inner
+            .confirm_command(&self.redact(message), prefix)
+            .await
+    }
+    async fn confirm_call(&self, message: &str, options: &ConfirmOptions) -> Confirmation {
+        self.inner
+            .confirm_call(&self.redact(message), options)
+            .await
+    }
+    fn display_separator(&self) {
+        self.inner.display_separator();
+    }
+    fn display_thinking(&self, message: &str) {
+        self.inner.display_thinking(message);
+    }
+    fn stop_thinking(&self) {
+        self.inner.stop_thinking();
+    }
+    fn display_header(
+        &self,
+        provider: &str,
+        model: &str,
+        yolo: bool,
+        limit: usize,
+        persona: Option<&str>,
+    ) {
+        self.inner
+            .display_header(provider, model, yolo, limit, persona);
+    }
+    fn start_input_queue(&self) {
+        self.inner.start_input_queue();
+    }
+    fn take_queued_input(&self) -> Vec<String> {
+        self.inner.take_queued_input()
+    }
+    fn display_summary(&self, stats: &BTreeMap<String, ToolStat>) {
+        self.inner.display_summary(stats);
+    }
+    fn display_tool_timing(&self, name: &str, elapsed: Duration) {
+        self.inner.display_tool_timing(name, elapsed);
+    }
+    fn display_turn_timing(&self, timing: &TurnTiming) {
+        self.inner.display_turn_timing(timing);
+    }
+    fn display_turn_stats(&self, stats: &TurnStats) {
+        self.inner.display_turn_stats(stats);
+    }
+    fn update_status(&self, status: &Status) {
+        self.inner.update_status(status);
+    }
+}
+
+/// Rings the terminal bell, and shows a desktop notification when enabled,
+/// when a confirmation is pending or a turn of at least `long_turn_secs`
+/// finished, so a run in a background terminal isn't left waiting.
+pub struct NotifyingOutput {
+    inner: Arc<dyn Output>,
+    config: NotificationsConfig,
+}
+
+impl NotifyingOutput {
+    pub fn new(inner: Arc<dyn Output>, config: NotificationsConfig) -> Self {
+        Self { inner, config }
+    }
+
+    fn notify(&self, message: &str) {
+        if self.config.bell {
+            let mut stderr = std::io::stderr();
+            let _ = stderr.write_all(b"\x07");
+            let _ = stderr.flush();
+        }
+        if self.config.desktop {
+            crate::platform::notify("picocode", message);
+        }
+    }
+}
+
+#[async_trait]
+impl Output for NotifyingOutput {
+    fn display_text(&self, text: &str) {
+        self.inner.display_text(text);
+    }
+    fn display_tool_call(&self, name: &str, args: &Value) {
+        self.inner.display_tool_call(name, args);
+    }
+    fn display_tool_result(&self, result: &str) {
+        self.inner.display_tool_result(result);
+    }
+    fn display_tool_progress(&self, line: &str) {
+        self.inner.display_tool_progress(line);
+    }
+    async fn get_user_input(&self, prompt: &str) -> String {
+        self.inner.get_user_input(prompt).await
+    }
+    async fn edit_input(&self, prompt: &str, initial: &str) -> String {
+        self.inner.edit_input(prompt, initial).await
+    }
+    fn display_error(&self, error: &str) {
+        self.inner.display_error(error);
+    }
+    fn display_system(&self, text: &str) {
+        self.inner.display_system(text);
+    }
+    async fn confirm(&self, message: &str) -> Confirmation {
+        self.notify(message);
+        self.inner.confirm(message).await
+    }
+    async fn confirm_command(&self, message: &str, prefix: &str) -> Confirmation {
+        self.notify(message);
+        self.inner.confirm_command(message, prefix).await
+    }
+    async fn confirm_call(&self, message: &str, options: &ConfirmOptions) -> Confirmation {
+        self.notify(message);
+        self.inner.confirm_call(message, options).await
+    }
+    fn display_separator(&self) {
+        self.inner.display_separator();
+    }
+    fn display_thinking(&self, message: &str) {
+        self.inner.display_thinking(message);
+    }
+    fn stop_thinking(&self) {
+        self.inner.stop_thinking();
+    }
+    fn display_header(
+        &self,
+        provider: &str,
+        model: &str,
+        yolo: bool,
+        limit: usize,
+        persona: Option<&str>,
+    ) {
+        self.inner
+            .display_header(provider, model, yolo, limit, persona);
+    }
+    fn start_input_queue(&self) {
+        self.inner.start_input_queue();
+    }
+    fn take_queued_input(&self) -> Vec<String> {
+        self.inner.take_queued_input()
+    }
+    fn display_summary(&self, stats: &BTreeMap<String, ToolStat>) {
+        self.inner.display_summary(stats);
+    }
+    fn display_tool_timing(&self, name: &str, elapsed: Duration) {
+        self.inner.display_tool_timing(name, elapsed);
+    }
+    fn display_turn_timing(&self, timing: &TurnTiming) {
+        self.inner.display_turn_timing(timing);
+        if timing.total >= Duration::from_secs(self.config.long_turn_secs) {
+            self.notify(&format!(
+                "Finished after {}",
+                crate::stats::format_duration(timing.total)
+            ));
+        }
+    }
+    fn display_turn_stats(&self, stats: &TurnStats) {
+        self.inner.display_turn_stats(stats);
+    }
+    fn update_status(&self, status: &Status) {
+        self.inner.update_status(status);
+    }
+}
+
+/// Stacks decorators on an output, innermost first:
+/// `Arc::new(ConsoleOutput::new()).tee(log.redact())`.
+pub trait OutputExt {
+    /// Also shows everything on `other`; see [`TeeOutput`].
+    fn tee(self, other: Arc<dyn Output>) -> Arc<dyn Output>;
+    /// Only shows the kinds of events `keep` accepts; see [`FilterOutput`].
+    fn filter(self, keep: impl Fn(Kind) -> bool + Send + Sync + 'static) -> Arc<dyn Output>;
+    /// Hides secrets; see [`RedactingOutput`].
+    fn redact(self) -> Arc<dyn Output>;
+    /// Rings when input is needed; see [`NotifyingOutput`].
+    fn notify(self, config: NotificationsConfig) -> Arc<dyn Output>;
+}
+
+impl<T: Output + 'static> OutputExt for Arc<T> {
+    fn tee(self, other: Arc<dyn Output>) -> Arc<dyn Output> {
+        Arc::new(TeeOutput::new(self, other))
+    }
+    fn filter(self, keep: impl Fn(Kind) -> bool + Send + Sync + 'static) -> Arc<dyn Output> {
+        Arc::new(FilterOutput::new(self, keep))
+    }
+    fn redact(self) -> Arc<dyn Output> {
+        Arc::new(RedactingOutput::new(self))
+    }
+    fn notify(self, config: NotificationsConfig) -> Arc<dyn Output> {
+        Arc::new(NotifyingOutput::new(self, config))
+    }
+}
+
+impl OutputExt for Arc<dyn Output> {
+    fn tee(self, other: Arc<dyn Output>) -> Arc<dyn Output> {
+        Arc::new(TeeOutput::new(self, other))
+    }
+    fn filter(self, keep: impl Fn(Kind) -> bool + Send + Sync + 'static) -> Arc<dyn Output> {
+        Arc::new(FilterOutput::new(self, keep))
+    }
+    fn redact(self) -> Arc<dyn Output> {
+        Arc::new(RedactingOutput::new(self))
+    }
+    fn notify(self, config: NotificationsConfig) -> Arc<dyn Output> {
+        Arc::new(NotifyingOutput::new(self, config))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::SyncOutput;
+    use std::sync::Mutex;
+
+    /// Records display calls and answers `get_user_input` with "yes".
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl SyncOutput for Recorder {
+        fn display_text(&self, text: &str) {
+            self.0.lock().unwrap().push(format!("text: {}", text));
+        }
+        fn display_tool_call(&self, name: &str, args: &Value) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("call: {} {}", name, args));
+        }
+        fn display_tool_result(&self, _result: &str) {}
+        fn get_user_input(&self, _prompt: &str) -> String {
+            "yes".into()
+        }
+        fn display_error(&self, _error: &str) {}
+        fn display_system(&self, text: &str) {
+            self.0.lock().unwrap().push(format!("system: {}", text));
+        }
+        fn confirm(&self, _message: &str) -> Confirmation {
+            Confirmation::Yes
+        }
+        fn display_separator(&self) {}
+        fn display_thinking(&self, _message: &str) {}
+        fn stop_thinking(&self) {}
+        fn display_header(
+            &self,
+            _provider: &str,
+            _model: &str,
+            _yolo: bool,
+            _limit: usize,
+            _persona: Option<&str>,
+        ) {
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stack() {
+        let console = Arc::new(Recorder::default());
+        let log = Arc::new(Recorder::default());
+        let quiet_log = log.clone().filter(|kind| kind != Kind::System);
+        let output = console.clone().tee(Arc::new(
+            RedactingOutput::new(quiet_log).with_secret("hunter2-hunter2"),
+        ));
+
+        output.display_text("password is hunter2-hunter2");
+        output.display_tool_call("bash", &serde_json::json!({"cmd": "echo hunter2-hunter2"}));
+        output.display_system("Resumed session");
+        assert_eq!(output.get_user_input("> ").await, "yes");
+
+        assert_eq!(
+            *console.0.lock().unwrap(),
+            [
+                "text: password is hunter2-hunter2",
+                r#"call: bash {"cmd":"echo hunter2-hunter2"}"#,
+                "system: Resumed session",
+            ]
+        );
+        assert_eq!(
+            *log.0.lock().unwrap(),
+            [
+                "text: password is <redacted>",
+                r#"call: bash {"cmd":"echo <redacted>"}"#,
+            ]
+        );
+    }
+}
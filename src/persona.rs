@@ -75,10 +75,38 @@ pub const PERSONAS: &[Persona] = &[
     },
 ];
 
+/// Resolves a persona by name or file path. A comma-separated list
+/// (e.g. `strict,security`) combines the prompts in the given order.
 pub fn get_persona(name: &str) -> Option<String> {
+    if name.contains(',') {
+        let prompts = name
+            .split(',')
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+            .map(get_single_persona)
+            .collect::<Option<Vec<_>>>()?;
+        return if prompts.is_empty() {
+            None
+        } else {
+            Some(prompts.join("\n\n"))
+        };
+    }
+    get_single_persona(name)
+}
+
+fn get_single_persona(name: &str) -> Option<String> {
     // Try to load from file first
-    if Path::new(name).exists() {
-        return fs::read_to_string(name).ok();
+    let path = Path::new(name);
+    if path.exists() {
+        let content = fs::read_to_string(path).ok()?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        return match crate::config::resolve_includes(&content, base) {
+            Ok(prompt) => Some(prompt),
+            Err(e) => {
+                tracing::warn!(target: "picocode", "Persona {}: {}", name, e);
+                None
+            }
+        };
     }
 
     // Then look for builtin
@@ -95,3 +123,22 @@ pub fn list_personas() -> String {
         .collect::<Vec<_>>()
         .join("\n")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_persona_combined_in_order() {
+        let combined = get_persona("strict, security").unwrap();
+        let strict = get_persona("strict").unwrap();
+        let security = get_persona("security").unwrap();
+        assert_eq!(combined, format!("{}\n\n{}", strict, security));
+    }
+
+    #[test]
+    fn test_get_persona_combined_unknown() {
+        assert!(get_persona("strict,no-such-persona").is_none());
+        assert!(get_persona(",").is_none());
+    }
+}
@@ -0,0 +1,101 @@
+use regex::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Canonical location of the current plan, written by `/write` and executed by `/go`.
+pub const PLAN_FILE: &str = ".picocode/plan.md";
+
+static CHECKBOX_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\s*[-*]\s+\[)([ xX])(\]\s+)(.*)$").unwrap());
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanStep {
+    pub text: String,
+    pub done: bool,
+}
+
+/// Returns the checkbox steps (`- [ ] ...` / `- [x] ...`) of a plan in order.
+pub fn parse_steps(content: &str) -> Vec<PlanStep> {
+    content
+        .lines()
+        .filter_map(|line| CHECKBOX_RE.captures(line))
+        .map(|cap| PlanStep {
+            text: cap[4].trim().to_string(),
+            done: &cap[2] != " ",
+        })
+        .collect()
+}
+
+/// Returns the 1-based index and the first unchecked step, if any.
+pub fn next_step(content: &str) -> Option<(usize, PlanStep)> {
+    parse_steps(content)
+        .into_iter()
+        .enumerate()
+        .find(|(_, step)| !step.done)
+        .map(|(i, step)| (i + 1, step))
+}
+
+/// Checks off the first unchecked step whose text matches `text`, or returns
+/// `None` when there is no such step.
+pub fn mark_done(content: &str, text: &str) -> Option<String> {
+    let mut marked = false;
+    let mut lines: Vec<String> = content
+        .lines()
+        .map(|line| {
+            if !marked {
+                if let Some(cap) = CHECKBOX_RE.captures(line) {
+                    if &cap[2] == " " && cap[4].trim() == text {
+                        marked = true;
+                        return format!("{}x{}{}", &cap[1], &cap[3], &cap[4]);
+                    }
+                }
+            }
+            line.to_string()
+        })
+        .collect();
+    if !marked {
+        return None;
+    }
+    if content.ends_with('\n') {
+        lines.push(String::new());
+    }
+    Some(lines.join("\n"))
+}
+
+pub fn load(path: &str) -> Option<String> {
+    std::fs::read_to_string(path).ok()
+}
+
+pub fn save(path: &str, content: &str) -> crate::Result<()> {
+    if let Some(parent) = Path::new(path).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAN: &str = "## Plan\n\n### Steps\n- [x] Add module\n- [ ] Wire it up\n  * [ ] Write tests\n";
+
+    #[test]
+    fn test_parse_steps() {
+        let steps = parse_steps(PLAN);
+        assert_eq!(steps.len(), 3);
+        assert!(steps[0].done);
+        assert_eq!(steps[2].text, "Write tests");
+        assert_eq!(next_step(PLAN).unwrap().0, 2);
+    }
+
+    #[test]
+    fn test_mark_done() {
+        let updated = mark_done(PLAN, "Wire it up").unwrap();
+        assert!(updated.contains("- [x] Wire it up"));
+        assert!(updated.ends_with('\n'));
+        assert_eq!(next_step(&updated).unwrap().1.text, "Write tests");
+        assert_eq!(mark_done(PLAN, "Add module"), None);
+        assert_eq!(mark_done(PLAN, "Reworded step"), None);
+    }
+}
@@ -0,0 +1,267 @@
+//! What differs between Unix and Windows: finding binaries on `PATH`, the
+//! shell `bash` commands run in, comparing paths and desktop notifications.
+//! The Windows rules are plain functions of their inputs, so they are tested
+//! on any host.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+pub const WINDOWS: bool = cfg!(windows);
+
+/// Extensions tried on Windows when `PATHEXT` is not set.
+const DEFAULT_PATHEXT: &str = ".COM;.EXE;.BAT;.CMD";
+
+/// Full path of `tool` if it is on `PATH` (or is itself a path to a file),
+/// trying each `PATHEXT` extension on Windows.
+pub fn which(tool: &str) -> Option<PathBuf> {
+    let exts = if WINDOWS {
+        std::env::var("PATHEXT").unwrap_or_else(|_| DEFAULT_PATHEXT.into())
+    } else {
+        String::new()
+    };
+    if tool.contains(['/', std::path::MAIN_SEPARATOR]) {
+        return find_in(tool, [PathBuf::new()], &exts);
+    }
+    let path = std::env::var_os("PATH")?;
+    find_in(tool, std::env::split_paths(&path), &exts)
+}
+
+fn find_in(tool: &str, dirs: impl IntoIterator<Item = PathBuf>, exts: &str) -> Option<PathBuf> {
+    let names: Vec<String> = std::iter::once(tool.to_string())
+        .chain(
+            exts.split(';')
+                .filter(|ext| !ext.is_empty())
+                .map(|ext| format!("{}{}", tool, ext.to_lowercase())),
+        )
+        .collect();
+    dirs.into_iter()
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| is_executable(path))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Shell that `bash` tool commands run in (`tool_config.bash.shell`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Shell {
+    Sh,
+    Bash,
+    Zsh,
+    Fish,
+    /// PowerShell 7 and later.
+    Pwsh,
+    /// Windows PowerShell 5, shipped with Windows.
+    PowerShell,
+    Cmd,
+}
+
+static DEFAULT_SHELL: LazyLock<Shell> =
+    LazyLock::new(|| Shell::default_for(WINDOWS, |tool| which(tool).is_some()));
+
+impl Shell {
+    /// `sh` on Unix. On Windows, PowerShell when installed, since models'
+    /// commands (`ls`, `cat`, `rm`) mostly work there, and `cmd` otherwise.
+    pub fn detect() -> Self {
+        *DEFAULT_SHELL
+    }
+
+    fn default_for(windows: bool, available: impl Fn(&str) -> bool) -> Self {
+        if !windows {
+            Shell::Sh
+        } else if available("pwsh") {
+            Shell::Pwsh
+        } else if available("powershell") {
+            Shell::PowerShell
+        } else {
+            Shell::Cmd
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Shell::Sh => "sh",
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+            Shell::Pwsh => "pwsh",
+            Shell::PowerShell => "powershell",
+            Shell::Cmd => "cmd",
+        }
+    }
+
+    /// Arguments that make the shell run `cmd` and exit, without reading
+    /// startup files, so rc quirks don't leak into commands.
+    pub fn args(self, cmd: &str) -> Vec<String> {
+        let flags: &[&str] = match self {
+            Shell::Sh => &["-c"],
+            Shell::Bash => &["--noprofile", "--norc", "-c"],
+            Shell::Zsh => &["-f", "-c"],
+            Shell::Fish => &["--no-config", "-c"],
+            Shell::Pwsh | Shell::PowerShell => &["-NoProfile", "-NonInteractive", "-Command"],
+            Shell::Cmd => &["/Q", "/C"],
+        };
+        flags
+            .iter()
+            .map(|flag| flag.to_string())
+            .chain([cmd.to_string()])
+            .collect()
+    }
+
+    /// Whether commands are parsed like `sh`, as the approval checks in
+    /// [`crate::shell`] assume.
+    pub fn is_posix(self) -> bool {
+        matches!(self, Shell::Sh | Shell::Bash | Shell::Zsh)
+    }
+
+    pub fn command(self, cmd: &str) -> duct::Expression {
+        duct::cmd(self.name(), self.args(cmd))
+    }
+}
+
+/// Whether `path` is `base` or inside it. Both are expected to be absolute and
+/// free of `.`/`..`; on Windows case and separators are ignored and a `\\?\`
+/// prefix (as returned by `canonicalize`) doesn't matter.
+pub fn is_within(path: &Path, base: &Path) -> bool {
+    if !WINDOWS {
+        return path.starts_with(base);
+    }
+    is_within_windows(&path.to_string_lossy(), &base.to_string_lossy())
+}
+
+fn is_within_windows(path: &str, base: &str) -> bool {
+    let key = |p: &str| {
+        let p = p.replace('/', "\\");
+        let p = match p.strip_prefix(r"\\?\UNC\") {
+            Some(share) => format!(r"\\{}", share),
+            None => p.strip_prefix(r"\\?\").map_or(p.clone(), String::from),
+        };
+        p.trim_end_matches('\\').to_lowercase()
+    };
+    let (path, base) = (key(path), key(base));
+    path == base
+        || path
+            .strip_prefix(&base)
+            .is_some_and(|rest| rest.starts_with('\\'))
+}
+
+/// Shows a desktop notification in the background, with `osascript` on macOS
+/// or `notify-send` elsewhere. Does nothing on Windows or when the program is
+/// missing.
+pub fn notify(title: &str, message: &str) {
+    let command: Vec<String> = if cfg!(target_os = "macos") {
+        vec![
+            "osascript".into(),
+            "-e".into(),
+            format!(
+                "display notification {} with title {}",
+                applescript_string(message),
+                applescript_string(title)
+            ),
+        ]
+    } else if !WINDOWS && which("notify-send").is_some() {
+        vec!["notify-send".into(), title.into(), message.into()]
+    } else {
+        return;
+    };
+    std::thread::spawn(move || {
+        let _ = std::process::Command::new(&command[0])
+            .args(&command[1..])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status();
+    });
+}
+
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn executable(path: &Path) {
+        std::fs::write(path, "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_find_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let dirs = || [dir.path().to_path_buf()];
+        executable(&dir.path().join("mytool"));
+        executable(&dir.path().join("wintool.exe"));
+        assert_eq!(
+            find_in("mytool", dirs(), ""),
+            Some(dir.path().join("mytool"))
+        );
+        assert_eq!(
+            find_in("wintool", dirs(), DEFAULT_PATHEXT),
+            Some(dir.path().join("wintool.exe"))
+        );
+        assert_eq!(find_in("wintool", dirs(), ""), None);
+        #[cfg(unix)]
+        {
+            std::fs::write(dir.path().join("notes"), "").unwrap();
+            assert_eq!(find_in("notes", dirs(), ""), None);
+        }
+    }
+
+    #[test]
+    fn test_shell() {
+        assert_eq!(Shell::default_for(false, |_| true), Shell::Sh);
+        assert_eq!(
+            Shell::default_for(true, |t| t == "powershell"),
+            Shell::PowerShell
+        );
+        assert_eq!(Shell::default_for(true, |_| false), Shell::Cmd);
+        assert_eq!(Shell::Sh.args("ls"), ["-c", "ls"]);
+        assert_eq!(Shell::Cmd.args("dir"), ["/Q", "/C", "dir"]);
+        assert_eq!(Shell::Fish.args("ls"), ["--no-config", "-c", "ls"]);
+        let shell: Shell = serde_json::from_value(serde_json::json!("pwsh")).unwrap();
+        assert_eq!(shell, Shell::Pwsh);
+        assert!(Shell::Zsh.is_posix());
+        assert!(!Shell::Fish.is_posix() && !Shell::Pwsh.is_posix());
+    }
+
+    #[test]
+    fn test_is_within_windows() {
+        assert!(is_within_windows(
+            r"C:\Work\repo\src\lib.rs",
+            r"c:\work\repo"
+        ));
+        assert!(is_within_windows(r"\\?\C:\work\repo", r"C:\work\repo\"));
+        assert!(is_within_windows("C:/work/repo/a.txt", r"C:\work\repo"));
+        assert!(is_within_windows(
+            r"\\?\UNC\server\share\x",
+            r"\\server\share"
+        ));
+        assert!(!is_within_windows(r"C:\work\repo2\a.txt", r"C:\work\repo"));
+        assert!(!is_within_windows(r"D:\work\repo", r"C:\work\repo"));
+    }
+
+    #[test]
+    fn test_applescript_string() {
+        assert_eq!(
+            applescript_string(r#"Run "rm" in C:\tmp?"#),
+            r#""Run \"rm\" in C:\\tmp?""#
+        );
+    }
+}
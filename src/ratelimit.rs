@@ -0,0 +1,138 @@
+use crate::config::RateLimit;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+// Limiters are shared per provider so that every agent in the process draws
+// from the same budget.
+static LIMITERS: LazyLock<Mutex<HashMap<String, Arc<RateLimiter>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+struct Bucket {
+    capacity: f64,
+    available: f64,
+    refill_per_sec: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn per_minute(capacity: f64) -> Self {
+        Self {
+            capacity,
+            available: capacity,
+            refill_per_sec: capacity / 60.0,
+            last: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.available = (self.available + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last = now;
+    }
+
+    /// Time to wait until `amount` is available. Zero when it can be taken now.
+    fn wait_for(&mut self, amount: f64) -> Duration {
+        self.refill();
+        if self.available >= amount {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((amount - self.available) / self.refill_per_sec)
+        }
+    }
+}
+
+/// Token-bucket limiter for requests/min and tokens/min of a single provider.
+pub struct RateLimiter {
+    requests: Option<Mutex<Bucket>>,
+    tokens: Option<Mutex<Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: &RateLimit) -> Self {
+        Self {
+            requests: limit
+                .requests_per_minute
+                .filter(|n| *n > 0)
+                .map(|n| Mutex::new(Bucket::per_minute(n as f64))),
+            tokens: limit
+                .tokens_per_minute
+                .filter(|n| *n > 0)
+                .map(|n| Mutex::new(Bucket::per_minute(n as f64))),
+        }
+    }
+
+    /// Returns the limiter shared by all agents talking to `provider`.
+    pub fn for_provider(provider: &str, limit: &RateLimit) -> Arc<Self> {
+        LIMITERS
+            .lock()
+            .unwrap()
+            .entry(provider.to_string())
+            .or_insert_with(|| Arc::new(Self::new(limit)))
+            .clone()
+    }
+
+    /// Waits until a request may be sent, then takes one request from the budget.
+    /// Token usage is only known after the response, so requests wait while the
+    /// token budget is exhausted and `record_tokens` settles the actual cost.
+    pub async fn acquire(&self) {
+        loop {
+            let mut wait = Duration::ZERO;
+            if let Some(tokens) = &self.tokens {
+                wait = wait.max(tokens.lock().unwrap().wait_for(1.0));
+            }
+            if let Some(requests) = &self.requests {
+                let mut bucket = requests.lock().unwrap();
+                let request_wait = bucket.wait_for(1.0);
+                if wait.is_zero() && request_wait.is_zero() {
+                    bucket.available -= 1.0;
+                    return;
+                }
+                wait = wait.max(request_wait);
+            } else if wait.is_zero() {
+                return;
+            }
+            tracing::info!(target: "picocode", "Rate limit reached, waiting {:.1}s", wait.as_secs_f64());
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    pub fn record_tokens(&self, tokens: u64) {
+        if let Some(bucket) = &self.tokens {
+            let mut bucket = bucket.lock().unwrap();
+            bucket.refill();
+            bucket.available -= tokens as f64;
+        }
+    }
+}
+
+/// Whether a provider error means the request was rate limited (HTTP 429).
+pub fn is_rate_limit_error(message: &str) -> bool {
+    message.contains("429") || message.to_lowercase().contains("rate limit")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_wait_when_empty() {
+        let mut bucket = Bucket::per_minute(60.0);
+        assert_eq!(bucket.wait_for(1.0), Duration::ZERO);
+        bucket.available = 0.0;
+        let wait = bucket.wait_for(1.0);
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_token_debt_blocks_requests() {
+        let limiter = RateLimiter::new(&RateLimit {
+            requests_per_minute: None,
+            tokens_per_minute: Some(600),
+        });
+        limiter.record_tokens(1200);
+        let wait = limiter.tokens.as_ref().unwrap().lock().unwrap().wait_for(1.0);
+        assert!(wait > Duration::from_secs(50));
+    }
+}
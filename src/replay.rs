@@ -0,0 +1,215 @@
+//! The `replay` provider: serves the completions recorded in a saved session
+//! or a `--debug-llm` capture, in order, instead of calling an API. Runs are
+//! deterministic and free, which makes them useful for testing the tool loop,
+//! output rendering and confirmations:
+//! `picocode --provider replay --model .picocode/debug/<id>.jsonl "prompt"`.
+
+use crate::session::{Session, TokenUsage};
+use crate::{PicocodeError, Result};
+use rig::agent::AgentBuilder;
+use rig::completion::{
+    CompletionError, CompletionModel, CompletionRequest, CompletionResponse, GetTokenUsage, Usage,
+};
+use rig::message::{AssistantContent, Message};
+use rig::streaming::StreamingCompletionResponse;
+use rig::OneOrMany;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Reply to requests without tools (session titles, result summaries), which
+/// are not part of a recording.
+const TOOLLESS_REPLY: &str = "Replay";
+
+/// One recorded completion.
+#[derive(Debug, Clone)]
+pub struct Recorded {
+    pub content: OneOrMany<AssistantContent>,
+    pub usage: TokenUsage,
+}
+
+/// Serves recorded completions in order. Clones share their position, so
+/// rebuilding the agent (`/persona`, mode switches) continues the replay.
+#[derive(Clone)]
+pub struct ReplayModel {
+    responses: Arc<Vec<Recorded>>,
+    next: Arc<AtomicUsize>,
+}
+
+impl ReplayModel {
+    pub fn new(responses: Vec<Recorded>) -> Self {
+        Self {
+            responses: Arc::new(responses),
+            next: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Reads the completions of a session file (`.json`, its assistant
+    /// messages) or a `--debug-llm` capture (`.jsonl`, its responses).
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            PicocodeError::Other(format!("Replay recording {}: {}", path.display(), e))
+        })?;
+        // A one-record capture is valid JSON too, so look for a session's messages
+        let is_session = serde_json::from_str::<serde_json::Value>(&text)
+            .is_ok_and(|value| value.get("messages").is_some());
+        let responses = if is_session {
+            from_session(&Session::from_json(&text)?)
+        } else {
+            from_capture(&text)?
+        };
+        if responses.is_empty() {
+            return Err(PicocodeError::Other(format!(
+                "No recorded completions in {}",
+                path.display()
+            )));
+        }
+        Ok(Self::new(responses))
+    }
+
+    /// This model, like a provider client's `completion_model`.
+    pub fn completion_model(&self, _model: &str) -> Self {
+        self.clone()
+    }
+
+    /// An agent builder on this model, like a provider client's `agent`.
+    pub fn agent(&self, _model: &str) -> AgentBuilder<Self> {
+        AgentBuilder::new(self.clone())
+    }
+
+    /// Recorded completions not served yet.
+    pub fn remaining(&self) -> usize {
+        self.responses
+            .len()
+            .saturating_sub(self.next.load(Ordering::Relaxed))
+    }
+}
+
+fn from_session(session: &Session) -> Vec<Recorded> {
+    session
+        .messages
+        .iter()
+        .filter_map(|m| match m {
+            Message::Assistant { content, .. } => Some(Recorded {
+                content: content.clone(),
+                usage: TokenUsage::default(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn from_capture(text: &str) -> Result<Vec<Recorded>> {
+    let mut responses = Vec::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let record: serde_json::Value = serde_json::from_str(line)?;
+        if record["kind"] != "response" {
+            continue;
+        }
+        responses.push(Recorded {
+            content: serde_json::from_value(record["content"].clone())?,
+            usage: serde_json::from_value(record["usage"].clone()).unwrap_or_default(),
+        });
+    }
+    Ok(responses)
+}
+
+/// The response serving `recorded`, with its token usage.
+pub(crate) fn completion_response(recorded: &Recorded) -> CompletionResponse<()> {
+    let mut usage = Usage::new();
+    usage.input_tokens = recorded.usage.input_tokens;
+    usage.output_tokens = recorded.usage.output_tokens;
+    usage.total_tokens = recorded.usage.total_tokens;
+    CompletionResponse {
+        choice: recorded.content.clone(),
+        usage,
+        raw_response: (),
+    }
+}
+
+/// Stand-in streaming response type: recorded completions are never streamed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoStreaming;
+
+impl GetTokenUsage for NoStreaming {
+    fn token_usage(&self) -> Option<Usage> {
+        None
+    }
+}
+
+impl CompletionModel for ReplayModel {
+    type Response = ();
+    type StreamingResponse = NoStreaming;
+    type Client = ReplayModel;
+
+    fn make(client: &Self::Client, _model: impl Into<String>) -> Self {
+        client.clone()
+    }
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> std::result::Result<CompletionResponse<()>, CompletionError> {
+        if request.tools.is_empty() {
+            return Ok(CompletionResponse {
+                choice: OneOrMany::one(AssistantContent::text(TOOLLESS_REPLY)),
+                usage: Usage::new(),
+                raw_response: (),
+            });
+        }
+        let n = self.next.fetch_add(1, Ordering::Relaxed);
+        let recorded = self.responses.get(n).ok_or_else(|| {
+            CompletionError::ProviderError(format!(
+                "Replay exhausted: all {} recorded completions were served",
+                self.responses.len()
+            ))
+        })?;
+        Ok(completion_response(recorded))
+    }
+
+    async fn stream(
+        &self,
+        _request: CompletionRequest,
+    ) -> std::result::Result<StreamingCompletionResponse<NoStreaming>, CompletionError> {
+        Err(CompletionError::ProviderError(
+            "The replay provider does not stream".into(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debug::DebugLog;
+
+    #[test]
+    fn test_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = DebugLog::new(dir.path(), "capture");
+        let usage = TokenUsage {
+            input_tokens: 10,
+            output_tokens: 2,
+            total_tokens: 12,
+        };
+        let content = OneOrMany::one(AssistantContent::text("hello"));
+        log.response(1, "claude-x", &content, &usage);
+        let replay = ReplayModel::load(log.path()).unwrap();
+        assert_eq!(replay.remaining(), 1);
+        assert_eq!(replay.responses[0].usage.total_tokens, 12);
+
+        let mut session = Session::new("s1".into(), "anthropic", "claude-x", None);
+        session.messages = vec![
+            Message::user("hi"),
+            Message::assistant("one"),
+            Message::user("again"),
+            Message::assistant("two"),
+        ];
+        let path = dir.path().join("s1.json");
+        std::fs::write(&path, session.to_json().unwrap()).unwrap();
+        assert_eq!(ReplayModel::load(&path).unwrap().remaining(), 2);
+
+        std::fs::write(&path, "").unwrap();
+        assert!(ReplayModel::load(&path).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+//! `picocode review`: reviews only the changed hunks of a git range and reports
+//! findings as text, GitHub Actions annotations, or SARIF.
+
+use crate::{PicocodeError, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::sync::LazyLock;
+
+static HUNK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^@@ -\d+(?:,\d+)? \+(\d+)(?:,(\d+))? @@").unwrap());
+
+const MAX_DIFF_CHARS: usize = 80_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Notice,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn github(&self) -> &'static str {
+        match self {
+            Severity::Notice => "notice",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    fn sarif(&self) -> &'static str {
+        match self {
+            Severity::Notice => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Finding {
+    pub file: String,
+    pub line: u32,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Runs `git diff` for a range such as `main..HEAD`.
+pub fn git_diff(range: &str) -> Result<String> {
+    let output = std::process::Command::new("git")
+        .args(["diff", "--unified=3", range])
+        .output()?;
+    if !output.status.success() {
+        return Err(PicocodeError::Other(format!(
+            "git diff {} failed: {}",
+            range,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// New-side line ranges of every hunk, keyed by file path.
+pub fn changed_lines(diff: &str) -> HashMap<String, Vec<RangeInclusive<u32>>> {
+    let mut changed: HashMap<String, Vec<RangeInclusive<u32>>> = HashMap::new();
+    let mut file: Option<String> = None;
+    for line in diff.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            file = path.strip_prefix("b/").map(str::to_string);
+        } else if let (Some(file), Some(cap)) = (&file, HUNK_RE.captures(line)) {
+            let start: u32 = cap[1].parse().unwrap_or(0);
+            let count: u32 = cap.get(2).map(|c| c.as_str().parse().unwrap_or(1)).unwrap_or(1);
+            if count > 0 {
+                changed
+                    .entry(file.clone())
+                    .or_default()
+                    .push(start..=start + count - 1);
+            }
+        }
+    }
+    changed
+}
+
+pub fn review_prompt(range: &str, diff: &str) -> String {
+    let mut diff = diff.to_string();
+    if diff.len() > MAX_DIFF_CHARS {
+        let mut end = MAX_DIFF_CHARS;
+        while !diff.is_char_boundary(end) {
+            end -= 1;
+        }
+        diff.truncate(end);
+        diff.push_str("\n... (diff truncated)\n");
+    }
+    format!(
+        "Review the changes in {} below. Only comment on changed lines; read files for context if needed, but do not modify anything.\n\n\
+Report real problems only: bugs, security issues, broken error handling, missing tests for new behavior. No style nits.\n\n\
+Reply with a JSON array and nothing else, one object per finding:\n\
+[{{\"file\": \"src/lib.rs\", \"line\": 42, \"severity\": \"error\" | \"warning\" | \"notice\", \"message\": \"...\"}}]\n\
+Use the new-file line numbers. Reply with [] if there is nothing to report.\n\n```diff\n{}\n```",
+        range, diff
+    )
+}
+
+/// Extracts the JSON findings array from a model response, tolerating code fences and prose.
+pub fn parse_findings(response: &str) -> Result<Vec<Finding>> {
+    let (Some(start), Some(end)) = (response.find('['), response.rfind(']')) else {
+        return Err(PicocodeError::Other(
+            "Review response did not contain a JSON array".into(),
+        ));
+    };
+    if end < start {
+        return Err(PicocodeError::Other("Malformed review response".into()));
+    }
+    Ok(serde_json::from_str(&response[start..=end])?)
+}
+
+/// Drops findings outside the changed hunks.
+pub fn on_changed_lines(
+    findings: Vec<Finding>,
+    changed: &HashMap<String, Vec<RangeInclusive<u32>>>,
+) -> Vec<Finding> {
+    findings
+        .into_iter()
+        .filter(|f| {
+            changed
+                .get(&f.file)
+                .is_some_and(|ranges| ranges.iter().any(|r| r.contains(&f.line)))
+        })
+        .collect()
+}
+
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// GitHub Actions workflow commands, rendered as inline PR annotations.
+pub fn to_github(findings: &[Finding]) -> String {
+    findings
+        .iter()
+        .map(|f| {
+            format!(
+                "::{} file={},line={}::{}\n",
+                f.severity.github(),
+                escape_property(&f.file),
+                f.line,
+                escape_data(&f.message)
+            )
+        })
+        .collect()
+}
+
+pub fn to_text(findings: &[Finding]) -> String {
+    findings
+        .iter()
+        .map(|f| format!("{}:{}: {}: {}\n", f.file, f.line, f.severity.github(), f.message))
+        .collect()
+}
+
+/// A minimal SARIF 2.1.0 log.
+pub fn to_sarif(findings: &[Finding]) -> String {
+    let results: Vec<serde_json::Value> = findings
+        .iter()
+        .map(|f| {
+            serde_json::json!({
+                "ruleId": "picocode-review",
+                "level": f.severity.sarif(),
+                "message": { "text": f.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": f.file },
+                        "region": { "startLine": f.line }
+                    }
+                }]
+            })
+        })
+        .collect();
+    let log = serde_json::json!({
+        "version": "2.1.0",
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "picocode",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/jondot/picocode",
+                    "rules": [{ "id": "picocode-review" }]
+                }
+            },
+            "results": results
+        }]
+    });
+    serde_json::to_string_pretty(&log).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "diff --git a/src/lib.rs b/src/lib.rs\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -10,2 +10,4 @@ fn a()\n ctx\n+new\n+new\n ctx\n@@ -40 +42,0 @@\n-gone\n";
+
+    #[test]
+    fn test_changed_lines_and_filter() {
+        let changed = changed_lines(DIFF);
+        assert_eq!(changed["src/lib.rs"], vec![10..=13]);
+
+        let response = "Here you go:\n```json\n[{\"file\": \"src/lib.rs\", \"line\": 11, \"severity\": \"error\", \"message\": \"boom\"},\n {\"file\": \"src/lib.rs\", \"line\": 90, \"severity\": \"warning\", \"message\": \"old code\"}]\n```";
+        let findings = on_changed_lines(parse_findings(response).unwrap(), &changed);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_github_format() {
+        let findings = vec![Finding {
+            file: "src/a,b.rs".into(),
+            line: 3,
+            severity: Severity::Warning,
+            message: "100% wrong\nsecond line".into(),
+        }];
+        assert_eq!(
+            to_github(&findings),
+            "::warning file=src/a%2Cb.rs,line=3::100%25 wrong%0Asecond line\n"
+        );
+        assert!(to_sarif(&findings).contains("\"startLine\": 3"));
+    }
+}
@@ -0,0 +1,98 @@
+//! `picocode roundtable`: one prompt answered by several agents in parallel,
+//! then synthesized by a moderator.
+
+use crate::{PicocodeError, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Participant {
+    pub persona: Option<String>,
+    pub model: Option<String>,
+}
+
+impl Participant {
+    pub fn label(&self) -> String {
+        match (&self.persona, &self.model) {
+            (Some(p), Some(m)) => format!("{} ({})", p, m),
+            (Some(p), None) => p.clone(),
+            (None, Some(m)) => m.clone(),
+            (None, None) => "default".to_string(),
+        }
+    }
+}
+
+fn split(list: Option<&str>) -> Vec<String> {
+    list.map(|l| {
+        l.split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Pairs comma-separated personas and models. Lists of equal length are zipped;
+/// a single entry (or none) on one side is shared by every participant.
+pub fn participants(personas: Option<&str>, models: Option<&str>) -> Result<Vec<Participant>> {
+    let personas = split(personas);
+    let models = split(models);
+    let count = personas.len().max(models.len());
+    let compatible = |n: usize| n <= 1 || n == count;
+    if !compatible(personas.len()) || !compatible(models.len()) {
+        return Err(PicocodeError::Other(format!(
+            "--personas has {} entries but --models has {}; use equal lengths or a single value",
+            personas.len(),
+            models.len()
+        )));
+    }
+    if count < 2 {
+        return Err(PicocodeError::Other(
+            "A roundtable needs at least two personas or models".into(),
+        ));
+    }
+    let pick = |list: &[String], i: usize| match list.len() {
+        0 => None,
+        1 => Some(list[0].clone()),
+        _ => Some(list[i].clone()),
+    };
+    Ok((0..count)
+        .map(|i| Participant {
+            persona: pick(&personas, i),
+            model: pick(&models, i),
+        })
+        .collect())
+}
+
+/// Builds the moderator's prompt from the participants' answers.
+pub fn moderator_prompt(request: &str, answers: &[(String, String)]) -> String {
+    let mut prompt = format!(
+        "You are the moderator of a roundtable. Several experts answered the same request independently. \
+Synthesize their answers into one response: state where they agree, resolve or clearly flag disagreements, \
+keep the strongest concrete recommendations, and list open questions. Attribute points to experts where it helps. \
+Don't use tools unless an answer needs checking.\n\n## Request\n\n{}\n",
+        request
+    );
+    for (label, answer) in answers {
+        prompt.push_str(&format!("\n## Answer from {}\n\n{}\n", label, answer.trim()));
+    }
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_participants() {
+        let p = participants(Some("architect, security"), None).unwrap();
+        assert_eq!(p.len(), 2);
+        assert_eq!(p[1].label(), "security");
+
+        let p = participants(Some("security"), Some("gpt-4o,claude-sonnet-4-6")).unwrap();
+        assert_eq!(p[0].label(), "security (gpt-4o)");
+        assert_eq!(p[1].persona.as_deref(), Some("security"));
+
+        assert!(participants(Some("a,b,c"), Some("x,y")).is_err());
+        assert!(participants(Some("architect"), None).is_err());
+    }
+}
@@ -0,0 +1,57 @@
+//! Heuristic turn classifier for cost-aware model routing (`routing:` in picocode.yaml).
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Route {
+    /// Questions and lookups a cheap model can answer.
+    Simple,
+    /// Anything that may edit code or needs deeper reasoning.
+    Complex,
+}
+
+/// Longer prompts usually carry enough context to deserve the strong model.
+const SIMPLE_MAX_CHARS: usize = 200;
+
+static COMPLEX_WORDS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\b(fix|implement|refactor|add|change|modify|write|create|update|remove|delete|rename|migrate|build|debug|optimi[sz]e|rewrite|edit|generate|port|convert|plan|design|review)\b",
+    )
+    .unwrap()
+});
+
+static FOLLOW_UP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^\s*(yes|yep|ok|okay|sure|go ahead|do it|continue|proceed|please do)\b").unwrap()
+});
+
+/// Decides which model handles a turn. Short questions go to the cheap model;
+/// edits, long prompts, code snippets and follow-ups ("yes, do it") stay on the strong one.
+pub fn classify(input: &str) -> Route {
+    let input = input.trim();
+    let complex = input.chars().count() > SIMPLE_MAX_CHARS
+        || input.contains("```")
+        || COMPLEX_WORDS.is_match(input)
+        || FOLLOW_UP.is_match(input);
+    if complex {
+        Route::Complex
+    } else {
+        Route::Simple
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("what does src/main.rs do?"), Route::Simple);
+        assert_eq!(classify("where is the config loaded"), Route::Simple);
+        assert_eq!(classify("fix the failing test in tools.rs"), Route::Complex);
+        assert_eq!(classify("Refactor the parser"), Route::Complex);
+        assert_eq!(classify("yes, do it"), Route::Complex);
+        assert_eq!(classify("why does this panic?\n```\nlet x = v[3];\n```"), Route::Complex);
+        assert_eq!(classify(&"explain ".repeat(40)), Route::Complex);
+    }
+}
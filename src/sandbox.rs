@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Runs `bash` tool commands inside a throwaway container with the workspace
+/// mounted, so yolo automations cannot touch the rest of the host.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SandboxConfig {
+    /// Container runtime binary (`docker` or `podman`).
+    #[serde(default = "default_runtime")]
+    pub runtime: String,
+    #[serde(default = "default_image")]
+    pub image: String,
+    /// Docker network mode; `none` disables networking.
+    #[serde(default = "default_network")]
+    pub network: String,
+    /// Memory limit, e.g. `2g`.
+    #[serde(default)]
+    pub memory: Option<String>,
+    /// CPU limit, e.g. `1.5`.
+    #[serde(default)]
+    pub cpus: Option<String>,
+    /// Mount the workspace read-only inside the container.
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+fn default_runtime() -> String {
+    "docker".into()
+}
+
+fn default_image() -> String {
+    "ubuntu:24.04".into()
+}
+
+fn default_network() -> String {
+    "none".into()
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            runtime: default_runtime(),
+            image: default_image(),
+            network: default_network(),
+            memory: None,
+            cpus: None,
+            read_only: false,
+        }
+    }
+}
+
+impl SandboxConfig {
+    /// Parses the `--sandbox` flag (`docker`, `docker:<image>`, `podman[:<image>]`),
+    /// layering it over the settings from the config file. `none` disables sandboxing.
+    pub fn from_arg(arg: &str, base: Option<SandboxConfig>) -> crate::Result<Option<Self>> {
+        let (runtime, image) = match arg.split_once(':') {
+            Some((runtime, image)) => (runtime, Some(image)),
+            None => (arg, None),
+        };
+        match runtime {
+            "none" | "off" => Ok(None),
+            "docker" | "podman" => {
+                let mut config = base.unwrap_or_default();
+                config.runtime = runtime.to_string();
+                if let Some(image) = image.filter(|i| !i.is_empty()) {
+                    config.image = image.to_string();
+                }
+                Ok(Some(config))
+            }
+            other => Err(crate::PicocodeError::Other(format!(
+                "Unsupported sandbox: {} (expected docker[:image] or podman[:image])",
+                other
+            ))),
+        }
+    }
+
+    /// Arguments for `<runtime> run ...` executing `cmd` with `workspace` mounted
+    /// at the same path, so paths in commands and tool output stay valid. The
+    /// container is called `name`, so it can be killed if the command is cancelled.
+    pub fn run_args(
+        &self,
+        name: &str,
+        cmd: &str,
+        workspace: &Path,
+        extra_roots: &[PathBuf],
+    ) -> Vec<String> {
+        let ws = workspace.display().to_string();
+        let mut args = vec![
+            "run".to_string(),
+            "--rm".to_string(),
+            "--name".to_string(),
+            name.to_string(),
+            "-i".to_string(),
+            "--network".to_string(),
+            self.network.clone(),
+            "-v".to_string(),
+            format!("{}:{}{}", ws, ws, if self.read_only { ":ro" } else { "" }),
+            "-w".to_string(),
+            ws,
+        ];
+        for root in extra_roots {
+            let root = root.display().to_string();
+            args.extend([
+                "-v".to_string(),
+                format!("{}:{}{}", root, root, if self.read_only { ":ro" } else { "" }),
+            ]);
+        }
+        if let Some(memory) = &self.memory {
+            args.extend(["--memory".to_string(), memory.clone()]);
+        }
+        if let Some(cpus) = &self.cpus {
+            args.extend(["--cpus".to_string(), cpus.clone()]);
+        }
+        if let Some(user) = workspace_owner(workspace) {
+            args.extend(["--user".to_string(), user]);
+        }
+        args.extend([
+            self.image.clone(),
+            "sh".to_string(),
+            "-c".to_string(),
+            cmd.to_string(),
+        ]);
+        args
+    }
+}
+
+/// Runs the container as the workspace owner so created files aren't root-owned.
+#[cfg(unix)]
+fn workspace_owner(workspace: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    let meta = std::fs::metadata(workspace).ok()?;
+    Some(format!("{}:{}", meta.uid(), meta.gid()))
+}
+
+#[cfg(not(unix))]
+fn workspace_owner(_workspace: &Path) -> Option<String> {
+    None
+}
@@ -0,0 +1,171 @@
+//! Cron-style schedules for recipes (`schedule: "0 9 * * 1"`) and the report
+//! files written by `picocode cron`.
+
+use crate::{PicocodeError, Result};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+use std::path::PathBuf;
+
+/// Where `picocode cron` writes one markdown report per run.
+pub const REPORT_DIR: &str = ".picocode/reports";
+
+/// A five-field cron expression (minute hour day-of-month month day-of-week),
+/// supporting `*`, lists, ranges and steps, plus `@hourly`, `@daily`, `@weekly`
+/// and `@monthly`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Option<u64> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)?),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (a.parse().ok()?, b.parse().ok()?)
+        } else {
+            let n: u32 = range.parse().ok()?;
+            (n, if part.contains('/') { max } else { n })
+        };
+        if start < min || end > max || start > end {
+            return None;
+        }
+        for n in (start..=end).step_by(step as usize) {
+            mask |= 1 << n;
+        }
+    }
+    Some(mask)
+}
+
+fn has(mask: u64, n: u32) -> bool {
+    mask & (1 << n) != 0
+}
+
+impl Schedule {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let expr = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let invalid = || PicocodeError::Other(format!("Invalid schedule '{}'", expr));
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(invalid());
+        };
+        let mut weekdays = parse_field(weekday, 0, 7).ok_or_else(invalid)?;
+        // Both 0 and 7 mean Sunday
+        if has(weekdays, 7) {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59).ok_or_else(invalid)?,
+            hours: parse_field(hour, 0, 23).ok_or_else(invalid)?,
+            days: parse_field(day, 1, 31).ok_or_else(invalid)?,
+            months: parse_field(month, 1, 12).ok_or_else(invalid)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        if !has(self.months, date.month()) {
+            return false;
+        }
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+        // As in cron: when both day fields are restricted, either may match
+        if self.any_day || self.any_weekday {
+            day && weekday
+        } else {
+            day || weekday
+        }
+    }
+
+    pub fn matches(&self, time: NaiveDateTime) -> bool {
+        self.day_matches(time.date()) && has(self.hours, time.hour()) && has(self.minutes, time.minute())
+    }
+
+    /// The first matching minute strictly after `after`, searching up to five years ahead.
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = after + Duration::days(5 * 366);
+        while time < limit {
+            if !self.day_matches(time.date()) {
+                time = time.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if !has(self.hours, time.hour()) {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if !has(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+/// Writes the outcome of a scheduled recipe run to `REPORT_DIR`, returning the file path.
+pub fn write_report(recipe: &str, status: &str, body: &str) -> Result<PathBuf> {
+    let now = chrono::Local::now();
+    let dir = PathBuf::from(REPORT_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}-{}.md", recipe, now.format("%Y%m%d-%H%M%S")));
+    let content = format!(
+        "# {}\n\n- Run: {}\n- Status: {}\n\n{}\n",
+        recipe,
+        now.to_rfc3339(),
+        status,
+        body
+    );
+    std::fs::write(&path, content)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_next_after() {
+        // 2026-01-07 is a Wednesday; next Monday 09:00 is the 12th
+        let weekly = Schedule::parse("0 9 * * 1").unwrap();
+        assert_eq!(weekly.next_after(at("2026-01-07 10:30")), Some(at("2026-01-12 09:00")));
+        assert_eq!(weekly.next_after(at("2026-01-12 09:00")), Some(at("2026-01-19 09:00")));
+
+        let every_15 = Schedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(every_15.next_after(at("2026-01-07 10:31")), Some(at("2026-01-07 10:45")));
+
+        let nightly = Schedule::parse("@daily").unwrap();
+        assert_eq!(nightly.next_after(at("2026-12-31 23:59")), Some(at("2027-01-01 00:00")));
+
+        let weekdays = Schedule::parse("30 18 * * 1-5").unwrap();
+        assert!(weekdays.matches(at("2026-01-09 18:30")));
+        assert!(!weekdays.matches(at("2026-01-10 18:30")));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Schedule::parse("0 9 * *").is_err());
+        assert!(Schedule::parse("60 * * * *").is_err());
+        assert!(Schedule::parse("*/0 * * * *").is_err());
+        assert!(Schedule::parse("0 9 * * mon").is_err());
+    }
+}
@@ -0,0 +1,474 @@
+use crate::stats::{ToolStat, TurnStats};
+use crate::storage::{FileStorage, Storage};
+use crate::{PicocodeError, Result};
+use chrono::{DateTime, Utc};
+use rig::message::{AssistantContent, Message, ToolResultContent, UserContent};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Version of the session JSON schema (see design/SESSIONS.md).
+pub const SESSION_SCHEMA_VERSION: u32 = 1;
+
+/// A persisted conversation: metadata plus the full message history in rig's
+/// message format, so it can be resumed, exported, and imported elsewhere.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub version: u32,
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub cwd: String,
+    pub provider: String,
+    pub model: String,
+    #[serde(default)]
+    pub persona: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub usage: TokenUsage,
+    /// Per-tool call counts, failures and durations.
+    #[serde(default)]
+    pub tool_stats: BTreeMap<String, ToolStat>,
+    /// Turn count and summed turn timings.
+    #[serde(default)]
+    pub turn_stats: TurnStats,
+    #[serde(default)]
+    pub messages: Vec<Message>,
+}
+
+/// Token totals reported by the provider over the whole session.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn add(&mut self, usage: &rig::completion::Usage) {
+        self.input_tokens += usage.input_tokens;
+        self.output_tokens += usage.output_tokens;
+        self.total_tokens += usage.total_tokens;
+    }
+}
+
+pub fn new_session_id() -> String {
+    format!(
+        "{}-{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        std::process::id()
+    )
+}
+
+/// Preamble for the one-off completion that names a session after its first exchange.
+pub const TITLE_PROMPT: &str = "You name coding sessions. Reply with a title of about 5 words describing the user's task. Reply with the title only: no quotes, no punctuation at the end.";
+
+/// Normalizes a model-generated title: first non-empty line, without quotes,
+/// markdown or a `Title:` prefix, capped at 8 words.
+pub fn clean_title(raw: &str) -> Option<String> {
+    let line = raw.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let line = line.trim_start_matches('#').trim();
+    let line = line
+        .strip_prefix("Title:")
+        .or_else(|| line.strip_prefix("title:"))
+        .unwrap_or(line);
+    let trimmed = line.trim_matches(|c: char| c.is_whitespace() || "\"'`*.".contains(c));
+    let title = trimmed
+        .split_whitespace()
+        .take(8)
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+/// A short title taken from the first line of the first prompt.
+pub fn title_from_prompt(prompt: &str) -> String {
+    const MAX_CHARS: usize = 60;
+    let line = prompt
+        .lines()
+        .find(|l| !l.trim().is_empty())
+        .unwrap_or("")
+        .trim();
+    if line.chars().count() > MAX_CHARS {
+        let truncated: String = line.chars().take(MAX_CHARS - 1).collect();
+        format!("{}…", truncated.trim_end())
+    } else {
+        line.to_string()
+    }
+}
+
+/// Parses ages like `30d`, `12h`, `2w` or `45m`.
+pub fn parse_age(age: &str) -> Result<chrono::Duration> {
+    let invalid =
+        || PicocodeError::Other(format!("Invalid age '{}', expected e.g. 30d, 12h, 2w", age));
+    let age = age.trim();
+    let split = age
+        .char_indices()
+        .last()
+        .map(|(i, _)| i)
+        .ok_or_else(invalid)?;
+    let (number, unit) = age.split_at(split);
+    let n: i64 = number.parse().map_err(|_| invalid())?;
+    match unit {
+        "m" => Ok(chrono::Duration::minutes(n)),
+        "h" => Ok(chrono::Duration::hours(n)),
+        "d" => Ok(chrono::Duration::days(n)),
+        "w" => Ok(chrono::Duration::weeks(n)),
+        _ => Err(invalid()),
+    }
+}
+
+fn validate_id(id: &str) -> Result<()> {
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && !id.starts_with('.');
+    if valid {
+        Ok(())
+    } else {
+        Err(PicocodeError::Other(format!("Invalid session id: {}", id)))
+    }
+}
+
+impl Session {
+    pub fn new(id: String, provider: &str, model: &str, persona: Option<&str>) -> Self {
+        let now = Utc::now();
+        Self {
+            version: SESSION_SCHEMA_VERSION,
+            id,
+            created_at: now,
+            updated_at: now,
+            cwd: std::env::current_dir()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            persona: persona.map(str::to_string),
+            title: None,
+            usage: TokenUsage::default(),
+            tool_stats: BTreeMap::new(),
+            turn_stats: TurnStats::default(),
+            messages: Vec::new(),
+        }
+    }
+
+    /// Parses a session document, rejecting unknown schema versions.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let session: Session = serde_json::from_str(json)?;
+        if session.version > SESSION_SCHEMA_VERSION {
+            return Err(PicocodeError::Other(format!(
+                "Session schema version {} is newer than supported version {}",
+                session.version, SESSION_SCHEMA_VERSION
+            )));
+        }
+        validate_id(&session.id)?;
+        Ok(session)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Renders the transcript as markdown, suitable for attaching to bug reports.
+    pub fn to_markdown(&self) -> String {
+        let mut md = format!(
+            "# {}\n\n- Session: `{}`\n- Created: {}\n- Provider: {} ({})\n- Directory: `{}`\n",
+            self.title.as_deref().unwrap_or("picocode session"),
+            self.id,
+            self.created_at.to_rfc3339(),
+            self.provider,
+            self.model,
+            self.cwd,
+        );
+        if let Some(persona) = &self.persona {
+            md.push_str(&format!("- Persona: {}\n", persona));
+        }
+        if self.usage.total_tokens > 0 {
+            md.push_str(&format!("- Tokens: {}\n", self.usage.total_tokens));
+        }
+        if let Some(cost) = crate::models::cost(&self.provider, &self.model, &self.usage) {
+            md.push_str(&format!("- Cost: ~{}\n", crate::models::format_cost(cost)));
+        }
+
+        for message in &self.messages {
+            match message {
+                Message::User { content } => {
+                    for item in content.iter() {
+                        match item {
+                            UserContent::Text(text) => {
+                                md.push_str(&format!("\n## User\n\n{}\n", text.text));
+                            }
+                            UserContent::ToolResult(result) => {
+                                let output = result
+                                    .content
+                                    .iter()
+                                    .filter_map(|c| match c {
+                                        ToolResultContent::Text(text) => Some(text.text.as_str()),
+                                        _ => None,
+                                    })
+                                    .collect::<Vec<_>>()
+                                    .join("\n");
+                                md.push_str(&format!(
+                                    "\n**Tool result**\n\n```\n{}\n```\n",
+                                    output
+                                ));
+                            }
+                            _ => md.push_str("\n_(non-text content omitted)_\n"),
+                        }
+                    }
+                }
+                Message::Assistant { content, .. } => {
+                    for item in content.iter() {
+                        match item {
+                            AssistantContent::Text(text) => {
+                                md.push_str(&format!("\n## Assistant\n\n{}\n", text.text));
+                            }
+                            AssistantContent::ToolCall(call) => {
+                                md.push_str(&format!(
+                                    "\n**Tool call** `{}`\n\n```json\n{}\n```\n",
+                                    call.function.name,
+                                    serde_json::to_string_pretty(&call.function.arguments)
+                                        .unwrap_or_default()
+                                ));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        md
+    }
+}
+
+/// Sessions stored as `<id>.json` entries of a [`Storage`], by default files
+/// under `~/.picocode/sessions`.
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    storage: Arc<dyn Storage>,
+    /// Kept up to date as sessions are saved and deleted.
+    #[cfg(feature = "sqlite")]
+    index: Option<Arc<crate::history::SearchIndex>>,
+}
+
+impl SessionStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self::with_storage(Arc::new(FileStorage::new(dir)))
+    }
+
+    pub fn with_storage(storage: Arc<dyn Storage>) -> Self {
+        Self {
+            storage,
+            #[cfg(feature = "sqlite")]
+            index: None,
+        }
+    }
+
+    /// Maintains the search index in the storage database, when built with
+    /// the `sqlite` feature. Without it, searches read every session.
+    pub fn indexed(self) -> Self {
+        #[cfg(feature = "sqlite")]
+        {
+            let index = crate::storage::database_path()
+                .ok_or_else(|| PicocodeError::Other("Could not determine home directory".into()))
+                .and_then(|path| crate::history::SearchIndex::open(&path));
+            match index {
+                Ok(index) => {
+                    return Self {
+                        index: Some(Arc::new(index)),
+                        ..self
+                    }
+                }
+                Err(e) => tracing::warn!(target: "picocode", "{}", e),
+            }
+        }
+        self
+    }
+
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::home_dir().map(|h| h.join(".picocode").join("sessions"))
+    }
+
+    /// The sessions of the configured storage backend, by default files
+    /// under `~/.picocode/sessions`.
+    pub fn open_default() -> Result<Self> {
+        let store = match crate::storage::sessions() {
+            Some(storage) => Self::with_storage(storage),
+            None => Self::default_dir()
+                .map(Self::new)
+                .ok_or_else(|| PicocodeError::Other("Could not determine home directory".into()))?,
+        };
+        Ok(store.indexed())
+    }
+
+    /// Where the sessions are kept, for messages.
+    pub fn location(&self) -> String {
+        self.storage.location("")
+    }
+
+    fn key(&self, id: &str) -> Result<String> {
+        validate_id(id)?;
+        Ok(format!("{}.json", id))
+    }
+
+    pub fn save(&self, session: &Session) -> Result<()> {
+        self.storage
+            .write(&self.key(&session.id)?, &session.to_json()?)?;
+        #[cfg(feature = "sqlite")]
+        if let Some(index) = &self.index {
+            index.update(session)?;
+        }
+        Ok(())
+    }
+
+    pub fn load(&self, id: &str) -> Result<Session> {
+        match self.storage.read(&self.key(id)?)? {
+            Some(json) => Session::from_json(&json),
+            None => Err(PicocodeError::Other(format!("Session not found: {}", id))),
+        }
+    }
+
+    pub fn exists(&self, id: &str) -> bool {
+        self.key(id)
+            .and_then(|key| self.storage.read(&key))
+            .is_ok_and(|json| json.is_some())
+    }
+
+    pub fn delete(&self, id: &str) -> Result<()> {
+        if !self.storage.delete(&self.key(id)?)? {
+            return Err(PicocodeError::Other(format!("Session not found: {}", id)));
+        }
+        #[cfg(feature = "sqlite")]
+        if let Some(index) = &self.index {
+            index.remove(id)?;
+        }
+        Ok(())
+    }
+
+    /// Sessions matching every word of `query`: the most relevant first with
+    /// the search index, else the most recent first.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<crate::history::Hit>> {
+        #[cfg(feature = "sqlite")]
+        if let Some(index) = &self.index {
+            self.sync_index(index)?;
+            return index.search(query, limit);
+        }
+        Ok(crate::history::scan(&self.list()?, query, limit))
+    }
+
+    /// Indexes sessions saved without the index, e.g. before it existed or on
+    /// another machine, and drops deleted ones.
+    #[cfg(feature = "sqlite")]
+    fn sync_index(&self, index: &crate::history::SearchIndex) -> Result<()> {
+        let indexed = index.ids()?;
+        let stored: Vec<String> = self
+            .storage
+            .list("")?
+            .into_iter()
+            .filter_map(|key| key.strip_suffix(".json").map(str::to_string))
+            .collect();
+        for id in stored.iter().filter(|id| !indexed.contains(*id)) {
+            if let Ok(session) = self.load(id) {
+                index.update(&session)?;
+            }
+        }
+        for id in indexed.iter().filter(|id| !stored.contains(id)) {
+            index.remove(id)?;
+        }
+        Ok(())
+    }
+
+    /// All readable sessions, most recently updated first. Unreadable entries are skipped.
+    pub fn list(&self) -> Result<Vec<Session>> {
+        let mut sessions: Vec<Session> = self
+            .storage
+            .list("")?
+            .into_iter()
+            .filter(|key| key.ends_with(".json"))
+            .filter_map(|key| self.storage.read(&key).ok().flatten())
+            .filter_map(|json| Session::from_json(&json).ok())
+            .collect();
+        sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        Ok(sessions)
+    }
+
+    /// Deletes sessions not updated within `max_age`, returning their ids.
+    pub fn prune(&self, max_age: chrono::Duration) -> Result<Vec<String>> {
+        let cutoff = Utc::now() - max_age;
+        let mut removed = Vec::new();
+        for session in self.list()? {
+            if session.updated_at < cutoff {
+                self.delete(&session.id)?;
+                removed.push(session.id);
+            }
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_roundtrip_and_markdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SessionStore::new(dir.path());
+        let mut session = Session::new("20260101-120000-42".into(), "anthropic", "claude", None);
+        session.messages = vec![Message::user("hello"), Message::assistant("hi there")];
+        store.save(&session).unwrap();
+
+        let loaded = store.load(&session.id).unwrap();
+        assert_eq!(loaded.messages.len(), 2);
+        let md = loaded.to_markdown();
+        assert!(md.contains("## User\n\nhello"));
+        assert!(md.contains("## Assistant\n\nhi there"));
+        assert_eq!(store.search("HI there", 5).unwrap()[0].id, session.id);
+        assert!(store.search("goodbye", 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_list_and_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SessionStore::new(dir.path());
+        let mut old = Session::new("old".into(), "openai", "gpt-4o", None);
+        old.updated_at = Utc::now() - chrono::Duration::days(40);
+        store.save(&old).unwrap();
+        store
+            .save(&Session::new("new".into(), "openai", "gpt-4o", None))
+            .unwrap();
+
+        let ids: Vec<_> = store.list().unwrap().into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec!["new", "old"]);
+        assert_eq!(store.prune(parse_age("30d").unwrap()).unwrap(), vec!["old"]);
+        assert!(!store.exists("old"));
+        assert!(store.exists("new"));
+    }
+
+    #[test]
+    fn test_parse_age_and_title() {
+        assert_eq!(parse_age("2w").unwrap(), chrono::Duration::days(14));
+        assert!(parse_age("30").is_err());
+        assert!(parse_age("").is_err());
+        assert_eq!(
+            title_from_prompt("\n  fix the build \nmore"),
+            "fix the build"
+        );
+        assert_eq!(title_from_prompt(&"x".repeat(100)).chars().count(), 60);
+        assert_eq!(
+            clean_title("Title: \"Fix flaky CI test runner.\"\n").as_deref(),
+            Some("Fix flaky CI test runner")
+        );
+        assert_eq!(clean_title("  \n**\n"), None);
+    }
+
+    #[test]
+    fn test_rejects_path_like_ids() {
+        let store = SessionStore::new("/tmp/sessions");
+        assert!(store.load("../etc/passwd").is_err());
+        assert!(store.load(".hidden").is_err());
+    }
+}
@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Aggregated calls of one tool.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct ToolStat {
+    pub calls: u64,
+    pub failures: u64,
+    pub total_ms: u64,
+}
+
+impl ToolStat {
+    pub fn avg_ms(&self) -> u64 {
+        self.total_ms.checked_div(self.calls).unwrap_or(0)
+    }
+
+    pub fn failure_rate(&self) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.calls as f64
+        }
+    }
+}
+
+/// Per-tool call counts, durations and failures for a session, fed by the prompt hook.
+#[derive(Default)]
+pub struct ToolStats {
+    stats: Mutex<BTreeMap<String, ToolStat>>,
+    // Start times of in-flight calls, matched to results in call order per tool
+    started: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl ToolStats {
+    pub fn new(initial: BTreeMap<String, ToolStat>) -> Self {
+        Self {
+            stats: Mutex::new(initial),
+            started: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn start(&self, tool: &str) {
+        self.started
+            .lock()
+            .unwrap()
+            .entry(tool.to_string())
+            .or_default()
+            .push_back(Instant::now());
+    }
+
+    /// Records a finished call and returns how long it took.
+    pub fn finish(&self, tool: &str, result: &str) -> Duration {
+        let elapsed = self
+            .started
+            .lock()
+            .unwrap()
+            .get_mut(tool)
+            .and_then(|q| q.pop_front())
+            .map(|start| start.elapsed())
+            .unwrap_or_default();
+        let mut stats = self.stats.lock().unwrap();
+        let stat = stats.entry(tool.to_string()).or_default();
+        stat.calls += 1;
+        stat.total_ms += elapsed.as_millis() as u64;
+        if is_failure(result) {
+            stat.failures += 1;
+        }
+        elapsed
+    }
+
+    /// Time spent in all tools so far.
+    pub fn total_time(&self) -> Duration {
+        Duration::from_millis(self.stats.lock().unwrap().values().map(|s| s.total_ms).sum())
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, ToolStat> {
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+/// Wall time of one turn, and the part of it spent running tools.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TurnTiming {
+    pub total: Duration,
+    pub tools: Duration,
+    /// Time until the first response arrived. Responses aren't streamed, so
+    /// this is when the first tokens reach picocode.
+    pub first_token: Option<Duration>,
+}
+
+impl TurnTiming {
+    /// Time spent waiting on the provider.
+    pub fn model(&self) -> Duration {
+        self.total.saturating_sub(self.tools)
+    }
+}
+
+/// Turn timings summed over a session, for averages in the summary.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct TurnStats {
+    pub turns: u64,
+    pub total_ms: u64,
+    pub model_ms: u64,
+    pub tools_ms: u64,
+    pub first_token_ms: u64,
+}
+
+impl TurnStats {
+    pub fn record(&mut self, timing: &TurnTiming) {
+        self.turns += 1;
+        self.total_ms += timing.total.as_millis() as u64;
+        self.model_ms += timing.model().as_millis() as u64;
+        self.tools_ms += timing.tools.as_millis() as u64;
+        self.first_token_ms += timing.first_token.unwrap_or_default().as_millis() as u64;
+    }
+
+    /// The average of a `*_ms` total over the turns.
+    pub fn avg(&self, total_ms: u64) -> Duration {
+        Duration::from_millis(total_ms.checked_div(self.turns).unwrap_or(0))
+    }
+}
+
+/// `0.4s`, `42.3s`, `2m 05s`.
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs_f64();
+    if secs < 60.0 {
+        format!("{:.1}s", secs)
+    } else {
+        format!("{}m {:02}s", d.as_secs() / 60, d.as_secs() % 60)
+    }
+}
+
+/// Tool errors reach the hook as rendered error strings rather than a flag.
+pub(crate) fn is_failure(result: &str) -> bool {
+    result.contains("ToolCallError") || result.starts_with("Error")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_stats() {
+        let stats = ToolStats::default();
+        stats.start("bash");
+        stats.start("bash");
+        stats.finish("bash", "ok");
+        stats.finish("bash", "ToolCallError: Action cancelled by user");
+        stats.start("read_file");
+        stats.finish("read_file", "   1| fn main() {}");
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot["bash"].calls, 2);
+        assert_eq!(snapshot["bash"].failures, 1);
+        assert_eq!(snapshot["bash"].failure_rate(), 0.5);
+        assert_eq!(snapshot["read_file"].failures, 0);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(42_340)), "42.3s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m 05s");
+        let timing = TurnTiming {
+            total: Duration::from_secs(3),
+            tools: Duration::from_secs(5),
+            first_token: None,
+        };
+        assert_eq!(timing.model(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_turn_stats() {
+        let mut stats = TurnStats::default();
+        assert_eq!(stats.avg(stats.total_ms), Duration::ZERO);
+        stats.record(&TurnTiming {
+            total: Duration::from_secs(4),
+            tools: Duration::from_secs(1),
+            first_token: Some(Duration::from_millis(800)),
+        });
+        stats.record(&TurnTiming {
+            total: Duration::from_secs(2),
+            tools: Duration::ZERO,
+            first_token: Some(Duration::from_millis(1200)),
+        });
+        assert_eq!(stats.turns, 2);
+        assert_eq!(stats.avg(stats.total_ms), Duration::from_secs(3));
+        assert_eq!(stats.avg(stats.model_ms), Duration::from_millis(2500));
+        assert_eq!(stats.avg(stats.first_token_ms), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,533 @@
+//! Where picocode keeps what outlives a run: saved sessions, project memory
+//! and the audit log. They are files by default. With `storage.backend:
+//! sqlite` or `redis` they go to a database instead, shared by the projects
+//! of a machine or by every machine using the server, e.g. when picocode runs
+//! in throwaway containers. Keys are paths like `.picocode/memory.md`.
+
+use crate::config::{StorageBackend, StorageConfig};
+use crate::{PicocodeError, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+/// Text values by key. Implementations must be safe to share between the
+/// agent and its tools.
+pub trait Storage: Send + Sync + std::fmt::Debug {
+    /// The value of `key`, `None` when it was never written.
+    fn read(&self, key: &str) -> Result<Option<String>>;
+    fn write(&self, key: &str, value: &str) -> Result<()>;
+    /// Adds `value` to the end of `key`, creating it if needed.
+    fn append(&self, key: &str, value: &str) -> Result<()>;
+    /// Removes `key`, returning whether it existed.
+    fn delete(&self, key: &str) -> Result<bool>;
+    /// Keys directly under `dir` (`""` for the top level), sorted.
+    fn list(&self, dir: &str) -> Result<Vec<String>>;
+    /// Where `key` is kept, for messages.
+    fn location(&self, key: &str) -> String;
+    /// The file holding `key`, for backends that keep files, so it can be
+    /// opened in an editor.
+    fn local_path(&self, _key: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// Files under `root`. An empty root is the current directory, and absolute
+/// keys are used as they are.
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    root: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        match (self.root.as_os_str().is_empty(), key.is_empty()) {
+            (true, true) => PathBuf::from("."),
+            (false, true) => self.root.clone(),
+            _ => self.root.join(key),
+        }
+    }
+
+    fn create_parent(path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(())
+    }
+}
+
+impl Storage for FileStorage {
+    fn read(&self, key: &str) -> Result<Option<String>> {
+        match std::fs::read_to_string(self.path(key)) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write(&self, key: &str, value: &str) -> Result<()> {
+        let path = self.path(key);
+        Self::create_parent(&path)?;
+        std::fs::write(path, value)?;
+        Ok(())
+    }
+
+    fn append(&self, key: &str, value: &str) -> Result<()> {
+        let path = self.path(key);
+        Self::create_parent(&path)?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        file.write_all(value.as_bytes())?;
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<bool> {
+        match std::fs::remove_file(self.path(key)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn list(&self, dir: &str) -> Result<Vec<String>> {
+        let entries = match std::fs::read_dir(self.path(dir)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut keys: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|ft| ft.is_file()))
+            .map(|entry| join(dir, &entry.file_name().to_string_lossy()))
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn location(&self, key: &str) -> String {
+        self.path(key).display().to_string()
+    }
+
+    fn local_path(&self, key: &str) -> Option<PathBuf> {
+        Some(self.path(key))
+    }
+}
+
+fn join(dir: &str, key: &str) -> String {
+    let key = key.trim_start_matches('/');
+    match dir.trim_end_matches('/') {
+        "" => key.to_string(),
+        dir => format!("{}/{}", dir, key),
+    }
+}
+
+/// Keys directly under `dir` among `keys`.
+#[cfg(any(feature = "sqlite", feature = "redis"))]
+fn children(keys: impl IntoIterator<Item = String>, dir: &str) -> Vec<String> {
+    let prefix = join(dir, "");
+    let mut children: Vec<String> = keys
+        .into_iter()
+        .filter(|key| {
+            key.strip_prefix(&prefix)
+                .is_some_and(|rest| !rest.is_empty() && !rest.contains('/'))
+        })
+        .collect();
+    children.sort();
+    children
+}
+
+/// The keys of `inner` under `prefix`, e.g. one project's part of a shared
+/// database.
+#[derive(Debug, Clone)]
+pub struct Prefixed {
+    inner: Arc<dyn Storage>,
+    prefix: String,
+}
+
+impl Prefixed {
+    pub fn new(inner: Arc<dyn Storage>, prefix: impl Into<String>) -> Self {
+        Self {
+            inner,
+            prefix: prefix.into(),
+        }
+    }
+
+    fn key(&self, key: &str) -> String {
+        join(&self.prefix, key)
+    }
+}
+
+impl Storage for Prefixed {
+    fn read(&self, key: &str) -> Result<Option<String>> {
+        self.inner.read(&self.key(key))
+    }
+
+    fn write(&self, key: &str, value: &str) -> Result<()> {
+        self.inner.write(&self.key(key), value)
+    }
+
+    fn append(&self, key: &str, value: &str) -> Result<()> {
+        self.inner.append(&self.key(key), value)
+    }
+
+    fn delete(&self, key: &str) -> Result<bool> {
+        self.inner.delete(&self.key(key))
+    }
+
+    fn list(&self, dir: &str) -> Result<Vec<String>> {
+        let prefix = self.key("");
+        Ok(self
+            .inner
+            .list(&self.key(dir))?
+            .into_iter()
+            .filter_map(|key| key.strip_prefix(&prefix).map(str::to_string))
+            .collect())
+    }
+
+    fn location(&self, key: &str) -> String {
+        self.inner.location(&self.key(key))
+    }
+
+    fn local_path(&self, key: &str) -> Option<PathBuf> {
+        self.inner.local_path(&self.key(key))
+    }
+}
+
+/// One table of keys and values in a SQLite database.
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+pub struct SqliteStorage {
+    path: PathBuf,
+    connection: std::sync::Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite_error(e: rusqlite::Error) -> PicocodeError {
+    PicocodeError::Other(format!("SQLite storage: {}", e))
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStorage {
+    pub fn open(path: &Path) -> Result<Self> {
+        FileStorage::create_parent(path)?;
+        let connection = rusqlite::Connection::open(path).map_err(sqlite_error)?;
+        // Several picocode processes may share the database
+        connection
+            .execute_batch(
+                "PRAGMA journal_mode = WAL;
+                 PRAGMA busy_timeout = 5000;
+                 CREATE TABLE IF NOT EXISTS entries (key TEXT PRIMARY KEY, value TEXT NOT NULL);",
+            )
+            .map_err(sqlite_error)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            connection: std::sync::Mutex::new(connection),
+        })
+    }
+
+    fn execute(&self, sql: &str, params: impl rusqlite::Params) -> Result<usize> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute(sql, params)
+            .map_err(sqlite_error)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl Storage for SqliteStorage {
+    fn read(&self, key: &str) -> Result<Option<String>> {
+        use rusqlite::OptionalExtension;
+        self.connection
+            .lock()
+            .unwrap()
+            .query_row("SELECT value FROM entries WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(sqlite_error)
+    }
+
+    fn write(&self, key: &str, value: &str) -> Result<()> {
+        self.execute(
+            "INSERT INTO entries (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            [key, value],
+        )?;
+        Ok(())
+    }
+
+    fn append(&self, key: &str, value: &str) -> Result<()> {
+        self.execute(
+            "INSERT INTO entries (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = entries.value || excluded.value",
+            [key, value],
+        )?;
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<bool> {
+        Ok(self.execute("DELETE FROM entries WHERE key = ?1", [key])? > 0)
+    }
+
+    fn list(&self, dir: &str) -> Result<Vec<String>> {
+        let pattern = format!(
+            "{}%",
+            join(dir, "")
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare("SELECT key FROM entries WHERE key LIKE ?1 ESCAPE '\\'")
+            .map_err(sqlite_error)?;
+        let keys = statement
+            .query_map([pattern], |row| row.get(0))
+            .map_err(sqlite_error)?
+            .collect::<rusqlite::Result<Vec<String>>>()
+            .map_err(sqlite_error)?;
+        Ok(children(keys, dir))
+    }
+
+    fn location(&self, key: &str) -> String {
+        format!("{}:{}", self.path.display(), key)
+    }
+}
+
+/// Keys prefixed `picocode:` on a Redis server.
+#[cfg(feature = "redis")]
+#[derive(Debug)]
+pub struct RedisStorage {
+    client: redis::Client,
+}
+
+#[cfg(feature = "redis")]
+const REDIS_NAMESPACE: &str = "picocode:";
+
+#[cfg(feature = "redis")]
+fn redis_error(e: redis::RedisError) -> PicocodeError {
+    PicocodeError::Other(format!("Redis storage: {}", e))
+}
+
+#[cfg(feature = "redis")]
+impl RedisStorage {
+    pub fn open(url: &str) -> Result<Self> {
+        let client = redis::Client::open(url).map_err(redis_error)?;
+        Ok(Self { client })
+    }
+
+    fn connection(&self) -> Result<redis::Connection> {
+        self.client.get_connection().map_err(redis_error)
+    }
+}
+
+#[cfg(feature = "redis")]
+impl Storage for RedisStorage {
+    fn read(&self, key: &str) -> Result<Option<String>> {
+        use redis::Commands;
+        self.connection()?
+            .get(format!("{}{}", REDIS_NAMESPACE, key))
+            .map_err(redis_error)
+    }
+
+    fn write(&self, key: &str, value: &str) -> Result<()> {
+        use redis::Commands;
+        self.connection()?
+            .set(format!("{}{}", REDIS_NAMESPACE, key), value)
+            .map_err(redis_error)
+    }
+
+    fn append(&self, key: &str, value: &str) -> Result<()> {
+        use redis::Commands;
+        self.connection()?
+            .append(format!("{}{}", REDIS_NAMESPACE, key), value)
+            .map_err(redis_error)
+    }
+
+    fn delete(&self, key: &str) -> Result<bool> {
+        use redis::Commands;
+        let removed: usize = self
+            .connection()?
+            .del(format!("{}{}", REDIS_NAMESPACE, key))
+            .map_err(redis_error)?;
+        Ok(removed > 0)
+    }
+
+    fn list(&self, dir: &str) -> Result<Vec<String>> {
+        use redis::Commands;
+        let escaped: String = join(dir, "")
+            .chars()
+            .flat_map(|c| match c {
+                '*' | '?' | '[' | ']' | '\\' => vec!['\\', c],
+                c => vec![c],
+            })
+            .collect();
+        let mut connection = self.connection()?;
+        let keys: Vec<String> = connection
+            .scan_match::<_, String>(format!("{}{}*", REDIS_NAMESPACE, escaped))
+            .map_err(redis_error)?
+            .filter_map(|key| key.strip_prefix(REDIS_NAMESPACE).map(str::to_string))
+            .collect();
+        Ok(children(keys, dir))
+    }
+
+    fn location(&self, key: &str) -> String {
+        format!("Redis key {}{}", REDIS_NAMESPACE, key)
+    }
+}
+
+/// The configured database, when `storage.backend` isn't `file`.
+static SHARED: OnceLock<Arc<dyn Storage>> = OnceLock::new();
+/// The current project's part of it.
+static PROJECT: OnceLock<Arc<dyn Storage>> = OnceLock::new();
+/// `storage.path`, when set.
+static DATABASE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Opens the backend of `config` for the rest of the run. Fails when it
+/// can't be reached or picocode was built without it.
+pub fn configure(config: &StorageConfig) -> Result<()> {
+    if let Some(path) = &config.path {
+        let _ = DATABASE.set(PathBuf::from(path));
+    }
+    let shared = match config.backend {
+        StorageBackend::File => return Ok(()),
+        StorageBackend::Sqlite => open_sqlite()?,
+        StorageBackend::Redis => open_redis(config)?,
+    };
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let key = project_key(crate::tools::forge::origin_url().as_deref(), &cwd);
+    let _ = PROJECT.set(Arc::new(Prefixed::new(
+        shared.clone(),
+        join("projects", &key),
+    )));
+    let _ = SHARED.set(shared);
+    Ok(())
+}
+
+/// The SQLite database: `storage.path`, by default `~/.picocode/picocode.db`.
+/// Also holds the session search index.
+pub fn database_path() -> Option<PathBuf> {
+    DATABASE
+        .get()
+        .cloned()
+        .or_else(|| dirs::home_dir().map(|home| home.join(".picocode").join("picocode.db")))
+}
+
+#[cfg(feature = "sqlite")]
+fn open_sqlite() -> Result<Arc<dyn Storage>> {
+    let path = database_path()
+        .ok_or_else(|| PicocodeError::Other("Could not determine home directory".into()))?;
+    Ok(Arc::new(SqliteStorage::open(&path)?))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_sqlite() -> Result<Arc<dyn Storage>> {
+    Err(PicocodeError::Other(
+        "storage.backend sqlite needs picocode built with `--features sqlite`".into(),
+    ))
+}
+
+#[cfg(feature = "redis")]
+fn open_redis(config: &StorageConfig) -> Result<Arc<dyn Storage>> {
+    let url = config
+        .url
+        .clone()
+        .or_else(|| std::env::var("REDIS_URL").ok())
+        .ok_or_else(|| {
+            PicocodeError::Other("storage.backend redis needs storage.url or REDIS_URL".into())
+        })?;
+    Ok(Arc::new(RedisStorage::open(&url)?))
+}
+
+#[cfg(not(feature = "redis"))]
+fn open_redis(_config: &StorageConfig) -> Result<Arc<dyn Storage>> {
+    Err(PicocodeError::Other(
+        "storage.backend redis needs picocode built with `--features redis`".into(),
+    ))
+}
+
+/// Storage of the current project: its directory, or its part of the
+/// configured database.
+pub fn project() -> Arc<dyn Storage> {
+    PROJECT
+        .get()
+        .cloned()
+        .unwrap_or_else(|| Arc::new(FileStorage::new("")))
+}
+
+/// Saved sessions in the configured database, `None` with the `file` backend.
+pub fn sessions() -> Option<Arc<dyn Storage>> {
+    SHARED
+        .get()
+        .map(|shared| Arc::new(Prefixed::new(shared.clone(), "sessions")) as Arc<dyn Storage>)
+}
+
+/// Names a project the same on every machine: its `origin` remote without
+/// scheme, user and `.git`, or else its directory.
+fn project_key(remote: Option<&str>, cwd: &Path) -> String {
+    let Some(remote) = remote.filter(|r| !r.is_empty()) else {
+        return cwd
+            .display()
+            .to_string()
+            .trim_start_matches('/')
+            .to_string();
+    };
+    let rest = remote.split_once("://").map_or(remote, |(_, rest)| rest);
+    let rest = rest.rsplit_once('@').map_or(rest, |(_, host)| host);
+    rest.trim_end_matches('/')
+        .trim_end_matches(".git")
+        .replacen(':', "/", 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn Storage> = Arc::new(FileStorage::new(dir.path()));
+        assert_eq!(storage.read("a.json").unwrap(), None);
+        storage.write("a.json", "{}").unwrap();
+        storage.append(".picocode/audit.jsonl", "1\n").unwrap();
+        storage.append(".picocode/audit.jsonl", "2\n").unwrap();
+        assert_eq!(
+            storage.read(".picocode/audit.jsonl").unwrap().as_deref(),
+            Some("1\n2\n")
+        );
+        assert_eq!(storage.list("").unwrap(), ["a.json"]);
+        assert_eq!(
+            storage.list(".picocode").unwrap(),
+            [".picocode/audit.jsonl"]
+        );
+        assert!(storage.delete("a.json").unwrap());
+        assert!(!storage.delete("a.json").unwrap());
+
+        let project = Prefixed::new(storage.clone(), "projects/github.com/o/r");
+        project.write(".picocode/memory.md", "- fact\n").unwrap();
+        assert_eq!(project.list(".picocode").unwrap(), [".picocode/memory.md"]);
+        assert!(dir
+            .path()
+            .join("projects/github.com/o/r/.picocode/memory.md")
+            .exists());
+    }
+
+    #[test]
+    fn test_project_key() {
+        let cwd = Path::new("/home/me/app");
+        for remote in [
+            "git@github.com:o/r.git",
+            "https://github.com/o/r",
+            "ssh://git@github.com/o/r.git/",
+        ] {
+            assert_eq!(project_key(Some(remote), cwd), "github.com/o/r");
+        }
+        assert_eq!(project_key(None, cwd), "home/me/app");
+    }
+}
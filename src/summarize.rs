@@ -0,0 +1,281 @@
+//! Condenses large tool results before they enter the history
+//! (`tool_summaries:` in picocode.yaml). The full result is kept on disk, in
+//! a private directory removed when the session ends, and the agent can read
+//! it back with the `fetch_full_result` tool.
+
+use crate::tools::ToolError;
+use crate::{PicocodeError, Result};
+use async_trait::async_trait;
+use regex::Regex;
+use rig::agent::Agent;
+use rig::completion::{CompletionModel, Prompt, ToolDefinition};
+use rig::tool::Tool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock};
+
+pub const SUMMARY_PROMPT: &str = "You condense the output of a coding agent's tool call so the agent can keep working without reading all of it. Keep every error, warning, failing test and file:line reference verbatim, along with counts, totals and anything else the agent needs to decide its next step. Drop repetition and routine noise. Reply with the condensed output only.";
+
+/// Lines always kept verbatim, whatever the summary says.
+static KEY_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(error|warning|failed|failure|panicked|exception|traceback)\b").unwrap()
+});
+
+const MAX_KEY_LINES: usize = 30;
+/// Lines returned by one `fetch_full_result` call unless `limit` is given.
+const FETCH_LINES: usize = 400;
+
+/// Writes summaries of tool output. Implemented for rig agents, so any
+/// provider's cheap model can be used.
+#[async_trait]
+pub trait Summarizer: Send + Sync {
+    async fn summarize(&self, request: &str) -> Result<String>;
+}
+
+#[async_trait]
+impl<M: CompletionModel + 'static> Summarizer for Agent<M> {
+    async fn summarize(&self, request: &str) -> Result<String> {
+        self.prompt(request)
+            .await
+            .map_err(|e| PicocodeError::Llm(e.to_string()))
+    }
+}
+
+/// Full tool results, saved as `<id>.txt` in a directory only the user can
+/// read, deleted with the store.
+#[derive(Debug)]
+pub struct ResultStore {
+    dir: tempfile::TempDir,
+    /// Random part of the directory name, in every id so that ids of an
+    /// earlier run of a resumed session don't name this run's results.
+    tag: String,
+    next: AtomicUsize,
+}
+
+impl ResultStore {
+    pub fn new_in(parent: &Path) -> Result<Self> {
+        std::fs::create_dir_all(parent)?;
+        let dir = tempfile::Builder::new()
+            .prefix("results-")
+            .tempdir_in(parent)?;
+        let tag = dir
+            .path()
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("results-"))
+            .unwrap_or_default()
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .take(6)
+            .collect();
+        Ok(Self {
+            dir,
+            tag,
+            next: AtomicUsize::new(1),
+        })
+    }
+
+    /// Store under the user's cache directory (`~/.cache/picocode` on Linux),
+    /// or the system temp directory without one.
+    pub fn for_session() -> Result<Self> {
+        let parent = dirs::cache_dir()
+            .map(|dir| dir.join("picocode"))
+            .unwrap_or_else(std::env::temp_dir);
+        Self::new_in(&parent)
+    }
+
+    pub fn save(&self, output: &str) -> Result<String> {
+        let id = format!(
+            "r{}-{}",
+            self.next.fetch_add(1, Ordering::Relaxed),
+            self.tag
+        );
+        std::fs::write(self.dir.path().join(format!("{}.txt", id)), output)?;
+        Ok(id)
+    }
+
+    pub fn load(&self, id: &str) -> Result<String> {
+        let valid =
+            id.starts_with('r') && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        let path = self.dir.path().join(format!("{}.txt", id));
+        if !valid || !path.exists() {
+            return Err(PicocodeError::Other(format!(
+                "No stored result with id {}",
+                id
+            )));
+        }
+        Ok(std::fs::read_to_string(path)?)
+    }
+}
+
+/// Settings shared by every summarized tool of an agent.
+#[derive(Clone)]
+pub struct ToolSummaries {
+    pub summarizer: Arc<dyn Summarizer>,
+    pub store: Arc<ResultStore>,
+    /// Results above this many estimated tokens are summarized.
+    pub threshold_tokens: usize,
+}
+
+/// Error and warning lines of `output`, capped at `MAX_KEY_LINES`.
+pub fn key_lines(output: &str) -> Vec<&str> {
+    output
+        .lines()
+        .filter(|line| KEY_LINE.is_match(line))
+        .take(MAX_KEY_LINES)
+        .collect()
+}
+
+fn summary_request(tool: &str, output: &str) -> String {
+    format!("Output of the `{}` tool:\n\n{}", tool, output)
+}
+
+/// What the model sees in place of a summarized result.
+pub fn condensed(summary: &str, output: &str, tokens: usize, id: &str) -> String {
+    let mut result = summary.trim().to_string();
+    let key = key_lines(output);
+    if !key.is_empty() {
+        result.push_str("\n\nKey lines:\n");
+        result.push_str(&key.join("\n"));
+    }
+    result.push_str(&format!(
+        "\n\n[Summarized from ~{} tokens. The full output is stored as `{}`: call fetch_full_result with id \"{}\" to read it.]",
+        tokens, id, id
+    ));
+    result
+}
+
+/// Wraps a tool so results above the threshold are replaced by a summary.
+/// Without `summaries` it passes results through unchanged.
+pub struct Summarized<T> {
+    pub tool: T,
+    pub summaries: Option<ToolSummaries>,
+}
+
+impl<T: Tool<Output = String, Error = ToolError>> Tool for Summarized<T> {
+    type Args = T::Args;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = T::NAME;
+
+    async fn definition(&self, prompt: String) -> ToolDefinition {
+        self.tool.definition(prompt).await
+    }
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<String, ToolError> {
+        let output = self.tool.call(args).await?;
+        let Some(summaries) = &self.summaries else {
+            return Ok(output);
+        };
+        let tokens = crate::tokens::estimate_tokens(&output);
+        if tokens <= summaries.threshold_tokens {
+            return Ok(output);
+        }
+        // Keep the full result if it can't be stored or summarized
+        let Ok(id) = summaries.store.save(&output) else {
+            return Ok(output);
+        };
+        match summaries
+            .summarizer
+            .summarize(&summary_request(T::NAME, &output))
+            .await
+        {
+            Ok(summary) => Ok(condensed(&summary, &output, tokens, &id)),
+            Err(_) => Ok(output),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct FetchFullResultArgs {
+    /// Id of the stored result, e.g. "r3-x7Kp2a"
+    pub id: String,
+    /// First line to return (0-based)
+    pub offset: Option<usize>,
+    /// Number of lines to return (default 400)
+    pub limit: Option<usize>,
+}
+
+/// Reads back a result that was summarized, a page of lines at a time.
+pub struct FetchFullResult(pub Arc<ResultStore>);
+
+impl Tool for FetchFullResult {
+    type Args = FetchFullResultArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "fetch_full_result";
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.into(),
+            description:
+                "Read the full output of a tool call that was summarized, by its stored id".into(),
+            parameters: serde_json::to_value(schemars::schema_for!(FetchFullResultArgs)).unwrap(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<String, ToolError> {
+        let output = self
+            .0
+            .load(&args.id)
+            .map_err(|e| ToolError::Generic(e.to_string()))?;
+        let lines: Vec<&str> = output.lines().collect();
+        let offset = args.offset.unwrap_or(0).min(lines.len());
+        let end = (offset + args.limit.unwrap_or(FETCH_LINES)).min(lines.len());
+        let mut page = lines[offset..end].join("\n");
+        if offset > 0 || end < lines.len() {
+            page.push_str(&format!(
+                "\n\n(lines {}-{} of {}; pass offset to read more)",
+                offset + 1,
+                end,
+                lines.len()
+            ));
+        }
+        Ok(page)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_store_and_condensed() {
+        let parent = tempfile::tempdir().unwrap();
+        let store = ResultStore::new_in(parent.path()).unwrap();
+        let output = "compiling 120 crates\nerror[E0308]: mismatched types\n --> src/lib.rs:4:5\nwarning: unused import\ndone";
+        let id = store.save(output).unwrap();
+        assert!(id.starts_with("r1-"));
+        assert_eq!(store.load(&id).unwrap(), output);
+        assert!(store.load("../secret").is_err());
+        assert!(store.load("r9").is_err());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(store.dir.path())
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        // Another run can't read this one's results, which go with the store
+        let other = ResultStore::new_in(parent.path()).unwrap();
+        assert!(other.save("x").unwrap().starts_with("r1-"));
+        assert!(other.load(&id).is_err());
+        let dir = store.dir.path().to_path_buf();
+        drop(store);
+        assert!(!dir.exists());
+
+        assert_eq!(
+            key_lines(output),
+            vec!["error[E0308]: mismatched types", "warning: unused import"]
+        );
+        let text = condensed("Build failed with 1 error.", output, 5000, "r1-abc123");
+        assert!(text.starts_with("Build failed with 1 error.\n\nKey lines:\nerror[E0308]"));
+        assert!(text.ends_with("call fetch_full_result with id \"r1-abc123\" to read it.]"));
+    }
+}
@@ -0,0 +1,78 @@
+//! Structured `tracing` spans and events for embedders, behind the
+//! `tracing-spans` cargo feature. Each prompt runs in a `turn` span carrying
+//! the turn id, provider, model and, once finished, its token counts and tool
+//! calls; completions and tool calls inside it are events. Without the
+//! feature these are no-ops, so call sites need no `cfg`.
+
+use crate::session::TokenUsage;
+use std::time::Duration;
+use tracing::Span;
+
+pub const TARGET: &str = "picocode::turn";
+
+/// Span for turn number `turn` of `session_id`.
+pub fn turn_span(session_id: &str, turn: usize, provider: &str, model: &str) -> Span {
+    #[cfg(feature = "tracing-spans")]
+    {
+        tracing::info_span!(
+            target: TARGET,
+            "turn",
+            turn_id = %format!("{}:{}", session_id, turn),
+            provider,
+            model,
+            input_tokens = tracing::field::Empty,
+            output_tokens = tracing::field::Empty,
+            tool_calls = tracing::field::Empty,
+            error = tracing::field::Empty,
+        )
+    }
+    #[cfg(not(feature = "tracing-spans"))]
+    {
+        let _ = (session_id, turn, provider, model);
+        Span::none()
+    }
+}
+
+/// Records what a finished turn used. `usage` is the turn's share of the
+/// session totals.
+pub fn finish_turn(span: &Span, usage: &TokenUsage, tool_calls: usize, error: Option<&str>) {
+    span.record("input_tokens", usage.input_tokens);
+    span.record("output_tokens", usage.output_tokens);
+    span.record("tool_calls", tool_calls as u64);
+    if let Some(error) = error {
+        span.record("error", error);
+    }
+}
+
+pub fn completion(usage: &rig::completion::Usage) {
+    #[cfg(feature = "tracing-spans")]
+    tracing::info!(
+        target: TARGET,
+        input_tokens = usage.input_tokens,
+        output_tokens = usage.output_tokens,
+        "completion"
+    );
+    #[cfg(not(feature = "tracing-spans"))]
+    let _ = usage;
+}
+
+pub fn tool_call(tool: &str, args: &str) {
+    #[cfg(feature = "tracing-spans")]
+    tracing::info!(target: TARGET, tool, args, "tool call");
+    #[cfg(not(feature = "tracing-spans"))]
+    let _ = (tool, args);
+}
+
+pub fn tool_result(tool: &str, elapsed: Duration, result: &str) {
+    #[cfg(feature = "tracing-spans")]
+    tracing::info!(
+        target: TARGET,
+        tool,
+        elapsed_ms = elapsed.as_millis() as u64,
+        result_bytes = result.len() as u64,
+        failed = crate::stats::is_failure(result),
+        "tool result"
+    );
+    #[cfg(not(feature = "tracing-spans"))]
+    let _ = (tool, elapsed, result);
+}
@@ -0,0 +1,77 @@
+use rig::message::{Message, UserContent};
+
+/// Share of the context window at which the user is warned.
+pub const WARN_RATIO: f64 = 0.8;
+/// Share of the context window at which history is compacted before sending.
+pub const COMPACT_RATIO: f64 = 0.9;
+
+/// Rough token estimate: one token per 4 characters, with no tokenizer.
+/// Messages are measured as serialized JSON, so the estimate counts field
+/// names and escaping too; it is only meant for the warning and compaction
+/// thresholds.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+pub fn estimate_message_tokens(message: &Message) -> usize {
+    serde_json::to_string(message)
+        .map(|s| estimate_tokens(&s))
+        .unwrap_or(0)
+}
+
+pub fn estimate_history_tokens(history: &[Message]) -> usize {
+    history.iter().map(estimate_message_tokens).sum()
+}
+
+fn is_user_text(message: &Message) -> bool {
+    match message {
+        Message::User { content } => content.iter().all(|c| matches!(c, UserContent::Text(_))),
+        _ => false,
+    }
+}
+
+/// Drops the oldest exchanges until the history fits in `budget` tokens. Cuts
+/// only happen at plain user messages so tool calls stay paired with their
+/// results. Returns the number of messages removed.
+pub fn compact_history(history: &mut Vec<Message>, budget: usize) -> usize {
+    let mut remaining = estimate_history_tokens(history);
+    let mut cut = 0;
+    for (i, message) in history.iter().enumerate() {
+        if remaining <= budget {
+            break;
+        }
+        remaining -= estimate_message_tokens(message);
+        if i + 1 < history.len() && is_user_text(&history[i + 1]) {
+            cut = i + 1;
+        }
+    }
+    if cut == 0 {
+        return 0;
+    }
+    history.drain(..cut);
+    history.insert(
+        0,
+        Message::user("[Earlier conversation was omitted to fit the model's context window.]"),
+    );
+    history.insert(1, Message::assistant("Understood."));
+    cut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_history_cuts_at_user_messages() {
+        let mut history = vec![
+            Message::user("a".repeat(400)),
+            Message::assistant("b".repeat(400)),
+            Message::user("c"),
+            Message::assistant("d"),
+        ];
+        let removed = compact_history(&mut history, 50);
+        assert_eq!(removed, 2);
+        assert_eq!(history.len(), 4);
+        assert!(is_user_text(&history[2]));
+    }
+}
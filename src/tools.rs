@@ -1,10 +1,48 @@
-use duct_sh::sh_dangerous;
+use crate::checkpoint;
+use crate::freshness;
+use crate::platform::{is_within, Shell};
 use rig_derive::rig_tool;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
 use tokio::fs;
 
+pub mod archive;
+pub mod ask;
+pub mod data;
+pub mod download;
+pub mod env;
+pub mod forge;
+pub mod git;
+pub mod github;
+pub mod gitlab;
+pub mod lint;
+pub mod memory;
+pub mod notebook;
+#[cfg(feature = "tree-sitter")]
+pub mod rename;
+pub mod replace;
+pub mod search;
+pub mod testing;
+
+pub use archive::{ArchiveCreate, ArchiveExtract};
+pub use ask::{Answers, AskUser};
+pub use data::DataPreview;
+pub use download::DownloadFile;
+pub use env::EnvInfo;
+pub use git::{GitDiff, GitLog};
+pub use github::{GhIssueGet, GhIssueList, GhPrComment, GhPrCreate, GitHub};
+pub use gitlab::{GitLab, GlIssueGet, GlIssueList, GlMrComment, GlMrCreate, GlPipelineStatus};
+pub use lint::{RunFormatter, RunLinter};
+pub use memory::Remember;
+pub use notebook::{NotebookEdit, NotebookRead};
+#[cfg(feature = "tree-sitter")]
+pub use rename::RenameSymbol;
+pub use replace::ReplaceInFiles;
+pub use search::WebSearch;
+pub use testing::RunTests;
+
 #[derive(Debug, thiserror::Error, Serialize, Deserialize, JsonSchema)]
 pub enum ToolError {
     #[error("IO error: {0}")]
@@ -24,14 +62,52 @@ impl From<tokio::task::JoinError> for ToolError {
     }
 }
 
-fn get_path(path: &str) -> Result<PathBuf, ToolError> {
-    validate_path(
+pub(crate) fn is_tool_available(tool: &str) -> bool {
+    crate::platform::which(tool).is_some()
+}
+
+/// Directories besides the current one that file tools may access (`workspaces` in picocode.yaml).
+static WORKSPACE_ROOTS: RwLock<Vec<PathBuf>> = RwLock::new(Vec::new());
+
+pub fn set_workspace_roots(roots: Vec<PathBuf>) {
+    *WORKSPACE_ROOTS.write().unwrap() = roots;
+}
+
+pub fn workspace_roots() -> Vec<PathBuf> {
+    WORKSPACE_ROOTS.read().unwrap().clone()
+}
+
+pub(crate) fn get_path(path: &str) -> Result<PathBuf, ToolError> {
+    validate_path_in(
         &std::env::current_dir().map_err(|e| ToolError::Io(e.to_string()))?,
+        &workspace_roots(),
         path,
     )
 }
 
+/// Like `get_path`, but refuses files protected by `secrets` (`.env` and
+/// `protected_files`) so their contents can't reach the model.
+pub(crate) fn get_readable_path(path: &str) -> Result<PathBuf, ToolError> {
+    let p = get_path(path)?;
+    if crate::secrets::is_protected(&p) {
+        return Err(ToolError::Generic(format!(
+            "Access denied: {} is a protected file",
+            path
+        )));
+    }
+    Ok(p)
+}
+
 fn validate_path(base: &std::path::Path, path: &str) -> Result<PathBuf, ToolError> {
+    validate_path_in(base, &[], path)
+}
+
+/// Resolves `path` against `base`, allowing results inside `base` or any of `roots`.
+fn validate_path_in(
+    base: &std::path::Path,
+    roots: &[PathBuf],
+    path: &str,
+) -> Result<PathBuf, ToolError> {
     let p = std::path::Path::new(path);
     let joined = if p.is_absolute() {
         p.to_path_buf()
@@ -50,12 +126,16 @@ fn validate_path(base: &std::path::Path, path: &str) -> Result<PathBuf, ToolErro
         }
     }
 
-    if result.starts_with(base) {
+    if is_within(&result, base) || roots.iter().any(|root| is_within(&result, root)) {
         Ok(result)
-    } else {
+    } else if roots.is_empty() {
         Err(ToolError::Generic(
             "Access denied: path must be within the current directory".into(),
         ))
+    } else {
+        Err(ToolError::Generic(
+            "Access denied: path must be within the current directory or a workspace root".into(),
+        ))
     }
 }
 
@@ -73,7 +153,9 @@ fn walk_files(base: &std::path::Path) -> impl Iterator<Item = ignore::DirEntry>
     required(path, offset, limit)
 )]
 pub async fn read_file(path: String, offset: u64, limit: u64) -> Result<String, ToolError> {
-    let content = fs::read_to_string(get_path(&path)?).await?;
+    let p = get_readable_path(&path)?;
+    let content = fs::read_to_string(&p).await?;
+    freshness::record(&p);
     let lines: Vec<_> = content
         .lines()
         .enumerate()
@@ -90,10 +172,24 @@ pub async fn read_file(path: String, offset: u64, limit: u64) -> Result<String,
 
 #[rig_tool(description = "Write content to file", required(path, content))]
 pub async fn write_file(path: String, content: String) -> Result<String, ToolError> {
-    fs::write(get_path(&path)?, content).await?;
+    let p = get_path(&path)?;
+    if freshness::is_stale(&p) {
+        return Ok(stale_error(&path));
+    }
+    checkpoint::save(&p);
+    fs::write(&p, content).await?;
+    freshness::record(&p);
     Ok("ok".into())
 }
 
+fn stale_error(path: &str) -> String {
+    format!("error: {path} changed on disk since you last read it, read it again first")
+}
+
+fn to_crlf(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\n', "\r\n")
+}
+
 #[rig_tool(
     description = "Replace old with new in file (old must be unique unless all=true)",
     required(path, old, new, all)
@@ -104,8 +200,17 @@ pub async fn edit_file(
     new: String,
     all: bool,
 ) -> Result<String, ToolError> {
-    let p = get_path(&path)?;
+    let p = get_readable_path(&path)?;
+    if freshness::is_stale(&p) {
+        return Ok(stale_error(&path));
+    }
     let text = fs::read_to_string(&p).await?;
+    // Models write `\n`; keep the file's line endings when it uses CRLF
+    let (old, new) = if text.contains("\r\n") {
+        (to_crlf(&old), to_crlf(&new))
+    } else {
+        (old, new)
+    };
     if !text.contains(&old) {
         return Ok("error: old_string not found".into());
     }
@@ -115,8 +220,9 @@ pub async fn edit_file(
             "error: old_string appears {count} times, must be unique (use all=true)"
         ));
     }
+    checkpoint::save(&p);
     fs::write(
-        p,
+        &p,
         if all {
             text.replace(&old, &new)
         } else {
@@ -124,38 +230,93 @@ pub async fn edit_file(
         },
     )
     .await?;
+    freshness::record(&p);
     Ok("ok".into())
 }
 
+/// Matches `glob_files` returns when `max_results` is 0.
+const DEFAULT_GLOB_RESULTS: usize = 200;
+
 #[rig_tool(
-    description = "Find files by pattern, sorted by mtime",
-    required(pat, path)
+    description = "Find files by pattern, newest first. max_results: cap on matches (0 = 200). include_dirs: match directories too. with_mtime/with_size: add modification time and size columns",
+    required(pat, path, max_results, include_dirs, with_mtime, with_size)
 )]
-pub async fn glob_files(pat: String, path: String) -> Result<String, ToolError> {
+pub async fn glob_files(
+    pat: String,
+    path: String,
+    max_results: u64,
+    include_dirs: bool,
+    with_mtime: bool,
+    with_size: bool,
+) -> Result<String, ToolError> {
     let base = get_path(&path)?;
     let matcher = globset::Glob::new(&pat)
         .map_err(|e| ToolError::Generic(e.to_string()))?
         .compile_matcher();
-    let entries = tokio::task::spawn_blocking(move || {
-        walk_files(&base)
+    let mut files = tokio::task::spawn_blocking(move || {
+        ignore::WalkBuilder::new(&base)
+            .hidden(false)
+            .require_git(false)
+            .build()
+            .filter_map(|r| r.ok())
+            .filter(|e| e.depth() > 0)
+            .filter(|e| {
+                e.file_type()
+                    .is_some_and(|ft| ft.is_file() || (include_dirs && ft.is_dir()))
+            })
             .filter(|e| matcher.is_match(e.path().strip_prefix(&base).unwrap_or(e.path())))
-            .map(|e| e.into_path())
+            .map(|e| {
+                let metadata = e.metadata().ok();
+                (e.into_path(), metadata)
+            })
             .collect::<Vec<_>>()
     })
     .await?;
 
-    let mut files = Vec::new();
-    for e in entries {
-        let mtime = fs::metadata(&e).await.and_then(|m| m.modified()).ok();
-        files.push((e, mtime));
-    }
-    files.sort_by_key(|(_, m)| std::cmp::Reverse(*m));
-    let res = files
+    files.sort_by_key(|(_, m)| std::cmp::Reverse(m.as_ref().and_then(|m| m.modified().ok())));
+    let limit = match max_results as usize {
+        0 => DEFAULT_GLOB_RESULTS,
+        n => n,
+    };
+    let more = files.len().saturating_sub(limit);
+    let mut res = files
         .iter()
-        .map(|(f, _)| f.to_string_lossy())
+        .take(limit)
+        .map(|(f, metadata)| {
+            let is_dir = metadata.as_ref().is_some_and(|m| m.is_dir());
+            let mut line = format!("{}{}", f.to_string_lossy(), if is_dir { "/" } else { "" });
+            if with_mtime {
+                let mtime = metadata
+                    .as_ref()
+                    .and_then(|m| m.modified().ok())
+                    .map(|t| {
+                        chrono::DateTime::<chrono::Local>::from(t)
+                            .format("%Y-%m-%d %H:%M")
+                            .to_string()
+                    })
+                    .unwrap_or_else(|| "-".into());
+                line.push_str(&format!("\t{}", mtime));
+            }
+            if with_size {
+                match metadata.as_ref().filter(|m| !m.is_dir()) {
+                    Some(m) => line.push_str(&format!("\t{}", format_size(m.len()))),
+                    None => line.push_str("\t-"),
+                }
+            }
+            line
+        })
         .collect::<Vec<_>>()
         .join("\n");
-    Ok(if res.is_empty() { "none".into() } else { res })
+    if res.is_empty() {
+        return Ok("none".into());
+    }
+    if more > 0 {
+        res.push_str(&format!(
+            "\n... {} more matches (narrow the pattern or raise max_results)",
+            more
+        ));
+    }
+    Ok(res)
 }
 
 #[rig_tool(description = "Search files for regex pattern", required(pat, path))]
@@ -164,6 +325,7 @@ pub async fn grep_text(pat: String, path: String) -> Result<String, ToolError> {
     let re = regex::Regex::new(&pat).map_err(|e| ToolError::Generic(e.to_string()))?;
     let hits = tokio::task::spawn_blocking(move || {
         walk_files(&base)
+            .filter(|e| !crate::secrets::is_protected(e.path()))
             .flat_map(|e| {
                 let p = e.path().to_owned();
                 std::fs::read_to_string(&p).ok().map(|c| (p, c))
@@ -188,12 +350,183 @@ pub async fn grep_text(pat: String, path: String) -> Result<String, ToolError> {
     })
 }
 
+trait Kill {
+    fn kill(&self) -> std::io::Result<()>;
+    fn pids(&self) -> Vec<u32>;
+}
+
+impl Kill for duct::Handle {
+    fn kill(&self) -> std::io::Result<()> {
+        duct::Handle::kill(self)
+    }
+
+    fn pids(&self) -> Vec<u32> {
+        duct::Handle::pids(self)
+    }
+}
+
+impl Kill for duct::ReaderHandle {
+    fn kill(&self) -> std::io::Result<()> {
+        duct::ReaderHandle::kill(self)
+    }
+
+    fn pids(&self) -> Vec<u32> {
+        duct::ReaderHandle::pids(self)
+    }
+}
+
+/// Kills a running command when dropped, unless it already finished. On Unix
+/// its whole process group is killed, so the processes a shell command
+/// started don't outlive it.
+struct KillOnDrop<H: Kill>(Option<Arc<H>>);
+
+impl<H: Kill> Drop for KillOnDrop<H> {
+    fn drop(&mut self) {
+        if let Some(handle) = self.0.take() {
+            #[cfg(unix)]
+            for pid in handle.pids() {
+                // SAFETY: kill(2) only sends a signal. Each command leads its
+                // own process group (see `in_own_group`), so a negative pid
+                // names that group and nothing else.
+                unsafe {
+                    libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+                }
+            }
+            let _ = handle.kill();
+        }
+    }
+}
+
+/// Kills a sandbox container when dropped, unless its command finished:
+/// killing the `docker run` client leaves the container running.
+struct KillContainerOnDrop {
+    runtime: String,
+    name: String,
+    finished: bool,
+}
+
+impl KillContainerOnDrop {
+    fn new(runtime: &str) -> Self {
+        static COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let count = COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Self {
+            runtime: runtime.to_string(),
+            name: format!("picocode-{}-{}", std::process::id(), count),
+            finished: false,
+        }
+    }
+}
+
+impl Drop for KillContainerOnDrop {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        let (runtime, name) = (self.runtime.clone(), self.name.clone());
+        std::thread::spawn(move || {
+            let _ = std::process::Command::new(runtime)
+                .args(["kill", &name])
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .status();
+        });
+    }
+}
+
+/// Receives each line a running command prints, e.g. to show it on the console.
+pub type Progress = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Keeps the keys loaded from `.env` out of tool commands, where `env` would
+/// print them.
+fn without_dotenv(expression: duct::Expression) -> duct::Expression {
+    crate::secrets::dotenv_vars()
+        .into_iter()
+        .fold(expression, |expression, var| expression.env_remove(var))
+}
+
+/// Starts a command as the leader of a new process group, for `KillOnDrop`.
+/// Outside the terminal's foreground group a read from the terminal would
+/// stop the command, so it gets no stdin.
+fn in_own_group(expression: duct::Expression) -> duct::Expression {
+    #[cfg(unix)]
+    let expression = expression.stdin_null().before_spawn(|command| {
+        std::os::unix::process::CommandExt::process_group(command, 0);
+        Ok(())
+    });
+    expression
+}
+
+/// Runs a command and returns its combined stdout and stderr. If the call is
+/// dropped before the command exits (e.g. the turn was cancelled), the process
+/// is killed instead of being left running in the background.
+async fn run_expression(expression: duct::Expression) -> Result<String, ToolError> {
+    let handle = Arc::new(
+        in_own_group(without_dotenv(expression))
+            .stderr_to_stdout()
+            .stdout_capture()
+            .unchecked()
+            .start()?,
+    );
+    let mut guard = KillOnDrop(Some(handle.clone()));
+    let output = tokio::task::spawn_blocking(move || {
+        handle
+            .wait()
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+    })
+    .await??;
+    guard.0 = None;
+    Ok(output)
+}
+
+/// Like `run_expression`, but passes each line to `progress` as it is printed.
+async fn run_expression_streaming(
+    expression: duct::Expression,
+    progress: Progress,
+) -> Result<String, ToolError> {
+    let reader = Arc::new(
+        in_own_group(without_dotenv(expression))
+            .stderr_to_stdout()
+            .unchecked()
+            .reader()?,
+    );
+    let mut guard = KillOnDrop(Some(reader.clone()));
+    let output = tokio::task::spawn_blocking(move || {
+        let mut lines = std::io::BufReader::new(&*reader);
+        let mut output = Vec::new();
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if std::io::BufRead::read_until(&mut lines, b'\n', &mut line)? == 0 {
+                break;
+            }
+            progress(String::from_utf8_lossy(&line).trim_end());
+            output.extend_from_slice(&line);
+        }
+        Ok::<_, std::io::Error>(String::from_utf8_lossy(&output).into_owned())
+    })
+    .await??;
+    guard.0 = None;
+    Ok(output)
+}
+
 #[derive(Deserialize, Serialize, JsonSchema)]
 pub struct BashArgs {
     pub cmd: String,
+    /// Run this command in another shell than the default one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<Shell>,
 }
 
-pub struct Bash;
+#[derive(Default)]
+pub struct Bash {
+    /// When set, commands run inside a container instead of on the host.
+    pub sandbox: Option<crate::sandbox::SandboxConfig>,
+    pub network: crate::config::NetworkPolicy,
+    /// `tool_config.bash.shell`; detected for the platform when unset.
+    pub shell: Option<Shell>,
+    /// Gets the output line by line while the command runs.
+    pub progress: Option<Progress>,
+}
 
 impl rig::tool::Tool for Bash {
     type Args = BashArgs;
@@ -205,20 +538,52 @@ impl rig::tool::Tool for Bash {
     async fn definition(&self, _prompt: String) -> rig::completion::ToolDefinition {
         rig::completion::ToolDefinition {
             name: Self::NAME.into(),
-            description: "Run shell command".into(),
+            description: match self.shell.unwrap_or_else(Shell::detect) {
+                Shell::Sh => "Run shell command".into(),
+                shell => format!("Run shell command (in {})", shell.name()),
+            },
             parameters: serde_json::to_value(schemars::schema_for!(BashArgs)).unwrap(),
         }
     }
 
     async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
-        let output = tokio::task::spawn_blocking(move || {
-            sh_dangerous(&args.cmd)
-                .stderr_to_stdout()
-                .unchecked()
-                .read()
-                .map_err(|e| ToolError::Io(e.to_string()))
-        })
-        .await??;
+        if crate::secrets::command_reads_protected(&args.cmd) {
+            return Err(ToolError::Generic(
+                "Access denied: the command references a protected file (.env or protected_files)"
+                    .into(),
+            ));
+        }
+        let shell = args.shell.or(self.shell).unwrap_or_else(Shell::detect);
+        let mut container = None;
+        let expression = match &self.sandbox {
+            Some(sandbox) => {
+                let cwd = std::env::current_dir()?;
+                let guard = container.insert(KillContainerOnDrop::new(&sandbox.runtime));
+                duct::cmd(
+                    sandbox.runtime.as_str(),
+                    sandbox.run_args(&guard.name, &args.cmd, &cwd, &workspace_roots()),
+                )
+            }
+            None if self.network == crate::config::NetworkPolicy::Deny => {
+                match crate::shell::without_network(shell, &args.cmd) {
+                    Some(expression) => expression,
+                    None if crate::shell::uses_network(&args.cmd) => {
+                        return Err(ToolError::Generic(
+                            "Network access is denied by tool_config.bash.network".into(),
+                        ));
+                    }
+                    None => shell.command(&args.cmd),
+                }
+            }
+            None => shell.command(&args.cmd),
+        };
+        let output = match &self.progress {
+            Some(progress) => run_expression_streaming(expression, progress.clone()).await?,
+            None => run_expression(expression).await?,
+        };
+        if let Some(container) = &mut container {
+            container.finished = true;
+        }
 
         let res = output.trim().to_string();
         Ok(if res.is_empty() {
@@ -229,34 +594,128 @@ impl rig::tool::Tool for Bash {
     }
 }
 
-#[rig_tool(description = "List files and directories in a path", required(path))]
-pub async fn list_dir(path: String) -> Result<String, ToolError> {
+/// Entries `list_dir` returns before cutting the listing short.
+const MAX_LIST_ENTRIES: usize = 500;
+
+struct DirEntry {
+    depth: usize,
+    name: String,
+    is_dir: bool,
+    size: Option<u64>,
+}
+
+impl DirEntry {
+    fn label(&self) -> String {
+        match self.size {
+            Some(size) => format!("{} ({})", self.name, format_size(size)),
+            None if self.is_dir => format!("{}/", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Renders entries in walk order (parents before children, depth from 1) as
+/// a tree with box-drawing connectors.
+fn render_tree(entries: &[DirEntry]) -> String {
+    // Whether each entry is the last child of its parent, found walking backwards
+    let mut is_last = vec![false; entries.len()];
+    let mut later_sibling: Vec<bool> = Vec::new();
+    for (i, entry) in entries.iter().enumerate().rev() {
+        later_sibling.resize(entry.depth + 1, false);
+        is_last[i] = !later_sibling[entry.depth];
+        later_sibling[entry.depth] = true;
+    }
+
+    let mut ancestors_last: Vec<bool> = Vec::new();
+    let mut lines = Vec::with_capacity(entries.len());
+    for (entry, last) in entries.iter().zip(is_last) {
+        ancestors_last.truncate(entry.depth - 1);
+        let mut line: String = ancestors_last
+            .iter()
+            .map(|&done| if done { "    " } else { "│   " })
+            .collect();
+        line.push_str(if last { "└── " } else { "├── " });
+        line.push_str(&entry.label());
+        lines.push(line);
+        ancestors_last.push(last);
+    }
+    lines.join("\n")
+}
+
+#[rig_tool(
+    description = "List files and directories in a path. depth: levels to descend (0 or 1 = this directory only; >1 renders a tree). show_sizes: include file sizes. dirs_only: list directories only",
+    required(path, depth, show_sizes, dirs_only)
+)]
+pub async fn list_dir(
+    path: String,
+    depth: u64,
+    show_sizes: bool,
+    dirs_only: bool,
+) -> Result<String, ToolError> {
     let base = get_path(&path)?;
+    let depth = (depth as usize).max(1);
 
-    let entries = tokio::task::spawn_blocking(move || {
+    let mut entries = tokio::task::spawn_blocking(move || {
         ignore::WalkBuilder::new(&base)
             .hidden(false)
             .require_git(false)
-            .max_depth(Some(1))
+            .max_depth(Some(depth))
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .filter_entry(move |e| !dirs_only || e.file_type().is_some_and(|ft| ft.is_dir()))
             .build()
             .filter_map(|r| r.ok())
             .filter(|e| e.depth() > 0) // Skip the root directory itself
+            .take(MAX_LIST_ENTRIES + 1)
             .map(|e| {
-                let name = e.file_name().to_string_lossy();
                 let is_dir = e.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
-                format!("{}{}", name, if is_dir { "/" } else { "" })
+                DirEntry {
+                    depth: e.depth(),
+                    name: e.file_name().to_string_lossy().into_owned(),
+                    is_dir,
+                    size: (show_sizes && !is_dir)
+                        .then(|| e.metadata().ok().map(|m| m.len()))
+                        .flatten(),
+                }
             })
-            .collect::<Vec<String>>()
+            .collect::<Vec<DirEntry>>()
     })
     .await?;
 
-    let mut res = entries;
-    res.sort();
-    Ok(if res.is_empty() {
-        "(empty)".into()
+    let truncated = entries.len() > MAX_LIST_ENTRIES;
+    entries.truncate(MAX_LIST_ENTRIES);
+    if entries.is_empty() {
+        return Ok("(empty)".into());
+    }
+    let mut res = if depth == 1 {
+        entries
+            .iter()
+            .map(DirEntry::label)
+            .collect::<Vec<_>>()
+            .join("\n")
     } else {
-        res.join("\n")
-    })
+        format!("{}/\n{}", path.trim_end_matches('/'), render_tree(&entries))
+    };
+    if truncated {
+        res.push_str(&format!(
+            "\n... (stopped at {} entries; list a subdirectory or lower depth)",
+            MAX_LIST_ENTRIES
+        ));
+    }
+    Ok(res)
 }
 
 #[rig_tool(
@@ -268,34 +727,209 @@ pub async fn make_dir(path: String) -> Result<String, ToolError> {
     Ok("ok".into())
 }
 
-#[rig_tool(description = "Remove a file or directory", required(path, recursive))]
-pub async fn remove(path: String, recursive: bool) -> Result<String, ToolError> {
+#[rig_tool(
+    description = "Remove a file or directory. It is moved to the project trash (.picocode/trash) and can be restored, unless permanent is true",
+    required(path, recursive, permanent)
+)]
+pub async fn remove(path: String, recursive: bool, permanent: bool) -> Result<String, ToolError> {
     let p = get_path(&path)?;
+    // Fails for missing paths before anything is moved
+    fs::symlink_metadata(&p).await?;
+    if p.is_dir() && !recursive && std::fs::read_dir(&p)?.next().is_some() {
+        return Err(ToolError::Generic(format!(
+            "{} is not empty (pass recursive=true to remove it)",
+            path
+        )));
+    }
+    checkpoint::save(&p);
+    if !permanent {
+        let trash =
+            crate::trash::Trash::open_default().map_err(|e| ToolError::Generic(e.to_string()))?;
+        let batch = tokio::task::spawn_blocking(move || trash.put(&p))
+            .await?
+            .map_err(|e| {
+                ToolError::Generic(format!(
+                    "Could not move {} to the trash: {} (pass permanent=true to delete it)",
+                    path, e
+                ))
+            })?;
+        return Ok(format!(
+            "ok (moved to {}; restore with `picocode trash restore`)",
+            batch.display()
+        ));
+    }
     if p.is_dir() {
-        if recursive {
-            fs::remove_dir_all(p).await?;
-        } else {
-            fs::remove_dir(p).await?;
-        }
+        fs::remove_dir_all(p).await?;
     } else {
         fs::remove_file(p).await?;
     }
     Ok("ok".into())
 }
 
-#[rig_tool(description = "Move or rename a file or directory", required(src, dst))]
-pub async fn move_file(src: String, dst: String) -> Result<String, ToolError> {
-    fs::rename(get_path(&src)?, get_path(&dst)?).await?;
-    Ok("ok".into())
+fn destination_exists(dst: &std::path::Path) -> ToolError {
+    ToolError::Generic(format!(
+        "Destination {} already exists (pass overwrite=true to replace it)",
+        dst.display()
+    ))
+}
+
+fn stale_destination(dst: &std::path::Path) -> ToolError {
+    ToolError::Generic(format!(
+        "{} changed on disk since you last read it, read it again before replacing it",
+        dst.display()
+    ))
+}
+
+/// Copies a file, or a directory recursively, returning the number of files
+/// copied. Symlinks are copied as links, not followed. Existing files in `dst`
+/// are replaced only with `overwrite`. Without `preserve_permissions` copies
+/// get default permissions.
+fn copy_path(
+    src: &std::path::Path,
+    dst: &std::path::Path,
+    overwrite: bool,
+    preserve_permissions: bool,
+) -> Result<usize, ToolError> {
+    let metadata = std::fs::symlink_metadata(src)?;
+    if !metadata.is_dir() {
+        if std::fs::symlink_metadata(dst).is_ok() {
+            if !overwrite {
+                return Err(destination_exists(dst));
+            }
+            if freshness::is_stale(dst) {
+                return Err(stale_destination(dst));
+            }
+            if metadata.is_symlink() || std::fs::symlink_metadata(dst)?.is_symlink() {
+                std::fs::remove_file(dst)?;
+            }
+        }
+        if metadata.is_symlink() {
+            copy_symlink(src, dst)?;
+            return Ok(1);
+        }
+        if preserve_permissions {
+            std::fs::copy(src, dst)?;
+        } else {
+            std::io::copy(
+                &mut std::fs::File::open(src)?,
+                &mut std::fs::File::create(dst)?,
+            )?;
+        }
+        freshness::record(dst);
+        return Ok(1);
+    }
+    if dst.starts_with(src) {
+        return Err(ToolError::Generic(format!(
+            "Cannot copy {} into itself",
+            src.display()
+        )));
+    }
+    std::fs::create_dir_all(dst)?;
+    if preserve_permissions {
+        std::fs::set_permissions(dst, metadata.permissions())?;
+    }
+    let mut copied = 0;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        copied += copy_path(
+            &entry.path(),
+            &dst.join(entry.file_name()),
+            overwrite,
+            preserve_permissions,
+        )?;
+    }
+    Ok(copied)
+}
+
+#[cfg(unix)]
+fn copy_symlink(src: &std::path::Path, dst: &std::path::Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(src)?, dst)
+}
+
+#[cfg(windows)]
+fn copy_symlink(src: &std::path::Path, dst: &std::path::Path) -> std::io::Result<()> {
+    let target = std::fs::read_link(src)?;
+    if std::fs::metadata(src).is_ok_and(|m| m.is_dir()) {
+        std::os::windows::fs::symlink_dir(target, dst)
+    } else {
+        std::os::windows::fs::symlink_file(target, dst)
+    }
 }
 
 #[rig_tool(
-    description = "Copy a file (does not support directories yet)",
-    required(src, dst)
+    description = "Move or rename a file or directory. overwrite: replace an existing destination. preserve_permissions: keep permission bits when the move has to copy across filesystems",
+    required(src, dst, overwrite, preserve_permissions)
 )]
-pub async fn copy_file(src: String, dst: String) -> Result<String, ToolError> {
-    fs::copy(get_path(&src)?, get_path(&dst)?).await?;
-    Ok("ok".into())
+pub async fn move_file(
+    src: String,
+    dst: String,
+    overwrite: bool,
+    preserve_permissions: bool,
+) -> Result<String, ToolError> {
+    let src = get_readable_path(&src)?;
+    let dst = get_path(&dst)?;
+    tokio::task::spawn_blocking(move || {
+        checkpoint::save(&src);
+        checkpoint::save_destination(&src, &dst);
+        if std::fs::symlink_metadata(&dst).is_ok() {
+            if !overwrite {
+                return Err(destination_exists(&dst));
+            }
+            if freshness::is_stale(&dst) {
+                return Err(stale_destination(&dst));
+            }
+            // The replaced destination can be restored like a removed file
+            crate::trash::Trash::open_default()
+                .and_then(|trash| trash.put(&dst))
+                .map_err(|e| {
+                    ToolError::Generic(format!(
+                        "Could not move {} to the trash: {}",
+                        dst.display(),
+                        e
+                    ))
+                })?;
+        }
+        if std::fs::rename(&src, &dst).is_err() {
+            // Renames fail across filesystems: copy, then remove the source
+            copy_path(&src, &dst, true, preserve_permissions)?;
+            if std::fs::symlink_metadata(&src)?.is_dir() {
+                std::fs::remove_dir_all(&src)?;
+            } else {
+                std::fs::remove_file(&src)?;
+            }
+        }
+        freshness::record(&src);
+        freshness::record(&dst);
+        Ok("ok".to_string())
+    })
+    .await?
+}
+
+#[rig_tool(
+    description = "Copy a file, or a directory recursively. overwrite: replace existing files at the destination (otherwise an existing destination is an error). preserve_permissions: keep permission bits (e.g. executable scripts)",
+    required(src, dst, overwrite, preserve_permissions)
+)]
+pub async fn copy_file(
+    src: String,
+    dst: String,
+    overwrite: bool,
+    preserve_permissions: bool,
+) -> Result<String, ToolError> {
+    let src = get_readable_path(&src)?;
+    let dst = get_path(&dst)?;
+    let copied = tokio::task::spawn_blocking(move || {
+        if src.is_dir() && dst.exists() && !overwrite {
+            return Err(destination_exists(&dst));
+        }
+        checkpoint::save_destination(&src, &dst);
+        copy_path(&src, &dst, overwrite, preserve_permissions)
+    })
+    .await??;
+    Ok(if copied == 1 {
+        "ok".into()
+    } else {
+        format!("ok ({} files copied)", copied)
+    })
 }
 
 #[rig_tool(
@@ -319,14 +953,7 @@ Commands:
 )]
 pub async fn agent_browser(args: String) -> Result<String, ToolError> {
     let cmd = format!("agent-browser {}", args);
-    let output = tokio::task::spawn_blocking(move || {
-        sh_dangerous(&cmd)
-            .stderr_to_stdout()
-            .unchecked()
-            .read()
-            .map_err(|e| ToolError::Io(e.to_string()))
-    })
-    .await??;
+    let output = run_expression(Shell::detect().command(&cmd)).await?;
 
     let res = output.trim().to_string();
     Ok(if res.is_empty() {
@@ -341,6 +968,94 @@ mod tests {
     use super::*;
     use std::path::Path;
 
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_run_expression_kills_process_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let script = format!("sleep 30 & echo $! > {}; wait", pid_file.display());
+        let run = run_expression(duct::cmd("sh", ["-c", &script]));
+        assert!(tokio::time::timeout(std::time::Duration::from_millis(500), run)
+            .await
+            .is_err());
+        // The shell's child was killed with it (at most a zombie is left)
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let stat = format!("/proc/{}/stat", pid.trim());
+        let dead = || std::fs::read_to_string(&stat).map_or(true, |s| s.contains(") Z "));
+        for _ in 0..20 {
+            if dead() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        assert!(dead());
+    }
+
+    #[test]
+    fn test_render_tree() {
+        let entry = |depth, name: &str, is_dir, size| DirEntry {
+            depth,
+            name: name.into(),
+            is_dir,
+            size,
+        };
+        let entries = vec![
+            entry(1, "src", true, None),
+            entry(2, "tools", true, None),
+            entry(3, "data.rs", false, Some(2048)),
+            entry(2, "main.rs", false, Some(512)),
+            entry(1, "Cargo.toml", false, Some(1_572_864)),
+        ];
+        assert_eq!(
+            render_tree(&entries),
+            "├── src/\n│   ├── tools/\n│   │   └── data.rs (2.0 KB)\n│   └── main.rs (512 B)\n└── Cargo.toml (1.5 MB)"
+        );
+    }
+
+    #[test]
+    fn test_copy_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(src.join("nested")).unwrap();
+        std::fs::write(src.join("a.txt"), "a").unwrap();
+        std::fs::write(src.join("nested/b.txt"), "b").unwrap();
+
+        let dst = dir.path().join("dst");
+        assert_eq!(copy_path(&src, &dst, false, true).unwrap(), 2);
+        assert_eq!(
+            std::fs::read_to_string(dst.join("nested/b.txt")).unwrap(),
+            "b"
+        );
+
+        std::fs::write(src.join("a.txt"), "changed").unwrap();
+        assert!(copy_path(&src.join("a.txt"), &dst.join("a.txt"), false, true).is_err());
+        copy_path(&src.join("a.txt"), &dst.join("a.txt"), true, false).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dst.join("a.txt")).unwrap(),
+            "changed"
+        );
+
+        assert!(copy_path(&src, &src.join("nested/copy"), true, true).is_err());
+
+        // Links are copied as links, so a link back up doesn't loop
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("..", src.join("nested/up")).unwrap();
+            let dst = dir.path().join("linked");
+            assert_eq!(copy_path(&src, &dst, false, true).unwrap(), 3);
+            assert_eq!(
+                std::fs::read_link(dst.join("nested/up")).unwrap(),
+                std::path::Path::new("..")
+            );
+        }
+    }
+
+    #[test]
+    fn test_to_crlf() {
+        assert_eq!(to_crlf("a\nb"), "a\r\nb");
+        assert_eq!(to_crlf("a\r\nb\n"), "a\r\nb\r\n");
+    }
+
     #[test]
     fn test_validate_path_normal() {
         let base = Path::new("/work");
@@ -401,6 +1116,22 @@ mod tests {
         assert_eq!(validate_path(base, "").unwrap(), Path::new("/work"));
     }
 
+    #[test]
+    fn test_validate_path_workspace_roots() {
+        let base = Path::new("/work/app");
+        let roots = vec![PathBuf::from("/work/shared-lib")];
+        assert_eq!(
+            validate_path_in(base, &roots, "../shared-lib/src/lib.rs").unwrap(),
+            Path::new("/work/shared-lib/src/lib.rs")
+        );
+        assert_eq!(
+            validate_path_in(base, &roots, "/work/shared-lib").unwrap(),
+            Path::new("/work/shared-lib")
+        );
+        assert!(validate_path_in(base, &roots, "../api/main.rs").is_err());
+        assert!(validate_path_in(base, &roots, "../shared-lib/../api").is_err());
+    }
+
     #[test]
     fn test_validate_path_unforgiving_edge_cases() {
         let base = Path::new("/work");
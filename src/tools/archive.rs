@@ -0,0 +1,413 @@
+use super::{destination_exists, get_path, stale_destination, walk_files, ToolError};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// Total bytes an extraction may write, so a zip bomb can't fill the disk.
+const MAX_EXTRACT_BYTES: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Result<Self, ToolError> {
+        let name = path.to_string_lossy().to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Ok(Format::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Ok(Format::TarGz)
+        } else if name.ends_with(".tar") {
+            Ok(Format::Tar)
+        } else {
+            Err(err("Unsupported archive format (zip, tar, tar.gz, tgz)"))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    File,
+    Dir,
+    Link,
+}
+
+/// An archive member, by its name in the archive.
+struct Entry {
+    name: PathBuf,
+    kind: Kind,
+}
+
+fn err(e: impl std::fmt::Display) -> ToolError {
+    ToolError::Generic(e.to_string())
+}
+
+/// Where entry `name` goes under `dest`, or `None` if the name is absolute or
+/// uses `..`, which could place it outside `dest` (zip-slip).
+fn entry_path(dest: &Path, name: &Path) -> Option<PathBuf> {
+    let mut path = dest.to_path_buf();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(path)
+}
+
+fn open_tar(path: &Path, format: Format) -> Result<tar::Archive<Box<dyn Read>>, ToolError> {
+    let file = File::open(path)?;
+    let reader: Box<dyn Read> = match format {
+        Format::TarGz => Box::new(flate2::read::GzDecoder::new(file)),
+        _ => Box::new(file),
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+fn tar_kind(entry_type: tar::EntryType) -> Kind {
+    if entry_type.is_dir() {
+        Kind::Dir
+    } else if entry_type.is_file() {
+        Kind::File
+    } else {
+        // Links, devices and other special entries are never extracted
+        Kind::Link
+    }
+}
+
+fn list(path: &Path, format: Format) -> Result<Vec<Entry>, ToolError> {
+    let mut entries = Vec::new();
+    if format == Format::Zip {
+        let mut zip = zip::ZipArchive::new(File::open(path)?).map_err(err)?;
+        for i in 0..zip.len() {
+            let file = zip.by_index(i).map_err(err)?;
+            let kind = if file.is_symlink() {
+                Kind::Link
+            } else if file.is_dir() {
+                Kind::Dir
+            } else {
+                Kind::File
+            };
+            entries.push(Entry {
+                name: PathBuf::from(file.name()),
+                kind,
+            });
+        }
+    } else {
+        for entry in open_tar(path, format)?.entries()? {
+            let entry = entry?;
+            entries.push(Entry {
+                name: entry.path()?.into_owned(),
+                kind: tar_kind(entry.header().entry_type()),
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// Writes one file, counting its bytes against `budget`.
+fn write_entry(
+    reader: &mut impl Read,
+    path: &Path,
+    mode: Option<u32>,
+    budget: &mut u64,
+) -> Result<(), ToolError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    crate::checkpoint::save(path);
+    let mut file = File::create(path)?;
+    let written = std::io::copy(&mut reader.take(*budget + 1), &mut file)?;
+    if written > *budget {
+        drop(file);
+        let _ = std::fs::remove_file(path);
+        return Err(err(format!(
+            "Extraction stopped: the archive expands to more than {} MB",
+            MAX_EXTRACT_BYTES / 1024 / 1024
+        )));
+    }
+    *budget -= written;
+    crate::freshness::record(path);
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o777))?;
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    Ok(())
+}
+
+/// Extracts `archive` into `dest` and returns the number of files written and
+/// links skipped. Every entry is checked before anything is written.
+fn extract(archive: &Path, dest: &Path, overwrite: bool) -> Result<(usize, usize), ToolError> {
+    let format = Format::from_path(archive)?;
+    let entries = list(archive, format)?;
+    for entry in &entries {
+        let Some(path) = entry_path(dest, &entry.name) else {
+            return Err(err(format!(
+                "Refusing to extract: {} would be written outside {}",
+                entry.name.display(),
+                dest.display()
+            )));
+        };
+        if entry.kind == Kind::File && path.exists() && !overwrite {
+            return Err(destination_exists(&path));
+        }
+        if entry.kind == Kind::File && crate::freshness::is_stale(&path) {
+            return Err(stale_destination(&path));
+        }
+    }
+
+    let mut budget = MAX_EXTRACT_BYTES;
+    let mut files = 0;
+    if format == Format::Zip {
+        let mut zip = zip::ZipArchive::new(File::open(archive)?).map_err(err)?;
+        for (i, entry) in entries.iter().enumerate() {
+            let path = entry_path(dest, &entry.name).unwrap();
+            match entry.kind {
+                Kind::Dir => std::fs::create_dir_all(&path)?,
+                Kind::File => {
+                    let mut file = zip.by_index(i).map_err(err)?;
+                    let mode = file.unix_mode();
+                    write_entry(&mut file, &path, mode, &mut budget)?;
+                    files += 1;
+                }
+                Kind::Link => {}
+            }
+        }
+    } else {
+        for entry in open_tar(archive, format)?.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.into_owned();
+            let path = entry_path(dest, &name).unwrap();
+            match tar_kind(entry.header().entry_type()) {
+                Kind::Dir => std::fs::create_dir_all(&path)?,
+                Kind::File => {
+                    let mode = entry.header().mode().ok();
+                    write_entry(&mut entry, &path, mode, &mut budget)?;
+                    files += 1;
+                }
+                Kind::Link => {}
+            }
+        }
+    }
+    let links = entries.iter().filter(|e| e.kind == Kind::Link).count();
+    Ok((files, links))
+}
+
+/// Files to archive from `sources`, with their names in the archive: relative
+/// to `root`, or to the source's parent for sources outside it. Protected
+/// files (`.env` and `protected_files`) and `skip` are left out.
+fn collect(
+    sources: &[PathBuf],
+    root: &Path,
+    skip: &Path,
+) -> Result<Vec<(PathBuf, PathBuf)>, ToolError> {
+    let mut files = Vec::new();
+    for source in sources {
+        let base = if source.starts_with(root) {
+            root
+        } else {
+            source.parent().unwrap_or(source)
+        };
+        if !source.exists() {
+            return Err(err(format!("{} does not exist", source.display())));
+        }
+        for entry in walk_files(source) {
+            let path = entry.path();
+            if path == skip || crate::secrets::is_protected(path) {
+                continue;
+            }
+            let name = path.strip_prefix(base).unwrap_or(path).to_path_buf();
+            files.push((path.to_path_buf(), name));
+        }
+    }
+    Ok(files)
+}
+
+fn create(archive: &Path, files: &[(PathBuf, PathBuf)]) -> Result<(), ToolError> {
+    let out = File::create(archive)?;
+    match Format::from_path(archive)? {
+        Format::Zip => {
+            let mut zip = zip::ZipWriter::new(out);
+            let options = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated);
+            for (path, name) in files {
+                #[cfg(unix)]
+                let options = {
+                    use std::os::unix::fs::PermissionsExt;
+                    options.unix_permissions(std::fs::metadata(path)?.permissions().mode())
+                };
+                zip.start_file(name.to_string_lossy(), options)
+                    .map_err(err)?;
+                std::io::copy(&mut File::open(path)?, &mut zip)?;
+            }
+            zip.finish().map_err(err)?.flush()?;
+        }
+        Format::Tar => {
+            let mut tar = tar::Builder::new(out);
+            for (path, name) in files {
+                tar.append_path_with_name(path, name)?;
+            }
+            tar.into_inner()?.flush()?;
+        }
+        Format::TarGz => {
+            let gz = flate2::write::GzEncoder::new(out, flate2::Compression::default());
+            let mut tar = tar::Builder::new(gz);
+            for (path, name) in files {
+                tar.append_path_with_name(path, name)?;
+            }
+            tar.into_inner()?.finish()?.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct ArchiveExtractArgs {
+    /// Archive to extract (.zip, .tar, .tar.gz or .tgz)
+    pub path: String,
+    /// Directory to extract into (created if missing)
+    pub dest: String,
+    /// Replace files that already exist in dest
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+pub struct ArchiveExtract;
+
+impl rig::tool::Tool for ArchiveExtract {
+    type Args = ArchiveExtractArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "archive_extract";
+
+    async fn definition(&self, _prompt: String) -> rig::completion::ToolDefinition {
+        rig::completion::ToolDefinition {
+            name: Self::NAME.into(),
+            description: "Extract a zip, tar or tar.gz archive into a directory in the workspace. Entries that would land outside it are refused and links are skipped".into(),
+            parameters: serde_json::to_value(schemars::schema_for!(ArchiveExtractArgs)).unwrap(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        let archive = get_path(&args.path)?;
+        let dest = get_path(&args.dest)?;
+        let (files, links) =
+            tokio::task::spawn_blocking(move || extract(&archive, &dest, args.overwrite)).await??;
+        let mut result = format!("Extracted {} file(s) to {}", files, args.dest);
+        if links > 0 {
+            result.push_str(&format!(" ({} link(s) skipped)", links));
+        }
+        Ok(result)
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct ArchiveCreateArgs {
+    /// Archive to write; the format follows the extension (.zip, .tar, .tar.gz or .tgz)
+    pub path: String,
+    /// Files and directories to include (directories are walked, honoring .gitignore)
+    pub sources: Vec<String>,
+    /// Replace an existing archive at path
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+pub struct ArchiveCreate;
+
+impl rig::tool::Tool for ArchiveCreate {
+    type Args = ArchiveCreateArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "archive_create";
+
+    async fn definition(&self, _prompt: String) -> rig::completion::ToolDefinition {
+        rig::completion::ToolDefinition {
+            name: Self::NAME.into(),
+            description: "Create a zip, tar or tar.gz archive of workspace files and directories. Paths inside it are relative to the current directory; .env and other protected files are left out".into(),
+            parameters: serde_json::to_value(schemars::schema_for!(ArchiveCreateArgs)).unwrap(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        let archive = get_path(&args.path)?;
+        Format::from_path(&archive)?;
+        if archive.exists() && !args.overwrite {
+            return Err(destination_exists(&archive));
+        }
+        let sources = args
+            .sources
+            .iter()
+            .map(|s| get_path(s))
+            .collect::<Result<Vec<_>, _>>()?;
+        let root = std::env::current_dir()?;
+        let count = tokio::task::spawn_blocking(move || {
+            let files = collect(&sources, &root, &archive)?;
+            crate::checkpoint::save(&archive);
+            create(&archive, &files)?;
+            Ok::<_, ToolError>(files.len())
+        })
+        .await??;
+        Ok(format!("Created {} with {} file(s)", args.path, count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_path() {
+        let dest = Path::new("/work/out");
+        assert_eq!(
+            entry_path(dest, Path::new("./a/b.txt")),
+            Some(PathBuf::from("/work/out/a/b.txt"))
+        );
+        assert_eq!(entry_path(dest, Path::new("../evil.sh")), None);
+        assert_eq!(entry_path(dest, Path::new("a/../../evil.sh")), None);
+        assert_eq!(entry_path(dest, Path::new("/etc/passwd")), None);
+    }
+
+    #[test]
+    fn test_create_and_extract() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("pkg/bin")).unwrap();
+        std::fs::write(root.join("pkg/bin/run.sh"), "echo hi").unwrap();
+        std::fs::write(root.join("pkg/.env"), "TOKEN=secret").unwrap();
+
+        for name in ["out.zip", "out.tar.gz"] {
+            let archive = root.join(name);
+            let files = collect(&[root.join("pkg")], root, &archive).unwrap();
+            assert_eq!(files.len(), 1);
+            assert_eq!(files[0].1, PathBuf::from("pkg/bin/run.sh"));
+            create(&archive, &files).unwrap();
+
+            let dest = root.join(format!("x-{}", name));
+            assert_eq!(extract(&archive, &dest, false).unwrap(), (1, 0));
+            let extracted = std::fs::read_to_string(dest.join("pkg/bin/run.sh")).unwrap();
+            assert_eq!(extracted, "echo hi");
+            assert!(extract(&archive, &dest, false).is_err());
+            assert!(extract(&archive, &dest, true).is_ok());
+        }
+
+        let evil = root.join("evil.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&evil).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("../escaped.txt", options).unwrap();
+        zip.write_all(b"x").unwrap();
+        zip.finish().unwrap();
+        assert!(extract(&evil, &root.join("x-evil"), false).is_err());
+        assert!(!root.join("escaped.txt").exists());
+    }
+}
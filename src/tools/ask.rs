@@ -0,0 +1,137 @@
+//! `ask_user`: the model asks a clarifying question in the middle of a turn.
+//! Interactive sessions prompt for the answer. Recipes and quiet runs have no
+//! one to ask, so the answer comes from the recipe's `answers:`, and without
+//! a matching one the run stops rather than letting the model guess.
+
+use super::ToolError;
+use crate::Output;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Key of the answer used when no other key matches.
+pub const DEFAULT_ANSWER: &str = "*";
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct AskUserArgs {
+    /// One specific question, listing the options if there are any
+    pub question: String,
+}
+
+/// Answers for runs without a user, and the question none of them matched.
+#[derive(Debug, Default)]
+pub struct Answers {
+    answers: HashMap<String, String>,
+    unanswered: Mutex<Option<String>>,
+}
+
+impl Answers {
+    pub fn new(answers: HashMap<String, String>) -> Self {
+        Self {
+            answers,
+            unanswered: Mutex::new(None),
+        }
+    }
+
+    /// The answer whose key occurs in `question`, ignoring case. The longest
+    /// matching key wins, then [`DEFAULT_ANSWER`].
+    pub fn find(&self, question: &str) -> Option<&str> {
+        let question = question.to_lowercase();
+        self.answers
+            .iter()
+            .filter(|(key, _)| {
+                key.as_str() != DEFAULT_ANSWER && question.contains(&key.to_lowercase())
+            })
+            .max_by_key(|(key, _)| key.len())
+            .or_else(|| self.answers.get_key_value(DEFAULT_ANSWER))
+            .map(|(_, answer)| answer.as_str())
+    }
+
+    /// Forgets the unanswered question of the previous prompt.
+    pub fn reset(&self) {
+        *self.unanswered.lock().unwrap() = None;
+    }
+
+    /// The question that ended the turn, if one went unanswered.
+    pub fn unanswered(&self) -> Option<String> {
+        self.unanswered.lock().unwrap().clone()
+    }
+}
+
+pub struct AskUser {
+    pub output: Arc<dyn Output>,
+    /// `None` in interactive sessions, where the user answers.
+    pub answers: Option<Arc<Answers>>,
+}
+
+impl rig::tool::Tool for AskUser {
+    type Args = AskUserArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "ask_user";
+
+    async fn definition(&self, _prompt: String) -> rig::completion::ToolDefinition {
+        rig::completion::ToolDefinition {
+            name: Self::NAME.into(),
+            description: "Ask the user a clarifying question and wait for the answer. Only for decisions the code and the request leave open, such as which of two approaches they want; not for permission to make changes".into(),
+            parameters: serde_json::to_value(schemars::schema_for!(AskUserArgs)).unwrap(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        let question = args.question.trim();
+        if question.is_empty() {
+            return Err(ToolError::Generic("The question is empty".into()));
+        }
+        let Some(answers) = &self.answers else {
+            self.output.display_system(&format!("❓ {}", question));
+            let answer = self.output.get_user_input("answer> ").await;
+            return Ok(match answer.trim() {
+                "" => "The user didn't answer. Make a reasonable choice and say which one.".into(),
+                answer => answer.to_string(),
+            });
+        };
+        match answers.find(question) {
+            Some(answer) => {
+                self.output
+                    .display_system(&format!("❓ {}\n↳ {} (from answers)", question, answer));
+                Ok(answer.to_string())
+            }
+            None => {
+                *answers.unanswered.lock().unwrap() = Some(question.to_string());
+                Err(ToolError::Generic(
+                    "Nobody can answer in this run, so it stops here".into(),
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_answer() {
+        let answers = Answers::new(HashMap::from([
+            ("database".to_string(), "PostgreSQL".to_string()),
+            ("test database".to_string(), "SQLite".to_string()),
+            ("*".to_string(), "Pick the simplest option".to_string()),
+        ]));
+        assert_eq!(
+            answers.find("Which Database should I use?"),
+            Some("PostgreSQL")
+        );
+        assert_eq!(
+            answers.find("Which test database should I use?"),
+            Some("SQLite")
+        );
+        assert_eq!(
+            answers.find("Tabs or spaces?"),
+            Some("Pick the simplest option")
+        );
+        assert_eq!(Answers::default().find("Tabs or spaces?"), None);
+    }
+}
@@ -0,0 +1,298 @@
+use super::{get_readable_path, ToolError};
+#[cfg(feature = "parquet")]
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use rig_derive::rig_tool;
+use serde_json::Value;
+use std::io::BufRead;
+use std::path::Path;
+
+const DEFAULT_ROWS: usize = 10;
+const MAX_ROWS: usize = 100;
+const MAX_CELL_CHARS: usize = 40;
+/// Rows sampled to infer column types of text formats.
+const SAMPLE_ROWS: usize = 200;
+
+struct Preview {
+    /// `(name, type)` per column.
+    schema: Vec<(String, String)>,
+    row_count: u64,
+    rows: Vec<Vec<String>>,
+}
+
+fn err(e: impl std::fmt::Display) -> ToolError {
+    ToolError::Generic(e.to_string())
+}
+
+fn infer_type(values: &[&str]) -> &'static str {
+    let values: Vec<&str> = values.iter().copied().filter(|v| !v.is_empty()).collect();
+    if values.is_empty() {
+        "empty"
+    } else if values.iter().all(|v| v.parse::<i64>().is_ok()) {
+        "int"
+    } else if values.iter().all(|v| v.parse::<f64>().is_ok()) {
+        "float"
+    } else if values
+        .iter()
+        .all(|v| v.eq_ignore_ascii_case("true") || v.eq_ignore_ascii_case("false"))
+    {
+        "bool"
+    } else {
+        "string"
+    }
+}
+
+fn preview_delimited(path: &Path, delimiter: u8, n: usize) -> Result<Preview, ToolError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_path(path)
+        .map_err(err)?;
+    let headers: Vec<String> = reader.headers().map_err(err)?.iter().map(String::from).collect();
+    let mut sample: Vec<Vec<String>> = Vec::new();
+    let mut row_count = 0u64;
+    for record in reader.records() {
+        let record = record.map_err(err)?;
+        if sample.len() < SAMPLE_ROWS {
+            sample.push(record.iter().map(String::from).collect());
+        }
+        row_count += 1;
+    }
+    let schema = headers
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let column: Vec<&str> = sample
+                .iter()
+                .map(|r| r.get(i).map(String::as_str).unwrap_or(""))
+                .collect();
+            (name.clone(), infer_type(&column).to_string())
+        })
+        .collect();
+    sample.truncate(n);
+    Ok(Preview {
+        schema,
+        row_count,
+        rows: sample,
+    })
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "int",
+        Value::Number(_) => "float",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn preview_jsonl(path: &Path, n: usize) -> Result<Preview, ToolError> {
+    let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut schema: Vec<(String, String)> = Vec::new();
+    let mut objects: Vec<serde_json::Map<String, Value>> = Vec::new();
+    let mut row_count = 0u64;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        row_count += 1;
+        if objects.len() >= SAMPLE_ROWS {
+            continue;
+        }
+        let value: Value = serde_json::from_str(&line)
+            .map_err(|e| ToolError::Generic(format!("Line {}: {}", i + 1, e)))?;
+        let Value::Object(object) = value else {
+            return Err(ToolError::Generic(format!("Line {} is not a JSON object", i + 1)));
+        };
+        for (key, value) in &object {
+            let kind = json_type(value);
+            match schema.iter_mut().find(|(name, _)| name == key) {
+                None => schema.push((key.clone(), kind.to_string())),
+                Some((_, t)) if t == "null" => *t = kind.to_string(),
+                Some((_, t)) if t != kind && kind != "null" => *t = "mixed".to_string(),
+                _ => {}
+            }
+        }
+        objects.push(object);
+    }
+    let rows = objects
+        .iter()
+        .take(n)
+        .map(|o| {
+            schema
+                .iter()
+                .map(|(key, _)| match o.get(key) {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(v) => v.to_string(),
+                    None => String::new(),
+                })
+                .collect()
+        })
+        .collect();
+    Ok(Preview {
+        schema,
+        row_count,
+        rows,
+    })
+}
+
+#[cfg(feature = "parquet")]
+fn preview_parquet(path: &Path, n: usize) -> Result<Preview, ToolError> {
+    let reader = SerializedFileReader::new(std::fs::File::open(path)?).map_err(err)?;
+    let metadata = reader.metadata().file_metadata();
+    let schema = metadata
+        .schema_descr()
+        .columns()
+        .iter()
+        .map(|col| {
+            let kind = match col.logical_type() {
+                Some(logical) => format!("{} ({:?})", col.physical_type(), logical),
+                None => col.physical_type().to_string(),
+            };
+            (col.path().string(), kind)
+        })
+        .collect();
+    let row_count = metadata.num_rows().max(0) as u64;
+    let mut rows = Vec::new();
+    for row in reader.get_row_iter(None).map_err(err)?.take(n) {
+        let row = row.map_err(err)?;
+        rows.push(
+            row.get_column_iter()
+                .map(|(_, field)| field.to_string().trim_matches('"').to_string())
+                .collect(),
+        );
+    }
+    Ok(Preview {
+        schema,
+        row_count,
+        rows,
+    })
+}
+
+#[cfg(not(feature = "parquet"))]
+fn preview_parquet(_path: &Path, _n: usize) -> Result<Preview, ToolError> {
+    Err(ToolError::Generic(
+        "Parquet previews need picocode built with `--features parquet`".into(),
+    ))
+}
+
+fn truncate_cell(value: &str) -> String {
+    let value = value.replace(['\n', '\r', '\t'], " ");
+    if value.chars().count() > MAX_CELL_CHARS {
+        let cut: String = value.chars().take(MAX_CELL_CHARS - 1).collect();
+        format!("{}…", cut)
+    } else {
+        value
+    }
+}
+
+fn render(preview: &Preview) -> String {
+    let mut out = format!(
+        "{} rows, {} columns\n\nSchema:\n",
+        preview.row_count,
+        preview.schema.len()
+    );
+    for (name, kind) in &preview.schema {
+        out.push_str(&format!("  {}: {}\n", name, kind));
+    }
+    if preview.rows.is_empty() {
+        return out.trim_end().to_string();
+    }
+
+    let header: Vec<String> = preview.schema.iter().map(|(n, _)| truncate_cell(n)).collect();
+    let rows: Vec<Vec<String>> = preview
+        .rows
+        .iter()
+        .map(|r| {
+            (0..header.len())
+                .map(|i| truncate_cell(r.get(i).map(String::as_str).unwrap_or("")))
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|r| r[i].chars().count())
+                .chain([header[i].chars().count()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |cells: &[String]| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("{:w$}", c, w = *w))
+            .collect();
+        format!("| {} |\n", padded.join(" | "))
+    };
+
+    out.push_str(&format!("\nFirst {} rows:\n", rows.len()));
+    out.push_str(&line(&header));
+    let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    out.push_str(&line(&rule));
+    for row in &rows {
+        out.push_str(&line(row));
+    }
+    out.trim_end().to_string()
+}
+
+fn preview(path: &Path, n: usize) -> Result<Preview, ToolError> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    match ext.as_str() {
+        "csv" => preview_delimited(path, b',', n),
+        "tsv" | "tab" => preview_delimited(path, b'\t', n),
+        "jsonl" | "ndjson" => preview_jsonl(path, n),
+        "parquet" | "pq" => preview_parquet(path, n),
+        _ => Err(ToolError::Generic(
+            "Unsupported format (csv, tsv, jsonl, ndjson, parquet)".into(),
+        )),
+    }
+}
+
+#[rig_tool(
+    description = "Preview a CSV/TSV/JSONL/Parquet file: schema, row count, and the first rows as a table (rows=0 for the default 10)",
+    required(path, rows)
+)]
+pub async fn data_preview(path: String, rows: u64) -> Result<String, ToolError> {
+    let p = get_readable_path(&path)?;
+    let n = match rows as usize {
+        0 => DEFAULT_ROWS,
+        n => n.min(MAX_ROWS),
+    };
+    let preview = tokio::task::spawn_blocking(move || preview(&p, n)).await??;
+    Ok(render(&preview))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_csv_and_jsonl() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv_path = dir.path().join("people.csv");
+        std::fs::write(&csv_path, "name,age,score\nada,36,9.5\nlinus,,7\nbob,41,8\n").unwrap();
+        let out = render(&preview(&csv_path, 2).unwrap());
+        assert_eq!(
+            out,
+            "3 rows, 3 columns\n\nSchema:\n  name: string\n  age: int\n  score: float\n\nFirst 2 rows:\n| name  | age | score |\n| ----- | --- | ----- |\n| ada   | 36  | 9.5   |\n| linus |     | 7     |"
+        );
+
+        let jsonl_path = dir.path().join("events.jsonl");
+        std::fs::write(&jsonl_path, "{\"id\": 1, \"tag\": null}\n\n{\"id\": 2, \"tag\": \"x\"}\n").unwrap();
+        let preview = preview(&jsonl_path, 10).unwrap();
+        assert_eq!(preview.row_count, 2);
+        assert_eq!(
+            preview.schema,
+            vec![("id".to_string(), "int".to_string()), ("tag".to_string(), "string".to_string())]
+        );
+    }
+}
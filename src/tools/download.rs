@@ -0,0 +1,179 @@
+use super::{destination_exists, format_size, get_path, stale_destination, ToolError};
+use crate::config::NetworkPolicy;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+/// Limit when a call doesn't give `max_bytes`.
+const DEFAULT_MAX_BYTES: u64 = 100 * 1024 * 1024;
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct DownloadFileArgs {
+    /// http(s) URL to fetch
+    pub url: String,
+    /// Destination path in the workspace
+    pub path: String,
+    /// Refuse downloads larger than this many bytes (default 100 MB)
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+    /// Expected SHA-256 as hex; the file is only kept if it matches
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// Replace an existing file at path
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+pub struct DownloadFile {
+    /// `tool_config.bash.network`: downloads are refused with `deny`.
+    network: NetworkPolicy,
+}
+
+impl DownloadFile {
+    pub fn new(network: NetworkPolicy) -> Self {
+        Self { network }
+    }
+
+    /// Streams `url` into `dst`, returning the size and SHA-256 digest.
+    async fn fetch(&self, url: &str, dst: &Path, max: u64) -> Result<(u64, String), ToolError> {
+        let too_large = || {
+            ToolError::Generic(format!(
+                "Download is larger than {} (raise max_bytes)",
+                format_size(max)
+            ))
+        };
+        let client = crate::network::client().map_err(err)?;
+        let mut response = client
+            .get(url)
+            .send()
+            .await
+            .map_err(err)?
+            .error_for_status()
+            .map_err(err)?;
+        if response.content_length().is_some_and(|len| len > max) {
+            return Err(too_large());
+        }
+        let mut file = fs::File::create(dst).await?;
+        let mut hasher = Sha256::new();
+        let mut size = 0u64;
+        while let Some(chunk) = response.chunk().await.map_err(err)? {
+            size += chunk.len() as u64;
+            if size > max {
+                return Err(too_large());
+            }
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        Ok((size, format!("{:x}", hasher.finalize())))
+    }
+}
+
+fn err(e: impl std::fmt::Display) -> ToolError {
+    ToolError::Generic(e.to_string())
+}
+
+/// Lowercase hex digest from `abc...` or `sha256:ABC...`.
+fn parse_sha256(digest: &str) -> Result<String, ToolError> {
+    let hex = digest
+        .trim()
+        .trim_start_matches("sha256:")
+        .to_ascii_lowercase();
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(err(format!("{} is not a SHA-256 hex digest", digest)));
+    }
+    Ok(hex)
+}
+
+impl rig::tool::Tool for DownloadFile {
+    type Args = DownloadFileArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "download_file";
+
+    async fn definition(&self, _prompt: String) -> rig::completion::ToolDefinition {
+        rig::completion::ToolDefinition {
+            name: Self::NAME.into(),
+            description: "Download a URL to a file in the workspace, optionally checking its SHA-256. Returns the size and digest".into(),
+            parameters: serde_json::to_value(schemars::schema_for!(DownloadFileArgs)).unwrap(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        if !args.url.starts_with("https://") && !args.url.starts_with("http://") {
+            return Err(err("Only http and https URLs can be downloaded"));
+        }
+        if self.network == NetworkPolicy::Deny {
+            return Err(err(
+                "Downloads are disabled (tool_config.bash.network is deny)",
+            ));
+        }
+        let dst = get_path(&args.path)?;
+        if dst.exists() && !args.overwrite {
+            return Err(destination_exists(&dst));
+        }
+        if crate::freshness::is_stale(&dst) {
+            return Err(stale_destination(&dst));
+        }
+        let expected = args
+            .sha256
+            .as_deref()
+            .filter(|s| !s.is_empty())
+            .map(parse_sha256)
+            .transpose()?;
+        let name = dst
+            .file_name()
+            .ok_or_else(|| err(format!("{} is not a file path", args.path)))?;
+        if let Some(parent) = dst.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        // Nothing appears at `path` until the download is complete and verified
+        let part = dst.with_file_name(format!("{}.part", name.to_string_lossy()));
+        let max = args.max_bytes.unwrap_or(DEFAULT_MAX_BYTES);
+        let (size, digest) = match self.fetch(&args.url, &part, max).await {
+            Ok(result) => result,
+            Err(e) => {
+                let _ = fs::remove_file(&part).await;
+                return Err(e);
+            }
+        };
+        if let Some(expected) = expected {
+            if expected != digest {
+                let _ = fs::remove_file(&part).await;
+                return Err(err(format!(
+                    "Checksum mismatch: expected sha256 {}, got {}; nothing was saved",
+                    expected, digest
+                )));
+            }
+        }
+        crate::checkpoint::save(&dst);
+        fs::rename(&part, &dst).await?;
+        crate::freshness::record(&dst);
+        Ok(format!(
+            "Downloaded {} to {} (sha256 {})",
+            format_size(size),
+            args.path,
+            digest
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sha256() {
+        let digest = "E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855";
+        assert_eq!(
+            parse_sha256(&format!("sha256:{}", digest)).unwrap(),
+            digest.to_ascii_lowercase()
+        );
+        assert!(parse_sha256("abc123").is_err());
+        assert!(parse_sha256(&"g".repeat(64)).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use super::{format_size, ToolError};
+use rig_derive::rig_tool;
+use std::path::Path;
+
+/// Toolchains reported by `env_info`, as `(binary, version flag)`.
+const TOOLCHAINS: &[(&str, &str)] = &[
+    ("rustc", "--version"),
+    ("cargo", "--version"),
+    ("node", "--version"),
+    ("npm", "--version"),
+    ("python3", "--version"),
+    ("go", "version"),
+    ("java", "-version"),
+    ("docker", "--version"),
+    ("git", "--version"),
+];
+
+/// Environment variable values are cut off after this many characters.
+const MAX_VALUE_CHARS: usize = 200;
+
+fn version(tool: &str, flag: &str) -> Option<String> {
+    // The full path, so `npm.cmd` and the like are found on Windows
+    let path = crate::platform::which(tool)?;
+    // `java -version` prints to stderr
+    let output = duct::cmd(path, [flag])
+        .stderr_to_stdout()
+        .stdout_capture()
+        .unchecked()
+        .run()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(String::from)
+}
+
+/// `NAME=value` lines, with values of likely secrets replaced.
+fn env_lines(vars: impl Iterator<Item = (String, String)>) -> Vec<String> {
+    let mut vars: Vec<(String, String)> = vars.collect();
+    vars.sort();
+    vars.into_iter()
+        .map(|(name, value)| {
+            let value = if crate::secrets::is_secret_var(&name, &value) {
+                "<redacted>".to_string()
+            } else if value.chars().count() > MAX_VALUE_CHARS {
+                let cut: String = value.chars().take(MAX_VALUE_CHARS).collect();
+                format!("{}...", cut)
+            } else {
+                value
+            };
+            format!("{}={}", name, value)
+        })
+        .collect()
+}
+
+fn system_lines(cwd: &Path) -> Vec<String> {
+    let os = sysinfo::System::long_os_version().unwrap_or_else(|| std::env::consts::OS.into());
+    let kernel = sysinfo::System::kernel_version().unwrap_or_default();
+    let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut lines = vec![format!(
+        "OS: {} (kernel {}), {}, {} CPUs",
+        os,
+        kernel,
+        std::env::consts::ARCH,
+        cpus
+    )];
+
+    let mut system = sysinfo::System::new();
+    system.refresh_memory();
+    lines.push(format!(
+        "Memory: {} available of {}",
+        format_size(system.available_memory()),
+        format_size(system.total_memory())
+    ));
+
+    // The disk holding the working directory is the one with the longest mount point
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    if let Some(disk) = disks
+        .list()
+        .iter()
+        .filter(|d| cwd.starts_with(d.mount_point()))
+        .max_by_key(|d| d.mount_point().as_os_str().len())
+    {
+        lines.push(format!(
+            "Disk: {} free of {} ({})",
+            format_size(disk.available_space()),
+            format_size(disk.total_space()),
+            disk.mount_point().display()
+        ));
+    }
+    if let Ok(shell) = std::env::var("SHELL") {
+        lines.push(format!("Shell: {}", shell));
+    }
+    lines
+}
+
+#[rig_tool(
+    description = "Describe the machine: OS, architecture, CPUs, memory, free disk space, and installed toolchain versions (rustc, cargo, node, npm, python3, go, java, docker, git). With include_env, also list environment variables, with secrets redacted",
+    required(include_env)
+)]
+pub async fn env_info(include_env: bool) -> Result<String, ToolError> {
+    let cwd = std::env::current_dir()?;
+    tokio::task::spawn_blocking(move || {
+        let mut out = system_lines(&cwd);
+        out.push(String::new());
+        out.push("Toolchains:".into());
+        for (tool, flag) in TOOLCHAINS {
+            let version = version(tool, flag).unwrap_or_else(|| "not installed".into());
+            out.push(format!("  {}: {}", tool, version));
+        }
+        if include_env {
+            out.push(String::new());
+            out.push("Environment (secrets redacted):".into());
+            for line in env_lines(std::env::vars()) {
+                out.push(format!("  {}", line));
+            }
+        }
+        Ok(out.join("\n"))
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_lines() {
+        let vars = [
+            ("PATH", "/usr/bin".to_string()),
+            ("OPENAI_API_KEY", "sk-123".to_string()),
+            ("LONG", "x".repeat(300)),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v));
+        let lines = env_lines(vars);
+        assert_eq!(lines[0], format!("LONG={}...", "x".repeat(MAX_VALUE_CHARS)));
+        assert_eq!(lines[1], "OPENAI_API_KEY=<redacted>");
+        assert_eq!(lines[2], "PATH=/usr/bin");
+    }
+}
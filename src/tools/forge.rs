@@ -0,0 +1,219 @@
+//! What the GitHub and GitLab tools share: a JSON REST client, list paging
+//! and the git facts (remote, branch) both look up.
+
+use super::ToolError;
+use reqwest::header::HeaderMap;
+use reqwest::Method;
+use serde_json::Value;
+
+/// Items a list tool returns when no limit is given.
+const DEFAULT_LIMIT: u32 = 30;
+/// Most items either API returns in one page.
+const MAX_PER_PAGE: u32 = 100;
+
+/// A JSON REST API under `base`, sending `headers` (its authentication) with
+/// every request.
+pub(crate) struct Api {
+    name: &'static str,
+    client: reqwest::Client,
+    base: String,
+    headers: HeaderMap,
+}
+
+impl Api {
+    /// `None` if the HTTP client can't be built from the network settings.
+    pub(crate) fn new(name: &'static str, base: String, headers: HeaderMap) -> Option<Self> {
+        Some(Self {
+            name,
+            client: crate::network::client().ok()?,
+            base,
+            headers,
+        })
+    }
+
+    /// GETs `path` with `query`, percent-encoding its values.
+    pub(crate) async fn get(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<Value, ToolError> {
+        self.send(Method::GET, path, query, None).await
+    }
+
+    pub(crate) async fn post(&self, path: &str, body: Value) -> Result<Value, ToolError> {
+        self.send(Method::POST, path, &[], Some(body)).await
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<Value>,
+    ) -> Result<Value, ToolError> {
+        let mut request = self
+            .client
+            .request(method, format!("{}{}", self.base, path))
+            .headers(self.headers.clone())
+            .query(query);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ToolError::Generic(e.to_string()))?;
+        let status = response.status();
+        let json = response.json::<Value>().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            return Err(ToolError::Generic(format!(
+                "{} API {}: {}",
+                self.name,
+                status,
+                error_message(&json)
+            )));
+        }
+        Ok(json)
+    }
+}
+
+/// The message of an error response: GitHub and GitLab put it in `message`,
+/// GitLab sometimes in `error` or as an object of field errors.
+fn error_message(json: &Value) -> String {
+    match (&json["message"], &json["error"]) {
+        (Value::String(message), _) | (_, Value::String(message)) => message.clone(),
+        (Value::Null, _) => "request failed".into(),
+        (message, _) => message.to_string(),
+    }
+}
+
+/// The `per_page` query parameter for a list of at most `limit` items.
+pub(crate) fn per_page(limit: Option<u32>) -> (&'static str, String) {
+    (
+        "per_page",
+        limit.unwrap_or(DEFAULT_LIMIT).min(MAX_PER_PAGE).to_string(),
+    )
+}
+
+/// Label names of an issue, given as strings (GitLab) or objects (GitHub).
+pub(crate) fn labels(item: &Value) -> String {
+    item["labels"]
+        .as_array()
+        .map(|labels| {
+            labels
+                .iter()
+                .filter_map(|l| l.as_str().or_else(|| l["name"].as_str()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default()
+}
+
+/// One line per listed item, or "none".
+pub(crate) fn list(items: &Value, line: impl Fn(&Value) -> String) -> String {
+    let lines: Vec<String> = items
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|item| line(item).trim_end().to_string())
+        .collect();
+    if lines.is_empty() {
+        "none".into()
+    } else {
+        lines.join("\n")
+    }
+}
+
+/// URL of the `origin` remote of the current repository.
+pub(crate) fn origin_url() -> Option<String> {
+    let output = std::process::Command::new("git")
+        .args(["remote", "get-url", "origin"])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Extracts `owner/repo` (or `group/subgroup/project`) from an https or ssh remote on `host`.
+pub(crate) fn repo_from_remote(url: &str, host: &str) -> Option<String> {
+    let rest = url.strip_prefix(&format!("git@{}:", host)).or_else(|| {
+        let (_, after_scheme) = url.split_once("://")?;
+        let after_user = after_scheme
+            .rsplit_once('@')
+            .map_or(after_scheme, |(_, r)| r);
+        after_user.strip_prefix(host)?.strip_prefix(['/', ':'])
+    })?;
+    let path = rest.trim_end_matches('/').trim_end_matches(".git");
+    path.contains('/').then(|| path.to_string())
+}
+
+pub(crate) fn current_branch() -> Option<String> {
+    let output = std::process::Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .output()
+        .ok()?;
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && branch != "HEAD").then_some(branch)
+}
+
+macro_rules! definition {
+    ($args:ty, $description:expr) => {
+        async fn definition(&self, _prompt: String) -> rig::completion::ToolDefinition {
+            rig::completion::ToolDefinition {
+                name: Self::NAME.into(),
+                description: $description.into(),
+                parameters: serde_json::to_value(schemars::schema_for!($args)).unwrap(),
+            }
+        }
+    };
+}
+pub(crate) use definition;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_repo_from_remote() {
+        let host = "github.com";
+        assert_eq!(
+            repo_from_remote("git@github.com:jondot/picocode.git", host).as_deref(),
+            Some("jondot/picocode")
+        );
+        assert_eq!(
+            repo_from_remote("https://x-token@github.com/jondot/picocode", host).as_deref(),
+            Some("jondot/picocode")
+        );
+        assert_eq!(
+            repo_from_remote(
+                "ssh://git@gitlab.acme.io/infra/tools/cli.git",
+                "gitlab.acme.io"
+            )
+            .as_deref(),
+            Some("infra/tools/cli")
+        );
+        assert_eq!(repo_from_remote("https://gitlab.com/a/b", host), None);
+    }
+
+    #[test]
+    fn test_response_helpers() {
+        assert_eq!(error_message(&json!({"message": "Not Found"})), "Not Found");
+        assert_eq!(
+            error_message(&json!({"error": "invalid_token"})),
+            "invalid_token"
+        );
+        assert_eq!(
+            error_message(&json!({"message": {"title": ["is missing"]}})),
+            r#"{"title":["is missing"]}"#
+        );
+        assert_eq!(error_message(&Value::Null), "request failed");
+        assert_eq!(per_page(Some(500)), ("per_page", "100".into()));
+
+        let issue = json!({"labels": [{"name": "bug"}, "ui"]});
+        assert_eq!(labels(&issue), "bug, ui");
+        assert_eq!(list(&json!([]), |_| String::new()), "none");
+    }
+}
@@ -0,0 +1,132 @@
+//! Read-only git tools for reviewing changes: `git_diff` and `git_log`.
+
+use super::{get_path, run_expression, ToolError};
+use rig_derive::rig_tool;
+
+/// Output is cut off after this many bytes.
+const MAX_OUTPUT_BYTES: usize = 60_000;
+/// Commits `git_log` lists when `max_count` is 0.
+const DEFAULT_LOG_COMMITS: u64 = 20;
+
+/// Refuses refs that git would parse as options (e.g. `--output=file`).
+fn check_ref(value: &str) -> Result<(), ToolError> {
+    if value.starts_with('-') {
+        return Err(ToolError::Generic(format!(
+            "Invalid revision '{}': must not start with '-'",
+            value
+        )));
+    }
+    Ok(())
+}
+
+/// Appends the revision and pathspec, validating both.
+fn push_target(args: &mut Vec<String>, rev: &str, path: &str) -> Result<(), ToolError> {
+    if !rev.is_empty() {
+        check_ref(rev)?;
+        args.push(rev.to_string());
+    }
+    if !path.is_empty() {
+        args.push("--".into());
+        args.push(get_path(path)?.display().to_string());
+    }
+    Ok(())
+}
+
+fn diff_args(base: &str, staged: bool, path: &str) -> Result<Vec<String>, ToolError> {
+    // External diff drivers and textconv filters run commands from the repository config
+    let mut args: Vec<String> = [
+        "diff",
+        "--no-color",
+        "--no-ext-diff",
+        "--no-textconv",
+        "--stat",
+        "--patch",
+    ]
+    .map(String::from)
+    .to_vec();
+    if staged {
+        args.push("--cached".into());
+    }
+    push_target(&mut args, base, path)?;
+    Ok(args)
+}
+
+fn log_args(range: &str, path: &str, max_count: u64) -> Result<Vec<String>, ToolError> {
+    let count = if max_count == 0 {
+        DEFAULT_LOG_COMMITS
+    } else {
+        max_count
+    };
+    let mut args: Vec<String> = vec![
+        "log".into(),
+        "--no-color".into(),
+        "--date=short".into(),
+        "--format=%h %ad %an: %s".into(),
+        format!("-n{}", count),
+    ];
+    push_target(&mut args, range, path)?;
+    Ok(args)
+}
+
+fn truncated(mut output: String) -> String {
+    if output.len() <= MAX_OUTPUT_BYTES {
+        return output;
+    }
+    let cut = (0..=MAX_OUTPUT_BYTES)
+        .rev()
+        .find(|&i| output.is_char_boundary(i))
+        .unwrap_or(0);
+    output.truncate(cut);
+    output.push_str("\n... output truncated (narrow it with a path)");
+    output
+}
+
+#[rig_tool(
+    description = "Show uncommitted changes as a diffstat and patch. base: compare against a ref or range instead (e.g. main, HEAD~3, main...HEAD). staged: only changes added to the index. path: limit to a file or directory",
+    required(base, staged, path)
+)]
+pub async fn git_diff(base: String, staged: bool, path: String) -> Result<String, ToolError> {
+    let output = run_expression(duct::cmd("git", diff_args(&base, staged, &path)?)).await?;
+    if output.trim().is_empty() {
+        return Ok("No changes".into());
+    }
+    Ok(truncated(output))
+}
+
+#[rig_tool(
+    description = "List commits (hash, date, author, subject), newest first. range: a ref or range (e.g. main..HEAD), empty for the current branch. path: only commits touching it. max_count: 0 = 20",
+    required(range, path, max_count)
+)]
+pub async fn git_log(range: String, path: String, max_count: u64) -> Result<String, ToolError> {
+    let output = run_expression(duct::cmd("git", log_args(&range, &path, max_count)?)).await?;
+    if output.trim().is_empty() {
+        return Ok("No commits".into());
+    }
+    Ok(truncated(output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args() {
+        assert_eq!(
+            diff_args("main...HEAD", true, "").unwrap(),
+            [
+                "diff",
+                "--no-color",
+                "--no-ext-diff",
+                "--no-textconv",
+                "--stat",
+                "--patch",
+                "--cached",
+                "main...HEAD"
+            ]
+        );
+        assert!(diff_args("--output=/tmp/x", false, "").is_err());
+        assert_eq!(log_args("", "", 0).unwrap().last().unwrap(), "-n20");
+        assert!(log_args("-p", "", 5).is_err());
+        assert!(log_args("", "../outside", 5).is_err());
+    }
+}
@@ -0,0 +1,256 @@
+use super::forge::{
+    current_branch, definition, labels, list, origin_url, per_page, repo_from_remote, Api,
+};
+use super::ToolError;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+const DEFAULT_API_URL: &str = "https://api.github.com";
+const MAX_COMMENTS: usize = 20;
+
+/// GitHub REST client for the current repository, authenticated with `GITHUB_TOKEN`.
+pub struct GitHub {
+    api: Api,
+    repo: String,
+}
+
+impl GitHub {
+    /// Returns a client when `GITHUB_TOKEN` (or `GH_TOKEN`) is set and the repository
+    /// is known from `GITHUB_REPOSITORY` or the `origin` remote.
+    /// `GITHUB_API_URL` points it at GitHub Enterprise.
+    pub fn from_env() -> Option<Arc<Self>> {
+        let token = std::env::var("GITHUB_TOKEN")
+            .or_else(|_| std::env::var("GH_TOKEN"))
+            .ok()?;
+        let api_url =
+            std::env::var("GITHUB_API_URL").unwrap_or_else(|_| DEFAULT_API_URL.to_string());
+        let repo = std::env::var("GITHUB_REPOSITORY").ok().or_else(|| {
+            let host = if api_url == DEFAULT_API_URL {
+                "github.com".to_string()
+            } else {
+                reqwest::Url::parse(&api_url).ok()?.host_str()?.to_string()
+            };
+            repo_from_remote(&origin_url()?, &host)
+        })?;
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, format!("Bearer {}", token).parse().ok()?);
+        headers.insert(
+            ACCEPT,
+            HeaderValue::from_static("application/vnd.github+json"),
+        );
+        headers.insert(
+            "x-github-api-version",
+            HeaderValue::from_static("2022-11-28"),
+        );
+        let base = format!("{}/repos/{}", api_url.trim_end_matches('/'), repo);
+        Some(Arc::new(Self {
+            api: Api::new("GitHub", base, headers)?,
+            repo,
+        }))
+    }
+
+    pub fn repo(&self) -> &str {
+        &self.repo
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct IssueGetArgs {
+    pub number: u64,
+}
+
+pub struct GhIssueGet(pub Arc<GitHub>);
+
+impl rig::tool::Tool for GhIssueGet {
+    type Args = IssueGetArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "gh_issue_get";
+
+    definition!(
+        IssueGetArgs,
+        "Get a GitHub issue or pull request with its comments"
+    );
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        let path = format!("/issues/{}", args.number);
+        let issue = self.0.api.get(&path, &[]).await?;
+        let comments = self
+            .0
+            .api
+            .get(
+                &format!("{}/comments", path),
+                &[per_page(Some(MAX_COMMENTS as u32))],
+            )
+            .await?;
+
+        let mut out = format!(
+            "#{} {} [{}] by {}\n{}\nlabels: {}\n\n{}",
+            args.number,
+            issue["title"].as_str().unwrap_or_default(),
+            issue["state"].as_str().unwrap_or_default(),
+            issue["user"]["login"].as_str().unwrap_or_default(),
+            issue["html_url"].as_str().unwrap_or_default(),
+            labels(&issue),
+            issue["body"].as_str().unwrap_or("(no description)").trim()
+        );
+        if let Some(comments) = comments.as_array().filter(|c| !c.is_empty()) {
+            out.push_str("\n\nComments:");
+            for c in comments.iter().take(MAX_COMMENTS) {
+                out.push_str(&format!(
+                    "\n- {}: {}",
+                    c["user"]["login"].as_str().unwrap_or_default(),
+                    c["body"].as_str().unwrap_or_default().trim()
+                ));
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct IssueListArgs {
+    /// open, closed, or all (default open)
+    #[serde(default)]
+    pub state: Option<String>,
+    /// Comma-separated label names
+    #[serde(default)]
+    pub labels: Option<String>,
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+pub struct GhIssueList(pub Arc<GitHub>);
+
+impl rig::tool::Tool for GhIssueList {
+    type Args = IssueListArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "gh_issue_list";
+
+    definition!(
+        IssueListArgs,
+        "List GitHub issues of the current repository"
+    );
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        let mut query = vec![
+            ("state", args.state.unwrap_or_else(|| "open".into())),
+            per_page(args.limit),
+        ];
+        query.extend(args.labels.map(|labels| ("labels", labels)));
+        let issues = self.0.api.get("/issues", &query).await?;
+        Ok(list(&issues, |i| {
+            let kind = if i.get("pull_request").is_some() {
+                " (PR)"
+            } else {
+                ""
+            };
+            format!(
+                "#{} {}{} [{}] {}",
+                i["number"],
+                i["title"].as_str().unwrap_or_default(),
+                kind,
+                i["state"].as_str().unwrap_or_default(),
+                labels(i)
+            )
+        }))
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct PrCreateArgs {
+    pub title: String,
+    pub body: String,
+    /// Branch with the changes, already pushed (default: current branch)
+    #[serde(default)]
+    pub head: Option<String>,
+    /// Target branch (default: the repository's default branch)
+    #[serde(default)]
+    pub base: Option<String>,
+    #[serde(default)]
+    pub draft: bool,
+}
+
+pub struct GhPrCreate(pub Arc<GitHub>);
+
+impl rig::tool::Tool for GhPrCreate {
+    type Args = PrCreateArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "gh_pr_create";
+
+    definition!(
+        PrCreateArgs,
+        "Open a GitHub pull request. Push the head branch first (e.g. `git push -u origin <branch>`)"
+    );
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        let head = args.head.or_else(current_branch).ok_or_else(|| {
+            ToolError::Generic("No head branch given and HEAD is detached".into())
+        })?;
+        let base = match args.base {
+            Some(base) => base,
+            None => self.0.api.get("", &[]).await?["default_branch"]
+                .as_str()
+                .unwrap_or("main")
+                .to_string(),
+        };
+        let pr = self
+            .0
+            .api
+            .post(
+                "/pulls",
+                json!({
+                    "title": args.title,
+                    "body": args.body,
+                    "head": head,
+                    "base": base,
+                    "draft": args.draft,
+                }),
+            )
+            .await?;
+        Ok(format!(
+            "Opened #{}: {}",
+            pr["number"],
+            pr["html_url"].as_str().unwrap_or_default()
+        ))
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct PrCommentArgs {
+    /// Pull request or issue number
+    pub number: u64,
+    pub body: String,
+}
+
+pub struct GhPrComment(pub Arc<GitHub>);
+
+impl rig::tool::Tool for GhPrComment {
+    type Args = PrCommentArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "gh_pr_comment";
+
+    definition!(PrCommentArgs, "Comment on a GitHub pull request or issue");
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        let comment = self
+            .0
+            .api
+            .post(
+                &format!("/issues/{}/comments", args.number),
+                json!({ "body": args.body }),
+            )
+            .await?;
+        Ok(comment["html_url"].as_str().unwrap_or("ok").to_string())
+    }
+}
@@ -0,0 +1,345 @@
+use super::forge::{
+    current_branch, definition, labels, list, origin_url, per_page, repo_from_remote, Api,
+};
+use super::ToolError;
+use crate::config::GitLabConfig;
+use reqwest::header::HeaderMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+const DEFAULT_URL: &str = "https://gitlab.com";
+const MAX_NOTES: usize = 20;
+
+/// GitLab REST (v4) client for one project, authenticated with `GITLAB_TOKEN`.
+pub struct GitLab {
+    api: Api,
+    project: String,
+}
+
+impl GitLab {
+    /// Returns a client when `GITLAB_TOKEN` is set and the project is known from the
+    /// config, `CI_PROJECT_PATH`, or the `origin` remote on the instance host.
+    pub fn from_env(config: &GitLabConfig) -> Option<Arc<Self>> {
+        let token = std::env::var("GITLAB_TOKEN").ok()?;
+        let url = config
+            .url
+            .clone()
+            .or_else(|| std::env::var("CI_SERVER_URL").ok())
+            .unwrap_or_else(|| DEFAULT_URL.to_string());
+        let project = config
+            .project
+            .clone()
+            .or_else(|| std::env::var("CI_PROJECT_PATH").ok())
+            .or_else(|| {
+                let host = reqwest::Url::parse(&url).ok()?.host_str()?.to_string();
+                repo_from_remote(&origin_url()?, &host)
+            })?;
+        let mut headers = HeaderMap::new();
+        headers.insert("private-token", token.parse().ok()?);
+        let base = format!(
+            "{}/api/v4/projects/{}",
+            url.trim_end_matches('/'),
+            project.replace('/', "%2F")
+        );
+        Some(Arc::new(Self {
+            api: Api::new("GitLab", base, headers)?,
+            project,
+        }))
+    }
+
+    pub fn project(&self) -> &str {
+        &self.project
+    }
+}
+
+/// Human-written notes of an issue or MR, oldest first, skipping system notes.
+fn format_notes(notes: &Value) -> String {
+    let notes: Vec<String> = notes
+        .as_array()
+        .map(|notes| {
+            notes
+                .iter()
+                .filter(|n| !n["system"].as_bool().unwrap_or(false))
+                .take(MAX_NOTES)
+                .map(|n| {
+                    format!(
+                        "\n- {}: {}",
+                        n["author"]["username"].as_str().unwrap_or_default(),
+                        n["body"].as_str().unwrap_or_default().trim()
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if notes.is_empty() {
+        String::new()
+    } else {
+        format!("\n\nComments:{}", notes.concat())
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct IssueGetArgs {
+    /// Issue number within the project (iid)
+    pub iid: u64,
+}
+
+pub struct GlIssueGet(pub Arc<GitLab>);
+
+impl rig::tool::Tool for GlIssueGet {
+    type Args = IssueGetArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "gl_issue_get";
+
+    definition!(IssueGetArgs, "Get a GitLab issue with its comments");
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        let path = format!("/issues/{}", args.iid);
+        let issue = self.0.api.get(&path, &[]).await?;
+        let notes = self
+            .0
+            .api
+            .get(&format!("{}/notes", path), &[("sort", "asc".into())])
+            .await?;
+        Ok(format!(
+            "#{} {} [{}] by {}\n{}\nlabels: {}\n\n{}{}",
+            args.iid,
+            issue["title"].as_str().unwrap_or_default(),
+            issue["state"].as_str().unwrap_or_default(),
+            issue["author"]["username"].as_str().unwrap_or_default(),
+            issue["web_url"].as_str().unwrap_or_default(),
+            labels(&issue),
+            issue["description"]
+                .as_str()
+                .unwrap_or("(no description)")
+                .trim(),
+            format_notes(&notes)
+        ))
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct IssueListArgs {
+    /// opened, closed, or all (default opened)
+    #[serde(default)]
+    pub state: Option<String>,
+    /// Comma-separated label names
+    #[serde(default)]
+    pub labels: Option<String>,
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+pub struct GlIssueList(pub Arc<GitLab>);
+
+impl rig::tool::Tool for GlIssueList {
+    type Args = IssueListArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "gl_issue_list";
+
+    definition!(IssueListArgs, "List GitLab issues of the current project");
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        let mut query = vec![
+            ("state", args.state.unwrap_or_else(|| "opened".into())),
+            per_page(args.limit),
+        ];
+        query.extend(args.labels.map(|labels| ("labels", labels)));
+        let issues = self.0.api.get("/issues", &query).await?;
+        Ok(list(&issues, |i| {
+            format!(
+                "#{} {} [{}] {}",
+                i["iid"],
+                i["title"].as_str().unwrap_or_default(),
+                i["state"].as_str().unwrap_or_default(),
+                labels(i)
+            )
+        }))
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct MrCreateArgs {
+    pub title: String,
+    pub description: String,
+    /// Branch with the changes, already pushed (default: current branch)
+    #[serde(default)]
+    pub source_branch: Option<String>,
+    /// Target branch (default: the project's default branch)
+    #[serde(default)]
+    pub target_branch: Option<String>,
+    #[serde(default)]
+    pub draft: bool,
+}
+
+pub struct GlMrCreate(pub Arc<GitLab>);
+
+impl rig::tool::Tool for GlMrCreate {
+    type Args = MrCreateArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "gl_mr_create";
+
+    definition!(
+        MrCreateArgs,
+        "Open a GitLab merge request. Push the source branch first (e.g. `git push -u origin <branch>`)"
+    );
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        let source = args.source_branch.or_else(current_branch).ok_or_else(|| {
+            ToolError::Generic("No source branch given and HEAD is detached".into())
+        })?;
+        let target = match args.target_branch {
+            Some(target) => target,
+            None => self.0.api.get("", &[]).await?["default_branch"]
+                .as_str()
+                .unwrap_or("main")
+                .to_string(),
+        };
+        let title = if args.draft {
+            format!("Draft: {}", args.title)
+        } else {
+            args.title
+        };
+        let mr = self
+            .0
+            .api
+            .post(
+                "/merge_requests",
+                json!({
+                    "title": title,
+                    "description": args.description,
+                    "source_branch": source,
+                    "target_branch": target,
+                }),
+            )
+            .await?;
+        Ok(format!(
+            "Opened !{}: {}",
+            mr["iid"],
+            mr["web_url"].as_str().unwrap_or_default()
+        ))
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct MrCommentArgs {
+    /// Merge request number within the project (iid)
+    pub iid: u64,
+    pub body: String,
+}
+
+pub struct GlMrComment(pub Arc<GitLab>);
+
+impl rig::tool::Tool for GlMrComment {
+    type Args = MrCommentArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "gl_mr_comment";
+
+    definition!(MrCommentArgs, "Comment on a GitLab merge request");
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        self.0
+            .api
+            .post(
+                &format!("/merge_requests/{}/notes", args.iid),
+                json!({ "body": args.body }),
+            )
+            .await?;
+        Ok("ok".into())
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct PipelineStatusArgs {
+    /// Branch or tag (default: current branch)
+    #[serde(default, rename = "ref")]
+    pub git_ref: Option<String>,
+}
+
+pub struct GlPipelineStatus(pub Arc<GitLab>);
+
+impl rig::tool::Tool for GlPipelineStatus {
+    type Args = PipelineStatusArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "gl_pipeline_status";
+
+    definition!(
+        PipelineStatusArgs,
+        "Status of the latest GitLab pipeline for a branch, with its jobs"
+    );
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        let git_ref = args
+            .git_ref
+            .or_else(current_branch)
+            .ok_or_else(|| ToolError::Generic("No ref given and HEAD is detached".into()))?;
+        let pipelines = self
+            .0
+            .api
+            .get("/pipelines", &[("ref", git_ref.clone()), per_page(Some(1))])
+            .await?;
+        let Some(pipeline) = pipelines.as_array().and_then(|p| p.first()) else {
+            return Ok(format!("No pipelines for {}", git_ref));
+        };
+        let jobs = self
+            .0
+            .api
+            .get(
+                &format!("/pipelines/{}/jobs", pipeline["id"]),
+                &[per_page(Some(100))],
+            )
+            .await?;
+        Ok(format_pipeline(pipeline, &jobs))
+    }
+}
+
+fn format_pipeline(pipeline: &Value, jobs: &Value) -> String {
+    let mut out = format!(
+        "Pipeline {} [{}] {}",
+        pipeline["id"],
+        pipeline["status"].as_str().unwrap_or_default(),
+        pipeline["web_url"].as_str().unwrap_or_default()
+    );
+    for job in jobs.as_array().into_iter().flatten() {
+        out.push_str(&format!(
+            "\n- {}/{}: {}",
+            job["stage"].as_str().unwrap_or_default(),
+            job["name"].as_str().unwrap_or_default(),
+            job["status"].as_str().unwrap_or_default()
+        ));
+        if job["status"] == "failed" {
+            out.push_str(&format!(" {}", job["web_url"].as_str().unwrap_or_default()));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_pipeline() {
+        let pipeline = json!({"id": 7, "status": "failed", "web_url": "https://gl/p/7"});
+        let jobs = json!([
+            {"stage": "test", "name": "unit", "status": "failed", "web_url": "https://gl/j/1"},
+            {"stage": "build", "name": "compile", "status": "success", "web_url": "https://gl/j/2"}
+        ]);
+        assert_eq!(
+            format_pipeline(&pipeline, &jobs),
+            "Pipeline 7 [failed] https://gl/p/7\n- test/unit: failed https://gl/j/1\n- build/compile: success"
+        );
+    }
+}
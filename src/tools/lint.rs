@@ -0,0 +1,353 @@
+use super::{Bash, BashArgs, ToolError};
+use crate::config::NetworkPolicy;
+use crate::platform::Shell;
+use regex::Regex;
+use rig::tool::Tool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::LazyLock;
+
+const MAX_FINDINGS: usize = 100;
+const TAIL_LINES: usize = 40;
+
+/// `path:line[:col]: message`, as printed by clippy (short), ruff (concise) and go vet.
+static LOCATED_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\S+?):(\d+):(?:(\d+):)?\s+(.+)$").unwrap());
+/// eslint's default "stylish" format: a path line, then `  line:col  severity  message  rule`.
+static STYLISH_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s+(\d+):(\d+)\s+(.+?)\s*$").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Linter {
+    Clippy,
+    Ruff,
+    Eslint,
+    GoVet,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Formatter {
+    Rustfmt,
+    Ruff,
+    Prettier,
+    Gofmt,
+}
+
+fn has_ruff(dir: &Path) -> bool {
+    dir.join("ruff.toml").exists()
+        || dir.join(".ruff.toml").exists()
+        || std::fs::read_to_string(dir.join("pyproject.toml"))
+            .is_ok_and(|s| s.contains("[tool.ruff"))
+}
+
+fn has_any(dir: &Path, prefixes: &[&str]) -> bool {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries.flatten().any(|e| {
+                let name = e.file_name();
+                let name = name.to_string_lossy();
+                prefixes.iter().any(|p| name.starts_with(p))
+            })
+        })
+        .unwrap_or(false)
+}
+
+impl Linter {
+    /// Linters configured in `dir`; a repository may use several.
+    pub fn detect(dir: &Path) -> Vec<Self> {
+        let mut linters = Vec::new();
+        if dir.join("Cargo.toml").exists() {
+            linters.push(Linter::Clippy);
+        }
+        if has_ruff(dir) {
+            linters.push(Linter::Ruff);
+        }
+        if has_any(dir, &["eslint.config.", ".eslintrc"]) {
+            linters.push(Linter::Eslint);
+        }
+        if dir.join("go.mod").exists() {
+            linters.push(Linter::GoVet);
+        }
+        linters
+    }
+
+    pub fn command(&self, fix: bool) -> String {
+        match (self, fix) {
+            (Linter::Clippy, false) => "cargo clippy --all-targets --message-format=short".into(),
+            (Linter::Clippy, true) => {
+                "cargo clippy --all-targets --fix --allow-dirty --allow-staged --message-format=short".into()
+            }
+            (Linter::Ruff, false) => "ruff check --output-format=concise .".into(),
+            (Linter::Ruff, true) => "ruff check --output-format=concise --fix .".into(),
+            (Linter::Eslint, false) => "npx eslint .".into(),
+            (Linter::Eslint, true) => "npx eslint --fix .".into(),
+            // go vet has no auto-fix
+            (Linter::GoVet, _) => "go vet ./...".into(),
+        }
+    }
+}
+
+impl Formatter {
+    pub fn detect(dir: &Path) -> Vec<Self> {
+        let mut formatters = Vec::new();
+        if dir.join("Cargo.toml").exists() {
+            formatters.push(Formatter::Rustfmt);
+        }
+        if has_ruff(dir) {
+            formatters.push(Formatter::Ruff);
+        }
+        if has_any(dir, &[".prettierrc", "prettier.config."]) {
+            formatters.push(Formatter::Prettier);
+        }
+        if dir.join("go.mod").exists() {
+            formatters.push(Formatter::Gofmt);
+        }
+        formatters
+    }
+
+    pub fn command(&self, check: bool) -> String {
+        match (self, check) {
+            (Formatter::Rustfmt, false) => "cargo fmt".into(),
+            (Formatter::Rustfmt, true) => "cargo fmt --check".into(),
+            (Formatter::Ruff, false) => "ruff format .".into(),
+            (Formatter::Ruff, true) => "ruff format --check .".into(),
+            (Formatter::Prettier, false) => "npx prettier --write .".into(),
+            (Formatter::Prettier, true) => "npx prettier --check .".into(),
+            (Formatter::Gofmt, false) => "gofmt -l -w .".into(),
+            (Formatter::Gofmt, true) => "gofmt -l .".into(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Finding {
+    pub file: String,
+    pub line: u32,
+    pub col: Option<u32>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.col {
+            Some(col) => write!(f, "{}:{}:{} {}", self.file, self.line, col, self.message),
+            None => write!(f, "{}:{} {}", self.file, self.line, self.message),
+        }
+    }
+}
+
+/// Parses located diagnostics from linter output, deduplicated in order.
+pub fn parse_findings(output: &str) -> Vec<Finding> {
+    let mut findings: Vec<Finding> = Vec::new();
+    let mut stylish_file: Option<String> = None;
+    for line in output.lines() {
+        let finding = if let Some(cap) = LOCATED_RE.captures(line) {
+            Some(Finding {
+                file: cap[1].trim_start_matches("./").to_string(),
+                line: cap[2].parse().unwrap_or(0),
+                col: cap.get(3).and_then(|c| c.as_str().parse().ok()),
+                message: cap[4].to_string(),
+            })
+        } else if let (Some(file), Some(cap)) = (&stylish_file, STYLISH_RE.captures(line)) {
+            Some(Finding {
+                file: file.clone(),
+                line: cap[1].parse().unwrap_or(0),
+                col: cap[2].parse().ok(),
+                message: cap[3].split_whitespace().collect::<Vec<_>>().join(" "),
+            })
+        } else {
+            if !line.starts_with(char::is_whitespace) && !line.is_empty() {
+                stylish_file = Some(line.trim().to_string());
+            }
+            None
+        };
+        if let Some(finding) = finding.filter(|f| !findings.contains(f)) {
+            findings.push(finding);
+        }
+    }
+    findings
+}
+
+fn tail(output: &str, n: usize) -> String {
+    let lines: Vec<&str> = output.lines().collect();
+    lines[lines.len().saturating_sub(n)..].join("\n")
+}
+
+fn cwd() -> Result<std::path::PathBuf, ToolError> {
+    Ok(std::env::current_dir()?)
+}
+
+async fn run(
+    sandbox: &Option<crate::sandbox::SandboxConfig>,
+    network: NetworkPolicy,
+    shell: Option<Shell>,
+    cmd: &str,
+) -> Result<String, ToolError> {
+    Bash {
+        sandbox: sandbox.clone(),
+        network,
+        shell,
+        progress: None,
+    }
+    .call(BashArgs {
+        cmd: cmd.to_string(),
+        shell: None,
+    })
+    .await
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct RunLinterArgs {
+    /// Apply automatic fixes where the linter supports them
+    #[serde(default)]
+    pub fix: bool,
+}
+
+/// Runs the project's linters through the same path as `bash` and lists findings.
+pub struct RunLinter {
+    pub sandbox: Option<crate::sandbox::SandboxConfig>,
+    pub network: NetworkPolicy,
+    pub shell: Option<Shell>,
+}
+
+impl RunLinter {
+    /// Commands a call with these args would run, joined with `&&`.
+    pub fn command_for(args: &RunLinterArgs) -> Option<String> {
+        let linters = Linter::detect(&cwd().ok()?);
+        (!linters.is_empty()).then(|| {
+            linters
+                .iter()
+                .map(|l| l.command(args.fix))
+                .collect::<Vec<_>>()
+                .join(" && ")
+        })
+    }
+}
+
+impl Tool for RunLinter {
+    type Args = RunLinterArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "run_linter";
+
+    async fn definition(&self, _prompt: String) -> rig::completion::ToolDefinition {
+        rig::completion::ToolDefinition {
+            name: Self::NAME.into(),
+            description: "Run the project's linters (clippy, ruff, eslint, go vet; detected automatically) and list findings as file:line:col message".into(),
+            parameters: serde_json::to_value(schemars::schema_for!(RunLinterArgs)).unwrap(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        let linters = Linter::detect(&cwd()?);
+        if linters.is_empty() {
+            return Err(ToolError::Generic(
+                "No linter configuration detected (Cargo.toml, ruff, eslint, go.mod)".into(),
+            ));
+        }
+        let mut sections = Vec::new();
+        for linter in linters {
+            let cmd = linter.command(args.fix);
+            let output = run(&self.sandbox, self.network, self.shell, &cmd).await?;
+            let findings = parse_findings(&output);
+            let mut section = format!("{}: {} finding(s)", cmd, findings.len());
+            for finding in findings.iter().take(MAX_FINDINGS) {
+                section.push_str(&format!("\n{}", finding));
+            }
+            if findings.len() > MAX_FINDINGS {
+                section.push_str(&format!("\n... {} more", findings.len() - MAX_FINDINGS));
+            }
+            if findings.is_empty() && output != "(empty)" {
+                section.push_str(&format!("\n{}", tail(&output, TAIL_LINES)));
+            }
+            sections.push(section);
+        }
+        Ok(sections.join("\n\n"))
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct RunFormatterArgs {
+    /// Only report files that need formatting, without changing them
+    #[serde(default)]
+    pub check: bool,
+}
+
+/// Runs the project's formatters through the same path as `bash`.
+pub struct RunFormatter {
+    pub sandbox: Option<crate::sandbox::SandboxConfig>,
+    pub network: NetworkPolicy,
+    pub shell: Option<Shell>,
+}
+
+impl RunFormatter {
+    pub fn command_for(args: &RunFormatterArgs) -> Option<String> {
+        let formatters = Formatter::detect(&cwd().ok()?);
+        (!formatters.is_empty()).then(|| {
+            formatters
+                .iter()
+                .map(|f| f.command(args.check))
+                .collect::<Vec<_>>()
+                .join(" && ")
+        })
+    }
+}
+
+impl Tool for RunFormatter {
+    type Args = RunFormatterArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "run_formatter";
+
+    async fn definition(&self, _prompt: String) -> rig::completion::ToolDefinition {
+        rig::completion::ToolDefinition {
+            name: Self::NAME.into(),
+            description: "Format the project with its formatters (rustfmt, ruff, prettier, gofmt; detected automatically), or check formatting".into(),
+            parameters: serde_json::to_value(schemars::schema_for!(RunFormatterArgs)).unwrap(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        let formatters = Formatter::detect(&cwd()?);
+        if formatters.is_empty() {
+            return Err(ToolError::Generic(
+                "No formatter detected (Cargo.toml, ruff, prettier, go.mod)".into(),
+            ));
+        }
+        let mut sections = Vec::new();
+        for formatter in formatters {
+            let cmd = formatter.command(args.check);
+            let output = run(&self.sandbox, self.network, self.shell, &cmd).await?;
+            sections.push(if output == "(empty)" {
+                format!("{}: ok", cmd)
+            } else {
+                format!("{}:\n{}", cmd, tail(&output, TAIL_LINES))
+            });
+        }
+        Ok(sections.join("\n\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_findings() {
+        let output = "# example.com/pkg\n./main.go:12:2: unreachable code\nsrc/lib.rs:3:9: warning: unused import: `std::fs`\napp.py:1:8: F401 [*] `os` imported but unused\nsrc/lib.rs:3:9: warning: unused import: `std::fs`\n\n/repo/web/index.js\n  4:7  error  'x' is assigned a value but never used  no-unused-vars\n\n✖ 1 problem";
+        let findings = parse_findings(output);
+        let lines: Vec<String> = findings.iter().map(|f| f.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "main.go:12:2 unreachable code",
+                "src/lib.rs:3:9 warning: unused import: `std::fs`",
+                "app.py:1:8 F401 [*] `os` imported but unused",
+                "/repo/web/index.js:4:7 error 'x' is assigned a value but never used no-unused-vars",
+            ]
+        );
+    }
+}
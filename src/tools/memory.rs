@@ -0,0 +1,12 @@
+use super::ToolError;
+use crate::{memory, storage};
+use rig_derive::rig_tool;
+
+#[rig_tool(
+    description = "Remember a project fact or convention for future sessions (one short sentence)",
+    required(fact)
+)]
+pub async fn remember(fact: String) -> Result<String, ToolError> {
+    memory::remember(&*storage::project(), &fact).map_err(|e| ToolError::Generic(e.to_string()))?;
+    Ok("ok".into())
+}
@@ -0,0 +1,266 @@
+use super::{get_path, ToolError};
+use rig_derive::rig_tool;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::fs;
+
+const MAX_OUTPUT_CHARS: usize = 2000;
+
+fn load(text: &str) -> Result<Value, ToolError> {
+    let notebook: Value = serde_json::from_str(text)
+        .map_err(|e| ToolError::Generic(format!("Not a valid notebook: {}", e)))?;
+    if !notebook["cells"].is_array() {
+        return Err(ToolError::Generic("Not a valid notebook: no cells".into()));
+    }
+    Ok(notebook)
+}
+
+/// Serializes like Jupyter does (one-space indent, trailing newline) so diffs stay small.
+fn dump(notebook: &Value) -> Result<String, ToolError> {
+    let mut buf = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b" ");
+    let mut ser = serde_json::Serializer::with_formatter(&mut buf, formatter);
+    notebook
+        .serialize(&mut ser)
+        .map_err(|e| ToolError::Generic(e.to_string()))?;
+    buf.push(b'\n');
+    String::from_utf8(buf).map_err(|e| ToolError::Generic(e.to_string()))
+}
+
+/// Cell sources and stream outputs are either a string or a list of lines.
+fn text_of(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+/// The list-of-lines form Jupyter writes, each line keeping its `\n`.
+fn source_lines(source: &str) -> Value {
+    Value::Array(
+        source
+            .split_inclusive('\n')
+            .map(|l| Value::String(l.to_string()))
+            .collect(),
+    )
+}
+
+fn summarize_outputs(outputs: &Value) -> String {
+    let mut text = String::new();
+    for output in outputs.as_array().into_iter().flatten() {
+        match output["output_type"].as_str() {
+            Some("stream") => text.push_str(&text_of(&output["text"])),
+            Some("error") => text.push_str(&format!(
+                "{}: {}\n",
+                output["ename"].as_str().unwrap_or_default(),
+                output["evalue"].as_str().unwrap_or_default()
+            )),
+            _ => {
+                let data = &output["data"];
+                if data["text/plain"].is_null() {
+                    let kinds: Vec<&str> = data
+                        .as_object()
+                        .map(|d| d.keys().map(String::as_str).collect())
+                        .unwrap_or_default();
+                    text.push_str(&format!("<{}>\n", kinds.join(", ")));
+                } else {
+                    text.push_str(&text_of(&data["text/plain"]));
+                    text.push('\n');
+                }
+            }
+        }
+    }
+    if text.len() > MAX_OUTPUT_CHARS {
+        let mut end = MAX_OUTPUT_CHARS;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n...");
+    }
+    text
+}
+
+fn render(notebook: &Value) -> String {
+    let cells = notebook["cells"].as_array().cloned().unwrap_or_default();
+    cells
+        .iter()
+        .enumerate()
+        .map(|(i, cell)| {
+            let kind = cell["cell_type"].as_str().unwrap_or("code");
+            let mut out = match cell["execution_count"].as_u64() {
+                Some(n) => format!("[{}] {} (run {})\n", i, kind, n),
+                None => format!("[{}] {}\n", i, kind),
+            };
+            out.push_str(text_of(&cell["source"]).trim_end());
+            let outputs = summarize_outputs(&cell["outputs"]);
+            if !outputs.trim().is_empty() {
+                out.push_str("\n--- output ---\n");
+                out.push_str(outputs.trim_end());
+            }
+            out
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn new_cell_id(notebook: &Value) -> Option<String> {
+    // Cell ids exist from nbformat 4.5 on
+    let minor = notebook["nbformat_minor"].as_u64().unwrap_or(0);
+    if notebook["nbformat"].as_u64().unwrap_or(4) == 4 && minor < 5 {
+        return None;
+    }
+    let taken: Vec<&str> = notebook["cells"]
+        .as_array()
+        .map(|c| c.iter().filter_map(|c| c["id"].as_str()).collect())
+        .unwrap_or_default();
+    let seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    (0u128..)
+        .map(|n| format!("{:08x}", (seed.wrapping_add(n) as u32)))
+        .find(|id| !taken.contains(&id.as_str()))
+}
+
+/// Applies one edit to the notebook's cells. Metadata and untouched cells are kept as is;
+/// replacing a code cell's source clears its now-stale outputs.
+fn apply_edit(
+    notebook: &mut Value,
+    index: usize,
+    mode: &str,
+    source: &str,
+    cell_type: &str,
+) -> Result<(), ToolError> {
+    let id = new_cell_id(notebook);
+    let cells = notebook["cells"].as_array_mut().unwrap();
+    let len = cells.len();
+    let out_of_range = || ToolError::Generic(format!("Cell {} out of range (0..{})", index, len));
+    match mode {
+        "replace" => {
+            let cell = cells.get_mut(index).ok_or_else(out_of_range)?;
+            cell["source"] = source_lines(source);
+            if !cell_type.is_empty() && cell["cell_type"] != cell_type {
+                cell["cell_type"] = json!(cell_type);
+            }
+            if cell["cell_type"] == "code" {
+                cell["outputs"] = json!([]);
+                cell["execution_count"] = Value::Null;
+            } else if let Some(cell) = cell.as_object_mut() {
+                cell.remove("outputs");
+                cell.remove("execution_count");
+            }
+        }
+        "insert" => {
+            if index > len {
+                return Err(out_of_range());
+            }
+            let mut cell = match cell_type {
+                "markdown" | "raw" => json!({"cell_type": cell_type, "metadata": {}}),
+                "" | "code" => json!({
+                    "cell_type": "code",
+                    "execution_count": null,
+                    "metadata": {},
+                    "outputs": [],
+                }),
+                other => return Err(ToolError::Generic(format!("Unknown cell type {}", other))),
+            };
+            cell["source"] = source_lines(source);
+            if let Some(id) = id {
+                cell["id"] = json!(id);
+            }
+            cells.insert(index, cell);
+        }
+        "delete" => {
+            if index >= len {
+                return Err(out_of_range());
+            }
+            cells.remove(index);
+        }
+        other => {
+            return Err(ToolError::Generic(format!(
+                "Unknown mode {} (use replace, insert or delete)",
+                other
+            )))
+        }
+    }
+    Ok(())
+}
+
+#[rig_tool(
+    description = "Read a Jupyter notebook (.ipynb) as numbered cells with their outputs",
+    required(path)
+)]
+pub async fn notebook_read(path: String) -> Result<String, ToolError> {
+    let p = get_path(&path)?;
+    let text = fs::read_to_string(&p).await?;
+    crate::freshness::record(&p);
+    Ok(render(&load(&text)?))
+}
+
+#[rig_tool(
+    description = "Edit a Jupyter notebook cell by index. mode: replace (set the cell's source), insert (new cell before index; index = cell count appends), delete. cell_type: code, markdown, raw, or empty to keep/default to code",
+    required(path, index, mode, source, cell_type)
+)]
+pub async fn notebook_edit(
+    path: String,
+    index: u64,
+    mode: String,
+    source: String,
+    cell_type: String,
+) -> Result<String, ToolError> {
+    let p = get_path(&path)?;
+    if crate::freshness::is_stale(&p) {
+        return Ok(super::stale_error(&path));
+    }
+    let mut notebook = load(&fs::read_to_string(&p).await?)?;
+    apply_edit(&mut notebook, index as usize, &mode, &source, &cell_type)?;
+    crate::checkpoint::save(&p);
+    fs::write(&p, dump(&notebook)?).await?;
+    crate::freshness::record(&p);
+    Ok("ok".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r#"{
+ "cells": [
+  {
+   "cell_type": "code",
+   "execution_count": 1,
+   "id": "a1",
+   "metadata": {"tags": ["setup"]},
+   "outputs": [{"name": "stdout", "output_type": "stream", "text": ["hi\n"]}],
+   "source": ["import os\n", "print('hi')"]
+  }
+ ],
+ "metadata": {"kernelspec": {"name": "python3"}},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"#;
+
+    #[test]
+    fn test_render_and_edit() {
+        let mut notebook = load(NOTEBOOK).unwrap();
+        assert_eq!(
+            render(&notebook),
+            "[0] code (run 1)\nimport os\nprint('hi')\n--- output ---\nhi"
+        );
+
+        apply_edit(&mut notebook, 0, "replace", "print(1)\nprint(2)", "").unwrap();
+        apply_edit(&mut notebook, 0, "insert", "# Title", "markdown").unwrap();
+        assert!(apply_edit(&mut notebook, 5, "delete", "", "").is_err());
+
+        let saved = load(&dump(&notebook).unwrap()).unwrap();
+        let cells = saved["cells"].as_array().unwrap();
+        assert_eq!(cells[0]["cell_type"], "markdown");
+        assert!(cells[0]["id"].is_string());
+        assert_eq!(cells[1]["source"], json!(["print(1)\n", "print(2)"]));
+        assert_eq!(cells[1]["outputs"], json!([]));
+        assert_eq!(cells[1]["metadata"]["tags"], json!(["setup"]));
+        assert_eq!(saved["metadata"]["kernelspec"]["name"], "python3");
+    }
+}
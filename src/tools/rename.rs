@@ -0,0 +1,780 @@
+use super::replace::{apply, Change};
+use super::{get_path, walk_files, ToolError};
+use regex::Regex;
+use rig_derive::rig_tool;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::LazyLock;
+use tree_sitter::{Language, Node, Parser, TreeCursor};
+
+static IDENTIFIER_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Lang {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Tsx,
+    Go,
+}
+
+impl Lang {
+    fn from_path(path: &Path) -> Option<Self> {
+        Some(match path.extension()?.to_str()? {
+            "rs" => Lang::Rust,
+            "py" | "pyi" => Lang::Python,
+            "js" | "jsx" | "mjs" | "cjs" => Lang::JavaScript,
+            "ts" | "mts" | "cts" => Lang::TypeScript,
+            "tsx" => Lang::Tsx,
+            "go" => Lang::Go,
+            _ => return None,
+        })
+    }
+
+    fn language(self) -> Language {
+        match self {
+            Lang::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Lang::Python => tree_sitter_python::LANGUAGE.into(),
+            Lang::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Lang::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Lang::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Lang::Go => tree_sitter_go::LANGUAGE.into(),
+        }
+    }
+}
+
+fn err(e: impl std::fmt::Display) -> ToolError {
+    ToolError::Generic(e.to_string())
+}
+
+/// What the renamed name refers to. Identifiers of the other kind with the
+/// same name are left alone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    /// Functions, types, constants, variables and modules.
+    Item,
+    /// Struct fields, methods, enum variants and properties (`x.old`,
+    /// `Type::old`).
+    Member,
+}
+
+impl Kind {
+    fn parse(kind: &str) -> Result<Self, ToolError> {
+        match kind {
+            "item" => Ok(Kind::Item),
+            "member" => Ok(Kind::Member),
+            _ => Err(err(format!("kind must be item or member, not {:?}", kind))),
+        }
+    }
+}
+
+/// Where a local binding is visible.
+#[derive(Clone, Copy)]
+enum Scope {
+    /// The rest of the enclosing block, after the binding statement.
+    Rest,
+    /// The whole function (or closure) it is declared in.
+    Function,
+    /// The binding node itself, e.g. a loop or match arm.
+    Own,
+    /// The nearest enclosing node of one of these kinds.
+    Enclosing(&'static [&'static str]),
+}
+
+/// A node that binds the names in its child `field` (any child without a
+/// field of [`EXPRESSION_FIELDS`] when `None`) as local variables.
+type Binding = (&'static str, Option<&'static str>, Scope);
+
+/// Fields holding expressions or types rather than the names a pattern binds.
+const EXPRESSION_FIELDS: &[&str] = &["type", "value", "right", "condition"];
+
+/// How each grammar names and binds things, for telling items, members and
+/// local variables apart.
+struct Syntax {
+    /// Identifier kinds that can name an item (or a local).
+    items: &'static [&'static str],
+    /// Identifier kinds that always name a member.
+    members: &'static [&'static str],
+    /// Shorthand `{ old }` fields (or their parents), naming a member and a
+    /// variable at once.
+    shorthands: &'static [&'static str],
+    /// Nodes with their own local variables.
+    functions: &'static [&'static str],
+    /// Nodes local `Scope::Rest` bindings end with.
+    blocks: &'static [&'static str],
+    /// Nodes between a binding and the names it binds, with the field the
+    /// names are in (`None`: any but [`EXPRESSION_FIELDS`]).
+    patterns: &'static [(&'static str, Option<&'static str>)],
+    bindings: &'static [Binding],
+}
+
+const RUST: Syntax = Syntax {
+    items: &[
+        "identifier",
+        "type_identifier",
+        "shorthand_field_identifier",
+    ],
+    members: &["field_identifier", "shorthand_field_identifier"],
+    shorthands: &["shorthand_field_identifier", "shorthand_field_initializer"],
+    functions: &["function_item", "closure_expression"],
+    blocks: &["block"],
+    patterns: &[
+        ("tuple_pattern", None),
+        ("tuple_struct_pattern", None),
+        ("struct_pattern", None),
+        ("field_pattern", None),
+        ("slice_pattern", None),
+        ("ref_pattern", None),
+        ("mut_pattern", None),
+        ("reference_pattern", None),
+        ("or_pattern", None),
+        ("captured_pattern", None),
+        ("match_pattern", None),
+    ],
+    bindings: &[
+        ("let_declaration", Some("pattern"), Scope::Rest),
+        ("parameter", Some("pattern"), Scope::Function),
+        ("closure_parameters", None, Scope::Function),
+        ("for_expression", Some("pattern"), Scope::Own),
+        ("match_arm", Some("pattern"), Scope::Own),
+        (
+            "let_condition",
+            Some("pattern"),
+            Scope::Enclosing(&["if_expression", "while_expression"]),
+        ),
+        ("function_item", Some("name"), Scope::Enclosing(&["block"])),
+    ],
+};
+
+const PYTHON: Syntax = Syntax {
+    items: &["identifier"],
+    members: &[],
+    shorthands: &[],
+    functions: &["function_definition", "lambda"],
+    blocks: &[],
+    patterns: &[
+        ("pattern_list", None),
+        ("tuple_pattern", None),
+        ("list_pattern", None),
+        ("list_splat_pattern", None),
+        ("dictionary_splat_pattern", None),
+        ("expression_list", None),
+        ("tuple", None),
+        ("list", None),
+        ("parenthesized_expression", None),
+        ("default_parameter", Some("name")),
+        ("typed_default_parameter", Some("name")),
+        ("typed_parameter", None),
+    ],
+    // Python variables belong to the whole function that assigns them
+    bindings: &[
+        ("parameters", None, Scope::Function),
+        ("lambda_parameters", None, Scope::Function),
+        ("assignment", Some("left"), Scope::Function),
+        ("augmented_assignment", Some("left"), Scope::Function),
+        ("for_statement", Some("left"), Scope::Function),
+        ("named_expression", Some("name"), Scope::Function),
+        ("as_pattern_target", None, Scope::Function),
+        ("function_definition", Some("name"), Scope::Function),
+        ("class_definition", Some("name"), Scope::Function),
+        (
+            "for_in_clause",
+            Some("left"),
+            Scope::Enclosing(&[
+                "list_comprehension",
+                "set_comprehension",
+                "dictionary_comprehension",
+                "generator_expression",
+            ]),
+        ),
+    ],
+};
+
+const JS_FUNCTIONS: &[&str] = &[
+    "function_declaration",
+    "function_expression",
+    "function",
+    "generator_function_declaration",
+    "generator_function",
+    "arrow_function",
+    "method_definition",
+];
+
+const JS_PATTERNS: &[(&str, Option<&str>)] = &[
+    ("object_pattern", None),
+    ("array_pattern", None),
+    ("pair_pattern", Some("value")),
+    ("assignment_pattern", Some("left")),
+    ("object_assignment_pattern", Some("left")),
+    ("rest_pattern", None),
+    ("required_parameter", Some("pattern")),
+    ("optional_parameter", Some("pattern")),
+];
+
+const JS_BINDINGS: &[Binding] = &[
+    ("variable_declarator", Some("name"), Scope::Rest),
+    ("formal_parameters", None, Scope::Function),
+    ("arrow_function", Some("parameter"), Scope::Function),
+    ("catch_clause", Some("parameter"), Scope::Own),
+    ("for_in_statement", Some("left"), Scope::Own),
+    (
+        "function_declaration",
+        Some("name"),
+        Scope::Enclosing(&["statement_block"]),
+    ),
+];
+
+const JAVASCRIPT: Syntax = Syntax {
+    items: &[
+        "identifier",
+        "shorthand_property_identifier",
+        "shorthand_property_identifier_pattern",
+    ],
+    members: &[
+        "property_identifier",
+        "private_property_identifier",
+        "shorthand_property_identifier",
+        "shorthand_property_identifier_pattern",
+    ],
+    shorthands: &[
+        "shorthand_property_identifier",
+        "shorthand_property_identifier_pattern",
+    ],
+    functions: JS_FUNCTIONS,
+    blocks: &["statement_block", "for_statement", "for_in_statement"],
+    patterns: JS_PATTERNS,
+    bindings: JS_BINDINGS,
+};
+
+const TYPESCRIPT: Syntax = Syntax {
+    items: &[
+        "identifier",
+        "type_identifier",
+        "shorthand_property_identifier",
+        "shorthand_property_identifier_pattern",
+    ],
+    ..JAVASCRIPT
+};
+
+const GO: Syntax = Syntax {
+    items: &["identifier", "type_identifier", "package_identifier"],
+    members: &["field_identifier"],
+    shorthands: &[],
+    functions: &["function_declaration", "method_declaration", "func_literal"],
+    blocks: &[
+        "block",
+        "if_statement",
+        "for_statement",
+        "expression_switch_statement",
+        "type_switch_statement",
+    ],
+    patterns: &[("expression_list", None)],
+    bindings: &[
+        ("short_var_declaration", Some("left"), Scope::Rest),
+        ("var_spec", Some("name"), Scope::Rest),
+        ("const_spec", Some("name"), Scope::Rest),
+        ("parameter_declaration", Some("name"), Scope::Function),
+        (
+            "variadic_parameter_declaration",
+            Some("name"),
+            Scope::Function,
+        ),
+        (
+            "range_clause",
+            Some("left"),
+            Scope::Enclosing(&["for_statement"]),
+        ),
+    ],
+};
+
+impl Lang {
+    fn syntax(self) -> &'static Syntax {
+        match self {
+            Lang::Rust => &RUST,
+            Lang::Python => &PYTHON,
+            Lang::JavaScript => &JAVASCRIPT,
+            Lang::TypeScript | Lang::Tsx => &TYPESCRIPT,
+            Lang::Go => &GO,
+        }
+    }
+}
+
+/// An identifier to rename. A shorthand field (`{ old }`) is expanded so
+/// that only the side being renamed changes.
+#[derive(Debug, Clone, PartialEq)]
+struct Occurrence {
+    range: Range<usize>,
+    shorthand: bool,
+}
+
+fn in_field(parent: Node, child: Node, field: &str) -> bool {
+    let mut cursor = parent.walk();
+    let found = parent
+        .children_by_field_name(field, &mut cursor)
+        .any(|c| c.id() == child.id());
+    found
+}
+
+/// Whether `child` is in `field` of `parent`, or with `None` in any child
+/// but an expression.
+fn in_pattern(parent: Node, child: Node, field: Option<&str>) -> bool {
+    match field {
+        Some(field) => in_field(parent, child, field),
+        None => !EXPRESSION_FIELDS
+            .iter()
+            .any(|field| in_field(parent, child, field)),
+    }
+}
+
+fn enclosing<'a>(node: Node<'a>, kinds: &[&str]) -> Option<Node<'a>> {
+    let mut parent = node.parent();
+    while let Some(node) = parent {
+        if kinds.contains(&node.kind()) {
+            return Some(node);
+        }
+        parent = node.parent();
+    }
+    None
+}
+
+/// Where the local variable `node` declares is visible, if it is a name
+/// bound by a parameter, `let`, assignment or loop inside a function.
+fn local_scope(syntax: &Syntax, node: Node) -> Option<Range<usize>> {
+    let mut child = node;
+    let mut parent = node.parent()?;
+    loop {
+        let binding = syntax
+            .bindings
+            .iter()
+            .find(|(kind, field, _)| *kind == parent.kind() && in_pattern(parent, child, *field));
+        if let Some((_, _, scope)) = binding {
+            // A function's own parameters are local to it, but bindings
+            // outside any function declare items
+            let own = syntax.functions.contains(&parent.kind()) && !in_field(parent, child, "name");
+            let function = if own {
+                parent
+            } else {
+                enclosing(parent, syntax.functions)?
+            };
+            return Some(match scope {
+                Scope::Rest => parent.end_byte()..enclosing(parent, syntax.blocks)?.end_byte(),
+                // Parameter defaults are evaluated outside the body
+                Scope::Function => function
+                    .child_by_field_name("body")
+                    .unwrap_or(function)
+                    .byte_range(),
+                Scope::Own => parent.byte_range(),
+                Scope::Enclosing(kinds) => enclosing(parent, kinds)?.byte_range(),
+            });
+        }
+        if !syntax
+            .patterns
+            .iter()
+            .any(|(kind, field)| *kind == parent.kind() && in_pattern(parent, child, *field))
+        {
+            return None;
+        }
+        child = parent;
+        parent = parent.parent()?;
+    }
+}
+
+/// Whether `node` (an identifier of an item kind) names a member: a Rust
+/// method, associated item or enum variant, `Type::old`, or a Python
+/// attribute, method or class attribute.
+fn names_member(lang: Lang, node: Node, source: &[u8], imports: &[String]) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    match lang {
+        Lang::Rust => match parent.kind() {
+            "function_item" | "function_signature_item" | "const_item" | "type_item"
+                if in_field(parent, node, "name") =>
+            {
+                parent.parent().is_some_and(|list| {
+                    list.kind() == "declaration_list"
+                        && list
+                            .parent()
+                            .is_some_and(|p| matches!(p.kind(), "impl_item" | "trait_item"))
+                })
+            }
+            "enum_variant" => in_field(parent, node, "name"),
+            "scoped_identifier" | "scoped_type_identifier" if in_field(parent, node, "name") => {
+                parent
+                    .child_by_field_name("path")
+                    .is_some_and(|path| is_type_path(path, source))
+            }
+            _ => false,
+        },
+        Lang::Python => match parent.kind() {
+            "attribute" if in_field(parent, node, "attribute") => !parent
+                .child_by_field_name("object")
+                .and_then(|object| object.utf8_text(source).ok())
+                .is_some_and(|object| imports.iter().any(|i| i == object)),
+            "function_definition" | "assignment" => {
+                let definition = if parent.kind() == "assignment" {
+                    if !in_field(parent, node, "left") {
+                        return false;
+                    }
+                    parent
+                        .parent()
+                        .filter(|p| p.kind() == "expression_statement")
+                } else {
+                    in_field(parent, node, "name").then_some(parent)
+                };
+                definition
+                    .map(|d| match d.parent() {
+                        Some(p) if p.kind() == "decorated_definition" => p,
+                        _ => d,
+                    })
+                    .and_then(|d| d.parent())
+                    .and_then(|block| block.parent())
+                    .is_some_and(|class| class.kind() == "class_definition")
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Whether a Rust path names a type (`Config`, `Self`, `Vec<T>`) rather
+/// than a module.
+fn is_type_path(path: Node, source: &[u8]) -> bool {
+    match path.kind() {
+        "identifier" => path
+            .utf8_text(source)
+            .is_ok_and(|name| name.starts_with(|c: char| c.is_ascii_uppercase())),
+        "scoped_identifier" => path
+            .child_by_field_name("name")
+            .is_some_and(|name| is_type_path(name, source)),
+        "generic_type" | "bracketed_type" => true,
+        _ => false,
+    }
+}
+
+/// Names of the packages or modules a Go or Python file imports, whose
+/// `pkg.old` references an item rather than a member.
+fn imports(lang: Lang, root: Node, source: &[u8]) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut cursor = root.walk();
+    let mut visit = |node: Node| match (lang, node.kind()) {
+        (Lang::Go, "import_spec") => {
+            let name = match node.child_by_field_name("name") {
+                Some(name) => name.utf8_text(source).ok().map(str::to_string),
+                None => node
+                    .child_by_field_name("path")
+                    .and_then(|path| path.utf8_text(source).ok())
+                    .map(|path| {
+                        let path = path.trim_matches(|c| c == '"' || c == '`');
+                        path.rsplit('/').next().unwrap_or(path).to_string()
+                    }),
+            };
+            names.extend(name);
+        }
+        (Lang::Python, "aliased_import") => names.extend(
+            node.child_by_field_name("alias")
+                .and_then(|alias| alias.utf8_text(source).ok())
+                .map(str::to_string),
+        ),
+        (Lang::Python, "dotted_name")
+            if node
+                .parent()
+                .is_some_and(|p| p.kind() == "import_statement") =>
+        {
+            names.extend(
+                node.named_child(0)
+                    .and_then(|first| first.utf8_text(source).ok())
+                    .map(str::to_string),
+            )
+        }
+        _ => {}
+    };
+    walk(&mut cursor, &mut visit);
+    names
+}
+
+/// Calls `visit` on every node under the cursor, in document order.
+fn walk<'a>(cursor: &mut TreeCursor<'a>, visit: &mut impl FnMut(Node<'a>)) {
+    loop {
+        visit(cursor.node());
+        if cursor.goto_first_child() {
+            continue;
+        }
+        loop {
+            if cursor.goto_next_sibling() {
+                break;
+            }
+            if !cursor.goto_parent() {
+                return;
+            }
+        }
+    }
+}
+
+/// The identifiers in `source` naming the `kind` called `name`. Item renames
+/// skip local variables of that name and everything they shadow.
+fn occurrences(
+    lang: Lang,
+    source: &str,
+    name: &str,
+    kind: Kind,
+) -> Result<Vec<Occurrence>, ToolError> {
+    let mut parser = Parser::new();
+    parser.set_language(&lang.language()).map_err(err)?;
+    let tree = parser
+        .parse(source, None)
+        .ok_or_else(|| err("tree-sitter could not parse the file"))?;
+    let syntax = lang.syntax();
+    let bytes = source.as_bytes();
+    let imports = imports(lang, tree.root_node(), bytes);
+
+    let mut named = Vec::new();
+    walk(&mut tree.walk(), &mut |node| {
+        if node.child_count() == 0 && node.utf8_text(bytes) == Ok(name) {
+            named.push(node);
+        }
+    });
+    // Python functions declaring the name global keep using the item
+    let globals: Vec<usize> = named
+        .iter()
+        .filter(|node| {
+            node.parent()
+                .is_some_and(|p| matches!(p.kind(), "global_statement" | "nonlocal_statement"))
+        })
+        .filter_map(|node| enclosing(*node, syntax.functions).map(|f| f.id()))
+        .collect();
+
+    // With whether a local variable of the name would shadow it
+    let mut found = Vec::new();
+    let mut scopes = Vec::new();
+    for node in named {
+        let shorthand = syntax.shorthands.contains(&node.kind())
+            || node
+                .parent()
+                .is_some_and(|p| syntax.shorthands.contains(&p.kind()));
+        let member = shorthand
+            || syntax.members.contains(&node.kind())
+            || (syntax.items.contains(&node.kind()) && names_member(lang, node, bytes, &imports));
+        // Go's `pkg.Old` is a selector like `x.field`
+        let qualified = lang == Lang::Go
+            && node.kind() == "field_identifier"
+            && node.parent().is_some_and(|p| {
+                p.kind() == "selector_expression"
+                    && p.child_by_field_name("operand")
+                        .and_then(|operand| operand.utf8_text(bytes).ok())
+                        .is_some_and(|operand| imports.iter().any(|i| i == operand))
+            });
+        // Locals don't shadow `module::old` or `module.old`
+        let path = qualified
+            || node.parent().is_some_and(|p| {
+                matches!(
+                    p.kind(),
+                    "scoped_identifier" | "scoped_type_identifier" | "attribute"
+                ) && (in_field(p, node, "name") || in_field(p, node, "attribute"))
+            });
+        let keyword = lang == Lang::Python
+            && node
+                .parent()
+                .is_some_and(|p| p.kind() == "keyword_argument" && in_field(p, node, "name"));
+        let occurrence = Occurrence {
+            range: node.byte_range(),
+            shorthand,
+        };
+        match kind {
+            Kind::Member if member && !qualified => found.push((occurrence, false)),
+            Kind::Item if (shorthand || !member || qualified) && !keyword => {
+                if !syntax.items.contains(&node.kind()) && !qualified {
+                    continue;
+                }
+                if path {
+                    found.push((occurrence, false));
+                    continue;
+                }
+                match local_scope(syntax, node) {
+                    Some(scope)
+                        if !enclosing(node, syntax.functions)
+                            .is_some_and(|f| globals.contains(&f.id())) =>
+                    {
+                        scopes.push(scope)
+                    }
+                    _ => found.push((occurrence, true)),
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(found
+        .into_iter()
+        .filter(|(o, shadowed)| !shadowed || !scopes.iter().any(|s| s.contains(&o.range.start)))
+        .map(|(o, _)| o)
+        .collect())
+}
+
+/// `source` with each occurrence renamed to `new`. A shorthand field keeps
+/// its other side: `{ old }` becomes `{ new: old }` for a member and
+/// `{ old: new }` for a variable.
+fn rename(source: &str, occurrences: &[Occurrence], old: &str, new: &str, kind: Kind) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut last = 0;
+    for occurrence in occurrences {
+        out.push_str(&source[last..occurrence.range.start]);
+        match (occurrence.shorthand, kind) {
+            (false, _) => out.push_str(new),
+            (true, Kind::Member) => out.push_str(&format!("{}: {}", new, old)),
+            (true, Kind::Item) => out.push_str(&format!("{}: {}", old, new)),
+        }
+        last = occurrence.range.end;
+    }
+    out.push_str(&source[last..]);
+    out
+}
+
+/// Supported source files under `base` (or `base` itself) that mention `old`.
+fn plan(base: &Path, old: &str, new: &str, kind: Kind) -> Result<Vec<Change>, ToolError> {
+    let mut changes = Vec::new();
+    for entry in walk_files(base).filter(|e| !crate::secrets::is_protected(e.path())) {
+        let Some(lang) = Lang::from_path(entry.path()) else {
+            continue;
+        };
+        let Ok(before) = std::fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        if !before.contains(old) {
+            continue;
+        }
+        let found = occurrences(lang, &before, old, kind)?;
+        if found.is_empty() {
+            continue;
+        }
+        changes.push(Change {
+            path: entry.path().to_owned(),
+            count: found.len(),
+            after: rename(&before, &found, old, new, kind),
+            before,
+        });
+    }
+    Ok(changes)
+}
+
+#[rig_tool(
+    description = "Rename an identifier in a file, or in every Rust/Python/JavaScript/TypeScript/Go file under a directory. Uses the syntax tree, so strings, comments and longer names containing old are left alone. kind: item (functions, types, constants, variables, modules; local variables of the same name inside functions are left alone) or member (struct fields, methods, enum variants, properties: x.old, Type::old). Local variables can't be renamed. Names are matched per file without type information, so a member rename covers every type's member called old. dry_run=true returns the diff without writing anything",
+    required(path, old, new, kind, dry_run)
+)]
+pub async fn rename_symbol(
+    path: String,
+    old: String,
+    new: String,
+    kind: String,
+    dry_run: bool,
+) -> Result<String, ToolError> {
+    for name in [&old, &new] {
+        if !IDENTIFIER_RE.is_match(name) {
+            return Err(err(format!("{} is not a valid identifier", name)));
+        }
+    }
+    let kind = Kind::parse(&kind)?;
+    let root = std::env::current_dir()?;
+    let base = get_path(&path)?;
+    tokio::task::spawn_blocking(move || {
+        let changes = plan(&base, &old, &new, kind)?;
+        if changes.is_empty() {
+            return Ok(format!("no {:?} identifiers named {}", kind, old).to_lowercase());
+        }
+        apply(&changes, &root, dry_run, "occurrence")
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn renamed(lang: Lang, source: &str, kind: Kind) -> String {
+        let found = occurrences(lang, source, "load", kind).unwrap();
+        rename(source, &found, "load", "fetch", kind)
+    }
+
+    #[test]
+    fn test_rename() {
+        let source = r#"struct Config { load: bool }
+impl Config {
+    fn load(&self) -> bool { self.load }
+}
+// load the config
+fn load(config: &Config) -> bool { println!("load"); config.load && load_all() }
+fn main() {
+    let ok = load(&Config { load: true });
+    let load = 1;
+    println!("{}", load);
+}
+"#;
+        assert_eq!(
+            renamed(Lang::Rust, source, Kind::Item),
+            r#"struct Config { load: bool }
+impl Config {
+    fn load(&self) -> bool { self.load }
+}
+// load the config
+fn fetch(config: &Config) -> bool { println!("load"); config.load && load_all() }
+fn main() {
+    let ok = fetch(&Config { load: true });
+    let load = 1;
+    println!("{}", load);
+}
+"#
+        );
+        assert_eq!(
+            renamed(Lang::Rust, source, Kind::Member),
+            r#"struct Config { fetch: bool }
+impl Config {
+    fn fetch(&self) -> bool { self.fetch }
+}
+// load the config
+fn load(config: &Config) -> bool { println!("load"); config.fetch && load_all() }
+fn main() {
+    let ok = load(&Config { fetch: true });
+    let load = 1;
+    println!("{}", load);
+}
+"#
+        );
+        let shorthand =
+            "fn f(load: bool) -> Config { Config { load } }\nfn g() { Config::load(); }\n";
+        assert_eq!(
+            renamed(Lang::Rust, shorthand, Kind::Member),
+            "fn f(load: bool) -> Config { Config { fetch: load } }\nfn g() { Config::fetch(); }\n"
+        );
+
+        let py = "import loader\n\ndef load(x):\n    return x  # load\n\nclass A:\n    load = 1\n\n    def f(self, load):\n        return load + self.load\n\nload(loader.load)\n";
+        assert_eq!(
+            renamed(Lang::Python, py, Kind::Item),
+            "import loader\n\ndef fetch(x):\n    return x  # load\n\nclass A:\n    load = 1\n\n    def f(self, load):\n        return load + self.load\n\nfetch(loader.fetch)\n"
+        );
+        assert_eq!(
+            renamed(Lang::Python, py, Kind::Member),
+            "import loader\n\ndef load(x):\n    return x  # load\n\nclass A:\n    fetch = 1\n\n    def f(self, load):\n        return load + self.fetch\n\nload(loader.load)\n"
+        );
+
+        let js = "function load() {}\nconst o = { load };\nfunction f(load) { return load; }\nconst g = () => { const { load } = o; return load; };\nload(o.load);\n";
+        assert_eq!(
+            renamed(Lang::JavaScript, js, Kind::Item),
+            "function fetch() {}\nconst o = { load: fetch };\nfunction f(load) { return load; }\nconst g = () => { const { load } = o; return load; };\nfetch(o.load);\n"
+        );
+        assert_eq!(
+            renamed(Lang::TypeScript, js, Kind::Member),
+            "function load() {}\nconst o = { fetch: load };\nfunction f(load) { return load; }\nconst g = () => { const { fetch: load } = o; return load; };\nload(o.fetch);\n"
+        );
+
+        let go = "package main\n\nimport \"example.com/load\"\n\nfunc Load() {}\n\nfunc main() {\n\tLoad()\n\tload.Load()\n}\n\nfunc f(load int) int { return load }\n";
+        let count = |name, kind| occurrences(Lang::Go, go, name, kind).unwrap().len();
+        assert_eq!(count("load", Kind::Item), 1);
+        assert_eq!(count("Load", Kind::Item), 3);
+        assert_eq!(count("Load", Kind::Member), 0);
+        assert!(occurrences(Lang::Tsx, "", "x", Kind::Item)
+            .unwrap()
+            .is_empty());
+        assert!(Kind::parse("local").is_err());
+    }
+}
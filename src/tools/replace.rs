@@ -0,0 +1,213 @@
+use super::{get_path, walk_files, ToolError};
+use globset::GlobMatcher;
+use regex::{NoExpand, Regex};
+use rig_derive::rig_tool;
+use std::path::{Path, PathBuf};
+
+/// Diff lines a dry run returns before cutting off.
+const MAX_DIFF_LINES: usize = 400;
+
+/// A file and its content after the edit.
+pub(super) struct Change {
+    pub path: PathBuf,
+    pub count: usize,
+    pub before: String,
+    pub after: String,
+}
+
+fn err(e: impl std::fmt::Display) -> ToolError {
+    ToolError::Generic(e.to_string())
+}
+
+/// Files under `base` matching `glob` that the replacement would change.
+/// `glob` is matched against the path relative to `base`.
+fn plan(
+    base: &Path,
+    re: &Regex,
+    replacement: &str,
+    literal: bool,
+    glob: Option<&GlobMatcher>,
+) -> Vec<Change> {
+    walk_files(base)
+        .filter(|e| !crate::secrets::is_protected(e.path()))
+        .filter(|e| {
+            let relative = e
+                .path()
+                .strip_prefix(base)
+                .ok()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(e.path());
+            glob.is_none_or(|g| g.is_match(relative))
+        })
+        .filter_map(|e| {
+            let before = std::fs::read_to_string(e.path()).ok()?;
+            let count = re.find_iter(&before).count();
+            let after = if literal {
+                re.replace_all(&before, NoExpand(replacement))
+            } else {
+                re.replace_all(&before, replacement)
+            }
+            .into_owned();
+            (after != before).then(|| Change {
+                path: e.path().to_owned(),
+                count,
+                before,
+                after,
+            })
+        })
+        .collect()
+}
+
+fn display_path<'a>(path: &'a Path, root: &Path) -> std::path::Display<'a> {
+    path.strip_prefix(root).unwrap_or(path).display()
+}
+
+pub(super) fn render_diff(changes: &[Change], root: &Path) -> String {
+    let diff: String = changes
+        .iter()
+        .map(|c| {
+            let name = display_path(&c.path, root).to_string();
+            similar::TextDiff::from_lines(&c.before, &c.after)
+                .unified_diff()
+                .context_radius(2)
+                .header(&format!("a/{}", name), &format!("b/{}", name))
+                .to_string()
+        })
+        .collect();
+    let lines: Vec<&str> = diff.lines().collect();
+    if lines.len() <= MAX_DIFF_LINES {
+        return diff;
+    }
+    format!(
+        "{}\n... {} more diff lines (narrow path or glob to see them)",
+        lines[..MAX_DIFF_LINES].join("\n"),
+        lines.len() - MAX_DIFF_LINES
+    )
+}
+
+/// Writes `changes` (or with `dry_run` only diffs them) and reports what was
+/// done, counting `Change::count` as `noun`s.
+pub(super) fn apply(
+    changes: &[Change],
+    root: &Path,
+    dry_run: bool,
+    noun: &str,
+) -> Result<String, ToolError> {
+    let summary = format!(
+        "{} {}(s) in {} file(s)",
+        changes.iter().map(|c| c.count).sum::<usize>(),
+        noun,
+        changes.len()
+    );
+    if dry_run {
+        return Ok(format!(
+            "{} (dry run, nothing written)\n\n{}",
+            summary,
+            render_diff(changes, root)
+        ));
+    }
+    if let Some(change) = changes.iter().find(|c| crate::freshness::is_stale(&c.path)) {
+        return Ok(super::stale_error(
+            &display_path(&change.path, root).to_string(),
+        ));
+    }
+    let mut files = Vec::new();
+    for change in changes {
+        crate::checkpoint::save(&change.path);
+        std::fs::write(&change.path, &change.after)?;
+        crate::freshness::record(&change.path);
+        files.push(format!(
+            "{} ({})",
+            display_path(&change.path, root),
+            change.count
+        ));
+    }
+    Ok(format!("{}:\n{}", summary, files.join("\n")))
+}
+
+#[rig_tool(
+    description = "Replace a regex (or exact text with literal=true) in every file under path, optionally only files matching glob (e.g. \"*.rs\", empty for all). In regex mode $1 or ${name} in the replacement insert capture groups. dry_run=true returns the diff without writing anything",
+    required(pat, replacement, path, glob, literal, dry_run)
+)]
+pub async fn replace_in_files(
+    pat: String,
+    replacement: String,
+    path: String,
+    glob: String,
+    literal: bool,
+    dry_run: bool,
+) -> Result<String, ToolError> {
+    let root = std::env::current_dir()?;
+    let base = get_path(&path)?;
+    let re = Regex::new(&if literal { regex::escape(&pat) } else { pat }).map_err(err)?;
+    let glob = match glob.as_str() {
+        "" => None,
+        glob => Some(globset::Glob::new(glob).map_err(err)?.compile_matcher()),
+    };
+    tokio::task::spawn_blocking(move || {
+        let changes = plan(&base, &re, &replacement, literal, glob.as_ref());
+        if changes.is_empty() {
+            return Ok("no matches".into());
+        }
+        apply(&changes, &root, dry_run, "replacement")
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_and_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("src/a.rs"),
+            "fn old_name() {}\nold_name();\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("notes.md"), "old_name(x)\n").unwrap();
+
+        let re = Regex::new(r"old_(\w+)").unwrap();
+        let matcher = globset::Glob::new("*.rs").unwrap().compile_matcher();
+        let changes = plan(dir.path(), &re, "new_$1", false, Some(&matcher));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].count, 2);
+        assert_eq!(changes[0].after, "fn new_name() {}\nnew_name();\n");
+        assert_eq!(
+            render_diff(&changes, dir.path()),
+            "--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1,2 +1,2 @@\n-fn old_name() {}\n-old_name();\n+fn new_name() {}\n+new_name();\n"
+        );
+
+        let re = Regex::new(&regex::escape("old_name(x)")).unwrap();
+        let changes = plan(dir.path(), &re, "$0", true, None);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].after, "$0\n");
+    }
+
+    #[test]
+    fn test_apply_stale() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.rs");
+        std::fs::write(&path, "old();\n").unwrap();
+        crate::freshness::record(&path);
+        std::fs::write(&path, "old(); // edited meanwhile\n").unwrap();
+
+        let re = Regex::new("old").unwrap();
+        let changes = plan(dir.path(), &re, "new", true, None);
+        let result = apply(&changes, dir.path(), false, "replacement").unwrap();
+        assert!(result.starts_with("error: a.rs changed on disk"));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "old(); // edited meanwhile\n"
+        );
+
+        crate::freshness::record(&path);
+        apply(&changes, dir.path(), false, "replacement").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "new(); // edited meanwhile\n"
+        );
+    }
+}
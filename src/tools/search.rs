@@ -0,0 +1,229 @@
+use super::ToolError;
+use crate::config::{SearchBackend, WebSearchConfig};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::LazyLock;
+
+const DEFAULT_MAX_RESULTS: usize = 8;
+
+static TAG_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]+>").unwrap());
+static DDG_RESULT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)class="result__a"[^>]*href="([^"]+)"[^>]*>(.*?)</a>.*?class="result__snippet"[^>]*>(.*?)</a>"#)
+        .unwrap()
+});
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct WebSearchArgs {
+    pub query: String,
+    /// Maximum number of results to return
+    #[serde(default)]
+    pub max_results: Option<usize>,
+}
+
+#[derive(Debug, PartialEq)]
+struct SearchResult {
+    title: String,
+    url: String,
+    snippet: String,
+}
+
+pub struct WebSearch {
+    config: WebSearchConfig,
+}
+
+impl WebSearch {
+    pub fn new(config: WebSearchConfig) -> Self {
+        Self { config }
+    }
+
+    fn api_key(&self, env_var: &str) -> Result<String, ToolError> {
+        self.config
+            .api_key
+            .clone()
+            .or_else(|| std::env::var(env_var).ok())
+            .ok_or_else(|| {
+                ToolError::Generic(format!(
+                    "web_search.api_key or {} must be set for this backend",
+                    env_var
+                ))
+            })
+    }
+
+    async fn get_json(&self, request: reqwest::RequestBuilder) -> Result<Value, ToolError> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ToolError::Generic(e.to_string()))?
+            .error_for_status()
+            .map_err(|e| ToolError::Generic(e.to_string()))?;
+        response
+            .json::<Value>()
+            .await
+            .map_err(|e| ToolError::Generic(e.to_string()))
+    }
+
+    async fn search(&self, query: &str, count: usize) -> Result<Vec<SearchResult>, ToolError> {
+        let client = crate::network::client().map_err(|e| ToolError::Generic(e.to_string()))?;
+        let count = count.to_string();
+        match self.config.backend {
+            SearchBackend::Brave => {
+                let key = self.api_key("BRAVE_API_KEY")?;
+                let json = self
+                    .get_json(
+                        client
+                            .get("https://api.search.brave.com/res/v1/web/search")
+                            .header("X-Subscription-Token", key)
+                            .header("Accept", "application/json")
+                            .query(&[("q", query), ("count", count.as_str())]),
+                    )
+                    .await?;
+                Ok(collect(&json["web"]["results"], "title", "url", "description"))
+            }
+            SearchBackend::Serpapi => {
+                let key = self.api_key("SERPAPI_API_KEY")?;
+                let json = self
+                    .get_json(client.get("https://serpapi.com/search.json").query(&[
+                        ("engine", "google"),
+                        ("q", query),
+                        ("num", count.as_str()),
+                        ("api_key", key.as_str()),
+                    ]))
+                    .await?;
+                Ok(collect(&json["organic_results"], "title", "link", "snippet"))
+            }
+            SearchBackend::Searx => {
+                let base = self.config.url.as_deref().ok_or_else(|| {
+                    ToolError::Generic("web_search.url must point to a Searx instance".into())
+                })?;
+                let json = self
+                    .get_json(
+                        client
+                            .get(format!("{}/search", base.trim_end_matches('/')))
+                            .query(&[("q", query), ("format", "json")]),
+                    )
+                    .await?;
+                Ok(collect(&json["results"], "title", "url", "content"))
+            }
+            SearchBackend::Duckduckgo => {
+                let html = client
+                    .get("https://html.duckduckgo.com/html/")
+                    .query(&[("q", query)])
+                    .send()
+                    .await
+                    .map_err(|e| ToolError::Generic(e.to_string()))?
+                    .text()
+                    .await
+                    .map_err(|e| ToolError::Generic(e.to_string()))?;
+                Ok(parse_duckduckgo(&html))
+            }
+        }
+    }
+}
+
+fn collect(results: &Value, title: &str, url: &str, snippet: &str) -> Vec<SearchResult> {
+    results
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .map(|item| SearchResult {
+                    title: clean_html(item[title].as_str().unwrap_or_default()),
+                    url: item[url].as_str().unwrap_or_default().to_string(),
+                    snippet: clean_html(item[snippet].as_str().unwrap_or_default()),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn clean_html(s: &str) -> String {
+    TAG_RE
+        .replace_all(s, "")
+        .replace("&amp;", "&")
+        .replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .trim()
+        .to_string()
+}
+
+fn parse_duckduckgo(html: &str) -> Vec<SearchResult> {
+    DDG_RESULT_RE
+        .captures_iter(html)
+        .map(|cap| {
+            let href = cap[1].replace("&amp;", "&");
+            // Result links are redirects of the form //duckduckgo.com/l/?uddg=<url>
+            let url = reqwest::Url::parse(&format!("https:{}", href))
+                .ok()
+                .and_then(|u| {
+                    u.query_pairs()
+                        .find(|(k, _)| k == "uddg")
+                        .map(|(_, v)| v.into_owned())
+                })
+                .unwrap_or(href);
+            SearchResult {
+                title: clean_html(&cap[2]),
+                url,
+                snippet: clean_html(&cap[3]),
+            }
+        })
+        .collect()
+}
+
+impl rig::tool::Tool for WebSearch {
+    type Args = WebSearchArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "web_search";
+
+    async fn definition(&self, _prompt: String) -> rig::completion::ToolDefinition {
+        rig::completion::ToolDefinition {
+            name: Self::NAME.into(),
+            description: "Search the web. Returns title, URL, and snippet for each result".into(),
+            parameters: serde_json::to_value(schemars::schema_for!(WebSearchArgs)).unwrap(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        let count = args
+            .max_results
+            .or(self.config.max_results)
+            .unwrap_or(DEFAULT_MAX_RESULTS);
+        let results = self.search(&args.query, count).await?;
+        if results.is_empty() {
+            return Ok("none".into());
+        }
+        Ok(results
+            .iter()
+            .take(count)
+            .enumerate()
+            .map(|(i, r)| format!("{}. {}\n   {}\n   {}", i + 1, r.title, r.url, r.snippet))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duckduckgo() {
+        let html = r#"<div><a rel="nofollow" class="result__a" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Fdocs.rs%2Frig&amp;rut=abc">The <b>rig</b> crate</a>
+            <a class="result__snippet" href="x">Build LLM apps &amp; agents</a></div>"#;
+        let results = parse_duckduckgo(html);
+        assert_eq!(
+            results,
+            vec![SearchResult {
+                title: "The rig crate".into(),
+                url: "https://docs.rs/rig".into(),
+                snippet: "Build LLM apps & agents".into(),
+            }]
+        );
+    }
+}
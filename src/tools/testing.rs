@@ -0,0 +1,290 @@
+use super::{Bash, BashArgs, ToolError};
+use crate::config::NetworkPolicy;
+use crate::platform::Shell;
+use regex::Regex;
+use rig::tool::Tool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::LazyLock;
+
+const MAX_FAILURE_LINES: usize = 60;
+const TAIL_LINES: usize = 40;
+
+static CARGO_TEST_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^test (\S+) \.\.\. (ok|FAILED|ignored)").unwrap());
+static PYTEST_COUNT_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(\d+) (passed|failed|skipped|error|errors)\b").unwrap());
+static PYTEST_FAILED_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^(?:FAILED|ERROR) (\S+)").unwrap());
+static GO_TEST_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s*--- (PASS|FAIL|SKIP): (\S+)").unwrap());
+static JEST_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^Tests:\s+(?:(\d+) failed, )?(?:(\d+) skipped, )?(?:(\d+) passed, )?\d+ total")
+        .unwrap()
+});
+static JEST_FAILED_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^\s*● (.+)$").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Runner {
+    Cargo,
+    Pytest,
+    Npm,
+    Go,
+}
+
+impl Runner {
+    /// Picks the test runner from the project files in `dir`.
+    pub fn detect(dir: &Path) -> Option<Self> {
+        if dir.join("Cargo.toml").exists() {
+            Some(Runner::Cargo)
+        } else if dir.join("go.mod").exists() {
+            Some(Runner::Go)
+        } else if dir.join("package.json").exists() {
+            Some(Runner::Npm)
+        } else if [
+            "pyproject.toml",
+            "pytest.ini",
+            "setup.py",
+            "setup.cfg",
+            "tox.ini",
+        ]
+        .iter()
+        .any(|f| dir.join(f).exists())
+        {
+            Some(Runner::Pytest)
+        } else {
+            None
+        }
+    }
+
+    pub fn command(&self, filter: Option<&str>) -> String {
+        let filter = filter.map(shell_quote);
+        match (self, filter) {
+            (Runner::Cargo, None) => "cargo test".into(),
+            (Runner::Cargo, Some(f)) => format!("cargo test {}", f),
+            (Runner::Pytest, None) => "pytest -q -rfE".into(),
+            (Runner::Pytest, Some(f)) => format!("pytest -q -rfE -k {}", f),
+            (Runner::Npm, None) => "npm test --silent".into(),
+            (Runner::Npm, Some(f)) => format!("npm test --silent -- {}", f),
+            (Runner::Go, None) => "go test ./...".into(),
+            (Runner::Go, Some(f)) => format!("go test ./... -run {}", f),
+        }
+    }
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct TestSummary {
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// Names of failing tests.
+    pub failures: Vec<String>,
+}
+
+/// Extracts pass/fail counts and failing test names. `None` when the output has no
+/// recognizable results (e.g. a compile error), in which case the raw tail is more useful.
+pub fn parse(runner: Runner, output: &str) -> Option<TestSummary> {
+    let mut summary = TestSummary::default();
+    match runner {
+        Runner::Cargo => {
+            for cap in CARGO_TEST_RE.captures_iter(output) {
+                match &cap[2] {
+                    "ok" => summary.passed += 1,
+                    "ignored" => summary.skipped += 1,
+                    _ => {
+                        summary.failed += 1;
+                        summary.failures.push(cap[1].to_string());
+                    }
+                }
+            }
+            if summary == TestSummary::default() && !output.contains("test result:") {
+                return None;
+            }
+        }
+        Runner::Pytest => {
+            let last = output.lines().rev().find(|l| PYTEST_COUNT_RE.is_match(l))?;
+            for cap in PYTEST_COUNT_RE.captures_iter(last) {
+                let n: usize = cap[1].parse().unwrap_or(0);
+                match &cap[2] {
+                    "passed" => summary.passed += n,
+                    "skipped" => summary.skipped += n,
+                    _ => summary.failed += n,
+                }
+            }
+            summary.failures = PYTEST_FAILED_RE
+                .captures_iter(output)
+                .map(|c| c[1].to_string())
+                .collect();
+        }
+        Runner::Go => {
+            for cap in GO_TEST_RE.captures_iter(output) {
+                match &cap[1] {
+                    "PASS" => summary.passed += 1,
+                    "SKIP" => summary.skipped += 1,
+                    _ => {
+                        summary.failed += 1;
+                        summary.failures.push(cap[2].to_string());
+                    }
+                }
+            }
+            if summary == TestSummary::default()
+                && !output
+                    .lines()
+                    .any(|l| l.starts_with("ok ") || l.starts_with("FAIL"))
+            {
+                return None;
+            }
+        }
+        Runner::Npm => {
+            let cap = JEST_RE.captures(output)?;
+            let count = |i| {
+                cap.get(i)
+                    .and_then(|m| m.as_str().parse().ok())
+                    .unwrap_or(0)
+            };
+            summary.failed = count(1);
+            summary.skipped = count(2);
+            summary.passed = count(3);
+            summary.failures = JEST_FAILED_RE
+                .captures_iter(output)
+                .map(|c| c[1].trim().to_string())
+                .filter(|name| !name.starts_with("Console"))
+                .collect();
+            summary.failures.dedup();
+        }
+    }
+    Some(summary)
+}
+
+fn tail(output: &str, n: usize) -> String {
+    let lines: Vec<&str> = output.lines().collect();
+    lines[lines.len().saturating_sub(n)..].join("\n")
+}
+
+/// Compact report: counts, failing tests, and the end of the output when something failed.
+pub fn report(command: &str, output: &str, summary: Option<&TestSummary>) -> String {
+    let Some(summary) = summary else {
+        return format!(
+            "{}: could not parse test results. Last lines:\n{}",
+            command,
+            tail(output, TAIL_LINES)
+        );
+    };
+    let mut out = format!(
+        "{}: {} passed, {} failed, {} skipped",
+        command, summary.passed, summary.failed, summary.skipped
+    );
+    if summary.failed > 0 {
+        for name in &summary.failures {
+            out.push_str(&format!("\nFAILED {}", name));
+        }
+        out.push_str(&format!(
+            "\n\nOutput (last lines):\n{}",
+            tail(output, MAX_FAILURE_LINES)
+        ));
+    }
+    out
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct RunTestsArgs {
+    /// Only run tests matching this name or pattern
+    #[serde(default)]
+    pub filter: Option<String>,
+}
+
+/// Runs the project's test suite through the same path as `bash` (sandbox and
+/// network policy included) and summarizes the results.
+pub struct RunTests {
+    pub sandbox: Option<crate::sandbox::SandboxConfig>,
+    pub network: NetworkPolicy,
+    pub shell: Option<Shell>,
+}
+
+impl RunTests {
+    /// The command a call with these args would run, if a runner is detected.
+    pub fn command_for(args: &RunTestsArgs) -> Option<(Runner, String)> {
+        let runner = Runner::detect(&std::env::current_dir().ok()?)?;
+        Some((runner, runner.command(args.filter.as_deref())))
+    }
+}
+
+impl Tool for RunTests {
+    type Args = RunTestsArgs;
+    type Output = String;
+    type Error = ToolError;
+
+    const NAME: &'static str = "run_tests";
+
+    async fn definition(&self, _prompt: String) -> rig::completion::ToolDefinition {
+        rig::completion::ToolDefinition {
+            name: Self::NAME.into(),
+            description: "Run the project's tests (cargo, pytest, npm, go; detected automatically) and summarize pass/fail".into(),
+            parameters: serde_json::to_value(schemars::schema_for!(RunTestsArgs)).unwrap(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> std::result::Result<Self::Output, Self::Error> {
+        let (runner, cmd) = Self::command_for(&args).ok_or_else(|| {
+            ToolError::Generic(
+                "No test runner detected (looked for Cargo.toml, go.mod, package.json, pyproject.toml, pytest.ini, setup.py, setup.cfg, tox.ini)".into(),
+            )
+        })?;
+        let bash = Bash {
+            sandbox: self.sandbox.clone(),
+            network: self.network,
+            shell: self.shell,
+            progress: None,
+        };
+        let output = bash
+            .call(BashArgs {
+                cmd: cmd.clone(),
+                shell: None,
+            })
+            .await?;
+        Ok(report(&cmd, &output, parse(runner, &output).as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cargo() {
+        let output = "running 3 tests\ntest a::one ... ok\ntest a::two ... FAILED\ntest a::three ... ignored\n\ntest result: FAILED. 1 passed; 1 failed; 1 ignored";
+        let summary = parse(Runner::Cargo, output).unwrap();
+        assert_eq!(
+            summary,
+            TestSummary {
+                passed: 1,
+                failed: 1,
+                skipped: 1,
+                failures: vec!["a::two".into()],
+            }
+        );
+        assert!(parse(Runner::Cargo, "error[E0425]: cannot find value `x`").is_none());
+    }
+
+    #[test]
+    fn test_parse_pytest_and_go() {
+        let pytest =
+            "..F.\nFAILED tests/test_x.py::test_y - assert 1 == 2\n1 failed, 3 passed in 0.12s";
+        let summary = parse(Runner::Pytest, pytest).unwrap();
+        assert_eq!((summary.passed, summary.failed), (3, 1));
+        assert_eq!(summary.failures, vec!["tests/test_x.py::test_y"]);
+
+        let go = "--- FAIL: TestAdd (0.00s)\n--- PASS: TestSub (0.00s)\nFAIL\tpkg/math\t0.01s";
+        let summary = parse(Runner::Go, go).unwrap();
+        assert_eq!((summary.passed, summary.failed), (1, 1));
+        assert_eq!(
+            Runner::Go.command(Some("TestAdd")),
+            "go test ./... -run 'TestAdd'"
+        );
+    }
+}
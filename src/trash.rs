@@ -0,0 +1,250 @@
+//! Recoverable deletes. The `remove` tool moves its targets into
+//! `.picocode/trash/<timestamp>/` instead of deleting them, and
+//! `picocode trash restore` puts them back where they were.
+
+use crate::{PicocodeError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const TRASH_DIR: &str = ".picocode/trash";
+const MANIFEST: &str = "manifest.json";
+
+/// One removed file or directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrashEntry {
+    /// Where it was removed from.
+    pub original: PathBuf,
+    /// Its name inside the batch directory.
+    pub stored: String,
+}
+
+/// Everything removed within the same second, in a directory named after it.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub name: String,
+    pub dir: PathBuf,
+    pub entries: Vec<TrashEntry>,
+}
+
+/// Trash of a project, by default `.picocode/trash` under the current directory.
+#[derive(Debug, Clone)]
+pub struct Trash {
+    dir: PathBuf,
+}
+
+fn read_manifest(dir: &Path) -> Result<Vec<TrashEntry>> {
+    let path = dir.join(MANIFEST);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+/// Moves `from` to `to`, copying it and removing the original when they are
+/// on different filesystems (a mounted directory inside the project).
+fn move_path(from: &Path, to: &Path) -> std::io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            copy_all(from, to)?;
+            if std::fs::symlink_metadata(from)?.is_dir() {
+                std::fs::remove_dir_all(from)
+            } else {
+                std::fs::remove_file(from)
+            }
+        }
+        result => result,
+    }
+}
+
+/// Copies a file or directory with its permissions, and symlinks as links.
+fn copy_all(from: &Path, to: &Path) -> std::io::Result<()> {
+    let metadata = std::fs::symlink_metadata(from)?;
+    if metadata.is_symlink() {
+        #[cfg(unix)]
+        return std::os::unix::fs::symlink(std::fs::read_link(from)?, to);
+        #[cfg(not(unix))]
+        return std::fs::copy(from, to).map(|_| ());
+    }
+    if !metadata.is_dir() {
+        return std::fs::copy(from, to).map(|_| ());
+    }
+    std::fs::create_dir(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        copy_all(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    std::fs::set_permissions(to, metadata.permissions())
+}
+
+impl Trash {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::new(std::env::current_dir()?.join(TRASH_DIR)))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Moves `path` into the current batch and returns the batch directory.
+    pub fn put(&self, path: &Path) -> Result<PathBuf> {
+        if self.dir.starts_with(path) {
+            return Err(PicocodeError::Other(format!(
+                "{} contains the trash and can't be moved into it",
+                path.display()
+            )));
+        }
+        let batch = self
+            .dir
+            .join(chrono::Local::now().format("%Y%m%d-%H%M%S").to_string());
+        std::fs::create_dir_all(&batch)?;
+        let mut entries = read_manifest(&batch)?;
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let stored = format!("{}-{}", entries.len() + 1, name);
+        move_path(path, &batch.join(&stored))?;
+        entries.push(TrashEntry {
+            original: path.to_path_buf(),
+            stored,
+        });
+        std::fs::write(
+            batch.join(MANIFEST),
+            serde_json::to_string_pretty(&entries)?,
+        )?;
+        Ok(batch)
+    }
+
+    /// Batches in the trash, most recent first.
+    pub fn batches(&self) -> Result<Vec<Batch>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut batches = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let dir = entry?.path();
+            if !dir.join(MANIFEST).exists() {
+                continue;
+            }
+            batches.push(Batch {
+                name: dir.file_name().unwrap().to_string_lossy().into_owned(),
+                entries: read_manifest(&dir)?,
+                dir,
+            });
+        }
+        batches.sort_by(|a, b| b.name.cmp(&a.name));
+        Ok(batches)
+    }
+
+    /// Puts back everything in batch `name` (the most recent one if `None`)
+    /// and returns the restored paths. Nothing is moved if one of them exists
+    /// again, unless `overwrite` is set.
+    pub fn restore(&self, name: Option<&str>, overwrite: bool) -> Result<Vec<PathBuf>> {
+        let batches = self.batches()?;
+        let batch = match name {
+            Some(name) => batches.into_iter().find(|b| b.name == name),
+            None => batches.into_iter().next(),
+        }
+        .ok_or_else(|| match name {
+            Some(name) => PicocodeError::Other(format!("No trash batch named {}", name)),
+            None => PicocodeError::Other("The trash is empty".into()),
+        })?;
+        if !overwrite {
+            if let Some(entry) = batch.entries.iter().find(|e| e.original.exists()) {
+                return Err(PicocodeError::Other(format!(
+                    "{} exists again (pass --force to replace it)",
+                    entry.original.display()
+                )));
+            }
+        }
+        let mut restored = Vec::new();
+        for entry in &batch.entries {
+            let original = &entry.original;
+            if original.is_dir() {
+                std::fs::remove_dir_all(original)?;
+            } else if original.exists() {
+                std::fs::remove_file(original)?;
+            }
+            if let Some(parent) = original.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            move_path(&batch.dir.join(&entry.stored), original)?;
+            restored.push(original.clone());
+        }
+        std::fs::remove_dir_all(&batch.dir)?;
+        Ok(restored)
+    }
+
+    /// Deletes everything in the trash, returning the number of batches.
+    pub fn empty(&self) -> Result<usize> {
+        let count = self.batches()?.len();
+        if self.dir.exists() {
+            std::fs::remove_dir_all(&self.dir)?;
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_put_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash = Trash::new(dir.path().join(TRASH_DIR));
+        let file = dir.path().join("a.txt");
+        let sub = dir.path().join("src/old");
+        std::fs::write(&file, "a").unwrap();
+        std::fs::create_dir_all(&sub).unwrap();
+        std::fs::write(sub.join("b.rs"), "b").unwrap();
+
+        trash.put(&file).unwrap();
+        trash.put(&sub).unwrap();
+        assert!(!file.exists() && !sub.exists());
+        assert!(trash.put(dir.path()).is_err());
+
+        let batches = trash.batches().unwrap();
+        let entries: usize = batches.iter().map(|b| b.entries.len()).sum();
+        assert_eq!(entries, 2);
+        for _ in &batches {
+            trash.restore(None, false).unwrap();
+        }
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "a");
+        assert_eq!(std::fs::read_to_string(sub.join("b.rs")).unwrap(), "b");
+
+        trash.put(&file).unwrap();
+        std::fs::write(&file, "new").unwrap();
+        assert!(trash.restore(None, false).is_err());
+        assert_eq!(trash.restore(None, true).unwrap(), vec![file.clone()]);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "a");
+        assert!(trash.batches().unwrap().is_empty());
+        assert_eq!(trash.empty().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_copy_all() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(src.join("nested")).unwrap();
+        std::fs::write(src.join("nested/a.txt"), "a").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("nested", src.join("link")).unwrap();
+
+        let dst = dir.path().join("dst");
+        copy_all(&src, &dst).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dst.join("nested/a.txt")).unwrap(),
+            "a"
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            std::fs::read_link(dst.join("link")).unwrap(),
+            Path::new("nested")
+        );
+    }
+}
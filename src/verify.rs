@@ -0,0 +1,113 @@
+//! Critic/verify loop: a reviewer agent checks what a run changed in the
+//! working tree and its findings are fed back to the main agent for one revision.
+
+use std::process::Command;
+
+/// Keeps reviewer prompts bounded on large change sets.
+const MAX_DIFF_CHARS: usize = 60_000;
+
+/// Reply the reviewer gives when it has nothing to flag.
+pub const APPROVAL: &str = "LGTM";
+
+fn git(args: &[&str]) -> Option<String> {
+    Command::new("git")
+        .args(args)
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+}
+
+fn untracked_files() -> Vec<String> {
+    git(&["ls-files", "--others", "--exclude-standard"])
+        .map(|files| files.lines().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+/// The working tree before a run, so the review covers only what the run
+/// changed, whether it left the changes uncommitted or committed them.
+pub struct Baseline {
+    /// A commit of the tracked files as they were (`git stash create`), or
+    /// HEAD when nothing was changed.
+    revision: String,
+    untracked: Vec<String>,
+}
+
+impl Baseline {
+    /// `None` outside a git repository or before its first commit.
+    pub fn record() -> Option<Self> {
+        let revision = git(&["stash", "create"])
+            .filter(|rev| !rev.trim().is_empty())
+            .or_else(|| git(&["rev-parse", "HEAD"]))?;
+        Some(Self {
+            revision: revision.trim().to_string(),
+            untracked: untracked_files(),
+        })
+    }
+}
+
+/// Changes since `baseline`, plus the names of files created since then and
+/// not yet tracked. Returns None when the run changed nothing.
+pub fn workspace_diff(baseline: &Baseline) -> Option<String> {
+    let mut diff = git(&["diff", &baseline.revision])?;
+    let untracked: Vec<String> = untracked_files()
+        .into_iter()
+        .filter(|file| !baseline.untracked.contains(file))
+        .collect();
+    if !untracked.is_empty() {
+        diff.push_str("\nUntracked files:\n");
+        diff.push_str(&untracked.join("\n"));
+        diff.push('\n');
+    }
+    if diff.trim().is_empty() {
+        return None;
+    }
+    if diff.len() > MAX_DIFF_CHARS {
+        let mut end = MAX_DIFF_CHARS;
+        while !diff.is_char_boundary(end) {
+            end -= 1;
+        }
+        diff.truncate(end);
+        diff.push_str("\n... (diff truncated)\n");
+    }
+    Some(diff)
+}
+
+pub fn review_prompt(request: &str, diff: &str) -> String {
+    format!(
+        "You are reviewing changes another agent made for this request:\n\n{}\n\n\
+Changes (git diff):\n\n```diff\n{}\n```\n\n\
+Check correctness, missed requirements, bugs, and regressions. You may read files for context but do not modify anything. \
+If the changes are good, reply with exactly {}. Otherwise list each concrete problem with the file and what to change.",
+        request, diff, APPROVAL
+    )
+}
+
+pub fn revision_prompt(request: &str, response: &str, critique: &str) -> String {
+    format!(
+        "You already worked on this request and your changes are in the working tree:\n\n{}\n\n\
+Your summary was:\n\n{}\n\nA reviewer raised these points:\n\n{}\n\n\
+Address the valid points (inspect the files as needed), then summarize what you changed and which points you disagreed with.",
+        request, response, critique
+    )
+}
+
+/// True when the reviewer had nothing to flag.
+pub fn approved(review: &str) -> bool {
+    review
+        .trim()
+        .trim_matches(|c: char| c == '*' || c == '`' || c == '.')
+        .eq_ignore_ascii_case(APPROVAL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_approved() {
+        assert!(approved("LGTM"));
+        assert!(approved("  **LGTM.**\n"));
+        assert!(!approved("LGTM, but src/lib.rs leaks a file handle"));
+    }
+}